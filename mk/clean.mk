@@ -37,6 +37,10 @@ clean-net:
 	@$(call E, "cleaning net")
 	$(Q)cd $(B)/src/components/net/ && rm -rf libnet*.dylib libnet*.dSYM libnet*.so $(DONE_net)
 
+clean-style:
+	@$(call E, "cleaning style")
+	$(Q)cd $(B)/src/components/style/ && rm -rf libstyle*.dylib libstyle*.dSYM libstyle*.so $(DONE_style)
+
 clean-gfx:
 	@$(call E, "cleaning gfx")
 	$(Q)cd $(B)/src/components/gfx/ && rm -rf libgfx*.dylib libgfx*.dSYM libgfx*.so $(DONE_gfx)
@@ -45,7 +49,7 @@ clean-script:
 	@$(call E, "cleaning script")
 	$(Q)cd $(B)/src/components/script/ && rm -rf libscript*.dylib libscript*.dSYM libscript*.so $(DONE_script)
 
-clean-servo: clean-gfx clean-util clean-net clean-script clean-msg
+clean-servo: clean-gfx clean-util clean-net clean-style clean-script clean-msg
 	@$(call E, "cleaning servo")
 	$(Q)rm -f servo servo-test libservo*.so
 	$(Q)cd $(BINDINGS_SRC) && rm -f *.pkl
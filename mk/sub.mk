@@ -11,7 +11,6 @@ NO_TESTS += \
 
 # These submodules will not be cleaned by the `make clean-fast` target.
 SLOW_BUILDS += \
-	libcss \
 	libparserutils \
 	libwapcaplet \
 	mozjs \
@@ -21,7 +20,6 @@ SLOW_BUILDS += \
 
 # Builds that do not require rustc
 NATIVE_BUILDS += \
-	libcss \
 	libhubbub \
 	libparserutils \
 	libwapcaplet \
@@ -70,12 +68,6 @@ DEPS_rust-hubbub += \
 	rust \
 	$(NULL)
 
-DEPS_rust-netsurfcss += \
-	libcss \
-	rust-wapcaplet \
-	rust \
-	$(NULL)
-
 DEPS_rust-wapcaplet += \
 	libwapcaplet \
 	rust \
@@ -85,12 +77,6 @@ CFLAGS_rust-wapcaplet += \
 	"-I$(S)src/libwapcaplet/include" \
 	$(NULL)
 
-DEPS_rust-css += \
-	rust-netsurfcss \
-	rust-wapcaplet \
-	rust \
-	$(NULL)
-
 DEPS_rust-mozjs += \
 	mozjs \
 	rust \
@@ -100,11 +86,6 @@ CFLAGS_rust-mozjs += \
 	"-I../mozjs/dist/include" \
 	$(NULL)
 
-DEPS_libcss += \
-	libwapcaplet \
-	libparserutils \
-	$(NULL)
-
 # Platform-specific dependencies
 ifeq ($(CFG_OSTYPE),apple-darwin)
 DEPS_rust-azure += \
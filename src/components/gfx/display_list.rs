@@ -16,7 +16,6 @@
 
 use color::Color;
 use geometry::Au;
-use newcss::values::CSSBorderStyle;
 use render_context::RenderContext;
 use text::SendableTextRun;
 
@@ -25,8 +24,7 @@ use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use servo_net::image::base::Image;
 use servo_util::range::Range;
 use extra::arc::Arc;
-
-use newcss::values::{CSSTextDecorationUnderline, CSSTextDecorationOverline, CSSTextDecorationLineThrough};
+use style::computed_values::border_style;
 
 /// A list of rendering operations to be performed.
 pub struct DisplayList<E> {
@@ -48,10 +46,28 @@ impl<E> DisplayList<E> {
         self.list.push(item)
     }
 
+    /// Appends all of the items of the given display list to this one.
+    pub fn append_list(&mut self, other: DisplayList<E>) {
+        let DisplayList {
+            list: items
+        } = other;
+        self.list.push_all_move(items)
+    }
+
+    /// Returns the number of items in the display list.
+    pub fn len(&self) -> uint {
+        self.list.len()
+    }
+
     /// Draws the display list into the given render context.
     pub fn draw_into_context(&self, render_context: &RenderContext) {
+        self.draw_range_into_context(render_context, &Range::new(0, self.list.len()))
+    }
+
+    /// Draws the items in the given range of the display list into the given render context.
+    pub fn draw_range_into_context(&self, render_context: &RenderContext, range: &Range) {
         debug!("Beginning display list.");
-        for item in self.list.iter() {
+        for item in self.list.slice(range.begin(), range.end()).iter() {
             // FIXME(Issue #150): crashes
             //debug!("drawing %?", *item);
             item.draw_into_context(render_context)
@@ -60,12 +76,117 @@ impl<E> DisplayList<E> {
     }
 }
 
+/// The layers into which a stacking context sorts the display items of the boxes in it that do not
+/// establish stacking contexts of their own. They are listed in the order in which they are painted,
+/// apart from the stacking contexts of positioned descendants, which go in between them. (CSS 2.1
+/// Appendix E)
+pub enum StackingLevel {
+    /// The background and borders of the element that establishes the stacking context.
+    BackgroundAndBordersStackingLevel,
+    /// The backgrounds and borders of the block-level descendants that are in flow.
+    BlockBackgroundsAndBordersStackingLevel,
+    /// The floats, each of which is painted as if it established a stacking context.
+    FloatStackingLevel,
+    /// The inline-level content: text, images, and the backgrounds and borders of inline boxes.
+    ContentStackingLevel,
+}
+
+/// The display items of an element that establishes a stacking context, and of the descendants in
+/// it, sorted into the layers in which they are painted. (CSS 2.1 § 9.9.1)
+pub struct StackingContext<E> {
+    /// The background and borders of the element that establishes the stacking context.
+    background_and_borders: DisplayList<E>,
+    /// The backgrounds and borders of the block-level descendants that are in flow.
+    block_backgrounds_and_borders: DisplayList<E>,
+    /// The floats.
+    floats: DisplayList<E>,
+    /// The inline-level content.
+    content: DisplayList<E>,
+    /// The stacking contexts of the positioned descendants, with their `z-index`, in tree order.
+    ///
+    /// TODO: Positioned descendants with `z-index: auto` are given a stacking context at level 0,
+    /// although their own positioned descendants should belong to this stacking context instead.
+    positioned_descendants: ~[(i32, StackingContext<E>)],
+}
+
+impl<E> StackingContext<E> {
+    /// Creates a new, empty stacking context.
+    pub fn new() -> StackingContext<E> {
+        StackingContext {
+            background_and_borders: DisplayList::new(),
+            block_backgrounds_and_borders: DisplayList::new(),
+            floats: DisplayList::new(),
+            content: DisplayList::new(),
+            positioned_descendants: ~[],
+        }
+    }
+
+    /// Returns the display list of the given layer.
+    pub fn list_for_level<'a>(&'a mut self, level: StackingLevel) -> &'a mut DisplayList<E> {
+        match level {
+            BackgroundAndBordersStackingLevel => &mut self.background_and_borders,
+            BlockBackgroundsAndBordersStackingLevel => &mut self.block_backgrounds_and_borders,
+            FloatStackingLevel => &mut self.floats,
+            ContentStackingLevel => &mut self.content,
+        }
+    }
+
+    /// Adds the stacking context of a positioned descendant with the given `z-index`.
+    pub fn add_positioned_descendant(&mut self, z_index: i32, context: StackingContext<E>) {
+        self.positioned_descendants.push((z_index, context))
+    }
+
+    /// Flattens the stacking context into a single display list, in painting order: the
+    /// background and borders, the descendant stacking contexts with negative `z-index`, the
+    /// blocks, the floats, the inline content, and then the descendant stacking contexts with
+    /// `z-index` 0 and above. Stacking contexts with the same `z-index` are painted in tree
+    /// order. (CSS 2.1 Appendix E)
+    pub fn flatten(self) -> DisplayList<E> {
+        let StackingContext {
+            background_and_borders: background_and_borders,
+            block_backgrounds_and_borders: block_backgrounds_and_borders,
+            floats: floats,
+            content: content,
+            positioned_descendants: positioned_descendants
+        } = self;
+
+        // Sort the descendants by `z-index`, keeping the tree order of those with the same one.
+        let mut sorted: ~[(i32, StackingContext<E>)] = ~[];
+        for (z_index, context) in positioned_descendants.move_iter() {
+            let index = sorted.iter().position(|&(other_z_index, _)| other_z_index > z_index);
+            let index = index.unwrap_or(sorted.len());
+            sorted.insert(index, (z_index, context))
+        }
+
+        let mut result = background_and_borders;
+        let mut positive = ~[];
+        for (z_index, context) in sorted.move_iter() {
+            if z_index < 0 {
+                result.append_list(context.flatten())
+            } else {
+                positive.push(context)
+            }
+        }
+        result.append_list(block_backgrounds_and_borders);
+        result.append_list(floats);
+        result.append_list(content);
+        for context in positive.move_iter() {
+            result.append_list(context.flatten())
+        }
+        result
+    }
+}
+
 /// One drawing command in the list.
 pub enum DisplayItem<E> {
     SolidColorDisplayItemClass(~SolidColorDisplayItem<E>),
     TextDisplayItemClass(~TextDisplayItem<E>),
     ImageDisplayItemClass(~ImageDisplayItem<E>),
+    TiledImageDisplayItemClass(~TiledImageDisplayItem<E>),
     BorderDisplayItemClass(~BorderDisplayItem<E>),
+    RoundedSolidColorDisplayItemClass(~RoundedSolidColorDisplayItem<E>),
+    RoundedBorderDisplayItemClass(~RoundedBorderDisplayItem<E>),
+    BoxShadowDisplayItemClass(~BoxShadowDisplayItem<E>),
 }
 
 /// Information common to all display items.
@@ -75,6 +196,11 @@ pub struct BaseDisplayItem<E> {
     /// TODO: Which coordinate system should this use?
     bounds: Rect<Au>,
 
+    /// The rectangle outside of which the item is not drawn, in the same coordinate system as
+    /// `bounds`. This is the intersection of the padding boxes of the ancestors that clip their
+    /// overflow, if any.
+    clip_rect: Option<Rect<Au>>,
+
     /// Extra data: either the originating flow (for hit testing) or nothing (for rendering).
     extra: E,
 }
@@ -85,12 +211,22 @@ pub struct SolidColorDisplayItem<E> {
     color: Color,
 }
 
+/// Renders a solid color in a rectangle with rounded corners.
+pub struct RoundedSolidColorDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    color: Color,
+    radii: BorderRadii<Au>,
+}
+
 /// Renders text.
 pub struct TextDisplayItem<E> {
     base: BaseDisplayItem<E>,
     text_run: ~SendableTextRun,
     range: Range,
     color: Color,
+
+    /// The space added to each word separator, for justified text.
+    word_spacing: Au,
 }
 
 /// Renders an image.
@@ -99,6 +235,21 @@ pub struct ImageDisplayItem<E> {
     image: Arc<~Image>,
 }
 
+/// Renders an image repeatedly, as for backgrounds. The image is drawn in `tile` and in the
+/// rectangles of the same size next to it in the directions in which it repeats, clipped to the
+/// bounds of the item.
+pub struct TiledImageDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    image: Arc<~Image>,
+
+    /// The position and size of one copy of the image.
+    tile: Rect<Au>,
+
+    /// Whether the image repeats horizontally and vertically.
+    repeat_x: bool,
+    repeat_y: bool,
+}
+
 /// Renders a border.
 pub struct BorderDisplayItem<E> {
     base: BaseDisplayItem<E>,
@@ -110,12 +261,106 @@ pub struct BorderDisplayItem<E> {
     color: SideOffsets2D<Color>,
 
     /// The border styles.
-    style: SideOffsets2D<CSSBorderStyle>
+    style: SideOffsets2D<border_style::ComputedValue>
+}
+
+/// Renders a border with rounded corners.
+pub struct RoundedBorderDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+
+    /// The border widths.
+    border: SideOffsets2D<Au>,
+
+    /// The border colors.
+    color: SideOffsets2D<Color>,
+
+    /// The border styles.
+    style: SideOffsets2D<border_style::ComputedValue>,
+
+    /// The radii of the outer edge of the border.
+    radii: BorderRadii<Au>,
+}
+
+/// Renders a shadow cast by a box. The bounds of the item are the area that the shadow may cover.
+pub struct BoxShadowDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+
+    /// The border box of the box that casts the shadow, or its padding box if the shadow is inset,
+    /// and the radii of its corners.
+    box_bounds: Rect<Au>,
+    radii: BorderRadii<Au>,
+
+    color: Color,
+    offset: Point2D<Au>,
+    blur_radius: Au,
+
+    /// How much the shadow is larger than the box, or smaller if it is inset.
+    spread_radius: Au,
+
+    /// Whether the shadow is cast inside the padding box instead of outside the border box.
+    inset: bool,
+}
+
+/// The radii of the corners of a rectangle. The width of each size is the horizontal radius and
+/// the height the vertical one.
+#[deriving(Clone)]
+pub struct BorderRadii<T> {
+    top_left: Size2D<T>,
+    top_right: Size2D<T>,
+    bottom_right: Size2D<T>,
+    bottom_left: Size2D<T>,
+}
+
+impl BorderRadii<Au> {
+    /// Returns radii that are all zero, for square corners.
+    pub fn zero() -> BorderRadii<Au> {
+        let zero = Size2D(Au(0), Au(0));
+        BorderRadii {
+            top_left: zero,
+            top_right: zero,
+            bottom_right: zero,
+            bottom_left: zero,
+        }
+    }
+
+    /// Returns the radii of the inner edge of a border with these outer radii, which are what is
+    /// left of them past the border widths.
+    pub fn inner_radii(&self, border: &SideOffsets2D<Au>) -> BorderRadii<Au> {
+        let shrink = |radius: Size2D<Au>, horizontal: Au, vertical: Au| {
+            Size2D(Au::max(radius.width - horizontal, Au(0)),
+                   Au::max(radius.height - vertical, Au(0)))
+        };
+        BorderRadii {
+            top_left: shrink(self.top_left, border.left, border.top),
+            top_right: shrink(self.top_right, border.right, border.top),
+            bottom_right: shrink(self.bottom_right, border.right, border.bottom),
+            bottom_left: shrink(self.bottom_left, border.left, border.bottom),
+        }
+    }
+
+    /// Returns true if all the corners are square.
+    pub fn is_zero(&self) -> bool {
+        let is_square = |radius: &Size2D<Au>| radius.width == Au(0) || radius.height == Au(0);
+        is_square(&self.top_left) && is_square(&self.top_right) &&
+            is_square(&self.bottom_right) && is_square(&self.bottom_left)
+    }
 }
 
 impl<E> DisplayItem<E> {
     /// Renders this display item into the given render context.
     fn draw_into_context(&self, render_context: &RenderContext) {
+        match self.base().clip_rect {
+            None => self.draw_unclipped_into_context(render_context),
+            Some(ref clip_rect) => {
+                render_context.draw_push_clip(clip_rect);
+                self.draw_unclipped_into_context(render_context);
+                render_context.draw_pop_clip();
+            }
+        }
+    }
+
+    /// Renders this display item into the given render context, ignoring its clip rect.
+    fn draw_unclipped_into_context(&self, render_context: &RenderContext) {
         match *self {
             SolidColorDisplayItemClass(ref solid_color) => {
                 render_context.draw_solid_color(&solid_color.base.bounds, solid_color.color)
@@ -135,7 +380,8 @@ impl<E> DisplayItem<E> {
                                             new_run,
                                             &text.range,
                                             baseline_origin,
-                                            text.color);
+                                            text.color,
+                                            text.word_spacing);
 
                 let width = text.base.bounds.size.width;
                 let underline_size = font.metrics.underline_size;
@@ -143,25 +389,22 @@ impl<E> DisplayItem<E> {
                 let strikeout_size = font.metrics.strikeout_size;
                 let strikeout_offset = font.metrics.strikeout_offset;
 
-                match new_run.decoration {
-                    CSSTextDecorationUnderline => {
-                        let underline_y = baseline_origin.y - underline_offset;
-                        let underline_bounds = Rect(Point2D(baseline_origin.x, underline_y),
-                                                    Size2D(width, underline_size));
-                        render_context.draw_solid_color(&underline_bounds, text.color);
-                    },
-                    CSSTextDecorationOverline => {
-                        let overline_bounds = Rect(Point2D(baseline_origin.x, origin.y),
-                                                   Size2D(width, underline_size));
-                        render_context.draw_solid_color(&overline_bounds, text.color);
-                    },
-                    CSSTextDecorationLineThrough => {
-                        let strikeout_y = baseline_origin.y - strikeout_offset;
-                        let strikeout_bounds = Rect(Point2D(baseline_origin.x, strikeout_y),
-                                                    Size2D(width, strikeout_size));
-                        render_context.draw_solid_color(&strikeout_bounds, text.color);
-                    },
-                    _ => ()
+                if new_run.decoration.underline {
+                    let underline_y = baseline_origin.y - underline_offset;
+                    let underline_bounds = Rect(Point2D(baseline_origin.x, underline_y),
+                                                Size2D(width, underline_size));
+                    render_context.draw_solid_color(&underline_bounds, text.color);
+                }
+                if new_run.decoration.overline {
+                    let overline_bounds = Rect(Point2D(baseline_origin.x, origin.y),
+                                               Size2D(width, underline_size));
+                    render_context.draw_solid_color(&overline_bounds, text.color);
+                }
+                if new_run.decoration.line_through {
+                    let strikeout_y = baseline_origin.y - strikeout_offset;
+                    let strikeout_bounds = Rect(Point2D(baseline_origin.x, strikeout_y),
+                                                Size2D(width, strikeout_size));
+                    render_context.draw_solid_color(&strikeout_bounds, text.color);
                 }
             }
 
@@ -171,12 +414,47 @@ impl<E> DisplayItem<E> {
                 render_context.draw_image(image_item.base.bounds, image_item.image.clone())
             }
 
+            TiledImageDisplayItemClass(ref image_item) => {
+                debug!("Drawing tiled image at %?.", image_item.base.bounds);
+
+                render_context.draw_tiled_image(&image_item.base.bounds,
+                                                &image_item.tile,
+                                                image_item.repeat_x,
+                                                image_item.repeat_y,
+                                                image_item.image.clone())
+            }
+
             BorderDisplayItemClass(ref border) => {
                 render_context.draw_border(&border.base.bounds,
                                            border.border,
                                            border.color,
                                            border.style)
             }
+
+            RoundedSolidColorDisplayItemClass(ref solid_color) => {
+                render_context.draw_rounded_solid_color(&solid_color.base.bounds,
+                                                        solid_color.color,
+                                                        &solid_color.radii)
+            }
+
+            RoundedBorderDisplayItemClass(ref border) => {
+                render_context.draw_rounded_border(&border.base.bounds,
+                                                   border.border,
+                                                   border.color,
+                                                   border.style,
+                                                   &border.radii)
+            }
+
+            BoxShadowDisplayItemClass(ref shadow) => {
+                render_context.draw_box_shadow(&shadow.base.bounds,
+                                               &shadow.box_bounds,
+                                               &shadow.radii,
+                                               shadow.color,
+                                               shadow.offset,
+                                               shadow.blur_radius,
+                                               shadow.spread_radius,
+                                               shadow.inset)
+            }
         }
     }
 
@@ -187,7 +465,13 @@ impl<E> DisplayItem<E> {
                 SolidColorDisplayItemClass(ref solid_color) => transmute_region(&solid_color.base),
                 TextDisplayItemClass(ref text) => transmute_region(&text.base),
                 ImageDisplayItemClass(ref image_item) => transmute_region(&image_item.base),
-                BorderDisplayItemClass(ref border) => transmute_region(&border.base)
+                TiledImageDisplayItemClass(ref image_item) => transmute_region(&image_item.base),
+                BorderDisplayItemClass(ref border) => transmute_region(&border.base),
+                RoundedSolidColorDisplayItemClass(ref solid_color) => {
+                    transmute_region(&solid_color.base)
+                }
+                RoundedBorderDisplayItemClass(ref border) => transmute_region(&border.base),
+                BoxShadowDisplayItemClass(ref shadow) => transmute_region(&shadow.base),
             }
         }
     }
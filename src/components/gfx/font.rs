@@ -10,6 +10,7 @@ use platform::font::{FontHandle, FontTable};
 use render_context::RenderContext;
 use servo_util::range::Range;
 use std::cast;
+use std::managed;
 use std::ptr;
 use std::str;
 use std::vec;
@@ -28,7 +29,7 @@ use servo_util::time;
 use servo_util::time::profile;
 use servo_util::time::ProfilerChan;
 
-use newcss::values::CSSTextDecoration;
+use style::computed_values::text_decoration;
 
 // FontHandle encapsulates access to the platform's font API,
 // e.g. quartz, FreeType. It provides access to metrics and tables
@@ -165,6 +166,25 @@ impl FontDescriptor {
 #[deriving(Clone, Eq)]
 pub enum FontSelector {
     SelectorPlatformIdentifier(~str),
+    /// A font that a document downloaded for an `@font-face` rule. Its data travels with it, since
+    /// no other task can load it by name.
+    SelectorWebFont(WebFontSource),
+}
+
+/// The data of a downloaded font, and where it came from.
+#[deriving(Clone)]
+pub struct WebFontSource {
+    /// The URL of the font data, which tells web fonts apart.
+    url: ~str,
+    data: Arc<~[u8]>,
+    /// The ranges of characters that the font is used for. Empty means all characters.
+    unicode_range: ~[(char, char)],
+}
+
+impl Eq for WebFontSource {
+    fn eq(&self, other: &WebFontSource) -> bool {
+        self.url == other.url && self.unicode_range == other.unicode_range
+    }
 }
 
 // This struct is the result of mapping a specified FontStyle into the
@@ -195,11 +215,63 @@ impl FontGroup {
         self.fonts = ~[];
     }
 
-    pub fn create_textrun(&self, text: ~str, decoration: CSSTextDecoration) -> TextRun {
+    /// Returns the font that renders the given character: the first font of the group that has a
+    /// glyph for it, or else a font of the system that does. Failing both, the first font of the
+    /// group draws it as a missing glyph.
+    pub fn font_for_char(&self, fctx: &mut FontContext, ch: char) -> @mut Font {
         assert!(self.fonts.len() > 0);
 
-        // TODO(Issue #177): Actually fall back through the FontGroup when a font is unsuitable.
-        return TextRun::new(self.fonts[0], text, decoration);
+        for &font in self.fonts.iter() {
+            if font.covers_char(ch) {
+                return font
+            }
+        }
+        match fctx.get_fallback_font_for_char(&self.style, ch) {
+            Some(font) => font,
+            None => self.fonts[0],
+        }
+    }
+
+    /// Splits the text into runs of left-to-right text, each of which one font renders. Returns
+    /// each run with the range of the characters of the text that it holds.
+    ///
+    /// Whitespace stays in the run of the characters before it, so that it does not split words
+    /// from their spaces.
+    pub fn create_textruns(&self,
+                           fctx: &mut FontContext,
+                           text: &str,
+                           decoration: text_decoration::ComputedValue)
+                           -> ~[(TextRun, Range)] {
+        assert!(self.fonts.len() > 0);
+
+        let mut runs = ~[];
+        let mut run_font = self.fonts[0];
+        let mut run_start_byte = 0;
+        let mut run_start_char = 0;
+        for (char_index, (byte_index, ch)) in text.char_offset_iter().enumerate() {
+            if ch.is_whitespace() {
+                loop
+            }
+            let font = self.font_for_char(fctx, ch);
+            if managed::mut_ptr_eq(font, run_font) {
+                loop
+            }
+            if byte_index > run_start_byte {
+                let run_text = text.slice(run_start_byte, byte_index).to_owned();
+                runs.push((TextRun::new(run_font, run_text, decoration, false),
+                           Range::new(run_start_char, char_index - run_start_char)));
+            }
+            run_font = font;
+            run_start_byte = byte_index;
+            run_start_char = char_index;
+        }
+        if text.len() > run_start_byte {
+            let run_text = text.slice_from(run_start_byte).to_owned();
+            let char_len = run_text.char_len();
+            runs.push((TextRun::new(run_font, run_text, decoration, false),
+                       Range::new(run_start_char, char_len)));
+        }
+        runs
     }
 }
 
@@ -243,10 +315,29 @@ pub struct Font {
     metrics: FontMetrics,
     backend: BackendType,
     profiler_chan: ProfilerChan,
-    shape_cache: HashCache<~str, Arc<GlyphStore>>,
+    /// The glyphs of text that was shaped before, by the text and whether it is right to left.
+    shape_cache: HashCache<(~str, bool), Arc<GlyphStore>>,
+    /// How other tasks find this font again, or None if the platform names it.
+    web_font: Option<WebFontSource>,
 }
 
 impl Font {
+    /// Creates a font from the data of a web font.
+    pub fn new_from_web_font(ctx: &FontContext,
+                             source: &WebFontSource,
+                             style: &SpecifiedFontStyle,
+                             backend: BackendType,
+                             profiler_chan: ProfilerChan)
+                             -> Result<@mut Font, ()> {
+        match Font::new_from_buffer(ctx, source.data.get().clone(), style, backend, profiler_chan) {
+            Ok(font) => {
+                font.web_font = Some(source.clone());
+                Ok(font)
+            }
+            Err(()) => Err(()),
+        }
+    }
+
     pub fn new_from_buffer(ctx: &FontContext,
                        buffer: ~[u8],
                        style: &SpecifiedFontStyle,
@@ -272,6 +363,7 @@ impl Font {
             backend: backend,
             profiler_chan: profiler_chan,
             shape_cache: HashCache::new(),
+            web_font: None,
         });
     }
 
@@ -289,6 +381,7 @@ impl Font {
             backend: backend,
             profiler_chan: profiler_chan,
             shape_cache: HashCache::new(),
+            web_font: None,
         }
     }
 
@@ -373,7 +466,8 @@ impl Font {
                               run: &TextRun,
                               range: &Range,
                               baseline_origin: Point2D<Au>,
-                              color: Color) {
+                              color: Color,
+                              word_spacing: Au) {
         use std::libc::types::common::c99::{uint16_t, uint32_t};
         use azure::{struct__AzDrawOptions,
                     struct__AzGlyph,
@@ -392,25 +486,38 @@ impl Font {
             fields: 0x0200 as uint16_t
         };
 
-        let mut origin = baseline_origin.clone();
-        let mut azglyphs = ~[];
-        azglyphs.reserve(range.length());
-
+        let mut glyph_entries = ~[];
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
             for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                let glyph_advance = glyph.advance();
+                let glyph_advance = if glyphs.is_whitespace() {
+                    glyph.advance() + word_spacing
+                } else {
+                    glyph.advance()
+                };
                 let glyph_offset = glyph.offset().unwrap_or(Au::zero_point());
+                glyph_entries.push((glyph.index(), glyph_advance, glyph_offset));
+            };
+        }
 
-                let azglyph = struct__AzGlyph {
-                    mIndex: glyph.index() as uint32_t,
-                    mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_nearest_px() as AzFloat,
-                        y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
-                    }
-                };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
+        // The glyphs of right-to-left text are kept in the order of the characters, so the last
+        // one is drawn leftmost.
+        if run.is_rtl {
+            glyph_entries.reverse();
+        }
+
+        let mut origin = baseline_origin.clone();
+        let mut azglyphs = ~[];
+        azglyphs.reserve(glyph_entries.len());
+        for &(glyph_index, glyph_advance, glyph_offset) in glyph_entries.iter() {
+            let azglyph = struct__AzGlyph {
+                mIndex: glyph_index as uint32_t,
+                mPosition: struct__AzPoint {
+                    x: (origin.x + glyph_offset.x).to_nearest_px() as AzFloat,
+                    y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
+                }
             };
+            origin = Point2D(origin.x + glyph_advance, origin.y);
+            azglyphs.push(azglyph)
         }
 
         let azglyph_buf_len = azglyphs.len();
@@ -433,7 +540,6 @@ impl Font {
     }
 
     pub fn measure_text(&self, run: &TextRun, range: &Range) -> RunMetrics {
-        // TODO(Issue #199): alter advance direction for RTL
         // TODO(Issue #98): using inter-char and inter-word spacing settings  when measuring text
         let mut advance = Au(0);
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
@@ -455,23 +561,40 @@ impl Font {
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
 
-    pub fn shape_text(@mut self, text: ~str, is_whitespace: bool) -> Arc<GlyphStore> {
+    pub fn shape_text(@mut self, text: ~str, is_whitespace: bool, is_rtl: bool)
+                      -> Arc<GlyphStore> {
         let shaper = self.get_shaper();
-        do self.shape_cache.find_or_create(&text) |txt| {
-            let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
-            shaper.shape_text(*txt, &mut glyphs);
+        do self.shape_cache.find_or_create(&(text, is_rtl)) |&(ref txt, is_rtl)| {
+            let mut glyphs = GlyphStore::new(txt.char_len(), is_whitespace);
+            shaper.shape_text(*txt, is_rtl, &mut glyphs);
             Arc::new(glyphs)
         }
     }
 
     pub fn get_descriptor(&self) -> FontDescriptor {
-        FontDescriptor::new(self.style.clone(), SelectorPlatformIdentifier(self.handle.face_identifier()))
+        let selector = match self.web_font {
+            Some(ref source) => SelectorWebFont(source.clone()),
+            None => SelectorPlatformIdentifier(self.handle.face_identifier()),
+        };
+        FontDescriptor::new(self.style.clone(), selector)
     }
 
     pub fn glyph_index(&self, codepoint: char) -> Option<GlyphIndex> {
         self.handle.glyph_index(codepoint)
     }
 
+    /// Whether this font renders the given character: it has a glyph for it and, for a web font,
+    /// the character is in its 'unicode-range'.
+    pub fn covers_char(&self, ch: char) -> bool {
+        let in_range = match self.web_font {
+            Some(ref source) if !source.unicode_range.is_empty() => {
+                source.unicode_range.iter().any(|&(start, end)| start <= ch && ch <= end)
+            }
+            _ => true,
+        };
+        in_range && self.glyph_index(ch).is_some()
+    }
+
     pub fn glyph_h_advance(&self, glyph: GlyphIndex) -> FractionalPixel {
         match self.handle.glyph_h_advance(glyph) {
           Some(adv) => adv,
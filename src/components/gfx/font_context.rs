@@ -2,8 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::{Font, FontDescriptor, FontGroup, FontHandleMethods, FontStyle,
-           SelectorPlatformIdentifier};
+use font::{CSSFontWeight, Font, FontDescriptor, FontGroup, FontHandleMethods, FontStyle,
+           SelectorPlatformIdentifier, SelectorWebFont, WebFontSource};
 use font::{SpecifiedFontStyle, UsedFontStyle};
 use font_list::FontList;
 use servo_util::cache::{Cache, LRUCache};
@@ -13,6 +13,7 @@ use platform::font::FontHandle;
 use platform::font_context::FontContextHandle;
 
 use azure::azure_hl::BackendType;
+use std::ascii::StrAsciiExt;
 use std::hashmap::HashMap;
 
 // TODO(Rust #3934): creating lots of new dummy styles is a workaround
@@ -33,6 +34,15 @@ pub trait FontContextHandleMethods {
     fn create_font_from_identifier(&self, ~str, UsedFontStyle) -> Result<FontHandle, ()>;
 }
 
+/// A font face that a document defined with an `@font-face` rule, and whose data it downloaded.
+#[deriving(Clone)]
+pub struct WebFontFace {
+    family: ~str,
+    weight: CSSFontWeight,
+    italic: bool,
+    source: WebFontSource,
+}
+
 pub struct FontContext {
     instance_cache: LRUCache<FontDescriptor, @mut Font>,
     font_list: Option<FontList>, // only needed by layout
@@ -40,6 +50,12 @@ pub struct FontContext {
     handle: FontContextHandle,
     backend: BackendType,
     generic_fonts: HashMap<~str,~str>,
+    /// The system font family that renders each character that fell back to the system, or
+    /// None if no family has a glyph for it.
+    fallback_families: HashMap<char, Option<~str>>,
+    /// The web fonts of the document, which come before the fonts of the system with the same
+    /// family name.
+    web_fonts: ~[WebFontFace],
     profiler_chan: ProfilerChan,
 }
 
@@ -68,6 +84,8 @@ impl<'self> FontContext {
             handle: handle,
             backend: backend,
             generic_fonts: generic_fonts,
+            fallback_families: HashMap::new(),
+            web_fonts: ~[],
             profiler_chan: profiler_chan,
         }
     }
@@ -110,6 +128,86 @@ impl<'self> FontContext {
         }
     }
 
+    /// Adds a web font that the document downloaded. Font groups are resolved again, since it may
+    /// belong in them.
+    pub fn add_web_font(&mut self, face: WebFontFace) {
+        self.web_fonts.push(face);
+        self.group_cache.evict_all();
+    }
+
+    /// Gives this context the web fonts of another one. Documents only ever add web fonts, so
+    /// their number tells whether there are new ones.
+    pub fn set_web_fonts(&mut self, faces: &[WebFontFace]) {
+        if faces.len() != self.web_fonts.len() {
+            self.web_fonts = faces.to_owned();
+            self.group_cache.evict_all();
+        }
+    }
+
+    /// Returns the fonts of the web fonts of the given family that best match the style: those of
+    /// its boldness and slant, or all of them if none is. A family may have several faces of one
+    /// style, which cover different 'unicode-range's.
+    fn get_web_fonts_for_family(&mut self, family: &str, style: &SpecifiedFontStyle)
+                                -> ~[@mut Font] {
+        let faces: ~[WebFontFace] = self.web_fonts.iter().filter(|face| {
+            face.family.eq_ignore_ascii_case(family)
+        }).map(|face| face.clone()).collect();
+        let is_italic = style.italic || style.oblique;
+        let matches_style = |face: &WebFontFace| {
+            face.weight.is_bold() == style.weight.is_bold() && face.italic == is_italic
+        };
+        let any_matches_style = faces.iter().any(|face| matches_style(face));
+
+        let mut fonts = ~[];
+        for face in faces.iter() {
+            if any_matches_style && !matches_style(face) {
+                loop
+            }
+            let font_desc = FontDescriptor::new((*style).clone(),
+                                                SelectorWebFont(face.source.clone()));
+            match self.get_font_by_descriptor(&font_desc) {
+                Ok(font) => fonts.push(font),
+                Err(()) => debug!("(get web fonts) couldn't read `%s`", face.source.url),
+            }
+        }
+        fonts
+    }
+
+    /// Returns a font of the system in the given style that has a glyph for the given character,
+    /// for characters that no font of the font group for the style can render.
+    pub fn get_fallback_font_for_char(&mut self, style: &SpecifiedFontStyle, ch: char)
+                                      -> Option<@mut Font> {
+        let cached_family = self.fallback_families.find(&ch).map(|family| (*family).clone());
+        let family = match cached_family {
+            Some(family) => family,
+            None => {
+                let family = do self.font_list.and_then_ref |fl| {
+                    fl.find_family_for_char(ch)
+                };
+                self.fallback_families.insert(ch, family.clone());
+                family
+            }
+        };
+
+        let font_entry = match family {
+            None => return None,
+            Some(family) => do self.font_list.and_then_ref |fl| {
+                fl.find_font_in_family(family, style)
+            },
+        };
+        let font_entry = match font_entry {
+            None => return None,
+            Some(font_entry) => font_entry,
+        };
+
+        let font_id = SelectorPlatformIdentifier(font_entry.handle.face_identifier());
+        let font_desc = FontDescriptor::new((*style).clone(), font_id);
+        match self.get_font_by_descriptor(&font_desc) {
+            Ok(font) if font.glyph_index(ch).is_some() => Some(font),
+            _ => None,
+        }
+    }
+
     fn transform_family(&self, family: &str) -> ~str {
         // FIXME: Need a find_like() in HashMap.
         let family = family.to_str();
@@ -128,6 +226,14 @@ impl<'self> FontContext {
         // TODO(Issue #193): make iteration over 'font-family' more robust.
         for family in style.families.split_iter(',') {
             let family_name = family.trim();
+
+            let web_fonts = self.get_web_fonts_for_family(family_name, style);
+            if !web_fonts.is_empty() {
+                debug!("(create font group) found web fonts for `%s`", family_name);
+                fonts.push_all_move(web_fonts);
+                loop
+            }
+
             let transformed_family_name = self.transform_family(family_name);
             debug!("(create font group) transformed family is `%s`", transformed_family_name);
 
@@ -196,6 +302,13 @@ impl<'self> FontContext {
                                                      self.profiler_chan.clone()))
                 }
             }
+            &SelectorWebFont(ref source) => {
+                Font::new_from_web_font(self,
+                                        source,
+                                        &desc.style,
+                                        self.backend,
+                                        self.profiler_chan.clone())
+            }
         };
     }
 }
@@ -18,6 +18,7 @@ pub type FontFamilyMap = HashMap<~str, @mut FontFamily>;
 trait FontListHandleMethods {
     fn get_available_families(&self, fctx: &FontContextHandle) -> FontFamilyMap;
     fn load_variations_for_family(&self, family: @mut FontFamily);
+    fn find_family_for_char(&self, ch: char) -> Option<~str>;
     fn get_last_resort_font_families() -> ~[~str];
 }
 
@@ -87,6 +88,14 @@ impl FontList {
         family.map(|f| **f)
     }
 
+    /// Returns the name of a family of the system that may have a glyph for the given character,
+    /// for text that no font of its font group can render.
+    pub fn find_family_for_char(&self, ch: char) -> Option<~str> {
+        let family = self.handle.find_family_for_char(ch);
+        debug!("FontList: fallback family for %? is %?", ch, family);
+        family
+    }
+
     pub fn get_last_resort_font_families() -> ~[~str] {
         let last_resort = FontListHandle::get_last_resort_font_families();
         last_resort
@@ -27,9 +27,13 @@ pub struct Opts {
     n_render_threads: uint,
 
     /// True to use CPU painting, false to use GPU painting via Skia-GL (`-c`). Note that
-    /// compositing is always done on the GPU.
+    /// compositing is done on the GPU unless `headless` is set.
     cpu_painting: bool,
 
+    /// True to paint and composite on the CPU, without a window or a GPU (`-z`). This implies
+    /// `cpu_painting`, and is only useful with `output_file`.
+    headless: bool,
+
     /// The maximum size of each tile in pixels (`-s`).
     tile_size: uint,
 
@@ -40,6 +44,8 @@ pub struct Opts {
     /// True to exit after the page load (`-x`).
     exit_after_load: bool,
 
+    /// The file to write a PNG image of the first composited frame to, after which Servo exits
+    /// (`-o`).
     output_file: Option<~str>,
 }
 
@@ -56,6 +62,7 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         getopts::optopt("t"),       // threads to render with
         getopts::optflagopt("p"),   // profiler flag and output interval
         getopts::optflag("x"),      // exit after load flag
+        getopts::optflag("z"),      // headless mode
     ];
 
     let opt_match = match getopts::getopts(args, opts) {
@@ -107,13 +114,16 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
 
     let output_file = opt_match.opt_str("o");
 
-    let cpu_painting = opt_match.opt_present("c");
+    let headless = opt_match.opt_present("z");
+
+    let cpu_painting = headless || opt_match.opt_present("c");
 
     Opts {
         urls: urls,
         render_backend: render_backend,
         n_render_threads: n_render_threads,
         cpu_painting: cpu_painting,
+        headless: headless,
         tile_size: tile_size,
         profiler_period: profiler_period,
         exit_after_load: exit_after_load,
@@ -6,7 +6,7 @@ extern mod freetype;
 extern mod fontconfig;
 
 use fontconfig::fontconfig::{
-    FcChar8, FcResultMatch, FcSetSystem, FcPattern,
+    FcChar8, FcChar32, FcResultMatch, FcSetSystem, FcPattern,
     FcResultNoMatch, FcMatchPattern, FC_SLANT_ITALIC, FC_WEIGHT_BOLD
 };
 use fontconfig::fontconfig::{
@@ -14,7 +14,8 @@ use fontconfig::fontconfig::{
     FcPatternDestroy, FcFontSetDestroy, FcConfigSubstitute,
     FcDefaultSubstitute, FcPatternCreate, FcPatternAddString, FcPatternAddInteger,
     FcFontMatch, FcFontSetList, FcObjectSetCreate, FcObjectSetDestroy,
-    FcObjectSetAdd, FcPatternGetInteger
+    FcObjectSetAdd, FcPatternGetInteger, FcCharSetCreate, FcCharSetAddChar,
+    FcCharSetDestroy, FcPatternAddCharSet
 };
 
 
@@ -130,6 +131,52 @@ impl FontListHandle {
         }
     }
 
+    /// Asks fontconfig for the family of the system font that best covers the given character.
+    /// The font that fontconfig matches need not have a glyph for it, so callers check.
+    #[fixed_stack_segment]
+    pub fn find_family_for_char(&self, ch: char) -> Option<~str> {
+        unsafe {
+            let config = FcConfigGetCurrent();
+            let wrapper = AutoPattern { pattern: FcPatternCreate() };
+            let pattern = wrapper.pattern;
+
+            // The pattern keeps its own reference to the character set.
+            let charset = FcCharSetCreate();
+            FcCharSetAddChar(charset, ch as FcChar32);
+            let res = do "charset".to_c_str().with_ref |FC_CHARSET| {
+                FcPatternAddCharSet(pattern, FC_CHARSET, charset)
+            };
+            FcCharSetDestroy(charset);
+            if res != 1 {
+                debug!("adding charset to pattern failed");
+                return None;
+            }
+
+            if FcConfigSubstitute(config, pattern, FcMatchPattern) != 1 {
+                debug!("substitution failed");
+                return None;
+            }
+            FcDefaultSubstitute(pattern);
+            let result = FcResultNoMatch;
+            let result_wrapper = AutoPattern { pattern: FcFontMatch(config, pattern, &result) };
+            let result_pattern = result_wrapper.pattern;
+            if result != FcResultMatch || result_pattern.is_null() {
+                debug!("obtaining match to pattern failed");
+                return None;
+            }
+
+            let family: *FcChar8 = ptr::null();
+            let res = do "family".to_c_str().with_ref |FC_FAMILY| {
+                FcPatternGetString(result_pattern, FC_FAMILY, 0, &family)
+            };
+            if res != FcResultMatch {
+                debug!("getting family for font failed");
+                return None;
+            }
+            Some(str::raw::from_c_str(family as *c_char))
+        }
+    }
+
     pub fn get_last_resort_font_families() -> ~[~str] {
         ~[~"Arial"]
     }
@@ -56,6 +56,14 @@ impl FontListHandle {
         }
     }
 
+    /// Returns the family of a system font that covers the given character.
+    ///
+    /// TODO: Ask Core Text with `CTFontCreateForString`. Until then, the last resort families,
+    /// which cover most of Unicode, are the only fallback.
+    pub fn find_family_for_char(&self, _: char) -> Option<~str> {
+        None
+    }
+
     pub fn get_last_resort_font_families() -> ~[~str] {
         ~[~"Arial Unicode MS",~"Arial"]
     }
@@ -3,10 +3,10 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use servo_msg::compositor_msg::LayerBuffer;
+use display_list::BorderRadii;
 use font_context::FontContext;
-use geometry::Au;
-use newcss::values::CSSBorderStyle;
-use newcss::values::{CSSBorderStyleNone, CSSBorderStyleHidden, CSSBorderStyleDotted, CSSBorderStyleDashed, CSSBorderStyleSolid, CSSBorderStyleDouble, CSSBorderStyleGroove, CSSBorderStyleRidge, CSSBorderStyleInset, CSSBorderStyleOutset};
+use geometry::{Au, to_frac_px};
+use style::computed_values::border_style;
 use opts::Opts;
 
 use azure::azure_hl::{B8G8R8A8, Color, ColorPattern, DrawOptions};
@@ -14,6 +14,7 @@ use azure::azure_hl::{DrawSurfaceOptions, DrawTarget, Linear, StrokeOptions};
 use azure::{AZ_CAP_BUTT, AZ_CAP_ROUND};
 use azure::AZ_JOIN_BEVEL;
 use azure::AzFloat;
+use std::f64;
 use std::vec;
 use std::libc::types::common::c99::uint16_t;
 use std::libc::size_t;
@@ -48,7 +49,7 @@ impl<'self> RenderContext<'self>  {
                        bounds: &Rect<Au>,
                        border: SideOffsets2D<Au>,
                        color: SideOffsets2D<Color>,
-                       style: SideOffsets2D<CSSBorderStyle>) {
+                       style: SideOffsets2D<border_style::ComputedValue>) {
         let draw_opts = DrawOptions(1 as AzFloat, 0 as uint16_t);
         let rect = bounds.to_azure_rect();
         let border = border.to_float_px();
@@ -102,6 +103,164 @@ impl<'self> RenderContext<'self>  {
                                      &draw_opts);
     }
 
+    /// Fills a rectangle with rounded corners.
+    pub fn draw_rounded_solid_color(&self,
+                                    bounds: &Rect<Au>,
+                                    color: Color,
+                                    radii: &BorderRadii<Au>) {
+        self.draw_target.make_current();
+        self.push_polygon_clip(&[rounded_rect_points(bounds, radii, true)]);
+        self.draw_target.fill_rect(&bounds.to_azure_rect(), &ColorPattern(color));
+        self.draw_target.pop_clip();
+    }
+
+    /// Draws a border whose outer edge has rounded corners.
+    ///
+    /// TODO: Dotted and dashed rounded borders, which are drawn solid for now.
+    pub fn draw_rounded_border(&self,
+                               bounds: &Rect<Au>,
+                               border: SideOffsets2D<Au>,
+                               color: SideOffsets2D<Color>,
+                               _: SideOffsets2D<border_style::ComputedValue>,
+                               radii: &BorderRadii<Au>) {
+        let inner_bounds = Rect(Point2D(bounds.origin.x + border.left,
+                                        bounds.origin.y + border.top),
+                                Size2D(bounds.size.width - border.left - border.right,
+                                       bounds.size.height - border.top - border.bottom));
+        let inner_radii = radii.inner_radii(&border);
+
+        self.draw_target.make_current();
+
+        // The border is the area between the two edges. Each side of it reaches as far as the
+        // lines from the outer corners of the bounds to the inner ones.
+        self.push_polygon_clip(&[rounded_rect_points(bounds, radii, true),
+                                 rounded_rect_points(&inner_bounds, &inner_radii, false)]);
+        let outer = bounds.to_azure_rect();
+        let inner = inner_bounds.to_azure_rect();
+        let (outer_left, outer_top) = (outer.origin.x, outer.origin.y);
+        let (outer_right, outer_bottom) = (outer.origin.x + outer.size.width,
+                                           outer.origin.y + outer.size.height);
+        let (inner_left, inner_top) = (inner.origin.x, inner.origin.y);
+        let (inner_right, inner_bottom) = (inner.origin.x + inner.size.width,
+                                           inner.origin.y + inner.size.height);
+        let sides = [
+            (~[Point2D(outer_left, outer_top), Point2D(outer_right, outer_top),
+               Point2D(inner_right, inner_top), Point2D(inner_left, inner_top)], color.top),
+            (~[Point2D(outer_right, outer_top), Point2D(outer_right, outer_bottom),
+               Point2D(inner_right, inner_bottom), Point2D(inner_right, inner_top)], color.right),
+            (~[Point2D(outer_right, outer_bottom), Point2D(outer_left, outer_bottom),
+               Point2D(inner_left, inner_bottom), Point2D(inner_right, inner_bottom)],
+             color.bottom),
+            (~[Point2D(outer_left, outer_bottom), Point2D(outer_left, outer_top),
+               Point2D(inner_left, inner_top), Point2D(inner_left, inner_bottom)], color.left),
+        ];
+        for &(ref points, side_color) in sides.iter() {
+            self.push_polygon_clip(&[points.clone()]);
+            self.draw_target.fill_rect(&outer, &ColorPattern(side_color));
+            self.draw_target.pop_clip();
+        }
+        self.draw_target.pop_clip();
+    }
+
+    /// Draws the shadow of a box. An outer shadow is drawn around the border box `box_bounds` and
+    /// an inset one inside the padding box `box_bounds`, in both cases only as far as `bounds`.
+    ///
+    /// The blur is approximated by layers of the shadow, each a little smaller than the last and
+    /// translucent enough that they add up to the color of the shadow where they all overlap.
+    pub fn draw_box_shadow(&self,
+                           bounds: &Rect<Au>,
+                           box_bounds: &Rect<Au>,
+                           radii: &BorderRadii<Au>,
+                           color: Color,
+                           offset: Point2D<Au>,
+                           blur_radius: Au,
+                           spread_radius: Au,
+                           inset: bool) {
+        let spread_radius = if inset { -spread_radius } else { spread_radius };
+        let shadow_bounds = inflate_rect(&box_bounds.translate(&offset), spread_radius);
+        let shadow_radii = inflate_radii(radii, spread_radius);
+
+        let layer_count = if blur_radius > Au(0) {
+            blur_radius.to_nearest_px().max(&1).min(&MAX_BLUR_LAYERS)
+        } else {
+            1
+        };
+        let layer_alpha = 1.0 - (1.0 - color.a as f64).pow(&(1.0 / (layer_count as f64)));
+        let layer_pattern = ColorPattern(Color(color.r, color.g, color.b, layer_alpha as AzFloat));
+        // The layers go from `blur_radius` outside the shadow to `blur_radius` inside it.
+        let layer_outset = |layer: int| {
+            blur_radius.scale_by(1.0 - ((2 * layer + 1) as f64) / (layer_count as f64))
+        };
+
+        self.draw_target.make_current();
+        let rect = bounds.to_azure_rect();
+        let bounds_points = rounded_rect_points(bounds, &BorderRadii::zero(), true);
+        if !inset {
+            // The shadow is not drawn under the box.
+            self.push_polygon_clip(&[bounds_points.clone(),
+                                     rounded_rect_points(box_bounds, radii, false)]);
+        } else {
+            self.push_polygon_clip(&[rounded_rect_points(box_bounds, radii, true)]);
+        }
+        for layer in range(0, layer_count) {
+            let outset = layer_outset(layer);
+            let layer_bounds = inflate_rect(&shadow_bounds, outset);
+            let layer_radii = inflate_radii(&shadow_radii, outset);
+            if !inset {
+                self.push_polygon_clip(&[rounded_rect_points(&layer_bounds, &layer_radii, true)]);
+            } else {
+                // An inset shadow is drawn around a hole the shape of the box.
+                self.push_polygon_clip(&[bounds_points.clone(),
+                                         rounded_rect_points(&layer_bounds, &layer_radii, false)]);
+            }
+            self.draw_target.fill_rect(&rect, &layer_pattern);
+            self.draw_target.pop_clip();
+        }
+        self.draw_target.pop_clip();
+    }
+
+    /// Restricts drawing to the area inside the given polygons until the next call to
+    /// `pop_clip`. A polygon whose points go the other way round from those of a polygon around it
+    /// cuts a hole into it.
+    fn push_polygon_clip(&self, polygons: &[~[Point2D<AzFloat>]]) {
+        let path_builder = self.draw_target.create_path_builder();
+        for points in polygons.iter() {
+            for (i, point) in points.iter().enumerate() {
+                if i == 0 {
+                    path_builder.move_to(*point)
+                } else {
+                    path_builder.line_to(*point)
+                }
+            }
+        }
+        let path = path_builder.finish();
+        self.draw_target.push_clip(&path);
+    }
+
+    /// Restricts drawing to the given rectangle until the matching call to `draw_pop_clip`.
+    pub fn draw_push_clip(&self, bounds: &Rect<Au>) {
+        let rect = bounds.to_azure_rect();
+        let path_builder = self.draw_target.create_path_builder();
+
+        let left = rect.origin.x;
+        let top = rect.origin.y;
+        let right = rect.origin.x + rect.size.width;
+        let bottom = rect.origin.y + rect.size.height;
+
+        path_builder.move_to(Point2D(left, top));
+        path_builder.line_to(Point2D(right, top));
+        path_builder.line_to(Point2D(right, bottom));
+        path_builder.line_to(Point2D(left, bottom));
+
+        let path = path_builder.finish();
+        self.draw_target.push_clip(&path);
+    }
+
+    /// Undoes the most recent call to `draw_push_clip`.
+    pub fn draw_pop_clip(&self) {
+        self.draw_target.pop_clip();
+    }
+
     pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<~Image>) {
         let image = image.get();
         let size = Size2D(image.width as i32, image.height as i32);
@@ -123,6 +282,76 @@ impl<'self> RenderContext<'self>  {
                                      draw_options);
     }
 
+    /// Draws copies of the image in `tile` and in the rectangles of the same size next to it in the
+    /// directions in which it repeats, as far as they cover `bounds`. Nothing is drawn outside
+    /// `bounds`.
+    pub fn draw_tiled_image(&self,
+                            bounds: &Rect<Au>,
+                            tile: &Rect<Au>,
+                            repeat_x: bool,
+                            repeat_y: bool,
+                            image: Arc<~Image>) {
+        if tile.size.width <= Au(0) || tile.size.height <= Au(0) {
+            return
+        }
+
+        // The first copy in each direction is the one that reaches over the top left corner of
+        // the bounds.
+        let first_tile_origin = |start: Au, bound: Au, size: Au, repeat: bool| {
+            if !repeat {
+                start
+            } else {
+                let offset = (start - bound) % size;
+                if offset > Au(0) { bound + offset - size } else { bound + offset }
+            }
+        };
+        let x_start = first_tile_origin(tile.origin.x, bounds.origin.x, tile.size.width,
+                                        repeat_x);
+        let y_start = first_tile_origin(tile.origin.y, bounds.origin.y, tile.size.height,
+                                        repeat_y);
+        let (x_end, y_end) = (bounds.origin.x + bounds.size.width,
+                              bounds.origin.y + bounds.size.height);
+
+        let image = image.get();
+        let size = Size2D(image.width as i32, image.height as i32);
+        let stride = image.width * 4;
+
+        self.draw_target.make_current();
+        self.draw_push_clip(bounds);
+        let draw_target_ref = &self.draw_target;
+        let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
+                               Size2D(image.width as AzFloat, image.height as AzFloat));
+        let draw_surface_options = DrawSurfaceOptions(Linear, true);
+        let draw_options = DrawOptions(1.0f64 as AzFloat, 0);
+
+        let mut y = y_start;
+        while y < y_end {
+            let mut x = x_start;
+            while x < x_end {
+                // FIXME: The surface could be shared by all the copies.
+                let azure_surface = draw_target_ref.create_source_surface_from_data(image.data,
+                                                                                    size,
+                                                                                    stride as i32,
+                                                                                    B8G8R8A8);
+                let dest_rect = Rect(Point2D(x, y), tile.size).to_azure_rect();
+                draw_target_ref.draw_surface(azure_surface,
+                                             dest_rect,
+                                             source_rect,
+                                             draw_surface_options,
+                                             draw_options);
+                if !repeat_x {
+                    break
+                }
+                x = x + tile.size.width
+            }
+            if !repeat_y {
+                break
+            }
+            y = y + tile.size.height
+        }
+        self.draw_pop_clip();
+    }
+
     pub fn clear(&self) {
         let pattern = ColorPattern(Color(1.0, 1.0, 1.0, 1.0));
         let rect = Rect(Point2D(self.page_rect.origin.x as AzFloat,
@@ -133,14 +362,25 @@ impl<'self> RenderContext<'self>  {
         self.draw_target.fill_rect(&rect, &pattern);
     }
 
-    fn apply_border_style(style: CSSBorderStyle, border_width: AzFloat, dash: &mut [AzFloat], stroke_opts: &mut StrokeOptions){
+    /// Clears the area of this context to transparent black, for layers that are composited
+    /// over other layers.
+    pub fn clear_transparent(&self) {
+        let rect = Rect(Point2D(self.page_rect.origin.x as AzFloat,
+                                self.page_rect.origin.y as AzFloat),
+                        Size2D(self.screen_rect.size.width as AzFloat,
+                               self.screen_rect.size.height as AzFloat));
+        self.draw_target.make_current();
+        self.draw_target.clear_rect(&rect);
+    }
+
+    fn apply_border_style(style: border_style::ComputedValue, border_width: AzFloat, dash: &mut [AzFloat], stroke_opts: &mut StrokeOptions){
         match style{
-            CSSBorderStyleNone => {
+            border_style::none => {
             }
-            CSSBorderStyleHidden => {
+            border_style::hidden => {
             }
             //FIXME(sammykim): This doesn't work with dash_pattern and cap_style well. I referred firefox code.
-            CSSBorderStyleDotted => {
+            border_style::dotted => {
                 stroke_opts.line_width = border_width;
                 
                 if border_width > 2.0 {
@@ -155,7 +395,7 @@ impl<'self> RenderContext<'self>  {
                 stroke_opts.mDashPattern = vec::raw::to_ptr(dash);
                 stroke_opts.mDashLength = dash.len() as size_t;
             }
-            CSSBorderStyleDashed => {
+            border_style::dashed => {
                 stroke_opts.set_cap_style(AZ_CAP_BUTT as u8);
                 stroke_opts.line_width = border_width;
                 dash[0] = border_width*3 as AzFloat;
@@ -164,26 +404,26 @@ impl<'self> RenderContext<'self>  {
                 stroke_opts.mDashLength = dash.len() as size_t;
             }
             //FIXME(sammykim): BorderStyleSolid doesn't show proper join-style with comparing firefox.
-            CSSBorderStyleSolid => {
+            border_style::solid => {
                 stroke_opts.set_cap_style(AZ_CAP_BUTT as u8);
                 stroke_opts.set_join_style(AZ_JOIN_BEVEL as u8);
                 stroke_opts.line_width = border_width; 
                 stroke_opts.mDashLength = 0 as size_t;
             }            
             //FIXME(sammykim): Five more styles should be implemented.
-            CSSBorderStyleDouble => {
+            border_style::double => {
 
             }
-            CSSBorderStyleGroove => {
+            border_style::groove => {
 
             }
-            CSSBorderStyleRidge => {
+            border_style::ridge => {
 
             }
-            CSSBorderStyleInset => {
+            border_style::inset => {
 
             }
-            CSSBorderStyleOutset => {
+            border_style::outset => {
 
             }
         }
@@ -200,6 +440,79 @@ impl to_float for u8 {
     }
 }
 
+/// The largest number of layers in which a blurred shadow is drawn.
+static MAX_BLUR_LAYERS: int = 16;
+
+/// The number of line segments that approximate the curve of a rounded corner.
+static SEGMENTS_PER_CORNER: uint = 8;
+
+/// Returns the points of a polygon that approximates a rectangle with rounded corners, clockwise
+/// or counterclockwise from the top left corner. Radii larger than half the rectangle are limited
+/// to it.
+fn rounded_rect_points(rect: &Rect<Au>, radii: &BorderRadii<Au>, clockwise: bool)
+                       -> ~[Point2D<AzFloat>] {
+    let rect = rect.to_azure_rect();
+    let width = rect.size.width.max(&0.0);
+    let height = rect.size.height.max(&0.0);
+    let (left, top) = (rect.origin.x, rect.origin.y);
+    let (right, bottom) = (left + width, top + height);
+
+    // Each corner is a quarter of an ellipse around a center inside the rectangle, which starts
+    // at `start_angle` in clockwise order.
+    let corners: [(Size2D<Au>, AzFloat, AzFloat, AzFloat, AzFloat, f64), ..4] = [
+        (radii.top_left, left, top, 1.0, 1.0, f64::consts::pi),
+        (radii.top_right, right, top, -1.0, 1.0, 1.5 * f64::consts::pi),
+        (radii.bottom_right, right, bottom, -1.0, -1.0, 0.0),
+        (radii.bottom_left, left, bottom, 1.0, -1.0, 0.5 * f64::consts::pi),
+    ];
+    let mut points = ~[];
+    for &(radius, corner_x, corner_y, x_direction, y_direction, start_angle) in corners.iter() {
+        let radius_x = (to_frac_px(radius.width) as AzFloat).min(&(width / 2.0));
+        let radius_y = (to_frac_px(radius.height) as AzFloat).min(&(height / 2.0));
+        if radius_x <= 0.0 || radius_y <= 0.0 {
+            points.push(Point2D(corner_x, corner_y));
+            loop
+        }
+        let center_x = corner_x + x_direction * radius_x;
+        let center_y = corner_y + y_direction * radius_y;
+        for i in range(0, SEGMENTS_PER_CORNER + 1) {
+            let angle = start_angle +
+                (i as f64) / (SEGMENTS_PER_CORNER as f64) * 0.5 * f64::consts::pi;
+            points.push(Point2D(center_x + radius_x * (angle.cos() as AzFloat),
+                                center_y + radius_y * (angle.sin() as AzFloat)));
+        }
+    }
+    if !clockwise {
+        points.reverse()
+    }
+    points
+}
+
+/// Grows a rectangle by `amount` on each side, or shrinks it if `amount` is negative.
+fn inflate_rect(rect: &Rect<Au>, amount: Au) -> Rect<Au> {
+    Rect(Point2D(rect.origin.x - amount, rect.origin.y - amount),
+         Size2D(Au::max(rect.size.width + amount + amount, Au(0)),
+                Au::max(rect.size.height + amount + amount, Au(0))))
+}
+
+/// Grows the rounded corners of a rectangle that is inflated by `amount`. Square corners stay
+/// square.
+fn inflate_radii(radii: &BorderRadii<Au>, amount: Au) -> BorderRadii<Au> {
+    let inflate = |radius: Size2D<Au>| {
+        if radius.width == Au(0) || radius.height == Au(0) {
+            radius
+        } else {
+            Size2D(Au::max(radius.width + amount, Au(0)), Au::max(radius.height + amount, Au(0)))
+        }
+    };
+    BorderRadii {
+        top_left: inflate(radii.top_left),
+        top_right: inflate(radii.top_right),
+        bottom_right: inflate(radii.bottom_right),
+        bottom_left: inflate(radii.bottom_left),
+    }
+}
+
 trait ToAzureRect {
     fn to_azure_rect(&self) -> Rect<AzFloat>;
 }
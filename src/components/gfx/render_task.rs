@@ -10,6 +10,7 @@ use azure::{AzFloat, AzGLContext, AzGLContextMetadataRef};
 use display_list::DisplayList;
 use font_context::FontContext;
 use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use layers::layers::ARGB32Format;
@@ -17,12 +18,15 @@ use layers::texturegl::{Texture, TextureImageData, TextureTargetRectangle};
 use layers::platform::surface::{NativeGraphicsMetadata, NativePaintingGraphicsContext};
 use layers::platform::surface::{NativeSurface, NativeSurfaceMethods};
 use layers;
-use servo_msg::compositor_msg::{LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{CpuLayerBufferSurface, LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{LayerId, NativeLayerBufferSurface, PageLayerId, ScrollLayerId};
+use servo_msg::compositor_msg::{ScrollLayerInfo};
 use servo_msg::compositor_msg::{RenderListener, IdleRenderState, RenderingRenderState, LayerBuffer};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, RendererReadyMsg};
 use servo_msg::platform::surface::NativeSurfaceAzureMethods;
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
+use servo_util::range::Range;
 
 use std::comm::{Chan, Port, SharedChan};
 use std::task::spawn_with;
@@ -32,17 +36,61 @@ use extra::arc::Arc;
 use buffer_map::BufferMap;
 use display_list::DisplayList;
 use font_context::FontContext;
+use geometry::{Au, to_frac_px};
 use opts::Opts;
 use render_context::RenderContext;
 
 pub struct RenderLayer<T> {
     display_list: Arc<DisplayList<T>>,
-    size: Size2D<uint>
+    size: Size2D<uint>,
+    /// The boxes whose contents scroll independently and the fixed-position elements, in the order
+    /// of their items in the display list. Their items are drawn in layers of their own rather
+    /// than in the page.
+    scroll_layers: ~[ScrollLayer],
+    /// The parts of the page that changed since the last display list, or `None` if all of it has
+    /// to be rendered again.
+    damaged_rects: Option<~[Rect<Au>]>,
+}
+
+/// A box whose contents scroll independently of the rest of the page, or a fixed-position element,
+/// which does not scroll with it.
+pub struct ScrollLayer {
+    /// The address of the node of the box, which identifies the layer across reflows.
+    id: uint,
+    /// The ID of the scroll layer that the box is drawn in, or `None` if it is drawn in the page.
+    parent: Option<uint>,
+    /// The items of the display list that make up the contents of the box.
+    range: Range,
+    /// The padding box of the box, in page coordinates, or the bounds of the items of a
+    /// fixed-position element, in viewport coordinates. The contents are drawn relative to its top
+    /// left corner.
+    rect: Rect<Au>,
+    /// The size of the contents of the box.
+    size: Size2D<Au>,
+    /// Whether this is the layer of a fixed-position element.
+    fixed_position: bool,
+}
+
+impl ScrollLayer {
+    /// Returns what the compositor needs to know to place this layer.
+    fn info(&self) -> ScrollLayerInfo {
+        ScrollLayerInfo {
+            id: self.id,
+            parent: self.parent,
+            rect: Rect(Point2D(to_frac_px(self.rect.origin.x) as f32,
+                               to_frac_px(self.rect.origin.y) as f32),
+                       Size2D(to_frac_px(self.rect.size.width) as f32,
+                              to_frac_px(self.rect.size.height) as f32)),
+            page_size: Size2D(to_frac_px(self.size.width) as f32,
+                              to_frac_px(self.size.height) as f32),
+            fixed_position: self.fixed_position,
+        }
+    }
 }
 
 pub enum Msg<T> {
     RenderMsg(RenderLayer<T>),
-    ReRenderMsg(~[BufferRequest], f32, Epoch),
+    ReRenderMsg(LayerId, ~[BufferRequest], f32, Epoch),
     UnusedBufferMsg(~[~LayerBuffer]),
     PaintPermissionGranted,
     PaintPermissionRevoked,
@@ -95,6 +143,8 @@ impl<T: Send> GenericSmartChan<Msg<T>> for RenderChan<T> {
 enum GraphicsContext {
     CpuGraphicsContext(NativeGraphicsMetadata),
     GpuGraphicsContext(NativeGraphicsMetadata),
+    /// The compositor is headless: we render on the CPU and hand it the pixels in memory.
+    HeadlessGraphicsContext,
 }
 
 pub struct RenderTask<C,T> {
@@ -111,8 +161,8 @@ pub struct RenderTask<C,T> {
     /// The graphics context to use.
     graphics_context: GraphicsContext,
 
-    /// The native graphics context.
-    native_graphics_context: NativePaintingGraphicsContext,
+    /// The native graphics context, or `None` if the compositor is headless.
+    native_graphics_context: Option<NativePaintingGraphicsContext>,
 
     /// The layer to be rendered
     render_layer: Option<RenderLayer<T>>,
@@ -142,8 +192,9 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
             let graphics_metadata = compositor.get_graphics_metadata();
             let cpu_painting = opts.cpu_painting;
-            let native_graphics_context =
-                    NativePaintingGraphicsContext::from_metadata(&graphics_metadata);
+            let native_graphics_context = do graphics_metadata.map |graphics_metadata| {
+                NativePaintingGraphicsContext::from_metadata(graphics_metadata)
+            };
 
             // FIXME: rust/#5967
             let mut render_task = RenderTask {
@@ -157,10 +208,15 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                 opts: opts,
                 profiler_chan: profiler_chan,
 
-                graphics_context: if cpu_painting {
-                    CpuGraphicsContext(graphics_metadata)
-                } else {
-                    GpuGraphicsContext(graphics_metadata)
+                graphics_context: match graphics_metadata {
+                    None => HeadlessGraphicsContext,
+                    Some(graphics_metadata) => {
+                        if cpu_painting {
+                            CpuGraphicsContext(graphics_metadata)
+                        } else {
+                            GpuGraphicsContext(graphics_metadata)
+                        }
+                    }
                 },
 
                 native_graphics_context: native_graphics_context,
@@ -176,7 +232,12 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             render_task.start();
 
             // Destroy all the buffers.
-            render_task.buffer_map.clear(&render_task.native_graphics_context)
+            match render_task.native_graphics_context {
+                Some(ref native_graphics_context) => {
+                    render_task.buffer_map.clear(native_graphics_context)
+                }
+                None => {}
+            }
         }
     }
 
@@ -186,36 +247,49 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
         loop {
             match self.port.recv() {
                 RenderMsg(render_layer) => {
+                    let had_render_layer = self.render_layer.is_some();
+                    self.render_layer = Some(render_layer);
+                    self.last_paint_msg = None;
                     if self.paint_permission {
-                        self.epoch.next();
-                        self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                        // The layer keeps its tiles if only parts of the page changed, and the
+                        // compositor asks for those parts again in the same epoch.
+                        match self.render_layer.get_ref().damaged_rects {
+                            Some(ref rects) if had_render_layer => {
+                                self.invalidate_rects(rects.as_slice())
+                            }
+                            _ => {
+                                self.epoch.next();
+                                self.send_layer_sizes();
+                            }
+                        }
                     } else {
                         self.constellation_chan.send(RendererReadyMsg(self.id));
                     }
-                    self.render_layer = Some(render_layer);
-                    self.last_paint_msg = None;
                 }
-                ReRenderMsg(tiles, scale, epoch) => {
+                ReRenderMsg(layer_id, tiles, scale, epoch) => {
                     if self.epoch == epoch {
-                        self.render(tiles, scale);
+                        self.render(layer_id, tiles, scale);
                     } else {
                         debug!("renderer epoch mismatch: %? != %?", self.epoch, epoch);
                     }
                 }
                 UnusedBufferMsg(unused_buffers) => {
-                    // move_rev_iter is more efficient
-                    for buffer in unused_buffers.move_rev_iter() {
-                        self.buffer_map.insert(&self.native_graphics_context, buffer);
+                    match self.native_graphics_context {
+                        Some(ref native_graphics_context) => {
+                            // move_rev_iter is more efficient
+                            for buffer in unused_buffers.move_rev_iter() {
+                                self.buffer_map.insert(native_graphics_context, buffer);
+                            }
+                        }
+                        // Buffers in memory are not worth keeping; we make new ones instead.
+                        None => {}
                     }
                 }
                 PaintPermissionGranted => {
                     self.paint_permission = true;
-                    match self.render_layer {
-                        Some(ref render_layer) => {
-                            self.epoch.next();
-                            self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
-                        }
-                        None => {}
+                    if self.render_layer.is_some() {
+                        self.epoch.next();
+                        self.send_layer_sizes();
                     }
                     // FIXME: This sends the last paint request, anticipating what
                     // the compositor will ask for. However, even if it sends the right
@@ -224,7 +298,10 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                     let last_paint_msg = util::replace(&mut self.last_paint_msg, None);
                     match last_paint_msg {
                         Some(layer_buffer_set) => {
-                            self.compositor.paint(self.id, layer_buffer_set, self.epoch);
+                            self.compositor.paint(self.id,
+                                                  PageLayerId,
+                                                  layer_buffer_set,
+                                                  self.epoch);
                         }
                         None => {} // Nothing to do
                     }
@@ -240,7 +317,35 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
         }
     }
 
-    fn render(&mut self, tiles: ~[BufferRequest], scale: f32) {
+    /// Tells the compositor the size of the page and where its scroll layers go.
+    /// Tells the compositor that the given parts of the page have to be rendered again.
+    fn invalidate_rects(&self, rects: &[Rect<Au>]) {
+        for rect in rects.iter() {
+            let left = to_frac_px(rect.origin.x).floor().max(&0.0);
+            let top = to_frac_px(rect.origin.y).floor().max(&0.0);
+            let right = to_frac_px(rect.origin.x + rect.size.width).ceil().max(&left);
+            let bottom = to_frac_px(rect.origin.y + rect.size.height).ceil().max(&top);
+            self.compositor.invalidate_rect(self.id,
+                                            Rect(Point2D(left as uint, top as uint),
+                                                 Size2D((right - left) as uint,
+                                                        (bottom - top) as uint)));
+        }
+    }
+
+    fn send_layer_sizes(&self) {
+        match self.render_layer {
+            Some(ref render_layer) => {
+                self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                let scroll_layers = render_layer.scroll_layers.iter().map(|scroll_layer| {
+                    scroll_layer.info()
+                }).collect();
+                self.compositor.set_scroll_layers(self.id, scroll_layers, self.epoch);
+            }
+            None => {}
+        }
+    }
+
+    fn render(&mut self, layer_id: LayerId, tiles: ~[BufferRequest], scale: f32) {
         let render_layer;
         match self.render_layer {
             Some(ref r_layer) => {
@@ -249,6 +354,17 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             _ => return, // nothing to do
         }
 
+        // The scroll layer may be gone if the page was laid out again.
+        let scroll_layer = match layer_id {
+            PageLayerId => None,
+            ScrollLayerId(id) => {
+                match render_layer.scroll_layers.iter().find(|scroll_layer| scroll_layer.id == id) {
+                    None => return,
+                    scroll_layer => scroll_layer,
+                }
+            }
+        };
+
         self.compositor.set_render_state(RenderingRenderState);
         do time::profile(time::RenderingCategory, self.profiler_chan.clone()) {
             // FIXME: Try not to create a new array here.
@@ -262,14 +378,14 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
                     let size = Size2D(width as i32, height as i32);
                     let draw_target = match self.graphics_context {
-                        CpuGraphicsContext(_) => {
+                        CpuGraphicsContext(_) | HeadlessGraphicsContext => {
                             DrawTarget::new(self.opts.render_backend, size, B8G8R8A8)
                         }
                         GpuGraphicsContext(_) => {
                             // FIXME(pcwalton): Cache the components of draw targets
                             // (texture color buffer, renderbuffers) instead of recreating them.
                             DrawTarget::new_with_fbo(self.opts.render_backend,
-                                                     &self.native_graphics_context,
+                                                     self.native_graphics_context.get_ref(),
                                                      size,
                                                      B8G8R8A8)
                         }
@@ -277,7 +393,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
                     // Make the appropriate context current.
                     match self.graphics_context {
-                        CpuGraphicsContext(_) => {}
+                        CpuGraphicsContext(_) | HeadlessGraphicsContext => {}
                         GpuGraphicsContext(*) => draw_target.make_current(),
                     }
 
@@ -299,18 +415,48 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                         
                         ctx.draw_target.set_transform(&matrix);
                         
-                        // Clear the buffer.
-                        ctx.clear();
-                        
                         // Draw the display list.
                         do profile(time::RenderingDrawingCategory, self.profiler_chan.clone()) {
-                            render_layer.display_list.get().draw_into_context(&ctx);
+                            let display_list = render_layer.display_list.get();
+                            match scroll_layer {
+                                None => {
+                                    ctx.clear();
+
+                                    // Leave out the contents of the scroll layers.
+                                    let mut start = 0;
+                                    for scroll_layer in render_layer.scroll_layers.iter() {
+                                        let length = scroll_layer.range.begin() - start;
+                                        display_list.draw_range_into_context(
+                                            &ctx,
+                                            &Range::new(start, length));
+                                        start = scroll_layer.range.end();
+                                    }
+                                    let length = display_list.len() - start;
+                                    display_list.draw_range_into_context(&ctx,
+                                                                         &Range::new(start,
+                                                                                     length));
+                                }
+                                Some(scroll_layer) => {
+                                    // The layer is composited over the page, which shows through
+                                    // wherever the contents leave it transparent.
+                                    ctx.clear_transparent();
+
+                                    let origin = scroll_layer.rect.origin;
+                                    let matrix = matrix.translate(
+                                        -to_frac_px(origin.x) as AzFloat,
+                                        -to_frac_px(origin.y) as AzFloat);
+                                    ctx.draw_target.set_transform(&matrix);
+                                    display_list.draw_range_into_context(&ctx,
+                                                                         &scroll_layer.range);
+                                }
+                            }
                             ctx.draw_target.flush();
                         }
                     }
 
                     // Extract the texture from the draw target and place it into its slot in the
-                    // buffer. If using CPU rendering, upload it first.
+                    // buffer. If using CPU rendering, upload it first, unless the compositor is
+                    // headless, in which case it takes the pixels as they are.
                     //
                     // FIXME(pcwalton): We should supply the texture and native surface *to* the
                     // draw target in GPU rendering mode, so that it doesn't have to recreate it.
@@ -322,7 +468,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                                     buffer.rect = tile.page_rect;
                                     buffer.screen_pos = tile.screen_rect;
                                     buffer.resolution = scale;
-                                    buffer.native_surface.mark_wont_leak();
+                                    buffer.surface.mark_wont_leak();
                                     buffer
                                 }
                                 None => {
@@ -330,13 +476,13 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                                     // in case it dies in transit to the compositor task.
                                     let mut native_surface: NativeSurface =
                                         layers::platform::surface::NativeSurfaceMethods::new(
-                                            &self.native_graphics_context,
+                                            self.native_graphics_context.get_ref(),
                                             Size2D(width as i32, height as i32),
                                             width as i32 * 4);
                                     native_surface.mark_wont_leak();
 
                                     ~LayerBuffer {
-                                        native_surface: native_surface,
+                                        surface: NativeLayerBufferSurface(native_surface),
                                         rect: tile.page_rect,
                                         screen_pos: tile.screen_rect,
                                         resolution: scale,
@@ -346,13 +492,36 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                             };
 
                             do draw_target.snapshot().get_data_surface().with_data |data| {
-                                buffer.native_surface.upload(&self.native_graphics_context, data);
-                                debug!("RENDERER uploading to native surface %d",
-                                       buffer.native_surface.get_id() as int);
+                                match buffer.surface {
+                                    NativeLayerBufferSurface(ref native_surface) => {
+                                        native_surface.upload(
+                                            self.native_graphics_context.get_ref(),
+                                            data);
+                                        debug!("RENDERER uploading to native surface %d",
+                                               native_surface.get_id() as int);
+                                    }
+                                    CpuLayerBufferSurface(_) => {
+                                        fail!("render_task: pixels in memory in the buffer map")
+                                    }
+                                }
                             }
 
                             buffer
                         }
+                        HeadlessGraphicsContext => {
+                            let mut pixels = ~[];
+                            do draw_target.snapshot().get_data_surface().with_data |data| {
+                                pixels = data.to_owned();
+                            }
+
+                            ~LayerBuffer {
+                                surface: CpuLayerBufferSurface(pixels),
+                                rect: tile.page_rect,
+                                screen_pos: tile.screen_rect,
+                                resolution: scale,
+                                stride: (width * 4) as uint
+                            }
+                        }
                         GpuGraphicsContext(ref graphics_metadata) => {
                             draw_target.make_current();
                             let StolenGLResources {
@@ -366,7 +535,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                             native_surface.mark_wont_leak();
 
                             ~LayerBuffer {
-                                native_surface: native_surface,
+                                surface: NativeLayerBufferSurface(native_surface),
                                 rect: tile.page_rect,
                                 screen_pos: tile.screen_rect,
                                 resolution: scale,
@@ -385,7 +554,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
             debug!("render_task: returning surface");
             if self.paint_permission {
-                self.compositor.paint(self.id, layer_buffer_set, self.epoch);
+                self.compositor.paint(self.id, layer_id, layer_buffer_set, self.epoch);
             } else {
                 self.constellation_chan.send(RendererReadyMsg(self.id));
             }
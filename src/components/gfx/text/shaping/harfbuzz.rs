@@ -24,7 +24,7 @@ use geom::Point2D;
 use harfbuzz::{hb_blob_create, hb_face_create_for_tables};
 use harfbuzz::{hb_buffer_add_utf8};
 use harfbuzz::{hb_buffer_get_glyph_positions};
-use harfbuzz::{hb_buffer_set_direction};
+use harfbuzz::{hb_buffer_reverse, hb_buffer_set_direction};
 use harfbuzz::{hb_buffer_destroy};
 use harfbuzz::{hb_face_destroy};
 use harfbuzz::{hb_font_create};
@@ -37,7 +37,7 @@ use harfbuzz::{hb_font_set_funcs};
 use harfbuzz::{hb_font_set_ppem};
 use harfbuzz::{hb_font_set_scale};
 use harfbuzz::{hb_shape, hb_buffer_get_glyph_infos};
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_RTL};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
 use harfbuzz::{hb_face_t, hb_font_t};
@@ -215,10 +215,11 @@ impl ShaperMethods for Shaper {
     /// Calculate the layout metrics associated with the given text when rendered in a specific
     /// font.
     #[fixed_stack_segment]
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore) {
+    fn shape_text(&self, text: &str, is_rtl: bool, glyphs: &mut GlyphStore) {
         unsafe {
             let hb_buffer: *hb_buffer_t = hb_buffer_create();
-            hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            let direction = if is_rtl { HB_DIRECTION_RTL } else { HB_DIRECTION_LTR };
+            hb_buffer_set_direction(hb_buffer, direction);
 
             // Using as_imm_buf because it never does a copy - we don't need the trailing null
             do text.as_imm_buf |ctext: *u8, _: uint| {
@@ -230,6 +231,11 @@ impl ShaperMethods for Shaper {
             }
 
             hb_shape(self.hb_font, hb_buffer, null(), 0);
+            // HarfBuzz returns the glyphs of right-to-left text in visual order, but the glyph
+            // store keeps them in the order of the characters.
+            if is_rtl {
+                hb_buffer_reverse(hb_buffer);
+            }
             self.save_glyph_results(text, glyphs, hb_buffer);
             hb_buffer_destroy(hb_buffer);
         }
@@ -14,6 +14,8 @@ pub use Shaper = text::shaping::harfbuzz::Shaper;
 pub mod harfbuzz;
 
 pub trait ShaperMethods {
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore);
+    /// Shapes `text` into `glyphs`, right to left if `is_rtl` is true. The glyphs are stored in
+    /// the order of the characters either way.
+    fn shape_text(&self, text: &str, is_rtl: bool, glyphs: &mut GlyphStore);
 }
 
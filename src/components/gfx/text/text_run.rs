@@ -10,22 +10,26 @@ use text::glyph::GlyphStore;
 use font::{Font, FontDescriptor, RunMetrics};
 use servo_util::range::Range;
 use extra::arc::Arc;
-use newcss::values::CSSTextDecoration;
+use style::computed_values::text_decoration;
 
 /// A text run.
 pub struct TextRun {
     text: ~str,
     font: @mut Font,
-    decoration: CSSTextDecoration,
+    decoration: text_decoration::ComputedValue,
     glyphs: ~[Arc<GlyphStore>],
+    /// Whether the text was shaped right to left. Its glyphs are in the order of the characters
+    /// either way.
+    is_rtl: bool,
 }
 
 /// This is a hack until TextRuns are normally sendable, or we instead use Arc<TextRun> everywhere.
 pub struct SendableTextRun {
     text: ~str,
     font: FontDescriptor,
-    decoration: CSSTextDecoration,
+    decoration: text_decoration::ComputedValue,
     priv glyphs: ~[Arc<GlyphStore>],
+    is_rtl: bool,
 }
 
 impl SendableTextRun {
@@ -40,6 +44,7 @@ impl SendableTextRun {
             font: font,
             decoration: self.decoration,
             glyphs: self.glyphs.clone(),
+            is_rtl: self.is_rtl,
         }
     }
 }
@@ -119,14 +124,19 @@ impl<'self> Iterator<Range> for LineIterator<'self> {
 }
 
 impl<'self> TextRun {
-    pub fn new(font: @mut Font, text: ~str, decoration: CSSTextDecoration) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, text);
+    pub fn new(font: @mut Font,
+               text: ~str,
+               decoration: text_decoration::ComputedValue,
+               is_rtl: bool)
+               -> TextRun {
+        let glyphs = TextRun::break_and_shape(font, text, is_rtl);
 
         let run = TextRun {
             text: text,
             font: font,
             decoration: decoration,
             glyphs: glyphs,
+            is_rtl: is_rtl,
         };
         return run;
     }
@@ -135,7 +145,7 @@ impl<'self> TextRun {
         self.font.teardown();
     }
 
-    pub fn break_and_shape(font: @mut Font, text: &str) -> ~[Arc<GlyphStore>] {
+    pub fn break_and_shape(font: @mut Font, text: &str, is_rtl: bool) -> ~[Arc<GlyphStore>] {
         // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
 
         let mut glyphs = ~[];
@@ -147,11 +157,26 @@ impl<'self> TextRun {
             let ch = range.ch;
             let next = range.next;
 
+            if ch == '\n' {
+                // A newline is a slice of its own, so that layout can end the line after it where
+                // 'white-space' preserves it. It takes up the space of a space, and is drawn as
+                // one.
+                if byte_i > byte_last_boundary {
+                    let slice = text.slice(byte_last_boundary, byte_i).to_owned();
+                    glyphs.push(font.shape_text(slice, cur_slice_is_whitespace, is_rtl));
+                }
+                glyphs.push(font.shape_text(~" ", true, is_rtl));
+                cur_slice_is_whitespace = true;
+                byte_last_boundary = next;
+                byte_i = next;
+                loop
+            }
+
             // Slices alternate between whitespace and non-whitespace,
             // representing line break opportunities.
             let can_break_before = if cur_slice_is_whitespace {
                 match ch {
-                    ' ' | '\t' | '\n' => false,
+                    ' ' | '\t' => false,
                     _ => {
                         cur_slice_is_whitespace = false;
                         true
@@ -159,7 +184,7 @@ impl<'self> TextRun {
                 }
             } else {
                 match ch {
-                    ' ' | '\t' | '\n' => {
+                    ' ' | '\t' => {
                         cur_slice_is_whitespace = true;
                         true
                     },
@@ -172,7 +197,7 @@ impl<'self> TextRun {
                 let slice = text.slice(byte_last_boundary, byte_i).to_owned();
                 debug!("creating glyph store for slice %? (ws? %?), %? - %? in run %?",
                         slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
-                glyphs.push(font.shape_text(slice, !cur_slice_is_whitespace));
+                glyphs.push(font.shape_text(slice, !cur_slice_is_whitespace, is_rtl));
                 byte_last_boundary = byte_i;
             }
 
@@ -184,7 +209,7 @@ impl<'self> TextRun {
             let slice = text.slice(byte_last_boundary, text.len()).to_owned();
             debug!("creating glyph store for final slice %? (ws? %?), %? - %? in run %?",
                 slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
-            glyphs.push(font.shape_text(slice, cur_slice_is_whitespace));
+            glyphs.push(font.shape_text(slice, cur_slice_is_whitespace, is_rtl));
         }
 
         glyphs
@@ -196,6 +221,7 @@ impl<'self> TextRun {
             font: self.font.get_descriptor(),
             decoration: self.decoration,
             glyphs: self.glyphs.clone(),
+            is_rtl: self.is_rtl,
         }
     }
 
@@ -11,17 +11,21 @@ use geom::size::Size2D;
 use gfx::render_task::{ReRenderMsg, UnusedBufferMsg};
 use layers::layers::{ContainerLayerKind, ContainerLayer, Flip, NoFlip, SurfaceLayerKind};
 use layers::layers::{SurfaceLayer, VerticalFlip};
-use layers::platform::surface::{NativeCompositingGraphicsContext, NativeSurfaceMethods};
+use layers::platform::surface::{NativeCompositingGraphicsContext, NativeSurface};
+use layers::platform::surface::{NativeSurfaceMethods};
 use layers::surfacetexture::SurfaceTexture;
 use layers::texturegl::{Texture, TextureTarget, TextureTarget2D, TextureTargetRectangle};
 use pipeline::Pipeline;
-use script::dom::event::{ClickEvent, MouseDownEvent, MouseUpEvent};
+use script::dom::event::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
 use script::script_task::SendEventMsg;
-use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch, Tile};
+use servo_msg::compositor_msg::{CpuLayerBufferSurface, LayerBuffer, LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{LayerId, NativeLayerBufferSurface, PageLayerId, ScrollLayerId};
+use servo_msg::compositor_msg::{ScrollLayerInfo, Tile};
 use servo_msg::constellation_msg::PipelineId;
 use servo_msg::platform::surface::NativeSurfaceAzureMethods;
 use std::cell::Cell;
 use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::MouseWindowMoveEvent;
 
 /// The CompositorLayer represents an element on a page that has a unique scroll
 /// or animation behavior. This can include absolute positioned elements, iframes, etc.
@@ -29,6 +33,9 @@ use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEve
 pub struct CompositorLayer {
     /// This layer's pipeline. BufferRequests and mouse events will be sent through this.
     pipeline: Pipeline,
+    /// Which layer of the pipeline this is: the page, or one of its boxes whose contents scroll
+    /// independently. The layers of such boxes are children of the layer of their page.
+    layer_id: LayerId,
     /// The size of the underlying page in page coordinates. This is an option
     /// because we may not know the size of the page until layout is finished completely.
     /// if we have no size yet, the layer is hidden until a size message is recieved.
@@ -65,6 +72,16 @@ struct CompositorLayerChild {
     /// A ContainerLayer managed by the parent node. This deals with clipping and
     /// positioning, and is added above the child's layer tree.
     container: @mut ContainerLayer,
+    /// Where the layer of a fixed-position element goes in the viewport, or `None` if the child
+    /// scrolls with its parent. The container is moved there whenever the parent scrolls.
+    fixed_rect: Option<Rect<f32>>,
+}
+
+impl CompositorLayerChild {
+    /// Returns true if the child is the layer of the page of the given pipeline.
+    fn is_page_of(&self, pipeline_id: PipelineId) -> bool {
+        self.child.pipeline.id == pipeline_id && self.child.layer_id == PageLayerId
+    }
 }
 
 /// Helper enum for storing quadtrees. Either contains a quadtree, or contains
@@ -94,6 +111,7 @@ impl CompositorLayer {
                -> CompositorLayer {
         CompositorLayer {
             pipeline: pipeline,
+            layer_id: PageLayerId,
             page_size: page_size,
             scroll_offset: Point2D(0f32, 0f32),
             children: ~[],
@@ -143,6 +161,7 @@ impl CompositorLayer {
             CompositorLayerChild {
                 child: child_layer,
                 container: container,
+                fixed_rect: None,
             }
         }).collect();
         layer.set_occlusions();
@@ -196,16 +215,42 @@ impl CompositorLayer {
                 self.root_layer.common.set_transform(identity().translate(self.scroll_offset.x,
                                                                           self.scroll_offset.y,
                                                                           0.0));
+                self.position_fixed_children();
                 true
             }
             FixedPosition => false, // Ignore this scroll event.
         }
     }
 
+    // Moves the layers of the fixed-position elements among the children of this layer to their
+    // place in the viewport, which is where they are in page coordinates minus the scroll offset.
+    fn position_fixed_children(&mut self) {
+        let scroll_offset = self.scroll_offset;
+        for child in self.children.mut_iter() {
+            match child.fixed_rect {
+                None => {}
+                Some(rect) => {
+                    let origin = rect.origin - scroll_offset;
+                    child.container.scissor = Some(Rect(origin, rect.size));
+                    child.container.common.set_transform(identity().translate(origin.x,
+                                                                              origin.y,
+                                                                              0.0));
+                }
+            }
+        }
+    }
+
     // Takes in a MouseWindowEvent, determines if it should be passed to children, and 
     // sends the event off to the appropriate pipeline. NB: the cursor position is in
     // page coordinates.
     pub fn send_mouse_event(&self, event: MouseWindowEvent, cursor: Point2D<f32>) {
+        self.send_mouse_event_at(event, cursor, Point2D(0f32, 0f32))
+    }
+
+    // Like send_mouse_event, for a layer whose top left corner is at `origin` in the coordinates
+    // in which layout places the contents of its page.
+    fn send_mouse_event_at(&self, event: MouseWindowEvent, cursor: Point2D<f32>,
+                           origin: Point2D<f32>) {
         let cursor = cursor - self.scroll_offset;
         for child in self.children.iter().filter(|&x| !x.child.hidden) {
             match child.container.scissor {
@@ -215,7 +260,22 @@ impl CompositorLayer {
                 Some(rect) => {
                     if cursor.x >= rect.origin.x && cursor.x < rect.origin.x + rect.size.width
                         && cursor.y >= rect.origin.y && cursor.y < rect.origin.y + rect.size.height {
-                        child.child.send_mouse_event(event, cursor - rect.origin);
+                        match child.child.layer_id {
+                            PageLayerId => {
+                                child.child.send_mouse_event(event, cursor - rect.origin)
+                            }
+                            ScrollLayerId(_) => {
+                                // Layout places the contents of a scrolled box as if it were not
+                                // scrolled, and fixed-position elements as if the page were not.
+                                let child_origin = match child.fixed_rect {
+                                    Some(fixed_rect) => fixed_rect.origin,
+                                    None => origin + rect.origin,
+                                };
+                                child.child.send_mouse_event_at(event,
+                                                                cursor - rect.origin,
+                                                                child_origin)
+                            }
+                        }
                         return;
                     }
                 }
@@ -223,10 +283,17 @@ impl CompositorLayer {
         }
         
         // This mouse event is mine!
+        self.send_mouse_event_to_script(event, cursor + origin);
+    }
+
+    // Sends a mouse event at the given position in page coordinates to the script task of this
+    // layer's pipeline.
+    fn send_mouse_event_to_script(&self, event: MouseWindowEvent, cursor: Point2D<f32>) {
         let message = match event {
             MouseWindowClickEvent(button, _) => ClickEvent(button, cursor),
             MouseWindowMouseDownEvent(button, _) => MouseDownEvent(button, cursor),
             MouseWindowMouseUpEvent(button, _) => MouseUpEvent(button, cursor),
+            MouseWindowMoveEvent(_) => MouseMoveEvent(cursor),
         };
         
         self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), message));
@@ -236,7 +303,7 @@ impl CompositorLayer {
     // and sends them off the the appropriate renderer.
     // Returns a bool that is true if the scene should be repainted.
     pub fn get_buffer_request(&mut self,
-                              graphics_context: &NativeCompositingGraphicsContext,
+                              graphics_context: Option<&NativeCompositingGraphicsContext>,
                               window_rect: Rect<f32>,
                               scale: f32)
                               -> bool {
@@ -256,7 +323,10 @@ impl CompositorLayer {
                 self.pipeline.render_chan.send(UnusedBufferMsg(unused));
             }
             if !request.is_empty() { // ask for tiles
-                self.pipeline.render_chan.send(ReRenderMsg(request, scale, self.epoch));
+                self.pipeline.render_chan.send(ReRenderMsg(self.layer_id,
+                                                           request,
+                                                           scale,
+                                                           self.epoch));
             }
         }
         if redisplay {
@@ -296,7 +366,7 @@ impl CompositorLayer {
     // If the layer is hidden and has a defined page size, unhide it.
     // This method returns false if the specified layer is not found.
     pub fn set_clipping_rect(&mut self, pipeline_id: PipelineId, new_rect: Rect<f32>) -> bool {
-        match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id) {
+        match self.children.iter().position(|x| x.is_page_of(pipeline_id)) {
             Some(i) => {
                 let child_node = &mut self.children[i];
                 let con = child_node.container;
@@ -336,7 +406,7 @@ impl CompositorLayer {
     // If the layer is hidden and has a defined clipping rect, unhide it.
     // This method returns false if the specified layer is not found.
     pub fn resize(&mut self, pipeline_id: PipelineId, new_size: Size2D<f32>, window_size: Size2D<f32>, epoch: Epoch) -> bool {
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.layer_id == PageLayerId {
             self.epoch = epoch;
             self.page_size = Some(new_size);
             match self.quadtree {
@@ -378,10 +448,21 @@ impl CompositorLayer {
     fn texture_flip_and_target(_: bool, _: Size2D<uint>) -> (Flip, TextureTarget) {
         (NoFlip, TextureTarget2D)
     }
+
+    // Returns the native surface of a buffer. Only the headless compositor gets buffers whose
+    // pixels are in memory, and it never binds them to textures.
+    fn native_surface_of<'a>(buffer: &'a ~LayerBuffer) -> &'a NativeSurface {
+        match buffer.surface {
+            NativeLayerBufferSurface(ref native_surface) => native_surface,
+            CpuLayerBufferSurface(_) => {
+                fail!("CompositorLayer: cannot bind pixels in memory to a texture")
+            }
+        }
+    }
     
     // A helper method to resize sublayers.
     fn resize_helper(&mut self, pipeline_id: PipelineId, new_size: Size2D<f32>, epoch: Epoch) -> bool {
-        let found = match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id) {
+        let found = match self.children.iter().position(|x| x.is_page_of(pipeline_id)) {
             Some(i) => {
                 let child_node = &mut self.children[i];
                 let child = &mut child_node.child;
@@ -423,8 +504,15 @@ impl CompositorLayer {
     }
 
     // Collect buffers from the quadtree. This method IS NOT recursive, so child CompositorLayers
-    // are not rebuilt directly from this method.
-    pub fn build_layer_tree(&mut self, graphics_context: &NativeCompositingGraphicsContext) {
+    // are not rebuilt directly from this method. Without a graphics context, the compositor is
+    // headless and composites the quadtree directly, so there is nothing to do.
+    pub fn build_layer_tree(&mut self,
+                            graphics_context: Option<&NativeCompositingGraphicsContext>) {
+        let graphics_context = match graphics_context {
+            None => return,
+            Some(graphics_context) => graphics_context,
+        };
+
         // Iterate over the children of the container layer.
         let mut current_layer_child = self.root_layer.first_child;
         
@@ -466,9 +554,10 @@ impl CompositorLayer {
 
                     // Make a new texture and bind the layer buffer's surface to it.
                     let texture = Texture::new(target);
+                    let native_surface = CompositorLayer::native_surface_of(*buffer);
                     debug!("COMPOSITOR binding to native surface %d",
-                           buffer.native_surface.get_id() as int);
-                    buffer.native_surface.bind_to_texture(graphics_context, &texture, size);
+                           native_surface.get_id() as int);
+                    native_surface.bind_to_texture(graphics_context, &texture, size);
                     let surface = SurfaceTexture {
                         texture: texture,
                     };
@@ -482,9 +571,10 @@ impl CompositorLayer {
                     surface_layer = existing_surface_layer;
 
                     let surface_texture = &mut surface_layer.surface_texture;
-                    buffer.native_surface.bind_to_texture(graphics_context,
-                                                          &surface_texture.texture,
-                                                          size);
+                    let native_surface = CompositorLayer::native_surface_of(*buffer);
+                    native_surface.bind_to_texture(graphics_context,
+                                                   &surface_texture.texture,
+                                                   size);
 
                     // Move on to the next sibling.
                     do current_layer_child.unwrap().with_common |common| {
@@ -518,20 +608,97 @@ impl CompositorLayer {
         }
     }
     
+    /// Composites the tiles of this layer and of its visible children into `pixels`, an RGB image
+    /// of `size` pixels, on the CPU. `origin` is where the top left corner of this layer goes in
+    /// the image, and nothing is drawn outside `clip`; both are in pixels at the scale `scale`.
+    /// The tiles must hold their pixels in memory, as those of the headless compositor do.
+    pub fn composite_in_software(&self,
+                                 pixels: &mut [u8],
+                                 size: Size2D<uint>,
+                                 origin: Point2D<f32>,
+                                 clip: Rect<f32>,
+                                 scale: f32) {
+        let page_origin = Point2D(origin.x + self.scroll_offset.x * scale,
+                                  origin.y + self.scroll_offset.y * scale);
+        let min_x = clip.origin.x.max(&0.0) as int;
+        let min_y = clip.origin.y.max(&0.0) as int;
+        let max_x = (clip.origin.x + clip.size.width).min(&(size.width as f32)) as int;
+        let max_y = (clip.origin.y + clip.size.height).min(&(size.height as f32)) as int;
+
+        match self.quadtree {
+            NoTree(*) => {} // Nothing to draw
+            Tree(ref quadtree) => {
+                for buffer in quadtree.get_all_tiles().iter() {
+                    let tile_pixels = match buffer.surface {
+                        CpuLayerBufferSurface(ref tile_pixels) => tile_pixels,
+                        NativeLayerBufferSurface(_) => {
+                            fail!("CompositorLayer: cannot composite a native surface on the CPU")
+                        }
+                    };
+
+                    let tile_x = (page_origin.x + buffer.rect.origin.x * scale) as int;
+                    let tile_y = (page_origin.y + buffer.rect.origin.y * scale) as int;
+                    let tile_width = buffer.screen_pos.size.width;
+                    for y in range(0, buffer.screen_pos.size.height) {
+                        let dest_y = tile_y + y as int;
+                        if dest_y < min_y || dest_y >= max_y {
+                            loop
+                        }
+                        for x in range(0, tile_width) {
+                            let dest_x = tile_x + x as int;
+                            if dest_x < min_x || dest_x >= max_x {
+                                loop
+                            }
+
+                            // Blend the premultiplied BGRA pixel of the tile over the RGB pixel
+                            // of the image.
+                            let src = (y * tile_width + x) * 4;
+                            let dest = (dest_y as uint * size.width + dest_x as uint) * 3;
+                            let alpha = tile_pixels[src + 3] as uint;
+                            for i in range(0u, 3) {
+                                let background = pixels[dest + i] as uint * (255 - alpha) / 255;
+                                pixels[dest + i] = (tile_pixels[src + 2 - i] as uint +
+                                                    background) as u8;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for child in self.children.iter().filter(|x| !x.child.hidden) {
+            let scissor = match child.container.scissor {
+                Some(scissor) => scissor,
+                None => fail!("CompositorLayer: Child layer not clipped"),
+            };
+            let child_origin = Point2D(page_origin.x + scissor.origin.x * scale,
+                                       page_origin.y + scissor.origin.y * scale);
+            let child_rect = Rect(child_origin, Size2D(scissor.size.width * scale,
+                                                       scissor.size.height * scale));
+            match child_rect.intersection(&clip) {
+                Some(child_clip) => {
+                    child.child.composite_in_software(pixels, size, child_origin, child_clip, scale)
+                }
+                None => {} // Layer is offscreen
+            }
+        }
+    }
+
     // Add LayerBuffers to the specified layer. Returns the layer buffer set back if the layer that
-    // matches the given pipeline ID was not found; otherwise returns None and consumes the layer
-    // buffer set.
+    // matches the given pipeline ID and layer ID was not found; otherwise returns None and
+    // consumes the layer buffer set.
     //
     // If the epoch of the message does not match the layer's epoch, the message is ignored, the
     // layer buffer set is consumed, and None is returned.
     pub fn add_buffers(&mut self,
-                       graphics_context: &NativeCompositingGraphicsContext,
+                       graphics_context: Option<&NativeCompositingGraphicsContext>,
                        pipeline_id: PipelineId,
+                       layer_id: LayerId,
                        mut new_buffers: ~LayerBufferSet,
                        epoch: Epoch)
                        -> Option<~LayerBufferSet> {
         let cell = Cell::new(new_buffers);
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.layer_id == layer_id {
             if self.epoch != epoch {
                 debug!("compositor epoch mismatch: %? != %?, id: %?",
                        self.epoch,
@@ -566,6 +733,7 @@ impl CompositorLayer {
         for child_layer in self.children.mut_iter() {
             match child_layer.child.add_buffers(graphics_context,
                                                 pipeline_id,
+                                                layer_id,
                                                 cell.take(),
                                                 epoch) {
                 None => return None,
@@ -573,16 +741,23 @@ impl CompositorLayer {
             }
         }
 
+        // The page was laid out again after the buffers were requested, and the scroll layer
+        // they belong to is gone.
+        if self.pipeline.id == pipeline_id && self.layer_id == PageLayerId {
+            self.pipeline.render_chan.send(UnusedBufferMsg(cell.take().buffers));
+            return None;
+        }
+
         // Not found. Give the caller the buffers back.
         Some(cell.take())
     }
 
     // Deletes a specified sublayer, including hidden children. Returns false if the layer is not found.
     pub fn delete(&mut self,
-                  graphics_context: &NativeCompositingGraphicsContext,
+                  graphics_context: Option<&NativeCompositingGraphicsContext>,
                   pipeline_id: PipelineId)
                   -> bool {
-        match self.children.iter().position(|x| x.child.pipeline.id == pipeline_id) {
+        match self.children.iter().position(|x| x.is_page_of(pipeline_id)) {
             Some(i) => {
                 let mut child = self.children.remove(i);
                 match self.quadtree {
@@ -611,7 +786,7 @@ impl CompositorLayer {
     }
 
     pub fn invalidate_rect(&mut self, pipeline_id: PipelineId, rect: Rect<f32>) -> bool {
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.layer_id == PageLayerId {
             let quadtree = match self.quadtree {
                 NoTree(*) => return true, // Nothing to do
                 Tree(ref mut quadtree) => quadtree,
@@ -624,6 +799,133 @@ impl CompositorLayer {
         }
     }
     
+    // Replaces the scroll layers of the page of the specified pipeline, which hold the contents of
+    // the boxes on the page that scroll independently and of its fixed-position elements. The
+    // layer of a box inside another such box is a child of the layer of that box. A scroll layer
+    // keeps the scroll offset of the old one with the same ID, as far as its new size allows. This
+    // method returns false if the specified layer is not found.
+    pub fn set_scroll_layers(&mut self,
+                             graphics_context: Option<&NativeCompositingGraphicsContext>,
+                             pipeline_id: PipelineId,
+                             scroll_layers: ~[ScrollLayerInfo],
+                             epoch: Epoch,
+                             tile_size: uint,
+                             max_mem: Option<uint>)
+                             -> bool {
+        if self.pipeline.id != pipeline_id || self.layer_id != PageLayerId {
+            // ID does not match ours, so recurse on descendents (including hidden children).
+            return self.children.mut_iter()
+                                .filter(|x| x.child.layer_id == PageLayerId)
+                                .map(|x| &mut x.child)
+                                .any(|x| x.set_scroll_layers(graphics_context,
+                                                             pipeline_id,
+                                                             scroll_layers.clone(),
+                                                             epoch,
+                                                             tile_size,
+                                                             max_mem))
+        }
+
+        // Remove the old scroll layers, sending their tiles back to the renderer.
+        let mut old_scroll_offsets = ~[];
+        let mut i = 0;
+        while i < self.children.len() {
+            if self.children[i].child.layer_id == PageLayerId {
+                i += 1;
+                loop
+            }
+            let mut old_child = self.children.remove(i);
+            old_child.child.clear_all();
+            old_child.child.collect_scroll_offsets(&mut old_scroll_offsets);
+        }
+
+        self.add_scroll_layers(graphics_context,
+                               scroll_layers.as_slice(),
+                               None,
+                               Point2D(0f32, 0f32),
+                               old_scroll_offsets.as_slice(),
+                               epoch,
+                               tile_size,
+                               max_mem);
+        self.position_fixed_children();
+        self.build_layer_tree(graphics_context);
+        true
+    }
+
+    // Adds the scroll layers whose parent is the scroll layer with the given ID, or the page if it
+    // is None, as children of this layer, whose top left corner is at `origin` in page coordinates.
+    fn add_scroll_layers(&mut self,
+                         graphics_context: Option<&NativeCompositingGraphicsContext>,
+                         scroll_layers: &[ScrollLayerInfo],
+                         parent: Option<uint>,
+                         origin: Point2D<f32>,
+                         old_scroll_offsets: &[(uint, Point2D<f32>)],
+                         epoch: Epoch,
+                         tile_size: uint,
+                         max_mem: Option<uint>) {
+        for scroll_layer in scroll_layers.iter().filter(|x| x.parent == parent) {
+            let id = scroll_layer.id;
+            let rect = Rect(scroll_layer.rect.origin - origin, scroll_layer.rect.size);
+            let container = @mut ContainerLayer();
+            container.scissor = Some(rect);
+            container.common.set_transform(identity().translate(rect.origin.x,
+                                                                rect.origin.y,
+                                                                0.0));
+            let mut child = ~CompositorLayer::new(self.pipeline.clone(),
+                                                  Some(scroll_layer.page_size),
+                                                  tile_size,
+                                                  max_mem,
+                                                  self.cpu_painting);
+            child.layer_id = ScrollLayerId(id);
+            child.epoch = epoch;
+            child.hidden = false;
+            if scroll_layer.fixed_position {
+                child.scroll_behavior = FixedPosition;
+            } else {
+                match old_scroll_offsets.iter().find(|&&(old_id, _)| old_id == id) {
+                    Some(&(_, old_scroll_offset)) => {
+                        // Call scroll for bounds checking in case the contents shrunk. Use
+                        // (-1, -1) as the cursor position to make sure the scroll isn't
+                        // propagated downwards.
+                        child.scroll_offset = old_scroll_offset;
+                        child.scroll(Point2D(0f32, 0f32), Point2D(-1f32, -1f32), rect.size);
+                        child.root_layer.common.set_transform(
+                            identity().translate(child.scroll_offset.x,
+                                                 child.scroll_offset.y,
+                                                 0.0));
+                    }
+                    None => {}
+                }
+            }
+            child.add_scroll_layers(graphics_context,
+                                    scroll_layers,
+                                    Some(id),
+                                    scroll_layer.rect.origin,
+                                    old_scroll_offsets,
+                                    epoch,
+                                    tile_size,
+                                    max_mem);
+            child.build_layer_tree(graphics_context);
+            container.add_child_start(ContainerLayerKind(child.root_layer));
+            self.children.push(CompositorLayerChild {
+                child: child,
+                container: container,
+                fixed_rect: if scroll_layer.fixed_position { Some(rect) } else { None },
+            });
+        }
+    }
+
+    // Appends the ID and the scroll offset of this layer, if it is a scroll layer, and of the
+    // scroll layers inside it to `offsets`.
+    fn collect_scroll_offsets(&self, offsets: &mut ~[(uint, Point2D<f32>)]) {
+        match self.layer_id {
+            ScrollLayerId(id) => offsets.push((id, self.scroll_offset)),
+            PageLayerId => {}
+        }
+        for child in self.children.iter() {
+            child.child.collect_scroll_offsets(offsets)
+        }
+    }
+
     // Adds a child.
     pub fn add_child(&mut self, pipeline: Pipeline, page_size: Option<Size2D<f32>>, tile_size: uint,
                      max_mem: Option<uint>, clipping_rect: Rect<f32>) {
@@ -641,6 +943,7 @@ impl CompositorLayer {
         self.children.push(CompositorLayerChild {
             child: child,
             container: container,
+            fixed_rect: None,
         });
         self.set_occlusions();
     }
@@ -652,7 +955,10 @@ impl CompositorLayer {
             NoTree(*) => return, // Cannot calculate occlusions
             Tree(ref mut quadtree) => quadtree,
         };
-        for child in self.children.iter().filter(|x| !x.child.hidden) {
+        // Scroll layers are transparent where the box has no content, so the page beneath them
+        // is still drawn.
+        for child in self.children.iter().filter(|x| !x.child.hidden &&
+                                                      x.child.layer_id == PageLayerId) {
             match child.container.scissor {
                 None => {} // Nothing to do
                 Some(rect) => {
@@ -3,16 +3,17 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use platform::{Application, Window};
+use platform::common::headless_windowing;
 
 pub use windowing;
 use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent};
-use windowing::{MouseWindowMouseUpEvent};
+use windowing::{MouseWindowMouseUpEvent, MouseWindowMoveEvent};
 
-use servo_msg::compositor_msg::{RenderListener, LayerBufferSet, RenderState};
-use servo_msg::compositor_msg::{ReadyState, ScriptListener, Epoch, Tile};
+use servo_msg::compositor_msg::{RenderListener, LayerBufferSet, LayerId, RenderState};
+use servo_msg::compositor_msg::{ReadyState, ScriptListener, ScrollLayerInfo, Epoch, Tile};
 use servo_msg::constellation_msg::{ConstellationChan, LoadUrlMsg, NavigateMsg, PipelineId};
 use servo_msg::constellation_msg::{ResizedWindowMsg};
 use servo_msg::constellation_msg;
@@ -76,14 +77,18 @@ impl ScriptListener for CompositorChan {
 
 /// Implementation of the abstract `RenderListener` interface.
 impl RenderListener for CompositorChan {
-    fn get_graphics_metadata(&self) -> NativeGraphicsMetadata {
+    fn get_graphics_metadata(&self) -> Option<NativeGraphicsMetadata> {
         let (port, chan) = comm::stream();
         self.chan.send(GetGraphicsMetadata(chan));
         port.recv()
     }
 
-    fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, epoch: Epoch) {
-        self.chan.send(Paint(id, layer_buffer_set, epoch))
+    fn paint(&self,
+             id: PipelineId,
+             layer_id: LayerId,
+             layer_buffer_set: ~LayerBufferSet,
+             epoch: Epoch) {
+        self.chan.send(Paint(id, layer_id, layer_buffer_set, epoch))
     }
 
     fn new_layer(&self, id: PipelineId, page_size: Size2D<uint>) {
@@ -94,6 +99,9 @@ impl RenderListener for CompositorChan {
         let Size2D { width, height } = page_size;
         self.chan.send(SetLayerPageSize(id, Size2D(width as f32, height as f32), epoch))
     }
+    fn set_scroll_layers(&self, id: PipelineId, scroll_layers: ~[ScrollLayerInfo], epoch: Epoch) {
+        self.chan.send(SetScrollLayers(id, scroll_layers, epoch))
+    }
     fn set_layer_clip_rect(&self, id: PipelineId, new_rect: Rect<uint>) {
         let new_rect = Rect(Point2D(new_rect.origin.x as f32,
                                     new_rect.origin.y as f32),
@@ -101,6 +109,9 @@ impl RenderListener for CompositorChan {
                                    new_rect.size.height as f32));
         self.chan.send(SetLayerClipRect(id, new_rect))
     }
+    fn invalidate_rect(&self, id: PipelineId, rect: Rect<uint>) {
+        self.chan.send(InvalidateRect(id, rect))
+    }
 
     fn delete_layer(&self, id: PipelineId) {
         self.chan.send(DeleteLayer(id))
@@ -138,13 +149,16 @@ pub enum Msg {
     GetSize(Chan<Size2D<int>>),
     /// Requests the compositor's graphics metadata. Graphics metadata is what the renderer needs
     /// to create surfaces that the compositor can see. On Linux this is the X display; on Mac this
-    /// is the pixel format.
-    GetGraphicsMetadata(Chan<NativeGraphicsMetadata>),
+    /// is the pixel format. A headless compositor has none.
+    GetGraphicsMetadata(Chan<Option<NativeGraphicsMetadata>>),
 
     /// Alerts the compositor that there is a new layer to be rendered.
     NewLayer(PipelineId, Size2D<f32>),
     /// Alerts the compositor that the specified layer's page has changed size.
     SetLayerPageSize(PipelineId, Size2D<f32>, Epoch),
+    /// Alerts the compositor that the boxes of the specified layer's page whose contents scroll
+    /// independently have changed.
+    SetScrollLayers(PipelineId, ~[ScrollLayerInfo], Epoch),
     /// Alerts the compositor that the specified layer's clipping rect has changed.
     SetLayerClipRect(PipelineId, Rect<f32>),
     /// Alerts the compositor that the specified layer has been deleted.
@@ -153,7 +167,7 @@ pub enum Msg {
     InvalidateRect(PipelineId, Rect<uint>),
 
     /// Requests that the compositor paint the given layer buffer set for the given page size.
-    Paint(PipelineId, ~LayerBufferSet, Epoch),
+    Paint(PipelineId, LayerId, ~LayerBufferSet, Epoch),
     /// Alerts the compositor to the current status of page loading.
     ChangeReadyState(ReadyState),
     /// Alerts the compositor to the current status of rendering.
@@ -195,21 +209,42 @@ impl CompositorTask {
 
     /// Starts the compositor, which listens for messages on the specified port. 
     pub fn run(&self) {
-        let app: Application = ApplicationMethods::new();
-        let window: @mut Window = WindowMethods::new(&app);
+        if self.opts.headless {
+            let app: headless_windowing::Application = ApplicationMethods::new();
+            let window: @mut headless_windowing::Window = WindowMethods::new(&app);
+            self.run_with_window(window);
+        } else {
+            let app: Application = ApplicationMethods::new();
+            let window: @mut Window = WindowMethods::new(&app);
+            self.run_with_window(window);
+        }
+    }
+
+    /// Runs the compositor in the given window. A headless compositor has no GL context: it
+    /// composites the tiles on the CPU, and can only write them to the output file.
+    fn run_with_window<A, W: WindowMethods<A>>(&self, window: @mut W) {
+        let headless = self.opts.headless;
 
         // Create an initial layer tree.
         //
         // TODO: There should be no initial layer tree until the renderer creates one from the display
         // list. This is only here because we don't have that logic in the renderer yet.
-        let context = rendergl::init_render_context();
+        let context = if headless {
+            None
+        } else {
+            Some(rendergl::init_render_context())
+        };
         let root_layer = @mut ContainerLayer();
         let window_size = window.size();
         let mut scene = Scene(ContainerLayerKind(root_layer), window_size, identity());
         let mut window_size = Size2D(window_size.width as uint, window_size.height as uint);
         let mut done = false;
         let mut recomposite = false;
-        let graphics_context = CompositorTask::create_graphics_context();
+        let graphics_context = if headless {
+            None
+        } else {
+            Some(CompositorTask::create_graphics_context())
+        };
 
         // Keeps track of the current zoom factor
         let mut world_zoom = 1f32;
@@ -227,7 +262,9 @@ impl CompositorTask {
             for layer in compositor_layer.mut_iter() {
                 if !layer.hidden {
                     let rect = Rect(Point2D(0f32, 0f32), window_size_page);
-                    recomposite = layer.get_buffer_request(&graphics_context, rect, world_zoom)
+                    recomposite = layer.get_buffer_request(graphics_context.as_ref(),
+                                                           rect,
+                                                           world_zoom)
                         || recomposite;
                 } else { 
                     debug!("Compositor: root layer is hidden!");
@@ -266,6 +303,14 @@ impl CompositorTask {
                         }
 
                         compositor_layer = Some(layer);
+
+                        // The new page is laid out assuming a device pixel ratio of 1; tell it
+                        // otherwise.
+                        let device_pixel_ratio = window.hidpi_factor();
+                        if device_pixel_ratio != 1.0 {
+                            new_constellation_chan.send(ResizedWindowMsg(window_size,
+                                                                         device_pixel_ratio));
+                        }
                         constellation_chan = Some(new_constellation_chan);
                     }
 
@@ -275,7 +320,11 @@ impl CompositorTask {
                     }
 
                     GetGraphicsMetadata(chan) => {
-                        chan.send(azure_hl::current_graphics_metadata())
+                        if headless {
+                            chan.send(None)
+                        } else {
+                            chan.send(Some(azure_hl::current_graphics_metadata()))
+                        }
                     }
 
                     NewLayer(_id, new_size) => {
@@ -318,6 +367,21 @@ impl CompositorTask {
                         }
                     }
 
+                    SetScrollLayers(id, scroll_layers, epoch) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_scroll_layers(graphics_context.as_ref(),
+                                                                id,
+                                                                scroll_layers,
+                                                                epoch,
+                                                                self.opts.tile_size,
+                                                                Some(10000000)));
+                                ask_for_tiles();
+                            }
+                            None => {}
+                        }
+                    }
+
                     SetLayerClipRect(id, new_rect) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
@@ -331,14 +395,14 @@ impl CompositorTask {
                     DeleteLayer(id) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
-                                assert!(layer.delete(&graphics_context, id));
+                                assert!(layer.delete(graphics_context.as_ref(), id));
                                 ask_for_tiles();
                             }
                             None => {}
                         }
                     }
 
-                    Paint(id, new_layer_buffer_set, epoch) => {
+                    Paint(id, layer_id, new_layer_buffer_set, epoch) => {
                         debug!("osmain: received new frame"); 
 
                         // From now on, if we destroy the buffers, they will leak.
@@ -347,8 +411,9 @@ impl CompositorTask {
 
                         match compositor_layer {
                             Some(ref mut layer) => {
-                                assert!(layer.add_buffers(&graphics_context,
+                                assert!(layer.add_buffers(graphics_context.as_ref(),
                                                           id,
+                                                          layer_id,
                                                           new_layer_buffer_set,
                                                           epoch).is_none());
                                 recomposite = true;
@@ -387,7 +452,7 @@ impl CompositorTask {
                         debug!("osmain: window resized to %ux%u", width, height);
                         window_size = new_size;
                         match constellation_chan {
-                            Some(ref chan) => chan.send(ResizedWindowMsg(new_size)),
+                            Some(ref chan) => chan.send(ResizedWindowMsg(new_size, window.hidpi_factor())),
                             None => error!("Compositor: Recieved resize event without initialized layout chan"),
                         }
                     } else {
@@ -414,6 +479,7 @@ impl CompositorTask {
                         MouseWindowClickEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                         MouseWindowMouseDownEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                         MouseWindowMouseUpEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
+                        MouseWindowMoveEvent(p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                     };
                     for layer in compositor_layer.iter() {
                         layer.send_mouse_event(mouse_window_event, point);
@@ -484,34 +550,57 @@ impl CompositorTask {
         let write_png = self.opts.output_file.is_some();
         let exit = self.opts.exit_after_load;
         let composite = || {
-            do profile(time::CompositingCategory, profiler_chan.clone()) {
-                debug!("compositor: compositing");
-                // Adjust the layer dimensions as necessary to correspond to the size of the window.
-                scene.size = window.size();
-
-                // Render the scene.
-                rendergl::render_scene(context, &scene);
+            // Without a GL context, there is nothing to composite to but the PNG.
+            if !headless {
+                do profile(time::CompositingCategory, profiler_chan.clone()) {
+                    debug!("compositor: compositing");
+                    // Adjust the layer dimensions as necessary to correspond to the size of the
+                    // window.
+                    scene.size = window.size();
+
+                    // Render the scene.
+                    rendergl::render_scene(*context.get_ref(), &scene);
+                }
             }
 
             // Render to PNG. We must read from the back buffer (ie, before
-            // window.present()) as OpenGL ES 2 does not have glReadBuffer().
+            // window.present()) as OpenGL ES 2 does not have glReadBuffer(). A headless
+            // compositor composites the tiles on the CPU instead.
             if write_png {
                 let (width, height) = (window_size.width as uint, window_size.height as uint);
                 let path = Path(*self.opts.output_file.get_ref());
-                let mut pixels = gl2::read_pixels(0, 0,
-                                                  width as gl2::GLsizei,
-                                                  height as gl2::GLsizei,
-                                                  gl2::RGB, gl2::UNSIGNED_BYTE);
-                // flip image vertically (texture is upside down)
-                let orig_pixels = pixels.clone();
-                let stride = width * 3;
-                for y in range(0, height) {
-                    let dst_start = y * stride;
-                    let src_start = (height - y - 1) * stride;
-                    vec::bytes::copy_memory(pixels.mut_slice(dst_start, dst_start + stride),
-                                            orig_pixels.slice(src_start, src_start + stride),
-                                            stride);
-                }
+                let pixels = if headless {
+                    let mut pixels = vec::from_elem(width * height * 3, 255u8);
+                    do profile(time::CompositingCategory, profiler_chan.clone()) {
+                        debug!("compositor: compositing on the CPU");
+                        let window_rect = Rect(Point2D(0f32, 0f32),
+                                               Size2D(width as f32, height as f32));
+                        for layer in compositor_layer.iter().filter(|layer| !layer.hidden) {
+                            layer.composite_in_software(pixels,
+                                                        window_size,
+                                                        Point2D(0f32, 0f32),
+                                                        window_rect,
+                                                        world_zoom);
+                        }
+                    }
+                    pixels
+                } else {
+                    let mut pixels = gl2::read_pixels(0, 0,
+                                                      width as gl2::GLsizei,
+                                                      height as gl2::GLsizei,
+                                                      gl2::RGB, gl2::UNSIGNED_BYTE);
+                    // flip image vertically (texture is upside down)
+                    let orig_pixels = pixels.clone();
+                    let stride = width * 3;
+                    for y in range(0, height) {
+                        let dst_start = y * stride;
+                        let src_start = (height - y - 1) * stride;
+                        vec::bytes::copy_memory(pixels.mut_slice(dst_start, dst_start + stride),
+                                                orig_pixels.slice(src_start, src_start + stride),
+                                                stride);
+                    }
+                    pixels
+                };
                 let img = png::Image {
                     width: width as u32,
                     height: height as u32,
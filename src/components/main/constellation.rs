@@ -39,6 +39,8 @@ pub struct Constellation {
     priv next_pipeline_id: PipelineId,
     pending_frames: ~[FrameChange],
     pending_sizes: HashMap<(PipelineId, SubpageId), Rect<f32>>,
+    /// The ratio of device pixels to CSS pixels, as last reported by the compositor.
+    device_pixel_ratio: f32,
     profiler_chan: ProfilerChan,
     opts: Opts,
 }
@@ -272,6 +274,7 @@ impl Constellation {
                 next_pipeline_id: PipelineId(0),
                 pending_frames: ~[],
                 pending_sizes: HashMap::new(),
+                device_pixel_ratio: 1.0,
                 profiler_chan: profiler_chan,
                 opts: opts
             };
@@ -348,8 +351,8 @@ impl Constellation {
                 self.handle_renderer_ready_msg(pipeline_id);
             }
 
-            ResizedWindowMsg(new_size) => {
-                self.handle_resized_window_msg(new_size);
+            ResizedWindowMsg(new_size, device_pixel_ratio) => {
+                self.handle_resized_window_msg(new_size, device_pixel_ratio);
             }
         }
         true
@@ -436,6 +439,7 @@ impl Constellation {
         // Update a child's frame rect and inform its script task of the change,
         // if it hasn't been already. Optionally inform the compositor if 
         // resize happens immediately.
+        let device_pixel_ratio = self.device_pixel_ratio;
         let update_child_rect = |child_frame_tree: &mut ChildFrameTree, is_active: bool| {
             child_frame_tree.rect = Some(rect.clone());
             let pipeline = &child_frame_tree.frame_tree.pipeline;
@@ -445,11 +449,12 @@ impl Constellation {
                     pipeline.script_chan.send(ResizeMsg(pipeline.id, Size2D {
                         width:  width  as uint,
                         height: height as uint
-                    }));
+                    }, device_pixel_ratio));
                     self.compositor_chan.send(SetLayerClipRect(pipeline.id, rect));
                 } else {
                     pipeline.script_chan.send(ResizeInactiveMsg(pipeline.id,
-                                                                Size2D(width as uint, height as uint)));
+                                                                Size2D(width as uint, height as uint),
+                                                                device_pixel_ratio));
                 }
                 already_sent.insert(pipeline.id);
             }
@@ -531,6 +536,7 @@ impl Constellation {
                                   self.chan.clone(),
                                   self.compositor_chan.clone(),
                                   self.image_cache_task.clone(),
+                                  self.resource_task.clone(),
                                   self.profiler_chan.clone(),
                                   self.opts.clone(),
                                   source_pipeline,
@@ -744,17 +750,20 @@ impl Constellation {
         }
     }
 
-    fn handle_resized_window_msg(&mut self, new_size: Size2D<uint>) {
+    fn handle_resized_window_msg(&mut self, new_size: Size2D<uint>, device_pixel_ratio: f32) {
+        self.device_pixel_ratio = device_pixel_ratio;
         let mut already_seen = HashSet::new();
         for &@FrameTree { pipeline: pipeline, _ } in self.current_frame().iter() {
-            pipeline.script_chan.send(ResizeMsg(pipeline.id, new_size));
+            pipeline.script_chan.send(ResizeMsg(pipeline.id, new_size, device_pixel_ratio));
             already_seen.insert(pipeline.id);
         }
         for frame_tree in self.navigation_context.previous.iter()
             .chain(self.navigation_context.next.iter()) {
             let pipeline = &frame_tree.pipeline;
             if !already_seen.contains(&pipeline.id) {
-                pipeline.script_chan.send(ResizeInactiveMsg(pipeline.id, new_size));
+                pipeline.script_chan.send(ResizeInactiveMsg(pipeline.id,
+                                                            new_size,
+                                                            device_pixel_ratio));
                 already_seen.insert(pipeline.id);
             }
         }
@@ -5,64 +5,114 @@
 // High-level interface to CSS selector matching.
 
 use css::node_util::NodeUtil;
-use css::select_handler::NodeSelectHandler;
 use layout::incremental;
+use layout::incremental::RestyleDamage;
 
+use script::dom::element::Element;
 use script::dom::node::{AbstractNode, LayoutView};
-use newcss::complete::CompleteSelectResults;
-use newcss::select::{SelectCtx, SelectResults};
+use style::{Stylist, ComputedValues, PseudoElement, Before, After, Marker, cascade};
+use style::computed_values::{content, display};
 use servo_util::tree::TreeNodeRef;
 
 pub trait MatchMethods {
-    fn restyle_subtree(&self, select_ctx: &SelectCtx);
+    fn restyle_subtree(&self, stylist: &Stylist);
 }
 
 impl MatchMethods for AbstractNode<LayoutView> {
     /**
-     * Performs CSS selector matching on a subtree.
+     * Performs CSS selector matching and the cascade on a subtree.
      *
      * This is, importantly, the function that updates the layout data for
      * the node (the reader-auxiliary box in the COW model) with the
-     * computed style.
+     * computed style. Parents are styled before their children, so that
+     * inherited values are available.
      */
-    fn restyle_subtree(&self, select_ctx: &SelectCtx) {
+    fn restyle_subtree(&self, stylist: &Stylist) {
         // Only elements have styles
         if self.is_element() {
-            do self.with_imm_element |elem| {
-                let inline_style = match elem.style_attribute {
+            let applicable_declarations = do self.with_imm_element |element| {
+                let style_attribute = match element.style_attribute {
                     None => None,
-                    Some(ref sheet) => Some(sheet),
+                    Some(ref style_attribute) => Some(style_attribute)
                 };
-                let select_handler = NodeSelectHandler { node: *self };
-                let incomplete_results = select_ctx.select_style(self, inline_style, &select_handler);
-                // Combine this node's results with its parent's to resolve all inherited values
-                let complete_results = compose_results(*self, incomplete_results);
+                stylist.get_applicable_declarations::<AbstractNode<LayoutView>, Element>(
+                    self, style_attribute, None)
+            };
 
-                // If there was an existing style, compute the damage that
-                // incremental layout will need to fix.
-                if self.have_css_select_results() {
-                    let damage = incremental::compute_damage(self, self.get_css_select_results(), &complete_results);
-                    self.set_restyle_damage(damage);
+            // Resolve inherited values against the parent element's computed values.
+            let computed_values = match find_parent_element_node(*self) {
+                None => cascade(applicable_declarations, None),
+                Some(parent_node) => {
+                    let parent_style = parent_node.get_css_select_results();
+                    cascade(applicable_declarations, Some(parent_style))
                 }
-                self.set_css_select_results(complete_results);
             };
+
+            // If there was an existing style, compute the damage that
+            // incremental layout will need to fix.
+            let had_style = self.have_css_select_results();
+            let mut damage = if had_style {
+                incremental::compute_damage(self.get_css_select_results(), &computed_values)
+            } else {
+                RestyleDamage::none()
+            };
+            for &pseudo_element in [Before, After].iter() {
+                let pseudo_style = cascade_pseudo_element(*self, stylist, pseudo_element,
+                                                          &computed_values);
+                // Generated content may have appeared, disappeared or changed.
+                if self.get_pseudo_css_select_results(pseudo_element) != pseudo_style.as_ref() {
+                    damage = RestyleDamage::all();
+                }
+                self.set_pseudo_css_select_results(pseudo_element, pseudo_style);
+            }
+            let marker_style = cascade_marker(*self, stylist, &computed_values);
+            if self.get_pseudo_css_select_results(Marker) != marker_style.as_ref() {
+                damage = RestyleDamage::all();
+            }
+            self.set_pseudo_css_select_results(Marker, marker_style);
+            if had_style {
+                self.set_restyle_damage(damage);
+            }
+            self.set_css_select_results(computed_values);
         }
 
         for kid in self.children() {
-            kid.restyle_subtree(select_ctx); 
+            kid.restyle_subtree(stylist);
         }
     }
 }
 
-fn compose_results(node: AbstractNode<LayoutView>, results: SelectResults)
-                   -> CompleteSelectResults {
-    match find_parent_element_node(node) {
-        None => CompleteSelectResults::new_root(results),
-        Some(parent_node) => {
-            let parent_results = parent_node.get_css_select_results();
-            CompleteSelectResults::new_from_parent(parent_results, results)
-        }
-    }    
+/// Computes the style of the `::before` or `::after` pseudo-element of `node`, whose own style is
+/// `parent_style`. Returns `None` if the pseudo-element generates no content.
+fn cascade_pseudo_element(node: AbstractNode<LayoutView>, stylist: &Stylist,
+                          pseudo_element: PseudoElement, parent_style: &ComputedValues)
+                          -> Option<ComputedValues> {
+    let applicable_declarations =
+        stylist.get_applicable_declarations::<AbstractNode<LayoutView>, Element>(
+            &node, None, Some(pseudo_element));
+    if applicable_declarations.is_empty() {
+        return None
+    }
+    let style = cascade(applicable_declarations, Some(parent_style));
+    // 'content: normal' computes to 'none' for these pseudo-elements. (CSS 2.1 § 12.2)
+    let generates_content = match style.Counters.content {
+        content::Content(*) => style.Box.display != display::none,
+        content::normal | content::none => false,
+    };
+    if generates_content { Some(style) } else { None }
+}
+
+/// Computes the style of the `::marker` pseudo-element of `node`, whose own style is
+/// `parent_style`. Every list item has a marker, even if no rule targets it; others have none.
+fn cascade_marker(node: AbstractNode<LayoutView>, stylist: &Stylist, parent_style: &ComputedValues)
+                  -> Option<ComputedValues> {
+    if parent_style.Box.display != display::list_item {
+        return None
+    }
+    let applicable_declarations =
+        stylist.get_applicable_declarations::<AbstractNode<LayoutView>, Element>(
+            &node, None, Some(Marker));
+    Some(cascade(applicable_declarations, Some(parent_style)))
 }
 
 fn find_parent_element_node(node: AbstractNode<LayoutView>) -> Option<AbstractNode<LayoutView>> {
@@ -72,4 +122,3 @@ fn find_parent_element_node(node: AbstractNode<LayoutView>) -> Option<AbstractNo
         None => None,
     }
 }
-
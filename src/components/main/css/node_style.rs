@@ -7,22 +7,28 @@
 use css::node_util::NodeUtil;
 use layout::incremental::RestyleDamage;
 
-use newcss::complete::CompleteStyle;
 use script::dom::node::{AbstractNode, LayoutView};
+use style::{ComputedValues, PseudoElement};
 
-/// Node mixin providing `style` method that returns a `NodeStyle`
+/// Node mixin providing `style` method that returns the computed values of a node
 pub trait StyledNode {
-    fn style(&self) -> CompleteStyle;
+    fn style<'a>(&'a self) -> &'a ComputedValues;
+    fn pseudo_style<'a>(&'a self, pseudo_element: PseudoElement) -> Option<&'a ComputedValues>;
     fn restyle_damage(&self) -> RestyleDamage;
 }
 
 impl StyledNode for AbstractNode<LayoutView> {
     #[inline(always)]
-    fn style(&self) -> CompleteStyle {
+    fn style<'a>(&'a self) -> &'a ComputedValues {
         // FIXME(pcwalton): Is this assertion needed for memory safety? It's slow.
         //debug_assert!(self.is_element()); // Only elements can have styles
-        let results = self.get_css_select_results();
-        results.computed_style()
+        self.get_css_select_results()
+    }
+
+    /// The style of the given pseudo-element of this node, if it generates content.
+    #[inline(always)]
+    fn pseudo_style<'a>(&'a self, pseudo_element: PseudoElement) -> Option<&'a ComputedValues> {
+        self.get_pseudo_css_select_results(pseudo_element)
     }
 
     fn restyle_damage(&self) -> RestyleDamage {
@@ -6,13 +6,17 @@ use layout::incremental::RestyleDamage;
 
 use std::cast;
 use std::cell::Cell;
-use newcss::complete::CompleteSelectResults;
+use style::{ComputedValues, PseudoElement, Before, After, FirstLine, FirstLetter, Marker};
 use script::dom::node::{AbstractNode, LayoutView};
 
 pub trait NodeUtil<'self> {
-    fn get_css_select_results(self) -> &'self CompleteSelectResults;
-    fn set_css_select_results(self, decl: CompleteSelectResults);
+    fn get_css_select_results(self) -> &'self ComputedValues;
+    fn set_css_select_results(self, decl: ComputedValues);
     fn have_css_select_results(self) -> bool;
+    fn get_pseudo_css_select_results(self, pseudo_element: PseudoElement)
+                                     -> Option<&'self ComputedValues>;
+    fn set_pseudo_css_select_results(self, pseudo_element: PseudoElement,
+                                     decl: Option<ComputedValues>);
 
     fn get_restyle_damage(self) -> RestyleDamage;
     fn set_restyle_damage(self, damage: RestyleDamage);
@@ -26,7 +30,7 @@ impl<'self> NodeUtil<'self> for AbstractNode<LayoutView> {
      * FIXME: This isn't completely memory safe since the style is
      * stored in a box that can be overwritten
      */
-    fn get_css_select_results(self) -> &'self CompleteSelectResults {
+    fn get_css_select_results(self) -> &'self ComputedValues {
         do self.read_layout_data |layout_data| {
             match layout_data.style {
                 None => fail!(~"style() called on node without a style!"),
@@ -41,11 +45,43 @@ impl<'self> NodeUtil<'self> for AbstractNode<LayoutView> {
     }
 
     /// Update the computed style of an HTML element with a style specified by CSS.
-    fn set_css_select_results(self, decl: CompleteSelectResults) {
+    fn set_css_select_results(self, decl: ComputedValues) {
         let cell = Cell::new(decl);
         self.write_layout_data(|data| data.style = Some(cell.take()));
     }
 
+    /// Returns the style results for the given pseudo-element of this node, or `None` if it
+    /// does not generate content.
+    fn get_pseudo_css_select_results(self, pseudo_element: PseudoElement)
+                                     -> Option<&'self ComputedValues> {
+        do self.read_layout_data |layout_data| {
+            let style = match pseudo_element {
+                Before => &layout_data.before_style,
+                After => &layout_data.after_style,
+                Marker => &layout_data.marker_style,
+                FirstLine | FirstLetter => fail!(~"::first-line and ::first-letter are not styled"),
+            };
+            match *style {
+                None => None,
+                Some(ref style) => Some(unsafe { cast::transmute_region(style) }),
+            }
+        }
+    }
+
+    /// Update the computed style of a pseudo-element of an HTML element.
+    fn set_pseudo_css_select_results(self, pseudo_element: PseudoElement,
+                                     decl: Option<ComputedValues>) {
+        let cell = Cell::new(decl);
+        do self.write_layout_data |data| {
+            match pseudo_element {
+                Before => data.before_style = cell.take(),
+                After => data.after_style = cell.take(),
+                Marker => data.marker_style = cell.take(),
+                FirstLine | FirstLetter => fail!(~"::first-line and ::first-letter are not styled"),
+            }
+        }
+    }
+
     /// Get the description of how to account for recent style changes.
     /// This is a simple bitfield and fine to copy by value.
     fn get_restyle_damage(self) -> RestyleDamage {
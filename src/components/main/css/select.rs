@@ -2,55 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use extra::url::Url;
-use std::cell::Cell;
-use newcss::stylesheet::Stylesheet;
-use newcss::select::SelectCtx;
-use newcss::types::OriginUA;
-use newcss::util::DataStream;
+use extra::url;
+use style::{Device, Screen, Stylesheet, Stylist, UserAgentOrigin};
+use style::computed_values::Length;
 
-pub fn new_css_select_ctx() -> SelectCtx {
-    let mut ctx = SelectCtx::new();
-    ctx.append_sheet(html4_default_style(), OriginUA);
-    ctx.append_sheet(servo_default_style(), OriginUA);
-    return ctx;
+pub fn new_stylist() -> Stylist {
+    // The viewport size is not known yet, it is set on the first reflow.
+    let mut stylist = Stylist::new(Device::new(Screen, Length(0), Length(0), 1.0));
+    stylist.add_stylesheet(html4_default_style(), UserAgentOrigin);
+    stylist
 }
 
 fn html4_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("html4_style"),
-                    style_stream(html4_default_style_str()))
+    Stylesheet::from_str(html4_default_style_str().as_slice(),
+                         url::from_str("about:user-agent-stylesheet").unwrap())
 }
-
-fn servo_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("servo_style"),
-                    style_stream(servo_default_style_str()))
-}
-
-fn default_url(name: &str) -> Url {
-    FromStr::from_str(fmt!("http://%s", name)).unwrap()
-}
-
-struct StyleStream {
-    style: Option<~str>,
-}
-
-impl DataStream for StyleStream {
-    fn read(&mut self) -> Option<~[u8]> {
-        if self.style.is_none() {
-            None
-        } else {
-            let style = self.style.take_unwrap();
-            Some(style.as_bytes().to_owned())
-        }
-    }
-}
-
-fn style_stream(style: &str) -> @mut DataStream {
-    @mut StyleStream {
-        style: Some(style.to_owned()),
-    } as @mut DataStream
-}
-
 fn html4_default_style_str() -> ~str {
 ~"
 html, address,
@@ -105,6 +71,7 @@ button, textarea,
 thead, tbody,
     tfoot           { vertical-align: middle }
     td, th, tr      { vertical-align: inherit }
+    td, th          { padding: 1px }
     s, strike, del  { text-decoration: line-through }
     hr              { border: 1px inset }
 ol, ul, dir,
@@ -136,10 +103,10 @@ ul, ol, dl    { page-break-before: avoid }
 :link           { color: blue }
 script          { display: none }
 style           { display: none }
+q:before        { content: open-quote }
+q:after         { content: close-quote }
+ul ul, ol ul    { list-style-type: circle }
+ul ul ul, ul ol ul,
+ol ul ul, ol ol ul { list-style-type: square }
 "
 }
-
-fn servo_default_style_str() -> ~str {
-    // libcss want's this to default to 2px..
-    ~"* { border-width: 0px; }"
-}
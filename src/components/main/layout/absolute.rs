@@ -0,0 +1,307 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS absolute and fixed positioning. (CSS 2.1 § 9.6)
+//!
+//! An absolutely positioned element is laid out like a block, but it takes no space in its
+//! parent. Its width is known once its containing block is, but its vertical position may depend
+//! on the height of the containing block, so it is only placed when the display list is built.
+
+use css::node_style::StyledNode;
+use layout::block::BlockFlow;
+use layout::box::RenderBoxUtils;
+use layout::context::LayoutContext;
+use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
+use layout::flow::{AbsoluteFlowClass, FlowClass, FlowContext, FlowData};
+use layout::flow;
+use layout::float_context::FloatContext;
+use layout::model::{Auto, CollapsibleMargins, MaybeAuto, Specified};
+
+use std::cell::Cell;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use gfx::display_list::DisplayList;
+use gfx::geometry::Au;
+use gfx::geometry;
+use style::computed_values::{direction, position};
+
+pub struct AbsoluteFlow {
+    /// The block flow that lays out the contents of the element, and holds its render box.
+    block_flow: BlockFlow,
+
+    /// The minimum width of the contents of the element, for shrink-to-fit.
+    min_content_width: Au,
+
+    /// The preferred width of the contents of the element, for shrink-to-fit.
+    pref_content_width: Au,
+
+    /// The left margin edge of the element, relative to its containing block. `None` if the
+    /// element stays at its static position.
+    left: Option<Au>,
+
+    /// The height of the margin box of the element.
+    height: Au,
+}
+
+impl AbsoluteFlow {
+    pub fn new(base: FlowData) -> AbsoluteFlow {
+        let mut block_flow = BlockFlow::new(base);
+        block_flow.establishes_bfc = true;
+        AbsoluteFlow {
+            block_flow: block_flow,
+            min_content_width: Au::new(0),
+            pref_content_width: Au::new(0),
+            left: None,
+            height: Au::new(0),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown()
+    }
+
+    /// Returns true if this element is fixed to the viewport rather than to an ancestor.
+    fn is_fixed(&self) -> bool {
+        match self.block_flow.box {
+            Some(box) => box.base().node.style().Box.position == position::fixed,
+            None => false,
+        }
+    }
+
+    /// Returns the containing block of this element: the viewport for fixed position elements,
+    /// which are placed as if the page were not scrolled since the compositor keeps their layer
+    /// in place, and the padding box of the nearest positioned ancestor otherwise. (CSS 2.1 § 10.1)
+    fn containing_block(&self, ctx: &LayoutContext) -> Rect<Au> {
+        if self.is_fixed() {
+            ctx.screen_size
+        } else {
+            self.block_flow.base.absolute_cb
+        }
+    }
+
+    /// Places this element against its containing block and builds its display list. The parent
+    /// has set `abs_position` to the static position of the element.
+    pub fn build_display_list_absolute<E:ExtraDisplayListData>(
+                                       &mut self,
+                                       builder: &DisplayListBuilder,
+                                       dirty: &Rect<Au>,
+                                       list: &Cell<DisplayList<E>>)
+                                       -> bool {
+        let containing_block = self.containing_block(builder.ctx);
+        let static_position = self.block_flow.base.abs_position;
+
+        let x = match self.left {
+            Some(left) => containing_block.origin.x + left,
+            None => static_position.x,
+        };
+
+        // Vertical positions depend on the height of the containing block, which is only known
+        // now. (CSS 2.1 § 10.6.4)
+        let mut height = self.height;
+        let mut y = static_position.y;
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let node = base.node;
+            let style = node.style();
+            let cb_height = containing_block.size.height;
+            let top = MaybeAuto::from_style(style.Box.top, cb_height);
+            let bottom = MaybeAuto::from_style(style.Box.bottom, cb_height);
+
+            match (top, MaybeAuto::from_style(style.Box.height, cb_height), bottom) {
+                (Specified(top), Auto, Specified(bottom)) => {
+                    // The element stretches between the two offsets.
+                    height = geometry::max(cb_height - top - bottom, Au::new(0));
+                    base.position.size.height = geometry::max(height - base.model.margin.top -
+                                                              base.model.margin.bottom,
+                                                              Au::new(0));
+                }
+                _ => {}
+            }
+
+            y = match (top, bottom) {
+                (Specified(top), _) => containing_block.origin.y + top,
+                (Auto, Specified(bottom)) => {
+                    containing_block.origin.y + cb_height - bottom - height
+                }
+                (Auto, Auto) => static_position.y,
+            };
+        }
+
+        self.block_flow.base.abs_position = Point2D(x, y);
+        self.block_flow.base.position.size.height = height;
+        self.block_flow.build_display_list_block(builder, dirty, list)
+    }
+}
+
+impl FlowContext for AbsoluteFlow {
+    fn class(&self) -> FlowClass {
+        AbsoluteFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_absolute<'a>(&'a mut self) -> &'a mut AbsoluteFlow {
+        self
+    }
+
+    /// Computes the widths of the contents of the element, for shrink-to-fit. Floats do not
+    /// escape an absolutely positioned element, and it adds nothing to the widths of its parent.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au::new(0);
+        let mut pref_width = Au::new(0);
+        let mut num_floats = 0;
+
+        for child_ctx in self.block_flow.base.child_iter() {
+            if child_ctx.class() == AbsoluteFlowClass {
+                loop
+            }
+
+            let child_base = flow::mut_base(*child_ctx);
+            min_width = geometry::max(min_width, child_base.min_width);
+            pref_width = geometry::max(pref_width, child_base.pref_width);
+            num_floats = num_floats + child_base.num_floats;
+        }
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let node = base.node;
+            base.model.compute_borders(node.style());
+        }
+
+        self.min_content_width = min_width;
+        self.pref_content_width = geometry::max(pref_width, min_width);
+        self.block_flow.base.min_width = Au::new(0);
+        self.block_flow.base.pref_width = Au::new(0);
+        self.block_flow.base.num_floats = num_floats;
+    }
+
+    /// Computes the width, the horizontal margins and the horizontal position of the element.
+    /// (CSS 2.1 § 10.3.7) The parent has set the position of this flow to its static position.
+    /// When 'left' and 'right' are over-constrained, 'right' gives way in a left-to-right
+    /// containing block and 'left' in a right-to-left one.
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_widths_absolute: assigning width for flow %?", self.block_flow.base.id);
+        let cb_width = self.containing_block(ctx).size.width;
+        let mut x_offset = Au::new(0);
+        let mut content_width = cb_width;
+        let mut margin_box_width = cb_width;
+        let mut left_position = None;
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let base = &mut *base;
+            let node = base.node;
+            let style = node.style();
+            let direction = base.containing_block_direction();
+            let model = &mut base.model;
+
+            model.compute_padding(style, cb_width);
+            let border_padding = model.border.left + model.border.right + model.padding.left +
+                model.padding.right;
+
+            let left = MaybeAuto::from_style(style.Box.left, cb_width);
+            let right = MaybeAuto::from_style(style.Box.right, cb_width);
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left, cb_width);
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right, cb_width);
+            let margin_left_or_zero = margin_left.specified_or_zero();
+            let margin_right_or_zero = margin_right.specified_or_zero();
+
+            let (width, margin_left, margin_right) =
+                    match (left, MaybeAuto::from_style(style.Box.width, cb_width), right) {
+                (Specified(left), Specified(width), Specified(right)) => {
+                    // Auto margins take up the remaining space.
+                    let remaining = cb_width - left - width - border_padding - right;
+                    match (margin_left, margin_right) {
+                        (Auto, Auto) => {
+                            let margin = geometry::max(remaining, Au::new(0)).scale_by(0.5);
+                            (width, margin, margin)
+                        }
+                        (Auto, Specified(margin_right)) => {
+                            (width, remaining - margin_right, margin_right)
+                        }
+                        (Specified(margin_left), _) => (width, margin_left, margin_right_or_zero),
+                    }
+                }
+                (Specified(left), Auto, Specified(right)) => {
+                    let width = cb_width - left - right - margin_left_or_zero -
+                        margin_right_or_zero - border_padding;
+                    (geometry::max(width, Au::new(0)), margin_left_or_zero, margin_right_or_zero)
+                }
+                (_, Specified(width), _) => (width, margin_left_or_zero, margin_right_or_zero),
+                (left, Auto, right) => {
+                    // Shrink-to-fit.
+                    let available_width = cb_width - left.specified_or_zero() -
+                        right.specified_or_zero() - margin_left_or_zero - margin_right_or_zero -
+                        border_padding;
+                    let width = geometry::min(geometry::max(self.min_content_width,
+                                                            available_width),
+                                              self.pref_content_width);
+                    (width, margin_left_or_zero, margin_right_or_zero)
+                }
+            };
+
+            model.margin.top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                     cb_width).specified_or_zero();
+            model.margin.right = margin_right;
+            model.margin.bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                        cb_width).specified_or_zero();
+            model.margin.left = margin_left;
+
+            x_offset = model.offset();
+            content_width = width;
+            margin_box_width = margin_left + border_padding + width + margin_right;
+            left_position = match (left, right) {
+                (Specified(_), Specified(right)) if direction == direction::rtl => {
+                    Some(cb_width - right - margin_box_width)
+                }
+                (Specified(left), _) => Some(left),
+                (Auto, Specified(right)) => Some(cb_width - right - margin_box_width),
+                (Auto, Auto) => None,
+            };
+
+            // The associated box is the border box of this flow.
+            base.position.origin.x = margin_left;
+            base.position.size.width = width + border_padding;
+        }
+
+        self.left = left_position;
+        self.block_flow.base.position.size.width = margin_box_width;
+
+        let num_floats = self.block_flow.base.num_floats;
+        self.block_flow.base.floats_in = FloatContext::new(num_floats);
+        self.block_flow.base.is_inorder = false;
+        self.block_flow.propagate_assigned_width_to_children(x_offset, content_width);
+    }
+
+    /// Lays out the contents of the element, then hides its height from the parent, which
+    /// stacks its children by their heights.
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height_absolute: assigning height for flow %?", self.block_flow.base.id);
+        // Like a table cell, an element with floats inside starts an in-order traversal.
+        if self.block_flow.base.num_floats > 0 {
+            self.block_flow.assign_height_inorder(ctx);
+        } else {
+            self.block_flow.assign_height(ctx);
+        }
+        self.height = self.block_flow.base.position.size.height;
+        self.block_flow.base.position.size.height = Au::new(0);
+    }
+
+    /// The height was assigned in `assign_height`, and the element does not affect the floats
+    /// around it.
+    fn assign_height_inorder(&mut self, _: &mut LayoutContext) {
+        self.block_flow.base.floats_out = self.block_flow.base.floats_in.clone();
+    }
+
+    /// Out-of-flow elements take no part in margin collapsing.
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
+        CollapsibleMargins::out_of_flow()
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("AbsoluteFlow(f%d)", self.block_flow.base.id)
+    }
+}
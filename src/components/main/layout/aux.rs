@@ -4,6 +4,8 @@
 
 //! Code for managing the layout data in the DOM.
 
+use layout::incremental::RestyleDamage;
+
 use script::dom::node::{AbstractNode, LayoutView};
 use servo_util::tree::TreeNodeRef;
 
@@ -19,6 +21,12 @@ impl LayoutAuxMethods for AbstractNode<LayoutView> {
         do self.write_layout_data |data| {
             data.boxes.display_list = None;
             data.boxes.range = None;
+
+            // The last reflow dealt with the damage of the last restyle. Nodes that were never
+            // styled keep having all of the damage.
+            if data.style.is_some() {
+                data.restyle_damage = Some(RestyleDamage::none().to_int())
+            }
         }
     }
 
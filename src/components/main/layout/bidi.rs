@@ -0,0 +1,407 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The Unicode Bidirectional Algorithm (UAX #9), which decides the direction of each character of
+//! mixed left-to-right and right-to-left text, and the order in which the characters of a line
+//! are displayed.
+//!
+//! The explicit embedding levels come from the 'direction' and 'unicode-bidi' properties rather
+//! than from formatting characters, as CSS 2.1 § 9.10 recommends.
+
+/// The bidirectional character types. (UAX #9 § 3.2)
+#[deriving(Eq, Clone)]
+pub enum BidiClass {
+    LeftToRight,
+    RightToLeft,
+    ArabicLetter,
+    EuropeanNumber,
+    EuropeanSeparator,
+    EuropeanTerminator,
+    ArabicNumber,
+    CommonSeparator,
+    NonspacingMark,
+    BoundaryNeutral,
+    ParagraphSeparator,
+    SegmentSeparator,
+    Whitespace,
+    OtherNeutral,
+}
+
+/// Returns the bidirectional type of a character.
+///
+/// FIXME: This covers the scripts and punctuation that pages commonly use rather than the whole
+/// of the Unicode Character Database. Anything else is taken to be left to right.
+pub fn bidi_class(ch: char) -> BidiClass {
+    match ch {
+        '0' .. '9' | '\xb2' | '\xb3' | '\xb9' | '\u06f0' .. '\u06f9' | '\u2070' |
+        '\u2074' .. '\u2079' | '\u2080' .. '\u2089' | '\uff10' .. '\uff19' => EuropeanNumber,
+        '+' | '-' | '\u207a' | '\u207b' | '\u208a' | '\u208b' | '\u2212' => EuropeanSeparator,
+        '#' | '$' | '%' | '\xa2' .. '\xa5' | '\xb0' | '\xb1' | '\u066a' |
+        '\u2030' .. '\u2034' | '\u20a0' .. '\u20cf' => EuropeanTerminator,
+        '\u0600' .. '\u0605' | '\u0660' .. '\u0669' | '\u066b' | '\u066c' => ArabicNumber,
+        ',' | '.' | '/' | ':' | '\xa0' | '\u060c' | '\u202f' | '\u2044' => CommonSeparator,
+        '\u0300' .. '\u036f' | '\u0483' .. '\u0489' | '\u0591' .. '\u05bd' | '\u05bf' |
+        '\u05c1' | '\u05c2' | '\u05c4' | '\u05c5' | '\u05c7' | '\u0610' .. '\u061a' |
+        '\u064b' .. '\u065f' | '\u0670' | '\u06d6' .. '\u06dc' | '\u06df' .. '\u06e4' |
+        '\u06e7' | '\u06e8' | '\u06ea' .. '\u06ed' | '\u20d0' .. '\u20ff' | '\ufb1e' |
+        '\ufe00' .. '\ufe0f' | '\ufe20' .. '\ufe2f' => NonspacingMark,
+        '\x00' .. '\x08' | '\x0e' .. '\x1b' | '\x7f' .. '\x84' | '\x86' .. '\x9f' | '\xad' |
+        '\u200b' .. '\u200d' | '\u2060' .. '\u2064' | '\ufeff' => BoundaryNeutral,
+        '\n' | '\r' | '\x1c' .. '\x1e' | '\x85' | '\u2029' => ParagraphSeparator,
+        '\t' | '\x0b' | '\x1f' => SegmentSeparator,
+        ' ' | '\x0c' | '\u2000' .. '\u200a' | '\u2028' | '\u205f' | '\u3000' => Whitespace,
+        '\u200e' => LeftToRight,
+        '\u200f' | '\u0590' .. '\u05ff' | '\u07c0' .. '\u085f' | '\ufb1d' .. '\ufb4f' |
+        '\U00010800' .. '\U00010fff' | '\U0001e800' .. '\U0001efff' => RightToLeft,
+        '\u0606' .. '\u07bf' | '\u0860' .. '\u08ff' | '\ufb50' .. '\ufdff' |
+        '\ufe70' .. '\ufefe' => ArabicLetter,
+        '!' | '"' | '&' | '\'' | '(' | ')' | '*' | ';' .. '@' | '[' .. '`' | '{' .. '~' |
+        '\xa1' | '\xa6' .. '\xa9' | '\xab' | '\xac' | '\xae' | '\xaf' | '\xb4' |
+        '\xb6' .. '\xb8' | '\xbb' .. '\xbf' | '\xd7' | '\xf7' | '\u2010' .. '\u2027' |
+        '\u2035' .. '\u205e' | '\u2190' .. '\u2bff' | '\u3001' .. '\u3004' |
+        '\u3008' .. '\u3020' | '\ufffc' | '\ufffd' => OtherNeutral,
+        _ => LeftToRight,
+    }
+}
+
+/// Returns true if text with characters of this type needs the algorithm to be laid out. Text
+/// without them is left to right unless an embedding says otherwise.
+pub fn is_right_to_left_class(class: BidiClass) -> bool {
+    match class {
+        RightToLeft | ArabicLetter | ArabicNumber => true,
+        _ => false,
+    }
+}
+
+/// The embedding that 'unicode-bidi' and 'direction' give to a character. (CSS 2.1 § 9.10)
+#[deriving(Eq, Clone)]
+pub struct Embedding {
+    /// The explicit embedding level. Odd levels are right to left.
+    level: u8,
+    /// True if 'unicode-bidi: bidi-override' forces all characters into the direction of the
+    /// level.
+    is_override: bool,
+}
+
+impl Embedding {
+    /// The embedding of the characters of a paragraph that are not inside any embedding.
+    pub fn new(paragraph_level: u8) -> Embedding {
+        Embedding {
+            level: paragraph_level,
+            is_override: false,
+        }
+    }
+
+    /// Returns the embedding of an inline element with the given 'direction' and
+    /// 'unicode-bidi: embed' or 'unicode-bidi: bidi-override' inside this one: the least level
+    /// above this one of the right parity. (UAX #9 X2–X5)
+    pub fn push(&self, is_rtl: bool, is_override: bool) -> Embedding {
+        let level = if is_rtl {
+            (self.level + 1) | 1
+        } else {
+            (self.level + 2) & !1
+        };
+        // Levels above the maximum depth are ignored.
+        if level > MAX_DEPTH {
+            return *self
+        }
+        Embedding {
+            level: level,
+            is_override: is_override,
+        }
+    }
+}
+
+/// The deepest explicit embedding level. (UAX #9 § 3.3.2)
+static MAX_DEPTH: u8 = 61;
+
+#[inline]
+fn is_odd(level: u8) -> bool {
+    level & 1 == 1
+}
+
+/// The strong type of the direction of an embedding level.
+#[inline]
+fn class_of_level(level: u8) -> BidiClass {
+    if is_odd(level) { RightToLeft } else { LeftToRight }
+}
+
+/// Resolves the embedding level of each character of a paragraph, given the embedding of each
+/// character and the base level of the paragraph. (UAX #9 § 3.3)
+pub fn resolve_levels(chars: &[char], embeddings: &[Embedding], paragraph_level: u8) -> ~[u8] {
+    assert!(chars.len() == embeddings.len());
+
+    let original_classes = chars.map(|&ch| bidi_class(ch));
+    let mut levels = embeddings.map(|embedding| embedding.level);
+
+    // X6: An override changes the type of every character that is not a separator or removed.
+    let mut classes = ~[];
+    for (i, &class) in original_classes.iter().enumerate() {
+        classes.push(match class {
+            BoundaryNeutral | ParagraphSeparator => class,
+            _ if embeddings[i].is_override => class_of_level(embeddings[i].level),
+            _ => class,
+        })
+    }
+
+    // X9: Boundary neutrals take no part in the rest of the algorithm.
+    let mut kept = ~[];
+    for (i, &class) in classes.iter().enumerate() {
+        if class != BoundaryNeutral {
+            kept.push(i)
+        }
+    }
+
+    // X10: Each maximal run of characters at the same level is resolved on its own. The types at
+    // its start and end come from the higher of the levels on either side of each boundary.
+    let explicit_level = |i: uint| embeddings[kept[i]].level;
+    let mut run_start = 0;
+    while run_start < kept.len() {
+        let level = explicit_level(run_start);
+        let mut run_end = run_start + 1;
+        while run_end < kept.len() && explicit_level(run_end) == level {
+            run_end += 1;
+        }
+
+        let previous_level = if run_start == 0 {
+            paragraph_level
+        } else {
+            explicit_level(run_start - 1)
+        };
+        let next_level = if run_end == kept.len() {
+            paragraph_level
+        } else {
+            explicit_level(run_end)
+        };
+        let sos = class_of_level(level.max(&previous_level));
+        let eos = class_of_level(level.max(&next_level));
+
+        let run = kept.slice(run_start, run_end);
+        let mut run_classes = run.map(|&i| classes[i]);
+        resolve_weak_types(run_classes, sos);
+        resolve_neutral_types(run_classes, sos, eos, level);
+        for (&i, &class) in run.iter().zip(run_classes.iter()) {
+            classes[i] = class;
+            // I1 and I2: Resolve the implicit levels.
+            levels[i] = match (is_odd(level), class) {
+                (false, RightToLeft) => level + 1,
+                (false, ArabicNumber) | (false, EuropeanNumber) => level + 2,
+                (true, LeftToRight) | (true, ArabicNumber) | (true, EuropeanNumber) => level + 1,
+                _ => level,
+            };
+        }
+
+        run_start = run_end;
+    }
+
+    // Boundary neutrals take the level of the character before them, so that they do not split
+    // the text that they are in.
+    for i in range(0, chars.len()) {
+        if classes[i] == BoundaryNeutral {
+            levels[i] = if i == 0 { paragraph_level } else { levels[i - 1] };
+        }
+    }
+
+    // L1: Separators, and the whitespace before them and at the end of the paragraph, go back to
+    // the paragraph level.
+    let mut trailing_whitespace = true;
+    for i in range(0, chars.len()).invert() {
+        match original_classes[i] {
+            ParagraphSeparator | SegmentSeparator => {
+                levels[i] = paragraph_level;
+                trailing_whitespace = true;
+            }
+            Whitespace | BoundaryNeutral if trailing_whitespace => levels[i] = paragraph_level,
+            _ => trailing_whitespace = false,
+        }
+    }
+
+    levels
+}
+
+/// Resolves the weak types of a level run. (UAX #9 W1–W7)
+fn resolve_weak_types(classes: &mut [BidiClass], sos: BidiClass) {
+    // W1: Nonspacing marks take the type of the character before them.
+    let mut previous = sos;
+    for class in classes.mut_iter() {
+        if *class == NonspacingMark {
+            *class = previous;
+        }
+        previous = *class;
+    }
+
+    // W2: European numbers after Arabic letters are Arabic numbers. W3: Arabic letters are right
+    // to left.
+    let mut last_strong = sos;
+    for class in classes.mut_iter() {
+        match *class {
+            LeftToRight | RightToLeft => last_strong = *class,
+            ArabicLetter => {
+                last_strong = ArabicLetter;
+                *class = RightToLeft;
+            }
+            EuropeanNumber if last_strong == ArabicLetter => *class = ArabicNumber,
+            _ => {}
+        }
+    }
+
+    // W4: A single separator between two numbers of the same type joins them.
+    for i in range(1, classes.len().max(&1) - 1) {
+        let (before, after) = (classes[i - 1], classes[i + 1]);
+        classes[i] = match (before, classes[i], after) {
+            (EuropeanNumber, EuropeanSeparator, EuropeanNumber) |
+            (EuropeanNumber, CommonSeparator, EuropeanNumber) => EuropeanNumber,
+            (ArabicNumber, CommonSeparator, ArabicNumber) => ArabicNumber,
+            (_, class, _) => class,
+        }
+    }
+
+    // W5: Terminators next to European numbers are European numbers.
+    let mut i = 0;
+    while i < classes.len() {
+        if classes[i] != EuropeanTerminator {
+            i += 1;
+            loop
+        }
+        let start = i;
+        while i < classes.len() && classes[i] == EuropeanTerminator {
+            i += 1;
+        }
+        let next_to_number = (start > 0 && classes[start - 1] == EuropeanNumber) ||
+            (i < classes.len() && classes[i] == EuropeanNumber);
+        if next_to_number {
+            for j in range(start, i) {
+                classes[j] = EuropeanNumber;
+            }
+        }
+    }
+
+    // W6: Other separators and terminators are neutral. W7: European numbers after left-to-right
+    // text are left to right.
+    let mut last_strong = sos;
+    for class in classes.mut_iter() {
+        match *class {
+            EuropeanSeparator | EuropeanTerminator | CommonSeparator => *class = OtherNeutral,
+            LeftToRight | RightToLeft => last_strong = *class,
+            EuropeanNumber if last_strong == LeftToRight => *class = LeftToRight,
+            _ => {}
+        }
+    }
+}
+
+/// Resolves the neutral types of a level run. (UAX #9 N1 and N2)
+fn resolve_neutral_types(classes: &mut [BidiClass], sos: BidiClass, eos: BidiClass, level: u8) {
+    // Numbers count as right to left here.
+    fn strong_direction(class: BidiClass) -> Option<BidiClass> {
+        match class {
+            LeftToRight => Some(LeftToRight),
+            RightToLeft | ArabicNumber | EuropeanNumber => Some(RightToLeft),
+            _ => None,
+        }
+    }
+
+    let mut i = 0;
+    while i < classes.len() {
+        if strong_direction(classes[i]).is_some() {
+            i += 1;
+            loop
+        }
+        let start = i;
+        while i < classes.len() && strong_direction(classes[i]).is_none() {
+            i += 1;
+        }
+        let before = if start == 0 { sos } else { strong_direction(classes[start - 1]).unwrap() };
+        let after = if i == classes.len() { eos } else { strong_direction(classes[i]).unwrap() };
+        // N1: Neutrals between text of the same direction take that direction. N2: Others take
+        // the direction of the embedding.
+        let class = if before == after { before } else { class_of_level(level) };
+        for j in range(start, i) {
+            classes[j] = class;
+        }
+    }
+}
+
+/// Returns the indices of the items of a line, given their resolved levels, in the order in which
+/// they are displayed from left to right: from the highest level down to the lowest odd level,
+/// each run of items at that level or above is reversed. (UAX #9 L2)
+pub fn visual_order(levels: &[u8]) -> ~[uint] {
+    let mut order: ~[uint] = range(0, levels.len()).collect();
+    if levels.is_empty() {
+        return order
+    }
+
+    let highest = levels.iter().fold(0u8, |a, &b| a.max(&b));
+    let lowest_odd = levels.iter().fold(highest | 1, |a, &b| if is_odd(b) { a.min(&b) } else { a });
+    let mut level = highest;
+    while level >= lowest_odd && level > 0 {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                loop
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order.mut_slice(start, i).reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+#[cfg(test)]
+mod bidi_tests {
+    use super::*;
+
+    fn levels_of(text: &str, paragraph_level: u8) -> ~[u8] {
+        let chars: ~[char] = text.iter().collect();
+        let embeddings = chars.map(|_| Embedding::new(paragraph_level));
+        resolve_levels(chars, embeddings, paragraph_level)
+    }
+
+    #[test]
+    fn left_to_right() {
+        assert_eq!(levels_of("abc def", 0), ~[0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(levels_of("abc", 1), ~[2, 2, 2]);
+    }
+
+    #[test]
+    fn hebrew_in_english() {
+        // "ab אב cd": the neutral spaces between text of different directions take the
+        // direction of the paragraph.
+        assert_eq!(levels_of("ab אב cd", 0), ~[0, 0, 0, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn numbers() {
+        // Numbers in right-to-left text are laid out left to right, at a higher level.
+        assert_eq!(levels_of("א 12", 1), ~[1, 1, 2, 2]);
+        // A separator between two numbers joins them.
+        assert_eq!(levels_of("א 1.5", 1), ~[1, 1, 2, 2, 2]);
+        // European numbers after Arabic letters are Arabic numbers.
+        assert_eq!(levels_of("ا 1", 0), ~[1, 1, 2]);
+    }
+
+    #[test]
+    fn bidi_override() {
+        let chars: ~[char] = "abא".iter().collect();
+        let embedding = Embedding::new(0).push(true, true);
+        let levels = resolve_levels(chars, [embedding, embedding, embedding], 0);
+        assert_eq!(levels, ~[1, 1, 1]);
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        assert_eq!(levels_of("א ", 0), ~[1, 0]);
+    }
+
+    #[test]
+    fn reordering() {
+        assert_eq!(visual_order([0, 0, 1, 1, 0]), ~[0, 1, 3, 2, 4]);
+        assert_eq!(visual_order([1, 1, 2, 2, 1]), ~[4, 2, 3, 1, 0]);
+        assert_eq!(visual_order([]), ~[]);
+    }
+}
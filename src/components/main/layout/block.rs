@@ -4,12 +4,15 @@
 
 //! CSS block layout.
 
+use css::node_style::StyledNode;
 use layout::box::{RenderBox, RenderBoxUtils};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
-use layout::flow::{BlockFlowClass, FlowClass, FlowContext, FlowData, ImmutableFlowUtils};
+use layout::flow::{AbsoluteFlowClass, BlockFlowClass, FloatFlowClass, FlowClass, FlowContext};
+use layout::flow::{FlowData, ImmutableFlowUtils};
 use layout::flow;
-use layout::model::{MaybeAuto, Specified, Auto};
+use layout::model::{AdjoiningMargins, CollapsibleMargins, MaybeAuto, SizeConstraint};
+use layout::model::{Specified, Auto, box_sizing_offset};
 use layout::float_context::{FloatContext, Invalid};
 
 use std::cell::Cell;
@@ -19,6 +22,7 @@ use geom::rect::Rect;
 use gfx::display_list::DisplayList;
 use gfx::geometry::{Au, to_frac_px};
 use gfx::geometry;
+use style::computed_values::{direction, overflow};
 
 pub struct BlockFlow {
     /// Data common to all flows.
@@ -28,7 +32,14 @@ pub struct BlockFlow {
     box: Option<@mut RenderBox>,
 
     /// Whether this block flow is the root flow.
-    is_root: bool
+    is_root: bool,
+
+    /// Whether this flow establishes a new block formatting context, as table cells do. The
+    /// margins of its children then do not collapse with its own. (CSS 2.1 § 8.3.1)
+    establishes_bfc: bool,
+
+    /// How the margins of this flow collapse with those around it, once its height is known.
+    collapsible_margins: CollapsibleMargins,
 }
 
 impl BlockFlow {
@@ -36,7 +47,9 @@ impl BlockFlow {
         BlockFlow {
             base: base,
             box: None,
-            is_root: false
+            is_root: false,
+            establishes_bfc: false,
+            collapsible_margins: CollapsibleMargins::without_margins(false),
         }
     }
 
@@ -44,7 +57,9 @@ impl BlockFlow {
         BlockFlow {
             base: base,
             box: None,
-            is_root: true
+            is_root: true,
+            establishes_bfc: true,
+            collapsible_margins: CollapsibleMargins::without_margins(false),
         }
     }
 
@@ -56,12 +71,14 @@ impl BlockFlow {
     }
 
     /// Computes left and right margins and width based on CSS 2.1 section 10.3.3.
-    /// Requires borders and padding to already be computed.
-    fn compute_horiz(&self,
+    /// Requires borders and padding to already be computed. `direction` is that of the containing
+    /// block.
+    pub fn compute_horiz(&self,
                      width: MaybeAuto, 
                      left_margin: MaybeAuto, 
                      right_margin: MaybeAuto, 
-                     available_width: Au)
+                     available_width: Au,
+                     direction: direction::ComputedValue)
                      -> (Au, Au, Au) {
         // If width is not 'auto', and width + margins > available_width, all 'auto' margins are
         // treated as 0.
@@ -83,8 +100,13 @@ impl BlockFlow {
         let (left_margin_Au, width_Au, right_margin_Au) = match (left_margin, width, right_margin) {
             //If all have a computed value other than 'auto', the system is over-constrained and we need to discard a margin.
             //if direction is ltr, ignore the specified right margin and solve for it. If it is rtl, ignore the specified 
-            //left margin. FIXME(eatkinson): this assumes the direction is ltr
-            (Specified(margin_l), Specified(width), Specified(_margin_r)) => (margin_l, width, available_width - (margin_l + width )),
+            //left margin.
+            (Specified(margin_l), Specified(width), Specified(margin_r)) => {
+                match direction {
+                    direction::ltr => (margin_l, width, available_width - (margin_l + width)),
+                    direction::rtl => (available_width - (width + margin_r), width, margin_r),
+                }
+            }
 
             //If exactly one value is 'auto', solve for it
             (Auto, Specified(width), Specified(margin_r)) => (available_width - (width + margin_r), width, margin_r),
@@ -111,10 +133,9 @@ impl BlockFlow {
     // methods
     #[inline(always)]
     fn assign_height_block_base(&mut self, ctx: &mut LayoutContext, inorder: bool) {
-        let mut cur_y = Au::new(0);
         let mut clearance = Au::new(0);
         let mut top_offset = Au::new(0);
-        let mut bottom_offset = Au::new(0);
+        let mut border_padding_top = Au::new(0);
         let mut left_offset = Au::new(0);
         let mut float_ctx = Invalid;
 
@@ -128,9 +149,8 @@ impl BlockFlow {
             };
 
             let model = &mut base.model;
-            top_offset = clearance + model.margin.top + model.border.top + model.padding.top;
-            cur_y = cur_y + top_offset;
-            bottom_offset = model.margin.bottom + model.border.bottom + model.padding.bottom;
+            border_padding_top = model.border.top + model.padding.top;
+            top_offset = clearance + model.margin.top + border_padding_top;
             left_offset = model.offset();
         }
 
@@ -150,72 +170,146 @@ impl BlockFlow {
             }
         }
 
-        let mut collapsible = Au::new(0);
-        let mut collapsing = Au::new(0);
+        // Collapse the margins of the children with each other and with those of this flow, and
+        // place the children. (CSS 2.1 § 8.3.1) The margins of this flow do not collapse with
+        // those of its children if it establishes a block formatting context, as the root, table
+        // cells and boxes that clip their overflow do, or if borders or padding separate them.
         let mut margin_top = Au::new(0);
         let mut margin_bottom = Au::new(0);
-        let mut top_margin_collapsible = false;
-        let mut bottom_margin_collapsible = false;
-        let mut first_in_flow = true;
+        let mut top_margin_collapsible = !self.establishes_bfc;
+        let mut bottom_margin_collapsible = !self.establishes_bfc;
         for &box in self.box.iter() {
-            let base = box.mut_base();
-            let model = &mut base.model;
-            if !self.is_root && model.border.top == Au::new(0) && model.padding.top == Au::new(0) {
-                collapsible = model.margin.top;
-                top_margin_collapsible = true;
-            }
-            if !self.is_root && model.border.bottom == Au::new(0) && model.padding.bottom == Au::new(0) {
-                bottom_margin_collapsible = true;
-            }
+            let base = box.base();
+            let model = &base.model;
+            let style = base.style();
+            let establishes_bfc = self.establishes_bfc ||
+                style.Box.overflow != overflow::visible;
+            let height_is_auto = match MaybeAuto::from_style(style.Box.height, Au::new(0)) {
+                Auto => true,
+                Specified(_) => false,
+            };
+            let min_height_is_zero = SizeConstraint::heights(style, Au::new(0)).min ==
+                Au::new(0);
+            top_margin_collapsible = !establishes_bfc && model.border.top == Au::new(0) &&
+                model.padding.top == Au::new(0);
+            bottom_margin_collapsible = !establishes_bfc && height_is_auto &&
+                min_height_is_zero && model.border.bottom == Au::new(0) &&
+                model.padding.bottom == Au::new(0);
             margin_top = model.margin.top;
             margin_bottom = model.margin.bottom;
         }
 
+        // The margins that adjoin at the top of the next in-flow child, which are measured from
+        // `content_end`: the top of the content of this flow, or the bottom border edge of the
+        // previous in-flow child. Until a child separates them, they collapse with the top margin
+        // of this flow if they can.
+        let mut top_margins = AdjoiningMargins::from_margin(margin_top);
+        let mut collapsing_with_top = top_margin_collapsible;
+        let (mut margins, mut content_end) = if top_margin_collapsible {
+            (top_margins, clearance)
+        } else {
+            (AdjoiningMargins::new(), top_offset)
+        };
+
+        // The float context of each child is relative to the bottom of the previous child.
+        //
+        // TODO: The float contexts should account for collapsed margins too. See #725
+        let mut floats_y = top_offset;
+
         for kid in self.base.child_iter() {
-            kid.collapse_margins(top_margin_collapsible,
-                                 &mut first_in_flow,
-                                 &mut margin_top,
-                                 &mut top_offset,
-                                 &mut collapsing,
-                                 &mut collapsible);
-
-            let child_node = flow::mut_base(*kid);
-            cur_y = cur_y - collapsing;
-            child_node.position.origin.y = cur_y;
-            cur_y = cur_y + child_node.position.size.height;
-        }
+            let collapsible = kid.collapsible_margins();
+            let is_float = kid.class() == FloatFlowClass;
+            let child_base = flow::mut_base(*kid);
 
-        // The bottom margin collapses with its last in-flow block-level child's bottom margin
-        // if the parent has no bottom boder, no bottom padding.
-        collapsing = if bottom_margin_collapsible {
-            if margin_bottom < collapsible {
-                margin_bottom = collapsible;
+            if !collapsible.in_flow {
+                // Out-of-flow children are placed where the next in-flow child would be, except
+                // for floats, which are placed by their float context.
+                child_base.position.origin.y = if is_float {
+                    floats_y
+                } else {
+                    content_end + margins.collapse()
+                };
+                loop
             }
-            collapsible
+
+            let child_border_height = child_base.position.size.height - collapsible.top_inset -
+                collapsible.bottom_inset;
+            if collapsible.has_clearance {
+                // Clearance separates the margins above the child from its own. It is measured
+                // from the bottom of the previous child.
+                if collapsing_with_top {
+                    top_margins = margins;
+                    collapsing_with_top = false;
+                }
+                child_base.position.origin.y = floats_y;
+                content_end = floats_y + collapsible.top_inset + child_border_height;
+                margins = collapsible.bottom;
+            } else if collapsible.collapse_through {
+                // The margins above an empty child collapse with those below it.
+                child_base.position.origin.y = content_end + margins.collapse() -
+                    collapsible.top_inset;
+                margins = margins.union(&collapsible.top);
+            } else {
+                let adjoining = margins.union(&collapsible.top);
+                if collapsing_with_top {
+                    top_margins = adjoining;
+                    collapsing_with_top = false;
+                }
+                let border_top = content_end + adjoining.collapse();
+                child_base.position.origin.y = border_top - collapsible.top_inset;
+                content_end = border_top + child_border_height;
+                margins = collapsible.bottom;
+            }
+            floats_y = child_base.position.origin.y + child_base.position.size.height;
+        }
+
+        // The bottom margin collapses with the margins below the last in-flow child if nothing
+        // separates them. If no in-flow child separates the top margin from the bottom margin
+        // either, the margins collapse through this flow.
+        let mut collapse_through = false;
+        let bottom_margins;
+        if collapsing_with_top && bottom_margin_collapsible {
+            collapse_through = true;
+            top_margins = margins.union_margin(margin_bottom);
+            bottom_margins = AdjoiningMargins::new();
+        } else if collapsing_with_top {
+            top_margins = margins;
+            content_end = clearance + top_margins.collapse();
+            bottom_margins = AdjoiningMargins::from_margin(margin_bottom);
+        } else if bottom_margin_collapsible {
+            bottom_margins = margins.union_margin(margin_bottom);
         } else {
-            Au::new(0)
-        };
-        
-        // TODO: A box's own margins collapse if the 'min-height' property is zero, and it has neither
-        // top or bottom borders nor top or bottom padding, and it has a 'height' of either 0 or 'auto',
-        // and it does not contain a line box, and all of its in-flow children's margins (if any) collapse.
+            content_end = content_end + margins.collapse();
+            bottom_margins = AdjoiningMargins::from_margin(margin_bottom);
+        }
 
+        margin_top = top_margins.collapse();
+        margin_bottom = bottom_margins.collapse();
+        top_offset = clearance + margin_top + border_padding_top;
 
-        let mut height = if self.is_root {
-            Au::max(ctx.screen_size.size.height, cur_y)
+        let mut height = if collapse_through {
+            Au::new(0)
+        } else if self.is_root {
+            Au::max(ctx.screen_size.size.height, content_end - top_offset)
         } else {
-            cur_y - top_offset - collapsing
+            content_end - top_offset
         };
 
+        // A specified 'height' replaces the height of the contents, and 'min-height' and
+        // 'max-height' constrain either. (CSS 2.1 § 10.5 and § 10.7)
         for &box in self.box.iter() {
-            let base = box.mut_base();
+            let base = box.base();
             let style = base.style();
-            let maybe_height = MaybeAuto::from_height(style.height(), Au::new(0), style.font_size());
-            let maybe_height = maybe_height.specified_or_zero();
-            height = geometry::max(height, maybe_height);
+            let model = &base.model;
+            let offset = box_sizing_offset(style,
+                                           model.border.top + model.border.bottom +
+                                           model.padding.top + model.padding.bottom);
+            let maybe_height = MaybeAuto::from_style(style.Box.height, Au::new(0)).minus(offset);
+            height = maybe_height.specified_or_default(height);
+            height = SizeConstraint::heights(style, offset).clamp(height);
         }
 
-        let mut noncontent_height = Au::new(0);
+        let mut noncontent_height = clearance + margin_top + margin_bottom;
         self.box.map(|&box| {
             let base = box.mut_base();
 
@@ -225,31 +319,63 @@ impl BlockFlow {
 
             base.position.origin.y = clearance + base.model.margin.top;
 
-            noncontent_height = base.model.padding.top + base.model.padding.bottom +
+            let border_padding_height = base.model.padding.top + base.model.padding.bottom +
                 base.model.border.top + base.model.border.bottom;
-            base.position.size.height = height + noncontent_height;
+            base.position.size.height = height + border_padding_height;
 
-            noncontent_height = noncontent_height + clearance +
-                base.model.margin.top + base.model.margin.bottom;
+            noncontent_height = noncontent_height + border_padding_height;
         });
 
-        //TODO(eatkinson): compute heights using the 'height' property.
         self.base.position.size.height = height + noncontent_height;
 
+        self.collapsible_margins = CollapsibleMargins {
+            in_flow: true,
+            top: top_margins,
+            bottom: bottom_margins,
+            collapse_through: collapse_through,
+            has_clearance: clearance > Au::new(0),
+            top_inset: clearance + margin_top,
+            bottom_inset: margin_bottom,
+        };
+
         if inorder {
-            let extra_height = height - (cur_y - top_offset) + bottom_offset; 
+            let extra_height = self.base.position.size.height - floats_y;
             self.base.floats_out = float_ctx.translate(Point2D(left_offset, -extra_height));
         } else {
             self.base.floats_out = self.base.floats_in.clone();
         }
     }
 
+    /// Gives the child flows the content box of this flow, whose left edge is `x_offset` and whose
+    /// width is `width`, as their containing block.
+    pub fn propagate_assigned_width_to_children(&mut self, x_offset: Au, width: Au) {
+        let has_inorder_children = self.base.is_inorder || self.base.num_floats > 0;
+        for kid in self.base.child_iter() {
+            assert!(kid.starts_block_flow() || kid.starts_inline_flow());
+
+            let child_base = flow::mut_base(*kid);
+            child_base.position.origin.x = x_offset;
+            child_base.position.size.width = width;
+            child_base.is_inorder = has_inorder_children;
+
+            if !child_base.is_inorder {
+                child_base.floats_in = FloatContext::new(0);
+            }
+        }
+    }
+
     pub fn build_display_list_block<E:ExtraDisplayListData>(
                                     &mut self,
                                     builder: &DisplayListBuilder,
                                     dirty: &Rect<Au>, 
                                     list: &Cell<DisplayList<E>>) 
                                     -> bool {
+        // Relatively positioned boxes move their children with them.
+        for &box in self.box.iter() {
+            let relative_offset = box.base().relative_position(self.base.containing_block_size);
+            self.base.abs_position = self.base.abs_position + relative_offset;
+        }
+
         if self.base.node.is_iframe_element() {
             let x = self.base.abs_position.x + do self.box.map_default(Au::new(0)) |box| {
                 let base = box.base();
@@ -275,19 +401,22 @@ impl BlockFlow {
             }
         }
 
+        // Absolutely positioned descendants may be drawn outside of this flow, so its children are
+        // visited even if it is not drawn itself.
         let abs_rect = Rect(self.base.abs_position, self.base.position.size);
-        if !abs_rect.intersects(dirty) {
-            return true;
+        if abs_rect.intersects(dirty) {
+            debug!("build_display_list_block: adding display element");
+
+            // add box that starts block context
+            self.box.map(|&box| {
+                box.build_display_list(builder,
+                                       dirty,
+                                       &self.base.abs_position,
+                                       &self.base.clip_rect,
+                                       list)
+            });
         }
 
-        debug!("build_display_list_block: adding display element");
-
-        // add box that starts block context
-        self.box.map(|&box| {
-            box.build_display_list(builder, dirty, &self.base.abs_position, list)
-        });
-
-        // TODO: handle any out-of-flow elements
         let this_position = self.base.abs_position;
         for child in self.base.child_iter() {
             let child_base = flow::mut_base(*child);
@@ -314,7 +443,6 @@ impl FlowContext for BlockFlow {
     any boxes it is responsible for flowing.  */
 
     /* TODO: floats */
-    /* TODO: absolute contexts */
     /* TODO: inline-blocks */
     fn bubble_widths(&mut self, ctx: &mut LayoutContext) {
         let mut min_width = Au::new(0);
@@ -325,6 +453,11 @@ impl FlowContext for BlockFlow {
         for child_ctx in self.base.child_iter() {
             assert!(child_ctx.starts_block_flow() || child_ctx.starts_inline_flow());
 
+            // Absolutely positioned children take no space in this flow.
+            if child_ctx.class() == AbsoluteFlowClass {
+                loop
+            }
+
             let child_base = flow::mut_base(*child_ctx);
             min_width = geometry::max(min_width, child_base.min_width);
             pref_width = geometry::max(pref_width, child_base.pref_width);
@@ -338,8 +471,8 @@ impl FlowContext for BlockFlow {
             {
                 // Can compute border width here since it doesn't depend on anything.
                 let base = box.mut_base();
-                let style = base.style();
-                base.model.compute_borders(style)
+                let node = base.node;
+                base.model.compute_borders(node.style())
             }
 
             let (this_minimum_width, this_preferred_width) = box.minimum_and_preferred_widths();
@@ -365,6 +498,10 @@ impl FlowContext for BlockFlow {
             self.base.position.size.width = ctx.screen_size.size.width;
             self.base.floats_in = FloatContext::new(self.base.num_floats);
             self.base.is_inorder = false;
+
+            // The initial containing block has the size of the viewport. (CSS 2.1 § 10.1)
+            self.base.absolute_cb = Rect(Au::zero_point(), ctx.screen_size.size);
+            self.base.containing_block_size = ctx.screen_size.size;
         }
 
         //position was set to the containing block by the flow's parent
@@ -374,7 +511,9 @@ impl FlowContext for BlockFlow {
         for &box in self.box.iter() {
             let base = box.mut_base();
             let base = &mut *base;
-            let style = base.style();
+            let node = base.node;
+            let style = node.style();
+            let direction = base.containing_block_direction();
             let model = &mut base.model;
 
             // Can compute padding here since we know containing block width.
@@ -384,23 +523,37 @@ impl FlowContext for BlockFlow {
             let available_width = remaining_width - model.noncontent_width();
 
             // Top and bottom margins for blocks are 0 if auto.
-            let font_size = style.font_size();
-            let margin_top = MaybeAuto::from_margin(style.margin_top(),
-                                                    remaining_width,
-                                                    font_size).specified_or_zero();
-            let margin_bottom = MaybeAuto::from_margin(style.margin_bottom(),
-                                                       remaining_width,
-                                                       font_size).specified_or_zero();
+            let margin_top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                   remaining_width).specified_or_zero();
+            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                      remaining_width).specified_or_zero();
 
+            let offset = box_sizing_offset(style, model.noncontent_width());
             let (width, margin_left, margin_right) =
-                (MaybeAuto::from_width(style.width(), remaining_width, font_size),
-                 MaybeAuto::from_margin(style.margin_left(), remaining_width, font_size),
-                 MaybeAuto::from_margin(style.margin_right(), remaining_width, font_size));
-
-            let (width, margin_left, margin_right) = self.compute_horiz(width,
-                                                                        margin_left,
-                                                                        margin_right,
-                                                                        available_width);
+                (MaybeAuto::from_style(style.Box.width, remaining_width).minus(offset),
+                 MaybeAuto::from_style(style.Margin.margin_left, remaining_width),
+                 MaybeAuto::from_style(style.Margin.margin_right, remaining_width));
+
+            let (mut width, mut margin_left, mut margin_right) =
+                self.compute_horiz(width, margin_left, margin_right, available_width, direction);
+
+            // If the width violates 'min-width' or 'max-width', the margins are computed again
+            // with the constrained width. (CSS 2.1 § 10.4)
+            let constrained_width = SizeConstraint::widths(style, remaining_width, offset)
+                .clamp(width);
+            if constrained_width != width {
+                let (new_width, new_margin_left, new_margin_right) =
+                    self.compute_horiz(Specified(constrained_width),
+                                       MaybeAuto::from_style(style.Margin.margin_left,
+                                                             remaining_width),
+                                       MaybeAuto::from_style(style.Margin.margin_right,
+                                                             remaining_width),
+                                       available_width,
+                                       direction);
+                width = new_width;
+                margin_left = new_margin_left;
+                margin_right = new_margin_right;
+            }
 
             model.margin.top = margin_top;
             model.margin.right = margin_right;
@@ -418,19 +571,7 @@ impl FlowContext for BlockFlow {
             base.position.size.width = remaining_width + pb;
         }
 
-        let has_inorder_children = self.base.is_inorder || self.base.num_floats > 0;
-        for kid in self.base.child_iter() {
-            assert!(kid.starts_block_flow() || kid.starts_inline_flow());
-
-            let child_base = flow::mut_base(*kid);
-            child_base.position.origin.x = x_offset;
-            child_base.position.size.width = remaining_width;
-            child_base.is_inorder = has_inorder_children;
-
-            if !child_base.is_inorder {
-                child_base.floats_in = FloatContext::new(0);
-            }
-        }
+        self.propagate_assigned_width_to_children(x_offset, remaining_width);
     }
 
     fn assign_height_inorder(&mut self, ctx: &mut LayoutContext) {
@@ -449,37 +590,8 @@ impl FlowContext for BlockFlow {
         self.assign_height_block_base(ctx, false);
     }
 
-    fn collapse_margins(&mut self,
-                        top_margin_collapsible: bool,
-                        first_in_flow: &mut bool,
-                        margin_top: &mut Au,
-                        top_offset: &mut Au,
-                        collapsing: &mut Au,
-                        collapsible: &mut Au) {
-        for &box in self.box.iter() {
-            let base = box.mut_base();
-            let model = &mut base.model;
-
-            // The top margin collapses with its first in-flow block-level child's
-            // top margin if the parent has no top border, no top padding.
-            if *first_in_flow && top_margin_collapsible {
-                // If top-margin of parent is less than top-margin of its first child, 
-                // the parent box goes down until its top is aligned with the child.
-                if *margin_top < model.margin.top {
-                    // TODO: The position of child floats should be updated and this
-                    // would influence clearance as well. See #725
-                    let extra_margin = model.margin.top - *margin_top;
-                    *top_offset = *top_offset + extra_margin;
-                    *margin_top = model.margin.top;
-                }
-            }
-            // The bottom margin of an in-flow block-level element collapses 
-            // with the top margin of its next in-flow block-level sibling.
-            *collapsing = geometry::min(model.margin.top, *collapsible);
-            *collapsible = model.margin.bottom;
-        }
-
-        *first_in_flow = false;
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
+        self.collapsible_margins
     }
 }
 
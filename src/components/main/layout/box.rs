@@ -8,7 +8,8 @@ use css::node_style::StyledNode;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData, ToGfxColor};
 use layout::float_context::{ClearType, ClearLeft, ClearRight, ClearBoth};
-use layout::model::{BoxModel, MaybeAuto};
+use layout::model::{Auto, BoxModel, MaybeAuto, SizeConstraint, Specified};
+use layout::model;
 use layout::text;
 
 use std::cast;
@@ -17,26 +18,27 @@ use std::cmp::ApproxEq;
 use std::managed;
 use std::num::Zero;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
-use gfx::display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass};
-use gfx::display_list::{DisplayList, ImageDisplayItem, ImageDisplayItemClass};
-use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, TextDisplayItem};
-use gfx::display_list::{TextDisplayItemClass};
-use gfx::font::{FontStyle, FontWeight300};
-use gfx::geometry::Au;
+use gfx::display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass, BorderRadii};
+use gfx::display_list::{BoxShadowDisplayItem, BoxShadowDisplayItemClass, DisplayList};
+use gfx::display_list::{ImageDisplayItem, ImageDisplayItemClass, RoundedBorderDisplayItem};
+use gfx::display_list::{RoundedBorderDisplayItemClass, RoundedSolidColorDisplayItem};
+use gfx::display_list::{RoundedSolidColorDisplayItemClass, SolidColorDisplayItem};
+use gfx::display_list::{SolidColorDisplayItemClass, TextDisplayItem, TextDisplayItemClass};
+use gfx::display_list::{TiledImageDisplayItem, TiledImageDisplayItemClass};
+use gfx::font::{CSSFontWeight, FontStyle, FontWeight100, FontWeight200, FontWeight300};
+use gfx::font::{FontWeight400, FontWeight500, FontWeight600, FontWeight700, FontWeight800};
+use gfx::font::{FontWeight900};
+use gfx::geometry::{Au, to_frac_px};
 use gfx::text::text_run::TextRun;
-use newcss::color::rgb;
-use newcss::complete::CompleteStyle;
-use newcss::units::{Em, Px};
-use newcss::units::{Cursive, Fantasy, Monospace, SansSerif, Serif};
-use newcss::values::{CSSBorderStyleDashed, CSSBorderStyleSolid};
-use newcss::values::{CSSClearNone, CSSClearLeft, CSSClearRight, CSSClearBoth};
-use newcss::values::{CSSFontFamilyFamilyName, CSSFontFamilyGenericFamily};
-use newcss::values::{CSSFontSizeLength, CSSFontStyleItalic, CSSFontStyleNormal};
-use newcss::values::{CSSFontStyleOblique, CSSTextAlign, CSSTextDecoration, CSSLineHeight, CSSVerticalAlign};
-use newcss::values::{CSSTextDecorationNone, CSSFloatNone, CSSPositionStatic};
-use newcss::values::{CSSDisplayInlineBlock, CSSDisplayInlineTable};
-use newcss::values::{CSSLineHeightNormal, CSSLineHeightNumber, CSSLineHeightLength};
-use newcss::values::{CSSLineHeightPercentage};
+use gfx::color::rgb;
+use style::{ComputedValues, PseudoElement, Marker};
+use style::computed_values::{background_repeat, border_style};
+use style::computed_values::{border_top_left_radius, clear};
+use style::computed_values::{direction, display, float, font_family, font_style};
+use style::computed_values::{font_weight, line_height, list_style_position, position, text_align};
+use style::computed_values::{text_decoration, vertical_align, white_space, LPA_Auto};
+use style::computed_values::{LengthOrPercentage, LP_Length, LP_Percentage, LPA_Length};
+use style::computed_values::{box_sizing, LPA_Percentage};
 use script::dom::node::{AbstractNode, LayoutView};
 use servo_net::image::holder::ImageHolder;
 use servo_net::local_image_cache::LocalImageCache;
@@ -72,6 +74,13 @@ pub trait RenderBox {
         fail!("as_text_render_box() called on a non-text-render-box")
     }
 
+    /// If this is an inline-block render box, returns the underlying object. Fails otherwise.
+    ///
+    /// FIXME(pcwalton): Ugly. Replace with a real downcast operation.
+    fn as_inline_block_render_box(@mut self) -> @mut InlineBlockRenderBox {
+        fail!("as_inline_block_render_box() called on a non-inline-block-render-box")
+    }
+
     /// If this is a text render box, returns the underlying object. Fails otherwise.
     ///
     /// FIXME(pcwalton): Ugly. Replace with a real downcast operation.
@@ -108,8 +117,8 @@ pub trait RenderBox {
 
     fn box_height(&mut self) -> Au;
 
-    /// Assigns the appropriate width.
-    fn assign_width(&mut self);
+    /// Assigns the appropriate width, given the width of the containing block.
+    fn assign_width(&mut self, containing_block_width: Au);
 
     fn debug_str(&self) -> ~str {
         ~"???"
@@ -166,15 +175,37 @@ pub trait RenderBoxUtils {
     /// list if necessary.
     fn paint_background_if_applicable<E:ExtraDisplayListData>(
                                       self,
+                                      builder: &DisplayListBuilder,
                                       list: &Cell<DisplayList<E>>,
-                                      absolute_bounds: &Rect<Au>);
+                                      absolute_bounds: &Rect<Au>,
+                                      clip_rect: &Option<Rect<Au>>);
+
+    /// Adds the display items necessary to paint the background image of this render box to the
+    /// display list if necessary.
+    fn paint_background_image_if_applicable<E:ExtraDisplayListData>(
+                                            self,
+                                            builder: &DisplayListBuilder,
+                                            list: &Cell<DisplayList<E>>,
+                                            absolute_bounds: &Rect<Au>,
+                                            clip_rect: &Option<Rect<Au>>);
+
+    /// Adds the display items necessary to paint the outer shadows of this render box, or its
+    /// inset shadows if `inset` is true, to the display list if necessary.
+    fn paint_box_shadows_if_applicable<E:ExtraDisplayListData>(
+                                       self,
+                                       list: &Cell<DisplayList<E>>,
+                                       absolute_bounds: &Rect<Au>,
+                                       radii: &BorderRadii<Au>,
+                                       clip_rect: &Option<Rect<Au>>,
+                                       inset: bool);
 
     /// Adds the display items necessary to paint the borders of this render box to a display list
     /// if necessary.
     fn paint_borders_if_applicable<E:ExtraDisplayListData>(
                                    self,
                                    list: &Cell<DisplayList<E>>,
-                                   abs_bounds: &Rect<Au>);
+                                   abs_bounds: &Rect<Au>,
+                                   clip_rect: &Option<Rect<Au>>);
 
     /// Adds the display items for this render box to the given display list.
     ///
@@ -183,18 +214,16 @@ pub trait RenderBoxUtils {
     /// * `dirty`: The dirty rectangle in the coordinate system of the owning flow.
     /// * `origin`: The total offset from the display list root flow to the owning flow of this
     ///   box.
-    /// * `list`: The display list to which items should be appended.
-    ///
-    /// TODO: To implement stacking contexts correctly, we need to create a set of display lists,
-    /// one per layer of the stacking context (CSS 2.1 § 9.9.1). Each box is passed the list set
-    /// representing the box's stacking context. When asked to construct its constituent display
-    /// items, each box puts its display items into the correct stack layer according to CSS 2.1
-    /// Appendix E. Finally, the builder flattens the list.
+    /// * `clip_rect`: The rectangle to which the display items of the owning flow are clipped, if
+    ///   any.
+    /// * `list`: The display list to which items should be appended. This is the layer of the
+    ///   stacking context of the box in which the owning flow is painted (CSS 2.1 Appendix E).
     fn build_display_list<E:ExtraDisplayListData>(
                           self,
                           _: &DisplayListBuilder,
                           dirty: &Rect<Au>,
                           offset: &Point2D<Au>,
+                          clip_rect: &Option<Rect<Au>>,
                           list: &Cell<DisplayList<E>>);
 }
 
@@ -229,7 +258,7 @@ impl RenderBox for GenericRenderBox {
         Au::new(0)
     }
 
-    fn assign_width(&mut self) {
+    fn assign_width(&mut self, _: Au) {
         // FIXME(pcwalton): This seems clownshoes; can we remove?
         self.base.position.size.width = Au::from_px(45)
     }
@@ -239,65 +268,142 @@ impl RenderBox for GenericRenderBox {
 pub struct ImageRenderBox {
     base: RenderBoxBase,
     image: ImageHolder,
+    /// The width of the containing block, against which a percentage 'width' is resolved. It is
+    /// `None` until widths are assigned, and a percentage acts as 'auto' until then.
+    containing_block_width: Option<Au>,
 }
 
 impl ImageRenderBox {
     pub fn new(base: RenderBoxBase, image_url: Url, local_image_cache: @mut LocalImageCache)
                -> ImageRenderBox {
-        assert!(base.node.is_image_element());
+        // Besides `img` elements, list markers show images.
+        assert!(base.node.is_image_element() || base.pseudo_element.is_some());
 
         ImageRenderBox {
             base: base,
             image: ImageHolder::new(image_url, local_image_cache),
+            containing_block_width: None,
         }
     }
 
-    // Calculate the width of an image, accounting for the width attribute
-    // TODO: This could probably go somewhere else
-    pub fn image_width(&mut self) -> Au {
-        let attr_width: Option<int> = do self.base.node.with_imm_element |elt| {
-            match elt.get_attr("width") {
-                Some(width) => {
-                    FromStr::from_str(width)
+    /// Returns the length in pixels given by the named attribute of the image element, if any.
+    fn attribute_length(&self, name: &str) -> Option<Au> {
+        if self.base.pseudo_element.is_some() {
+            return None
+        }
+        let attr_length: Option<int> = do self.base.node.with_imm_element |elt| {
+            match elt.get_attr(name) {
+                Some(length) => {
+                    FromStr::from_str(length)
                 }
                 None => {
                     None
                 }
             }
         };
+        attr_length.map_move(Au::from_px)
+    }
 
-        // TODO: Consult margins and borders?
-        let px_width = if attr_width.is_some() {
-            attr_width.unwrap()
-        } else {
-            self.image.get_size().unwrap_or(Size2D(0, 0)).width
+    /// Returns the height of the content box of the block that contains the image, if its 'height'
+    /// is a length rather than depending on its contents. (CSS 2.1 § 10.5)
+    ///
+    /// TODO: The height of a containing block with 'box-sizing: border-box' is treated as unknown.
+    fn containing_block_height(&self) -> Option<Au> {
+        // Generated content is a child of the element that generates it.
+        let mut ancestor = match self.base.pseudo_element {
+            Some(_) => Some(self.base.node),
+            None => self.base.node.parent_node(),
         };
-
-        Au::from_px(px_width)
+        loop {
+            match ancestor {
+                Some(node) if node.is_element() => {
+                    let style = node.style();
+                    if style.Box.display == display::inline {
+                        ancestor = node.parent_node();
+                        loop
+                    }
+                    return match (style.Box.height, style.Box.box_sizing) {
+                        (LPA_Length(length), box_sizing::content_box) => {
+                            Some(model::from_length(length))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => return None,
+            }
+        }
     }
 
-    // Calculate the height of an image, accounting for the height attribute
-    // TODO: This could probably go somewhere else
-    pub fn image_height(&mut self) -> Au {
-        let attr_height: Option<int> = do self.base.node.with_imm_element |elt| {
-            match elt.get_attr("height") {
-                Some(height) => {
-                    FromStr::from_str(height)
-                }
-                None => {
-                    None
-                }
+    /// Returns the used size of the content box of the image. This is given by 'width' and
+    /// 'height', or else by the 'width' and 'height' attributes, or else by the intrinsic size of
+    /// the image. If only one dimension is given, the other keeps the aspect ratio of the image.
+    /// The result is constrained by 'min-width', 'max-width', 'min-height' and 'max-height'.
+    /// (CSS 2.1 § 10.3.2, § 10.4, § 10.6.2 and § 10.7)
+    pub fn used_size(&mut self) -> Size2D<Au> {
+        let intrinsic_size = self.image.get_size().unwrap_or(Size2D(0, 0));
+        let intrinsic_size = Size2D(Au::from_px(intrinsic_size.width),
+                                    Au::from_px(intrinsic_size.height));
+
+        let style = self.base.style();
+        let border = self.base.model.border;
+        let padding = self.base.model.padding;
+        let width_offset = model::box_sizing_offset(style,
+                                                    border.left + border.right + padding.left +
+                                                    padding.right);
+        let height_offset = model::box_sizing_offset(style,
+                                                     border.top + border.bottom + padding.top +
+                                                     padding.bottom);
+
+        // Percentages of a containing block whose size is not known act as 'auto'.
+        let width = match (style.Box.width, self.containing_block_width) {
+            (LPA_Length(length), _) => Some(Au::max(model::from_length(length) - width_offset,
+                                                    Au(0))),
+            (LPA_Percentage(percent), Some(containing_width)) => {
+                Some(Au::max(containing_width.scale_by(percent) - width_offset, Au(0)))
             }
+            (LPA_Percentage(_), None) | (LPA_Auto, _) => self.attribute_length("width"),
         };
-
-        // TODO: Consult margins and borders?
-        let px_height = if attr_height.is_some() {
-            attr_height.unwrap()
-        } else {
-            self.image.get_size().unwrap_or(Size2D(0, 0)).height
+        let height = match (style.Box.height, self.containing_block_height()) {
+            (LPA_Length(length), _) => Some(Au::max(model::from_length(length) - height_offset,
+                                                    Au(0))),
+            (LPA_Percentage(percent), Some(containing_height)) => {
+                Some(Au::max(containing_height.scale_by(percent) - height_offset, Au(0)))
+            }
+            (LPA_Percentage(_), None) | (LPA_Auto, _) => self.attribute_length("height"),
         };
 
-        Au::from_px(px_height)
+        let widths = SizeConstraint::new(style.Box.min_width, style.Box.max_width,
+                                         self.containing_block_width, width_offset);
+        let heights = SizeConstraint::heights(style, height_offset);
+
+        match (width, height) {
+            (Some(width), Some(height)) => Size2D(widths.clamp(width), heights.clamp(height)),
+            (Some(width), None) => {
+                let width = widths.clamp(width);
+                let height = scale_preserving_ratio(intrinsic_size.height,
+                                                    intrinsic_size.width,
+                                                    width);
+                Size2D(width, heights.clamp(height))
+            }
+            (None, Some(height)) => {
+                let height = heights.clamp(height);
+                let width = scale_preserving_ratio(intrinsic_size.width,
+                                                   intrinsic_size.height,
+                                                   height);
+                Size2D(widths.clamp(width), height)
+            }
+            (None, None) => constrain_preserving_ratio(intrinsic_size, &widths, &heights),
+        }
+    }
+
+    /// Returns the used width of the content box of the image.
+    pub fn image_width(&mut self) -> Au {
+        self.used_size().width
+    }
+
+    /// Returns the used height of the content box of the image.
+    pub fn image_height(&mut self) -> Au {
+        self.used_size().height
     }
 
     /// If this is an image render box, returns the underlying object. Fails otherwise.
@@ -318,20 +424,23 @@ impl RenderBox for ImageRenderBox {
     }
 
     fn minimum_and_preferred_widths(&mut self) -> (Au, Au) {
-        let guessed_width = self.base.guess_width();
+        // The guessed width includes any specified 'width', which the used width of the image
+        // replaces.
+        let specified_width = MaybeAuto::from_style(self.base.style().Box.width, Au(0));
+        let guessed_width = self.base.guess_width() - specified_width.specified_or_zero();
         let image_width = self.image_width();
         (guessed_width + image_width, guessed_width + image_width)
     }
 
     fn box_height(&mut self) -> Au {
-        let size = self.image.get_size();
-        let height = Au::from_px(size.unwrap_or(Size2D(0, 0)).height);
+        let height = self.image_height();
         self.base.position.size.height = height;
         debug!("box_height: found image height: %?", height);
         height
     }
 
-    fn assign_width(&mut self) {
+    fn assign_width(&mut self, containing_block_width: Au) {
+        self.containing_block_width = Some(containing_block_width);
         let width = self.image_width();
         self.base.position.size.width = width;
     }
@@ -344,6 +453,126 @@ impl RenderBox for ImageRenderBox {
     }
 }
 
+/// Scales `length` by `new_size / old_size`, to keep an aspect ratio when the other dimension
+/// changes from `old_size` to `new_size`.
+fn scale_preserving_ratio(length: Au, old_size: Au, new_size: Au) -> Au {
+    if old_size == Au(0) {
+        length
+    } else {
+        length.scale_by((*new_size as f64) / (*old_size as f64))
+    }
+}
+
+/// Constrains the intrinsic size of a replaced element whose 'width' and 'height' are both
+/// 'auto', keeping its aspect ratio where the constraints allow. (CSS 2.1 § 10.4, the table of
+/// constraint violations)
+fn constrain_preserving_ratio(size: Size2D<Au>, widths: &SizeConstraint, heights: &SizeConstraint)
+                              -> Size2D<Au> {
+    let (width, height) = (size.width, size.height);
+    if width == Au(0) || height == Au(0) {
+        return Size2D(widths.clamp(width), heights.clamp(height))
+    }
+
+    // A maximum smaller than the minimum is raised to the minimum.
+    let (min_width, min_height) = (widths.min, heights.min);
+    let max_width = widths.max.map_move(|max| Au::max(max, min_width));
+    let max_height = heights.max.map_move(|max| Au::max(max, min_height));
+    let too_wide = max_width.map_default(false, |&max| width > max);
+    let too_tall = max_height.map_default(false, |&max| height > max);
+    let too_narrow = width < min_width;
+    let too_short = height < min_height;
+    let max_width = max_width.unwrap_or(width);
+    let max_height = max_height.unwrap_or(height);
+
+    // The factor by which a constraint scales a dimension.
+    fn ratio(new_size: Au, old_size: Au) -> f64 {
+        (*new_size as f64) / (*old_size as f64)
+    }
+
+    let (width, height) = if too_wide && too_tall {
+        if ratio(max_width, width) <= ratio(max_height, height) {
+            (max_width, Au::max(min_height, scale_preserving_ratio(height, width, max_width)))
+        } else {
+            (Au::max(min_width, scale_preserving_ratio(width, height, max_height)), max_height)
+        }
+    } else if too_narrow && too_short {
+        if ratio(min_width, width) <= ratio(min_height, height) {
+            (Au::min(max_width, scale_preserving_ratio(width, height, min_height)), min_height)
+        } else {
+            (min_width, Au::min(max_height, scale_preserving_ratio(height, width, min_width)))
+        }
+    } else if too_narrow && too_tall {
+        (min_width, max_height)
+    } else if too_wide && too_short {
+        (max_width, min_height)
+    } else if too_wide {
+        (max_width, Au::max(scale_preserving_ratio(height, width, max_width), min_height))
+    } else if too_narrow {
+        (min_width, Au::min(scale_preserving_ratio(height, width, min_width), max_height))
+    } else if too_tall {
+        (Au::max(scale_preserving_ratio(width, height, max_height), min_width), max_height)
+    } else if too_short {
+        (Au::min(scale_preserving_ratio(width, height, min_height), max_width), min_height)
+    } else {
+        (width, height)
+    };
+    Size2D(width, height)
+}
+
+/// A box that stands for an 'inline-block' element in the line boxes of its inline flow. The
+/// element itself is laid out by an `InlineBlockFlow`, which sets the size of this box to its
+/// margin box, and is placed where line breaking puts this box. This box draws nothing.
+pub struct InlineBlockRenderBox {
+    base: RenderBoxBase,
+
+    /// The minimum width of the margin box of the element.
+    min_width: Au,
+
+    /// The preferred width of the margin box of the element.
+    pref_width: Au,
+
+    /// The distance from the top of the margin box of the element to its baseline.
+    baseline: Au,
+}
+
+impl InlineBlockRenderBox {
+    pub fn new(base: RenderBoxBase) -> InlineBlockRenderBox {
+        InlineBlockRenderBox {
+            base: base,
+            min_width: Au::new(0),
+            pref_width: Au::new(0),
+            baseline: Au::new(0),
+        }
+    }
+}
+
+impl RenderBox for InlineBlockRenderBox {
+    fn class(&self) -> RenderBoxClass {
+        InlineBlockRenderBoxClass
+    }
+
+    fn as_inline_block_render_box(@mut self) -> @mut InlineBlockRenderBox {
+        self
+    }
+
+    /// An inline-block is atomic: it is never broken across lines.
+    fn split_to_width(@mut self, _: Au, _: bool) -> SplitBoxResult {
+        CannotSplit(self as @mut RenderBox)
+    }
+
+    fn minimum_and_preferred_widths(&mut self) -> (Au, Au) {
+        (self.min_width, self.pref_width)
+    }
+
+    fn box_height(&mut self) -> Au {
+        self.base.position.size.height
+    }
+
+    /// The `InlineBlockFlow` assigns the width.
+    fn assign_width(&mut self, _: Au) {
+    }
+}
+
 /// A box representing a single run of text with a distinct style. A `TextRenderBox` may be split
 /// into two or more boxes across line breaks. Several `TextBox`es may correspond to a
 /// single DOM text node. Split text boxes are implemented by referring to subsets of a master
@@ -352,6 +581,9 @@ pub struct TextRenderBox {
     base: RenderBoxBase,
     run: @TextRun,
     range: Range,
+
+    /// The space added to each word separator of this box to justify its line.
+    word_spacing: Au,
 }
 
 impl TextRenderBox {
@@ -378,8 +610,43 @@ impl TextRenderBox {
             base: base,
             run: run,
             range: range,
+            word_spacing: Au::new(0),
+        }
+    }
+
+    /// Returns the number of word separators in this box, which justification widens. Trailing
+    /// separators are only counted if `count_trailing` is true.
+    pub fn word_separator_count(&self, count_trailing: bool) -> uint {
+        let mut count = 0;
+        let mut trailing_count = 0;
+        for (glyphs, _, slice_range) in self.run.iter_slices_for_range(&self.range) {
+            if glyphs.is_whitespace() {
+                count += slice_range.length();
+                trailing_count = slice_range.length();
+            } else {
+                trailing_count = 0;
+            }
+        }
+        if count_trailing {
+            count
+        } else {
+            count - trailing_count
         }
     }
+
+    /// Adds `word_spacing` to each of `count` word separators of this box, and widens the box to
+    /// match. A spacing of zero gives the box its natural width back.
+    pub fn set_word_spacing(&mut self, word_spacing: Au, count: uint) {
+        let natural_width = self.run.metrics_for_range(&self.range).advance_width;
+        self.word_spacing = word_spacing;
+        self.base.position.size.width = natural_width + word_spacing * Au::new(count as i32);
+    }
+
+    /// Returns true if this box ends with a newline that 'white-space' preserves, after which the
+    /// line must end. The text scanner splits text boxes after such newlines.
+    pub fn ends_with_newline(&self) -> bool {
+        self.run.text.iter().nth(self.range.end() - 1) == Some('\n')
+    }
 }
 
 impl RenderBox for TextRenderBox {
@@ -397,6 +664,12 @@ impl RenderBox for TextRenderBox {
 
     fn minimum_and_preferred_widths(&mut self) -> (Au, Au) {
         let guessed_width = self.base.guess_width();
+        if !text::white_space_allows_wrapping(self.base.white_space()) {
+            // The box is never broken, so it is its own minimum.
+            let width = self.run.metrics_for_range(&self.range).advance_width;
+            return (guessed_width + width, guessed_width + width)
+        }
+
         let min_width = self.run.min_width_for_range(&self.range);
 
         let mut max_line_width = Au::new(0);
@@ -409,18 +682,10 @@ impl RenderBox for TextRenderBox {
     }
 
     fn box_height(&mut self) -> Au {
-        let range = &self.range;
-        let run = &self.run;
-
-        // Compute the height based on the line-height and font size
-        let text_bounds = run.metrics_for_range(range).bounding_box;
-        let em_size = text_bounds.size.height;
-        let line_height = self.base.calculate_line_height(em_size);
-
-        line_height
+        self.base.calculate_line_height()
     }
 
-    fn assign_width(&mut self) {
+    fn assign_width(&mut self, _: Au) {
         // Text boxes are preinitialized.
     }
 
@@ -437,6 +702,9 @@ impl RenderBox for TextRenderBox {
                self.range,
                max_width);
 
+        let preserves_spaces = text::white_space_preserves_spaces(self.base.white_space());
+        let ends_with_newline = self.ends_with_newline();
+
         for (glyphs, offset, slice_range) in self.run.iter_slices_for_range(&self.range) {
             debug!("split_to_width: considering slice (offset=%?, range=%?, remain_width=%?)",
                    offset,
@@ -447,10 +715,14 @@ impl RenderBox for TextRenderBox {
             let advance = metrics.advance_width;
             let should_continue: bool;
 
-            if advance <= remaining_width {
+            // A preserved newline stays on the line it ends.
+            let is_newline = ends_with_newline && offset + slice_range.end() == self.range.end();
+
+            if advance <= remaining_width || is_newline {
                 should_continue = true;
 
-                if starts_line && pieces_processed_count == 0 && glyphs.is_whitespace() {
+                if starts_line && pieces_processed_count == 0 && glyphs.is_whitespace() &&
+                        !preserves_spaces && !is_newline {
                     debug!("split_to_width: case=skipping leading trimmable whitespace");
                     left_range.shift_by(slice_range.length() as int);
                 } else {
@@ -520,10 +792,20 @@ pub struct UnscannedTextRenderBox {
     // Cache font-style and text-decoration to check whether
     // this box can merge with another render box.
     font_style: Option<FontStyle>,
-    text_decoration: Option<CSSTextDecoration>,
+    text_decoration: Option<text_decoration::ComputedValue>,
 }
 
 impl UnscannedTextRenderBox {
+    /// Creates a new instance of `UnscannedTextRenderBox` for generated content.
+    pub fn from_text(base: RenderBoxBase, text: ~str) -> UnscannedTextRenderBox {
+        UnscannedTextRenderBox {
+            base: base,
+            text: text,
+            font_style: None,
+            text_decoration: None,
+        }
+    }
+
     /// Creates a new instance of `UnscannedTextRenderBox`.
     pub fn new(base: RenderBoxBase) -> UnscannedTextRenderBox {
         assert!(base.node.is_text());
@@ -560,8 +842,12 @@ impl RenderBox for UnscannedTextRenderBox {
         if other.class() == UnscannedTextRenderBoxClass {
             let this_base = &self.base;
             let other_base = other.base();
-            return this_base.font_style() == other_base.font_style() &&
-                this_base.text_decoration() == other_base.text_decoration()
+            // List markers are laid out on their own.
+            return this_base.pseudo_element != Some(Marker) &&
+                other_base.pseudo_element != Some(Marker) &&
+                this_base.font_style() == other_base.font_style() &&
+                this_base.text_decoration() == other_base.text_decoration() &&
+                this_base.white_space() == other_base.white_space()
         }
         false
     }
@@ -581,7 +867,7 @@ impl RenderBox for UnscannedTextRenderBox {
         fail!("WAT: shouldn't be an unscanned text box here.")
     }
 
-    fn assign_width(&mut self) {
+    fn assign_width(&mut self, _: Au) {
         fail!("WAT: shouldn't be an unscanned text box here.")
     }
 
@@ -597,6 +883,7 @@ impl RenderBox for UnscannedTextRenderBox {
 pub enum RenderBoxClass {
     GenericRenderBoxClass,
     ImageRenderBoxClass,
+    InlineBlockRenderBoxClass,
     TextRenderBoxClass,
     UnscannedTextRenderBoxClass,
 }
@@ -615,12 +902,19 @@ pub struct RenderBoxBase {
     /// The DOM node that this `RenderBox` originates from.
     node: AbstractNode<LayoutView>,
 
+    /// The pseudo-element of `node` that generated this box, if any.
+    pseudo_element: Option<PseudoElement>,
+
     /// The position of this box relative to its owning flow.
     position: Rect<Au>,
 
     /// The core parameters (border, padding, margin) used by the box model.
     model: BoxModel,
 
+    /// The resolved bidirectional embedding level of the contents of this box, which decides
+    /// where it goes when its line is reordered. Odd levels are right to left.
+    bidi_level: u8,
+
     /// A debug ID.
     ///
     /// TODO(#87) Make this only present in debug builds.
@@ -633,12 +927,27 @@ impl RenderBoxBase {
                -> RenderBoxBase {
         RenderBoxBase {
             node: node,
+            pseudo_element: None,
             position: Au::zero_rect(),
             model: Zero::zero(),
+            bidi_level: 0,
             id: id,
         }
     }
 
+    /// Constructs a new `RenderBoxBase` instance for content generated by a pseudo-element of
+    /// the given element.
+    pub fn new_for_pseudo_element(node: AbstractNode<LayoutView>,
+                                  pseudo_element: PseudoElement,
+                                  id: int)
+                                  -> RenderBoxBase {
+        assert!(node.pseudo_style(pseudo_element).is_some());
+        RenderBoxBase {
+            pseudo_element: Some(pseudo_element),
+            .. RenderBoxBase::new(node, id)
+        }
+    }
+
     pub fn id(&self) -> int {
         0
     }
@@ -649,39 +958,27 @@ impl RenderBoxBase {
         }
 
         let style = self.style();
-        let font_size = style.font_size();
-        let width = MaybeAuto::from_width(style.width(),
-                                          Au(0),
-                                          font_size).specified_or_zero();
-        let margin_left = MaybeAuto::from_margin(style.margin_left(),
-                                                 Au(0),
-                                                 font_size).specified_or_zero();
-        let margin_right = MaybeAuto::from_margin(style.margin_right(),
-                                                  Au(0),
-                                                  font_size).specified_or_zero();
-        let padding_left = self.model.compute_padding_length(style.padding_left(),
-                                                             Au(0),
-                                                             font_size);
-        let padding_right = self.model.compute_padding_length(style.padding_right(),
-                                                              Au(0),
-                                                              font_size);
+        let width = MaybeAuto::from_style(style.Box.width, Au(0)).specified_or_zero();
+        let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                Au(0)).specified_or_zero();
+        let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                 Au(0)).specified_or_zero();
+        let padding_left = self.model.compute_padding_length(style.Padding.padding_left, Au(0));
+        let padding_right = self.model.compute_padding_length(style.Padding.padding_right,
+                                                              Au(0));
 
         width + margin_left + margin_right + padding_left + padding_right + 
             self.model.border.left + self.model.border.right
     }
 
-    pub fn calculate_line_height(&self, font_size: Au) -> Au { 
-        match self.line_height() {
-            CSSLineHeightNormal => font_size.scale_by(1.14),
-            CSSLineHeightNumber(l) => font_size.scale_by(l),
-            CSSLineHeightLength(Em(l)) => font_size.scale_by(l),
-            CSSLineHeightLength(Px(l)) => Au::from_frac_px(l),
-            CSSLineHeightPercentage(p) => font_size.scale_by(p / 100.0)
-        }
+    /// Returns the used 'line-height' of the contents of this box.
+    pub fn calculate_line_height(&self) -> Au {
+        line_height_of_style(self.nearest_style())
     }
 
     pub fn compute_padding(&mut self, containing_block_width: Au) {
-        self.model.compute_padding(self.node.style(), containing_block_width);
+        let style = self.style();
+        self.model.compute_padding(style, containing_block_width);
     }
 
     pub fn get_noncontent_width(&self) -> Au {
@@ -733,107 +1030,167 @@ impl RenderBoxBase {
     // Always inline for SCCP.
     #[inline(always)]
     pub fn clear(&self) -> Option<ClearType> {
-        let style = self.node.style();
-        match style.clear() {
-            CSSClearNone => None,
-            CSSClearLeft => Some(ClearLeft),
-            CSSClearRight => Some(ClearRight),
-            CSSClearBoth => Some(ClearBoth)
+        let style = self.style();
+        match style.Box.clear {
+            clear::none => None,
+            clear::left => Some(ClearLeft),
+            clear::right => Some(ClearRight),
+            clear::both => Some(ClearBoth),
         }
     }
 
-    /// Converts this node's computed style to a font style used for rendering.
-    pub fn font_style(&self) -> FontStyle {
-        let my_style = self.nearest_ancestor_element().style();
-
-        debug!("(font style) start: %?", self.nearest_ancestor_element().type_id());
-
-        // FIXME: Too much allocation here.
-        let font_families = do my_style.font_family().map |family| {
-            match *family {
-                CSSFontFamilyFamilyName(ref family_str) => (*family_str).clone(),
-                CSSFontFamilyGenericFamily(Serif)       => ~"serif",
-                CSSFontFamilyGenericFamily(SansSerif)   => ~"sans-serif",
-                CSSFontFamilyGenericFamily(Cursive)     => ~"cursive",
-                CSSFontFamilyGenericFamily(Fantasy)     => ~"fantasy",
-                CSSFontFamilyGenericFamily(Monospace)   => ~"monospace",
-            }
+    /// Returns how far 'position: relative' moves this box from where it was laid out, given the
+    /// size of its containing block. (CSS 2.1 § 9.4.3)
+    pub fn relative_position(&self, containing_block: Size2D<Au>) -> Point2D<Au> {
+        let style = self.style();
+        if style.Box.position != position::relative {
+            return Point2D(Au::new(0), Au::new(0))
+        }
+
+        // When both offsets on an axis are given, 'left' and 'top' win.
+        let x = match (MaybeAuto::from_style(style.Box.left, containing_block.width),
+                       MaybeAuto::from_style(style.Box.right, containing_block.width)) {
+            (Specified(left), _) => left,
+            (Auto, Specified(right)) => -right,
+            (Auto, Auto) => Au::new(0),
         };
-        let font_families = font_families.connect(", ");
-        debug!("(font style) font families: `%s`", font_families);
-
-        let font_size = match my_style.font_size() {
-            CSSFontSizeLength(Px(length)) => length,
-            // todo: this is based on a hard coded font size, should be the parent element's font size
-            CSSFontSizeLength(Em(length)) => length * 16.0,
-            _ => 16.0 // px units
+        let y = match (MaybeAuto::from_style(style.Box.top, containing_block.height),
+                       MaybeAuto::from_style(style.Box.bottom, containing_block.height)) {
+            (Specified(top), _) => top,
+            (Auto, Specified(bottom)) => -bottom,
+            (Auto, Auto) => Au::new(0),
         };
-        debug!("(font style) font size: `%fpx`", font_size);
-
-        let (italic, oblique) = match my_style.font_style() {
-            CSSFontStyleNormal => (false, false),
-            CSSFontStyleItalic => (true, false),
-            CSSFontStyleOblique => (false, true),
+        Point2D(x, y)
+    }
+
+    /// The 'direction' of the block that contains this box, which decides which of its horizontal
+    /// margins gives way when they are over-constrained. (CSS 2.1 § 10.1) That is the nearest
+    /// positioned ancestor of an absolutely positioned box, and the parent element of any other
+    /// box. The initial containing block, which contains fixed-position boxes and absolutely
+    /// positioned boxes without a positioned ancestor, takes its 'direction' from the root
+    /// element, and this box's own style is used at the root.
+    pub fn containing_block_direction(&self) -> direction::ComputedValue {
+        let own_position = self.style().Box.position;
+
+        // The element that generated a pseudo-element is its parent.
+        let mut ancestor = match self.pseudo_element {
+            Some(_) => Some(self.node),
+            None => self.node.parent_node(),
         };
+        let mut containing_element = None;
+        loop {
+            match ancestor {
+                Some(node) if node.is_element() => {
+                    containing_element = Some(node);
+                    let contains = match own_position {
+                        position::absolute => node.style().Box.position != position::static_,
+                        position::fixed => false,
+                        position::static_ | position::relative => true,
+                    };
+                    if contains {
+                        break
+                    }
+                    ancestor = node.parent_node();
+                }
+                _ => break,
+            }
+        }
 
-        FontStyle {
-            pt_size: font_size,
-            weight: FontWeight300,
-            italic: italic,
-            oblique: oblique,
-            families: font_families,
+        match containing_element {
+            Some(element) => element.style().InheritedBox.direction,
+            None => self.style().InheritedBox.direction,
         }
     }
 
+    /// Converts this node's computed style to a font style used for rendering.
+    pub fn font_style(&self) -> FontStyle {
+        debug!("(font style) start: %?", self.node.type_id());
+        font_style_of_style(self.nearest_style())
+    }
+
+    /// The style of this box: that of its node, or of the pseudo-element that generated it.
     #[inline(always)]
-    pub fn style(&self) -> CompleteStyle {
-        self.node.style()
+    pub fn style<'a>(&'a self) -> &'a ComputedValues {
+        match self.pseudo_element {
+            None => self.node.style(),
+            Some(pseudo_element) => self.node.pseudo_style(pseudo_element).unwrap(),
+        }
+    }
+
+    /// Whether this box is the marker of a list item that goes outside of its principal box.
+    pub fn is_outside_marker(&self) -> bool {
+        self.pseudo_element == Some(Marker) &&
+            self.style().List.list_style_position == list_style_position::outside
+    }
+
+    /// The style that applies to the contents of this box: that of the nearest ancestor-or-self
+    /// `Element`, or of the pseudo-element that generated this box.
+    pub fn nearest_style<'a>(&'a self) -> &'a ComputedValues {
+        match self.pseudo_element {
+            None => {
+                let node = self.nearest_ancestor_element();
+                // FIXME: The style lives in the node's layout data, not in this temporary.
+                unsafe { cast::transmute_region(node.style()) }
+            }
+            Some(_) => self.style(),
+        }
     }
 
     /// Returns the text alignment of the computed style of the nearest ancestor-or-self `Element`
-    /// node.
-    pub fn text_align(&self) -> CSSTextAlign {
-        self.nearest_ancestor_element().style().text_align()
+    /// node, or of the generating pseudo-element.
+    pub fn text_align(&self) -> text_align::ComputedValue {
+        self.nearest_style().InheritedText.text_align
     }
 
-    pub fn line_height(self) -> CSSLineHeight {
-        self.nearest_ancestor_element().style().line_height()
+    pub fn line_height(&self) -> line_height::ComputedValue {
+        self.nearest_style().InheritedBox.line_height
     }
 
-    pub fn vertical_align(self) -> CSSVerticalAlign {
-        self.nearest_ancestor_element().style().vertical_align()
+    pub fn vertical_align(&self) -> vertical_align::ComputedValue {
+        self.nearest_style().Box.vertical_align
+    }
+
+    /// Returns the white-space handling of the computed style of the nearest ancestor-or-self
+    /// `Element` node, or of the generating pseudo-element.
+    pub fn white_space(&self) -> white_space::ComputedValue {
+        self.nearest_style().InheritedText.white_space
     }
 
     /// Returns the text decoration of the computed style of the nearest `Element` node
-    pub fn text_decoration(self) -> CSSTextDecoration {
+    pub fn text_decoration(&self) -> text_decoration::ComputedValue {
         /// Computes the propagated value of text-decoration, as specified in CSS 2.1 § 16.3.1
         /// TODO: make sure this works with anonymous box generation.
-        fn get_propagated_text_decoration(element: AbstractNode<LayoutView>) -> CSSTextDecoration {
+        fn get_propagated_text_decoration(element: AbstractNode<LayoutView>)
+                                          -> text_decoration::ComputedValue {
             //Skip over non-element nodes in the DOM
             if(!element.is_element()){
                 return match element.parent_node() {
-                    None => CSSTextDecorationNone,
+                    None => text_decoration::get_initial_value(),
                     Some(parent) => get_propagated_text_decoration(parent),
                 };
             }
 
-            //FIXME: is the root param on display() important?
-            let display_in_flow = match element.style().display(false) {
-                CSSDisplayInlineTable | CSSDisplayInlineBlock => false,
+            propagate_text_decoration(element.style(), element.parent_node())
+        }
+
+        /// Computes the propagated value of text-decoration for the given style, whose parent
+        /// element (or an ancestor of it) is `parent`.
+        fn propagate_text_decoration(style: &ComputedValues,
+                                     parent: Option<AbstractNode<LayoutView>>)
+                                     -> text_decoration::ComputedValue {
+            let display_in_flow = match style.Box.display {
+                display::inline_block => false,
                 _ => true,
             };
 
-            let position = element.style().position();
-            let float = element.style().float();
-
-            let in_flow = (position == CSSPositionStatic) && (float == CSSFloatNone) &&
-                display_in_flow;
+            let in_flow = (style.Box.position == position::static_) &&
+                (style.Box.float == float::none) && display_in_flow;
 
-            let text_decoration = element.style().text_decoration();
+            let text_decoration = style.Text.text_decoration;
 
-            if(text_decoration == CSSTextDecorationNone && in_flow){
-                match element.parent_node() {
-                    None => CSSTextDecorationNone,
+            if(text_decoration == text_decoration::get_initial_value() && in_flow){
+                match parent {
+                    None => text_decoration::get_initial_value(),
                     Some(parent) => get_propagated_text_decoration(parent),
                 }
             }
@@ -841,9 +1198,115 @@ impl RenderBoxBase {
                 text_decoration
             }
         }
-        get_propagated_text_decoration(self.nearest_ancestor_element())
+        match self.pseudo_element {
+            // Generated content is a child of the element that generates it.
+            Some(_) => propagate_text_decoration(self.style(), Some(self.node)),
+            None => get_propagated_text_decoration(self.nearest_ancestor_element()),
+        }
+    }
+
+}
+
+/// Converts a computed style to a font style used for rendering.
+pub fn font_style_of_style(style: &ComputedValues) -> FontStyle {
+    // FIXME: Too much allocation here.
+    let font_families = do style.Font.font_family.map |family| {
+        match *family {
+            font_family::FamilyName(ref family_str) => (*family_str).clone(),
+        }
+    };
+    let font_families = font_families.connect(", ");
+    debug!("(font style) font families: `%s`", font_families);
+
+    let font_size = to_frac_px(model::from_length(style.Font.font_size));
+    debug!("(font style) font size: `%fpx`", font_size);
+
+    let (italic, oblique) = match style.Font.font_style {
+        font_style::normal => (false, false),
+        font_style::italic => (true, false),
+        font_style::oblique => (false, true),
+    };
+
+    FontStyle {
+        pt_size: font_size,
+        weight: css_font_weight(style.Font.font_weight),
+        italic: italic,
+        oblique: oblique,
+        families: font_families,
+    }
+}
+
+/// Converts a computed 'font-weight' to the weight that fonts are matched with.
+pub fn css_font_weight(weight: font_weight::ComputedValue) -> CSSFontWeight {
+    match weight {
+        font_weight::Weight100 => FontWeight100,
+        font_weight::Weight200 => FontWeight200,
+        font_weight::Weight300 => FontWeight300,
+        font_weight::Weight400 => FontWeight400,
+        font_weight::Weight500 => FontWeight500,
+        font_weight::Weight600 => FontWeight600,
+        font_weight::Weight700 => FontWeight700,
+        font_weight::Weight800 => FontWeight800,
+        font_weight::Weight900 => FontWeight900,
+    }
+}
+
+/// Returns the used 'line-height' of a computed style. (CSS 2.1 § 10.8.1)
+/// Returns the radii of the corners of a box with the given style and border box size.
+/// Percentages refer to the size of the box. Radii that would overlap along a side are all
+/// scaled down until they fit. (CSS Backgrounds and Borders Module Level 3 § 5.5)
+fn border_radii_of_style(style: &ComputedValues, size: &Size2D<Au>) -> BorderRadii<Au> {
+    let radius = |value: &border_top_left_radius::ComputedValue| {
+        let length = |value: LengthOrPercentage, size: Au| {
+            match value {
+                LP_Length(length) => model::from_length(length),
+                LP_Percentage(percent) => size.scale_by(percent),
+            }
+        };
+        Size2D(length(value.horizontal, size.width), length(value.vertical, size.height))
+    };
+    let radii = BorderRadii {
+        top_left: radius(&style.Border.border_top_left_radius),
+        top_right: radius(&style.Border.border_top_right_radius),
+        bottom_right: radius(&style.Border.border_bottom_right_radius),
+        bottom_left: radius(&style.Border.border_bottom_left_radius),
+    };
+    if radii.is_zero() {
+        return radii
+    }
+
+    let mut factor = 1.0f64;
+    let sides = [
+        (size.width, radii.top_left.width + radii.top_right.width),
+        (size.height, radii.top_right.height + radii.bottom_right.height),
+        (size.width, radii.bottom_right.width + radii.bottom_left.width),
+        (size.height, radii.bottom_left.height + radii.top_left.height),
+    ];
+    for &(length, radii_length) in sides.iter() {
+        if radii_length > length {
+            factor = factor.min(&(to_frac_px(length) / to_frac_px(radii_length)))
+        }
     }
+    if factor >= 1.0 {
+        return radii
+    }
+    let scale = |radius: Size2D<Au>| Size2D(radius.width.scale_by(factor),
+                                            radius.height.scale_by(factor));
+    BorderRadii {
+        top_left: scale(radii.top_left),
+        top_right: scale(radii.top_right),
+        bottom_right: scale(radii.bottom_right),
+        bottom_left: scale(radii.bottom_left),
+    }
+}
 
+pub fn line_height_of_style(style: &ComputedValues) -> Au {
+    let font_size = model::from_length(style.Font.font_size);
+    match style.InheritedBox.line_height {
+        line_height::Normal => font_size.scale_by(1.14),
+        line_height::Number(l) => font_size.scale_by(l),
+        line_height::Length(l) => model::from_length(l),
+    }
 }
 
 impl RenderBoxUtils for @mut RenderBox {
@@ -868,7 +1331,8 @@ impl RenderBoxUtils for @mut RenderBox {
     }
 
     fn can_split(self) -> bool {
-        self.class() == TextRenderBoxClass
+        self.class() == TextRenderBoxClass &&
+            text::white_space_allows_wrapping(self.base().white_space())
     }
 
     /// Returns the amount of left and right "fringe" used by this box. This is based on margins,
@@ -889,26 +1353,195 @@ impl RenderBoxUtils for @mut RenderBox {
     /// list if necessary.
     fn paint_background_if_applicable<E:ExtraDisplayListData>(
                                       self,
+                                      builder: &DisplayListBuilder,
                                       list: &Cell<DisplayList<E>>,
-                                      absolute_bounds: &Rect<Au>) {
+                                      absolute_bounds: &Rect<Au>,
+                                      clip_rect: &Option<Rect<Au>>) {
         // FIXME: This causes a lot of background colors to be displayed when they are clearly not
         // needed. We could use display list optimization to clean this up, but it still seems
         // inefficient. What we really want is something like "nearest ancestor element that
         // doesn't have a render box".
-        let nearest_ancestor_element = self.base().nearest_ancestor_element();
+        let style = self.base().nearest_style();
+
+        // Text boxes take the background of the inline element around them. The background of a
+        // block has been painted by the block already, and copies of its image, shadows and
+        // rounded corners positioned at the text would show over it.
+        if self.class() == TextRenderBoxClass && style.Box.display != display::inline {
+            return
+        }
+
+        // The shadows outside the box go under the background, and those inside it over it.
+        // (CSS Backgrounds and Borders Module Level 3 § 7.1)
+        let radii = border_radii_of_style(style, &absolute_bounds.size);
+        self.paint_box_shadows_if_applicable(list, absolute_bounds, &radii, clip_rect, false);
 
-        let background_color = nearest_ancestor_element.style().background_color();
+        let background_color = style.resolve_color(style.Background.background_color);
         if !background_color.alpha.approx_eq(&0.0) {
             do list.with_mut_ref |list| {
-                let solid_color_display_item = ~SolidColorDisplayItem {
+                let base = BaseDisplayItem {
+                    bounds: *absolute_bounds,
+                    clip_rect: *clip_rect,
+                    extra: ExtraDisplayListData::new(self),
+                };
+                if radii.is_zero() {
+                    let solid_color_display_item = ~SolidColorDisplayItem {
+                        base: base,
+                        color: background_color.to_gfx_color(),
+                    };
+                    list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
+                } else {
+                    let solid_color_display_item = ~RoundedSolidColorDisplayItem {
+                        base: base,
+                        color: background_color.to_gfx_color(),
+                        radii: radii.clone(),
+                    };
+                    list.append_item(RoundedSolidColorDisplayItemClass(solid_color_display_item))
+                }
+            }
+        }
+
+        // The image goes over the color.
+        self.paint_background_image_if_applicable(builder, list, absolute_bounds, clip_rect);
+
+        self.paint_box_shadows_if_applicable(list, absolute_bounds, &radii, clip_rect, true);
+    }
+
+    /// Adds the display items necessary to paint the background image of this render box to the
+    /// display list if necessary.
+    ///
+    /// TODO: Clip the image to rounded corners.
+    fn paint_background_image_if_applicable<E:ExtraDisplayListData>(
+                                            self,
+                                            builder: &DisplayListBuilder,
+                                            list: &Cell<DisplayList<E>>,
+                                            absolute_bounds: &Rect<Au>,
+                                            clip_rect: &Option<Rect<Au>>) {
+        let style = self.base().nearest_style();
+        let image_url = match style.Background.background_image {
+            Some(ref url) => url.clone(),
+            None => return,
+        };
+        // The image cache sends a reflow when an image that is not ready yet arrives.
+        let mut holder = ImageHolder::new(image_url, builder.ctx.image_cache);
+        let image = match holder.get_image() {
+            Some(image) => image,
+            None => return,
+        };
+        let image_size = {
+            let image = image.get();
+            Size2D(Au::from_px(image.width as int), Au::from_px(image.height as int))
+        };
+
+        // The image is positioned relative to the padding box and painted under the border box.
+        // (CSS 2.1 § 14.2.1)
+        let border = self.base().model.border;
+        let positioning_area = Rect(Point2D(absolute_bounds.origin.x + border.left,
+                                            absolute_bounds.origin.y + border.top),
+                                    Size2D(absolute_bounds.size.width - border.left - border.right,
+                                           absolute_bounds.size.height - border.top -
+                                           border.bottom));
+        let position = style.Background.background_position;
+        let offset = |value: LengthOrPercentage, available: Au| {
+            match value {
+                LP_Length(length) => model::from_length(length),
+                LP_Percentage(percent) => available.scale_by(percent),
+            }
+        };
+        let tile_origin = Point2D(
+            positioning_area.origin.x +
+                offset(position.horizontal, positioning_area.size.width - image_size.width),
+            positioning_area.origin.y +
+                offset(position.vertical, positioning_area.size.height - image_size.height));
+
+        let (repeat_x, repeat_y) = match style.Background.background_repeat {
+            background_repeat::repeat => (true, true),
+            background_repeat::repeat_x => (true, false),
+            background_repeat::repeat_y => (false, true),
+            background_repeat::no_repeat => (false, false),
+        };
+
+        do list.with_mut_ref |list| {
+            let image_display_item = ~TiledImageDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: *absolute_bounds,
+                    clip_rect: *clip_rect,
+                    extra: ExtraDisplayListData::new(self),
+                },
+                image: image.clone(),
+                tile: Rect(tile_origin, image_size),
+                repeat_x: repeat_x,
+                repeat_y: repeat_y,
+            };
+            list.append_item(TiledImageDisplayItemClass(image_display_item))
+        }
+    }
+
+    /// Adds the display items necessary to paint the outer shadows of this render box, or its
+    /// inset shadows if `inset` is true, to the display list if necessary.
+    fn paint_box_shadows_if_applicable<E:ExtraDisplayListData>(
+                                       self,
+                                       list: &Cell<DisplayList<E>>,
+                                       absolute_bounds: &Rect<Au>,
+                                       radii: &BorderRadii<Au>,
+                                       clip_rect: &Option<Rect<Au>>,
+                                       inset: bool) {
+        let base = self.base();
+        let style = base.nearest_style();
+        if style.Border.box_shadow.is_empty() {
+            return
+        }
+
+        // Outer shadows are cast by the border box, and inset ones inside the padding box.
+        let (box_bounds, radii) = if !inset {
+            (*absolute_bounds, radii.clone())
+        } else {
+            let border = base.model.border;
+            let padding_box = Rect(Point2D(absolute_bounds.origin.x + border.left,
+                                           absolute_bounds.origin.y + border.top),
+                                   Size2D(absolute_bounds.size.width - border.left - border.right,
+                                          absolute_bounds.size.height - border.top -
+                                          border.bottom));
+            (padding_box, radii.inner_radii(&border))
+        };
+
+        // The first shadow is drawn on top.
+        for shadow in style.Border.box_shadow.iter().invert() {
+            if shadow.inset != inset {
+                loop
+            }
+            let offset = Point2D(model::from_length(shadow.offset_x),
+                                 model::from_length(shadow.offset_y));
+            let blur_radius = model::from_length(shadow.blur_radius);
+            let spread_radius = model::from_length(shadow.spread_radius);
+
+            // The area that the shadow may cover.
+            let bounds = if inset {
+                box_bounds
+            } else {
+                let outset = Au::max(spread_radius, Au(0)) + blur_radius;
+                let shadow_bounds = box_bounds.translate(&offset);
+                Rect(Point2D(shadow_bounds.origin.x - outset, shadow_bounds.origin.y - outset),
+                     Size2D(shadow_bounds.size.width + outset + outset,
+                            shadow_bounds.size.height + outset + outset))
+            };
+
+            let color = style.resolve_color(shadow.color).to_gfx_color();
+            do list.with_mut_ref |list| {
+                let box_shadow_display_item = ~BoxShadowDisplayItem {
                     base: BaseDisplayItem {
-                        bounds: *absolute_bounds,
+                        bounds: bounds,
+                        clip_rect: *clip_rect,
                         extra: ExtraDisplayListData::new(self),
                     },
-                    color: background_color.to_gfx_color(),
+                    box_bounds: box_bounds,
+                    radii: radii.clone(),
+                    color: color,
+                    offset: offset,
+                    blur_radius: blur_radius,
+                    spread_radius: spread_radius,
+                    inset: inset,
                 };
-
-                list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
+                list.append_item(BoxShadowDisplayItemClass(box_shadow_display_item))
             }
         }
     }
@@ -918,7 +1551,8 @@ impl RenderBoxUtils for @mut RenderBox {
     fn paint_borders_if_applicable<E:ExtraDisplayListData>(
                                    self,
                                    list: &Cell<DisplayList<E>>,
-                                   abs_bounds: &Rect<Au>) {
+                                   abs_bounds: &Rect<Au>,
+                                   clip_rect: &Option<Rect<Au>>) {
         // Fast path.
         let base = self.base();
         let border = base.model.border;
@@ -926,30 +1560,53 @@ impl RenderBoxUtils for @mut RenderBox {
             return
         }
 
-        let (top_color, right_color, bottom_color, left_color) = (base.style().border_top_color(), base.style().border_right_color(), base.style().border_bottom_color(), base.style().border_left_color());
-        let (top_style, right_style, bottom_style, left_style) = (base.style().border_top_style(), base.style().border_right_style(), base.style().border_bottom_style(), base.style().border_left_style());
+        let style = base.style();
+        let top_color = style.resolve_color(style.Border.border_top_color);
+        let right_color = style.resolve_color(style.Border.border_right_color);
+        let bottom_color = style.resolve_color(style.Border.border_bottom_color);
+        let left_color = style.resolve_color(style.Border.border_left_color);
+        let top_style = style.Border.border_top_style;
+        let right_style = style.Border.border_right_style;
+        let bottom_style = style.Border.border_bottom_style;
+        let left_style = style.Border.border_left_style;
+        let radii = border_radii_of_style(style, &abs_bounds.size);
         // Append the border to the display list.
         do list.with_mut_ref |list| {
-            let border_display_item = ~BorderDisplayItem {
-                base: BaseDisplayItem {
-                    bounds: *abs_bounds,
-                    extra: ExtraDisplayListData::new(self),
-                },
-                border: SideOffsets2D::new(border.top,
-                                           border.right,
-                                           border.bottom,
-                                           border.left),
-                color: SideOffsets2D::new(top_color.to_gfx_color(),
-                                          right_color.to_gfx_color(),
-                                          bottom_color.to_gfx_color(),
-                                          left_color.to_gfx_color()),
-                style: SideOffsets2D::new(top_style,
-                                          right_style,
-                                          bottom_style,
-                                          left_style)
+            let base = BaseDisplayItem {
+                bounds: *abs_bounds,
+                clip_rect: *clip_rect,
+                extra: ExtraDisplayListData::new(self),
             };
-
-            list.append_item(BorderDisplayItemClass(border_display_item))
+            let widths = SideOffsets2D::new(border.top,
+                                            border.right,
+                                            border.bottom,
+                                            border.left);
+            let colors = SideOffsets2D::new(top_color.to_gfx_color(),
+                                            right_color.to_gfx_color(),
+                                            bottom_color.to_gfx_color(),
+                                            left_color.to_gfx_color());
+            let styles = SideOffsets2D::new(top_style,
+                                            right_style,
+                                            bottom_style,
+                                            left_style);
+            if radii.is_zero() {
+                let border_display_item = ~BorderDisplayItem {
+                    base: base,
+                    border: widths,
+                    color: colors,
+                    style: styles,
+                };
+                list.append_item(BorderDisplayItemClass(border_display_item))
+            } else {
+                let border_display_item = ~RoundedBorderDisplayItem {
+                    base: base,
+                    border: widths,
+                    color: colors,
+                    style: styles,
+                    radii: radii.clone(),
+                };
+                list.append_item(RoundedBorderDisplayItemClass(border_display_item))
+            }
         }
     }
 
@@ -960,18 +1617,16 @@ impl RenderBoxUtils for @mut RenderBox {
     /// * `dirty`: The dirty rectangle in the coordinate system of the owning flow.
     /// * `origin`: The total offset from the display list root flow to the owning flow of this
     ///   box.
-    /// * `list`: The display list to which items should be appended.
-    ///
-    /// TODO: To implement stacking contexts correctly, we need to create a set of display lists,
-    /// one per layer of the stacking context (CSS 2.1 § 9.9.1). Each box is passed the list set
-    /// representing the box's stacking context. When asked to construct its constituent display
-    /// items, each box puts its display items into the correct stack layer according to CSS 2.1
-    /// Appendix E. Finally, the builder flattens the list.
+    /// * `clip_rect`: The rectangle to which the display items of the owning flow are clipped, if
+    ///   any.
+    /// * `list`: The display list to which items should be appended. This is the layer of the
+    ///   stacking context of the box in which the owning flow is painted (CSS 2.1 Appendix E).
     fn build_display_list<E:ExtraDisplayListData>(
                           self,
-                          _: &DisplayListBuilder,
+                          builder: &DisplayListBuilder,
                           dirty: &Rect<Au>,
                           offset: &Point2D<Au>,
+                          clip_rect: &Option<Rect<Au>>,
                           list: &Cell<DisplayList<E>>) {
         let base = self.base();
         let box_bounds = base.position;
@@ -987,28 +1642,42 @@ impl RenderBoxUtils for @mut RenderBox {
             return;
         }
 
+        match *clip_rect {
+            Some(ref clip_rect) if !absolute_box_bounds.intersects(clip_rect) => {
+                debug!("RenderBox::build_display_list: Clipped out...");
+                return;
+            }
+            _ => {}
+        }
+
         match self.class() {
             UnscannedTextRenderBoxClass => fail!("Shouldn't see unscanned boxes here."),
+            // The `InlineBlockFlow` draws the element.
+            InlineBlockRenderBoxClass => return,
             TextRenderBoxClass => {
                 let text_box = self.as_text_render_box();
 
                 // Add the background to the list, if applicable.
-                self.paint_background_if_applicable(list, &absolute_box_bounds);
+                self.paint_background_if_applicable(builder,
+                                                    list,
+                                                    &absolute_box_bounds,
+                                                    clip_rect);
 
-                let nearest_ancestor_element = base.nearest_ancestor_element();
-                let color = nearest_ancestor_element.style().color().to_gfx_color();
+                let color = base.nearest_style().Color.color.to_gfx_color();
 
                 // Create the text box.
                 do list.with_mut_ref |list| {
                     let text_display_item = ~TextDisplayItem {
                         base: BaseDisplayItem {
                             bounds: absolute_box_bounds,
+                            clip_rect: *clip_rect,
                             extra: ExtraDisplayListData::new(self),
                         },
                         // FIXME(pcwalton): Allocation? Why?!
                         text_run: ~text_box.run.serialize(),
                         range: text_box.range,
                         color: color,
+                        word_spacing: text_box.word_spacing,
                     };
 
                     list.append_item(TextDisplayItemClass(text_display_item))
@@ -1026,11 +1695,12 @@ impl RenderBoxUtils for @mut RenderBox {
                         let border_display_item = ~BorderDisplayItem {
                             base: BaseDisplayItem {
                                 bounds: absolute_box_bounds,
+                                clip_rect: *clip_rect,
                                 extra: ExtraDisplayListData::new(self),
                             },
                             border: debug_border,
-                            color: SideOffsets2D::new_all_same(rgb(0, 0, 200).to_gfx_color()),
-                            style: SideOffsets2D::new_all_same(CSSBorderStyleSolid)
+                            color: SideOffsets2D::new_all_same(rgb(0, 0, 200)),
+                            style: SideOffsets2D::new_all_same(border_style::solid)
 
                         };
                         list.append_item(BorderDisplayItemClass(border_display_item))
@@ -1048,11 +1718,12 @@ impl RenderBoxUtils for @mut RenderBox {
                         let border_display_item = ~BorderDisplayItem {
                             base: BaseDisplayItem {
                                 bounds: baseline,
+                                clip_rect: *clip_rect,
                                 extra: ExtraDisplayListData::new(self),
                             },
                             border: debug_border,
-                            color: SideOffsets2D::new_all_same(rgb(0, 200, 0).to_gfx_color()),
-                            style: SideOffsets2D::new_all_same(CSSBorderStyleDashed)
+                            color: SideOffsets2D::new_all_same(rgb(0, 200, 0)),
+                            style: SideOffsets2D::new_all_same(border_style::dashed)
 
                         };
                         list.append_item(BorderDisplayItemClass(border_display_item))
@@ -1063,7 +1734,10 @@ impl RenderBoxUtils for @mut RenderBox {
             },
             GenericRenderBoxClass => {
                 // Add the background to the list, if applicable.
-                self.paint_background_if_applicable(list, &absolute_box_bounds);
+                self.paint_background_if_applicable(builder,
+                                                    list,
+                                                    &absolute_box_bounds,
+                                                    clip_rect);
 
                 // FIXME(pcwalton): This is a bit of an abuse of the logging infrastructure. We
                 // should have a real `SERVO_DEBUG` system.
@@ -1074,11 +1748,12 @@ impl RenderBoxUtils for @mut RenderBox {
                         let border_display_item = ~BorderDisplayItem {
                             base: BaseDisplayItem {
                                 bounds: absolute_box_bounds,
+                                clip_rect: *clip_rect,
                                 extra: ExtraDisplayListData::new(self),
                             },
                             border: debug_border,
-                            color: SideOffsets2D::new_all_same(rgb(0, 0, 200).to_gfx_color()),
-                            style: SideOffsets2D::new_all_same(CSSBorderStyleSolid)
+                            color: SideOffsets2D::new_all_same(rgb(0, 0, 200)),
+                            style: SideOffsets2D::new_all_same(border_style::solid)
 
                         };
                         list.append_item(BorderDisplayItemClass(border_display_item))
@@ -1091,7 +1766,10 @@ impl RenderBoxUtils for @mut RenderBox {
                 let image_box = self.as_image_render_box();
 
                 // Add the background to the list, if applicable.
-                self.paint_background_if_applicable(list, &absolute_box_bounds);
+                self.paint_background_if_applicable(builder,
+                                                    list,
+                                                    &absolute_box_bounds,
+                                                    clip_rect);
 
                 match image_box.image.get_image() {
                     Some(image) => {
@@ -1102,6 +1780,7 @@ impl RenderBoxUtils for @mut RenderBox {
                             let image_display_item = ~ImageDisplayItem {
                                 base: BaseDisplayItem {
                                     bounds: absolute_box_bounds,
+                                    clip_rect: *clip_rect,
                                     extra: ExtraDisplayListData::new(self),
                                 },
                                 image: image.clone(),
@@ -1122,7 +1801,7 @@ impl RenderBoxUtils for @mut RenderBox {
         // Add a border, if applicable.
         //
         // TODO: Outlines.
-        self.paint_borders_if_applicable(list, &absolute_box_bounds);
+        self.paint_borders_if_applicable(list, &absolute_box_bounds, clip_rect);
     }
 }
 
@@ -4,28 +4,34 @@
 
 //! Creates CSS boxes from a DOM tree.
 
+use layout::absolute::AbsoluteFlow;
 use layout::block::BlockFlow;
 use layout::float::FloatFlow;
 use layout::box::{GenericRenderBox, GenericRenderBoxClass, ImageRenderBox, ImageRenderBoxClass};
-use layout::box::{RenderBox, RenderBoxBase, RenderBoxClass, RenderBoxUtils, TextRenderBoxClass};
+use layout::box::{InlineBlockRenderBox, InlineBlockRenderBoxClass, RenderBox, RenderBoxBase};
+use layout::box::{RenderBoxClass, RenderBoxUtils, TextRenderBoxClass};
 use layout::box::{UnscannedTextRenderBox, UnscannedTextRenderBoxClass};
 use layout::context::LayoutContext;
 use layout::float_context::FloatType;
-use layout::flow::{AbsoluteFlow, BlockFlow, BlockFlowClass, FloatFlow, FloatFlowClass};
-use layout::flow::{FlowContext, FlowData, ImmutableFlowUtils, InlineBlockFlow};
-use layout::flow::{InlineBlockFlowClass, InlineFlowClass, MutableFlowUtils, TableFlow};
+use layout::flow::{AbsoluteFlowClass, BlockFlowClass, FloatFlowClass};
+use layout::flow::{FlowContext, FlowData, ImmutableFlowUtils, InlineBlockFlowClass};
+use layout::flow::{InlineFlowClass, MutableFlowUtils};
+use layout::flow::{TableCellFlowClass, TableColGroupFlowClass, TableFlowClass};
+use layout::flow::{TableRowFlowClass, TableRowGroupFlowClass};
 use layout::flow;
+use layout::generated_content::GeneratedContentState;
 use layout::inline::{InlineFlow};
+use layout::inline_block::InlineBlockFlow;
+use layout::table::TableFlow;
+use layout::table_cell::TableCellFlow;
+use layout::table_colgroup::TableColGroupFlow;
+use layout::table_row::TableRowFlow;
+use layout::table_rowgroup::TableRowGroupFlow;
 use layout::text::TextRunScanner;
 use css::node_style::StyledNode;
 
-use newcss::values::{CSSDisplayBlock, CSSDisplayInline, CSSDisplayInlineBlock};
-use newcss::values::{CSSDisplayTable, CSSDisplayInlineTable, CSSDisplayListItem};
-use newcss::values::{CSSDisplayTableRowGroup, CSSDisplayTableHeaderGroup, CSSDisplayTableFooterGroup};
-use newcss::values::{CSSDisplayTableRow, CSSDisplayTableColumnGroup, CSSDisplayTableColumn};
-use newcss::values::{CSSDisplayTableCell, CSSDisplayTableCaption};
-use newcss::values::{CSSDisplayNone};
-use newcss::values::{CSSFloatNone, CSSFloatLeft, CSSFloatRight};
+use style::{PseudoElement, Before, After, Marker};
+use style::computed_values::{display, float, position};
 use layout::float_context::{FloatLeft, FloatRight};
 use script::dom::node::{AbstractNode, CommentNodeTypeId, DoctypeNodeTypeId};
 use script::dom::node::{ElementNodeTypeId, LayoutView, TextNodeTypeId};
@@ -42,11 +48,16 @@ enum FlowType {
     InlineFlowType,
     RootFlowType,
     TableFlowType,
+    TableRowGroupFlowType,
+    TableRowFlowType,
+    TableCellFlowType,
+    TableColGroupFlowType,
 }
 
 pub struct LayoutTreeBuilder {
     next_cid: int,
     next_bid: int,
+    generated_content: GeneratedContentState,
 }
 
 impl LayoutTreeBuilder {
@@ -54,6 +65,7 @@ impl LayoutTreeBuilder {
         LayoutTreeBuilder {
             next_cid: -1,
             next_bid: -1,
+            generated_content: GeneratedContentState::new(),
         }
     }
 }
@@ -63,6 +75,9 @@ impl LayoutTreeBuilder {
 struct BoxGenerator<'self> {
     flow: &'self mut FlowContext,
     range_stack: @mut ~[uint],
+    /// If the flow is an inline flow, the flow that holds it. Blocks that split the inline flow
+    /// go there.
+    container: Option<&'self mut FlowContext>,
 }
 
 enum InlineSpacerSide {
@@ -77,21 +92,48 @@ impl<'self> BoxGenerator<'self> {
         debug!("Creating box generator for flow: %s", flow.debug_str());
         BoxGenerator {
             flow: flow,
-            range_stack: @mut ~[]
+            range_stack: @mut ~[],
+            container: None,
         }
     }
 
+    /// Creates the generator of `flow`, which is a child of the flow of `parent`.
+    fn new_child(flow: &'self mut FlowContext, parent: &mut BoxGenerator<'self>)
+                 -> BoxGenerator<'self> {
+        let mut generator = BoxGenerator::new(flow);
+        if generator.flow.class() == InlineFlowClass {
+            // FIXME: Unsafe, as in `with_clone`.
+            generator.container = Some(unsafe {
+                cast::transmute_copy(&parent.flow)
+            });
+        }
+        generator
+    }
+
     fn with_clone<R>(&mut self, cb: &fn(BoxGenerator<'self>) -> R) -> R {
         // FIXME(pcwalton): This is a hack; it can be done safely with linearity.
         unsafe {
             let gen = BoxGenerator {
                 flow: cast::transmute_copy(&self.flow),
-                range_stack: self.range_stack
+                range_stack: self.range_stack,
+                container: cast::transmute_copy(&self.container),
             };
             cb(gen)
         }
     }
 
+    /// Returns a generator for the flow that holds the inline flow of this generator.
+    fn container_generator(&mut self) -> BoxGenerator<'self> {
+        match self.container {
+            // FIXME: Unsafe, as in `with_clone`.
+            Some(ref mut container) => BoxGenerator::new(unsafe {
+                cast::transmute_copy(container)
+            }),
+            None => fail!("flow f%d is not an inline flow with a container",
+                          flow::base(self.flow).id),
+        }
+    }
+
     /* Whether "spacer" boxes are needed to stand in for this DOM node */
     fn inline_spacers_needed_for_node(_: AbstractNode<LayoutView>) -> bool {
         return false;
@@ -135,9 +177,8 @@ impl<'self> BoxGenerator<'self> {
                         inline.boxes.push(*spacer);
                     }
                 }
-                // TODO: cases for inline-block, etc.
             },
-            BlockFlowClass => {
+            AbsoluteFlowClass | BlockFlowClass | InlineBlockFlowClass => {
                 let block = self.flow.as_block();
                 debug!("BoxGenerator[f%d]: point b", block.base.id);
                 let new_box = BoxGenerator::make_box(ctx, box_type, node, builder);
@@ -164,6 +205,29 @@ impl<'self> BoxGenerator<'self> {
                 assert!(float.box.is_none() && float.index.is_none());
                 float.box = Some(new_box);
             }
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass | TableCellFlowClass => {
+                let block = self.flow.as_block();
+                let new_box = BoxGenerator::make_box(ctx, box_type, node, builder);
+
+                debug!("BoxGenerator[f%d]: attaching box[b%d] to table flow (node: %s)",
+                       block.base.id,
+                       new_box.base().id(),
+                       node.debug_str());
+
+                assert!(block.box.is_none());
+                block.box = Some(new_box);
+            }
+            TableColGroupFlowClass => {
+                let colgroup = self.flow.as_table_colgroup();
+                let new_box = BoxGenerator::make_box(ctx, box_type, node, builder);
+                match node.style().Box.display {
+                    display::table_column => colgroup.cols.push(new_box),
+                    _ => {
+                        assert!(colgroup.box.is_none());
+                        colgroup.box = Some(new_box);
+                    }
+                }
+            }
             _ => warn!("push_node() not implemented for flow f%d", flow::base(self.flow).id),
         }
     }
@@ -195,8 +259,12 @@ impl<'self> BoxGenerator<'self> {
                 debug!("BoxGenerator: adding element range=%?", node_range);
                 inline.elems.add_mapping(node, &node_range);
             },
-            BlockFlowClass => assert!(self.range_stack.len() == 0),
+            AbsoluteFlowClass | BlockFlowClass | InlineBlockFlowClass => {
+                assert!(self.range_stack.len() == 0)
+            }
             FloatFlowClass => assert!(self.range_stack.len() == 0),
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass | TableCellFlowClass |
+            TableColGroupFlowClass => assert!(self.range_stack.len() == 0),
             _ => warn!("pop_node() not implemented for flow %?", flow::base(self.flow).id),
         }
     }
@@ -215,6 +283,9 @@ impl<'self> BoxGenerator<'self> {
                 @mut UnscannedTextRenderBox::new(base) as @mut RenderBox
             }
             ImageRenderBoxClass => BoxGenerator::make_image_box(layout_ctx, node, base),
+            InlineBlockRenderBoxClass => {
+                fail!("inline-block boxes are made along with their flows")
+            }
         };
         debug!("BoxGenerator: created box: %s", result.debug_str());
         result
@@ -263,16 +334,17 @@ enum BoxGenResult<'self> {
     ParentGenerator,
     SiblingGenerator,
     NewGenerator(BoxGenerator<'self>),
-    /// Start a new generator, but also switch the parent out for the
-    /// grandparent, ending the parent generator.
+    /// Start a new generator for a block that splits its parent inline flow. The block goes into
+    /// the flow that holds the inline flow, and so does the rest of the inline content, after it.
     ReparentingGenerator(BoxGenerator<'self>),
     Mixed(BoxGenerator<'self>, ~BoxGenResult<'self>),
 }
 
 /// Determines whether the result of child box construction needs to reparent
 /// or not. Reparenting is needed when a block flow is a child of an inline;
-/// in that case, we need to let the level up the stack no to end the parent
-/// genertor and continue with the grandparent.
+/// in that case, the generator is a child of the flow that holds the inline
+/// flow, and the siblings that follow must continue there rather than in the
+/// inline flow (CSS 2.1 § 9.2.1.1).
 enum BoxConstructResult<'self> {
     Normal(Option<BoxGenerator<'self>>),
     Reparent(BoxGenerator<'self>),
@@ -289,21 +361,16 @@ impl LayoutTreeBuilder {
                                  &mut self,
                                  layout_ctx: &LayoutContext,
                                  cur_node: AbstractNode<LayoutView>,
-                                 mut grandparent_generator: Option<BoxGenerator<'a>>,
                                  mut parent_generator: BoxGenerator<'a>,
                                  mut prev_sibling_generator: Option<BoxGenerator<'a>>)
                                  -> BoxConstructResult<'a> {
         debug!("Considering node: %s", cur_node.debug_str());
         let box_gen_result = {
-            let grandparent_gen_ref = match grandparent_generator {
-                Some(ref mut generator) => Some(generator),
-                None => None,
-            };
             let sibling_gen_ref = match prev_sibling_generator {
                 Some(ref mut generator) => Some(generator),
                 None => None,
             };
-            self.box_generator_for_node(cur_node, grandparent_gen_ref, &mut parent_generator, sibling_gen_ref)
+            self.box_generator_for_node(cur_node, &mut parent_generator, sibling_gen_ref)
         };
 
         let mut reparent = false;
@@ -330,6 +397,7 @@ impl LayoutTreeBuilder {
                     }
                 }
                 SiblingGenerator => prev_sibling_generator.take_unwrap(),
+                NewGenerator(gen) => gen,
                 _ => fail!("Unexpect BoxGenResult")
             }))
         };
@@ -340,39 +408,68 @@ impl LayoutTreeBuilder {
         this_generator.push_node(layout_ctx, cur_node, self);
         debug!("point b: %s", cur_node.debug_str());
 
-        // recurse on child nodes.
-        let prev_gen_cell = Cell::new(Normal(None));
+        if cur_node.is_element() {
+            self.generated_content.update_counters(cur_node.style());
+            self.generated_content.update_list_item_counter(cur_node);
+            self.generated_content.push_scope();
+        }
+
+        // The marker of a list item comes first, then the ::before pseudo-element, then the child
+        // nodes.
+        let marker_generator = self.construct_marker(layout_ctx, cur_node, &mut this_generator);
+        let before_generator = self.construct_pseudo_element(cur_node,
+                                                             Before,
+                                                             &mut this_generator,
+                                                             marker_generator);
+
+        // recurse on child nodes. Once a block child has split the inline flow of this node, the
+        // children after it go into the flow that holds the inline flow.
+        let is_inline = this_generator.flow.class() == InlineFlowClass;
+        let mut split = false;
+        let prev_gen_cell = Cell::new(before_generator);
         for child_node in cur_node.children() {
-            do parent_generator.with_clone |grandparent_clone| {
-                let grandparent_clone_cell = Cell::new(Some(grandparent_clone));
-                do this_generator.with_clone |parent_clone| {
-                    match prev_gen_cell.take() {
-                        Normal(prev_gen) => {
-                            let prev_generator = self.construct_recursively(layout_ctx,
-                                                                            child_node,
-                                                                            grandparent_clone_cell.take(),
-                                                                            parent_clone,
-                                                                            prev_gen);
-                            prev_gen_cell.put_back(prev_generator);
-                        }
-                        Reparent(prev_gen) => {
-                            let prev_generator = self.construct_recursively(layout_ctx,
-                                                                            child_node,
-                                                                            None,
-                                                                            grandparent_clone_cell.take().unwrap(),
-                                                                            Some(prev_gen));
-                            prev_gen_cell.put_back(prev_generator);
-                        } 
+            do this_generator.with_clone |parent_clone| {
+                let mut parent_clone = parent_clone;
+                let parent = if split {
+                    parent_clone.container_generator()
+                } else {
+                    parent_clone
+                };
+                match self.construct_recursively(layout_ctx,
+                                                 child_node,
+                                                 parent,
+                                                 prev_gen_cell.take()) {
+                    Normal(prev_gen) => prev_gen_cell.put_back(prev_gen),
+                    Reparent(prev_gen) => {
+                        split = split || is_inline;
+                        prev_gen_cell.put_back(Some(prev_gen));
                     }
                 }
             }
         }
 
+        // The ::after pseudo-element comes after the child nodes.
+        let after_generator = if split {
+            let mut container_generator = this_generator.container_generator();
+            self.construct_pseudo_element(cur_node,
+                                          After,
+                                          &mut container_generator,
+                                          prev_gen_cell.take())
+        } else {
+            self.construct_pseudo_element(cur_node, After, &mut this_generator, prev_gen_cell.take())
+        };
+
+        if cur_node.is_element() {
+            self.generated_content.pop_scope();
+        }
+
         this_generator.pop_node(layout_ctx, cur_node);
         self.simplify_children_of_flow(layout_ctx, this_generator.flow);
 
         match next_generator {
             Some(n_gen) => Normal(Some(n_gen)),
+            // The inline content after this node continues after the block that split it.
+            None if split => Reparent(after_generator.unwrap()),
             None => {
                 if reparent {
                     Reparent(this_generator)
@@ -385,40 +482,272 @@ impl LayoutTreeBuilder {
 
     
 
+    /// Creates the box for the `::before` or `::after` pseudo-element of `node`, if it generates
+    /// content, and adds it to the flow of `parent_generator` (the generator of `node`). Returns
+    /// the generator that the next sibling box should use, as `construct_recursively` does.
+    ///
+    /// TODO: Generated content is always inline-level for now.
+    fn construct_pseudo_element<'a>(&mut self,
+                                    node: AbstractNode<LayoutView>,
+                                    pseudo_element: PseudoElement,
+                                    parent_generator: &mut BoxGenerator<'a>,
+                                    sibling_generator: Option<BoxGenerator<'a>>)
+                                    -> Option<BoxGenerator<'a>> {
+        if !node.is_element() {
+            return sibling_generator
+        }
+        let text = match node.pseudo_style(pseudo_element) {
+            None => return sibling_generator,
+            Some(style) => {
+                self.generated_content.update_counters(style);
+                self.generated_content.generate_text(node, style)
+            }
+        };
+        let base = RenderBoxBase::new_for_pseudo_element(node, pseudo_element, self.next_box_id());
+        let new_box = @mut UnscannedTextRenderBox::from_text(base, text) as @mut RenderBox;
+        debug!("LayoutTreeBuilder: created box for %? of %s: %s",
+               pseudo_element, node.debug_str(), new_box.debug_str());
+        self.add_generated_box(node, new_box, parent_generator, sibling_generator)
+    }
+
+    /// Creates the marker box of `node` if it is a list item: its 'list-style-image', or else
+    /// the text of its 'list-style-type'. Returns the generator that the next sibling box should
+    /// use, as `construct_recursively` does.
+    ///
+    /// TODO: An image that fails to load should fall back to the 'list-style-type'.
+    fn construct_marker<'a>(&mut self,
+                            layout_ctx: &LayoutContext,
+                            node: AbstractNode<LayoutView>,
+                            parent_generator: &mut BoxGenerator<'a>)
+                            -> Option<BoxGenerator<'a>> {
+        if !node.is_element() {
+            return None
+        }
+        let style = match node.pseudo_style(Marker) {
+            None => return None,
+            Some(style) => style,
+        };
+        let base = RenderBoxBase::new_for_pseudo_element(node, Marker, self.next_box_id());
+        let new_box = match style.List.list_style_image {
+            Some(ref url) => {
+                let image_cache = layout_ctx.image_cache;
+                @mut ImageRenderBox::new(base, url.clone(), image_cache) as @mut RenderBox
+            }
+            None => {
+                let text = self.generated_content.generate_marker_text(style);
+                if text.is_empty() {
+                    return None
+                }
+                @mut UnscannedTextRenderBox::from_text(base, text) as @mut RenderBox
+            }
+        };
+        debug!("LayoutTreeBuilder: created marker box of %s: %s",
+               node.debug_str(), new_box.debug_str());
+        self.add_generated_box(node, new_box, parent_generator, None)
+    }
+
+    /// Adds a box generated for `node` to the flow of `parent_generator` (the generator of
+    /// `node`), or to the inline flow that follows the box of the previous sibling. Returns the
+    /// generator that the next sibling box should use.
+    fn add_generated_box<'a>(&mut self,
+                             node: AbstractNode<LayoutView>,
+                             new_box: @mut RenderBox,
+                             parent_generator: &mut BoxGenerator<'a>,
+                             sibling_generator: Option<BoxGenerator<'a>>)
+                             -> Option<BoxGenerator<'a>> {
+        match parent_generator.flow.class() {
+            InlineFlowClass => {
+                parent_generator.flow.as_inline().boxes.push(new_box);
+                sibling_generator
+            }
+            AbsoluteFlowClass | BlockFlowClass | FloatFlowClass | InlineBlockFlowClass |
+            TableCellFlowClass => {
+                // Continue the inline flow of the previous sibling, as inline nodes do.
+                let sibling_is_inline = match sibling_generator {
+                    Some(ref mut generator) => {
+                        match generator.flow.class() {
+                            InlineFlowClass => true,
+                            _ => false,
+                        }
+                    }
+                    None => false,
+                };
+                let mut generator = if sibling_is_inline {
+                    sibling_generator.unwrap()
+                } else {
+                    self.create_child_generator(node, parent_generator, InlineFlowType)
+                };
+                generator.flow.as_inline().boxes.push(new_box);
+                Some(generator)
+            }
+            _ => {
+                warn!("generated content not implemented for flow f%d",
+                      flow::base(parent_generator.flow).id);
+                sibling_generator
+            }
+        }
+    }
+
     pub fn box_generator_for_node<'a>(
                                   &mut self,
                                   node: AbstractNode<LayoutView>,
-                                  grandparent_generator: Option<&mut BoxGenerator<'a>>,
                                   parent_generator: &mut BoxGenerator<'a>,
                                   mut sibling_generator: Option<&mut BoxGenerator<'a>>)
                                   -> BoxGenResult<'a> {
         let display = if node.is_element() {
-            match node.style().display(node.is_root()) {
-                CSSDisplayNone => return NoGenerator, // tree ends here if 'display: none'
+            match node.style().Box.display {
+                display::none => return NoGenerator, // tree ends here if 'display: none'
                 // TODO(eatkinson) these are hacks so that the code doesn't crash
                 // when unsupported display values are used. They should be deleted
                 // as they are implemented.
-                CSSDisplayListItem => CSSDisplayBlock,
-                CSSDisplayTable => CSSDisplayBlock,
-                CSSDisplayInlineTable => CSSDisplayInlineBlock,
-                CSSDisplayTableRowGroup => CSSDisplayBlock,
-                CSSDisplayTableHeaderGroup => CSSDisplayBlock,
-                CSSDisplayTableFooterGroup => CSSDisplayBlock,
-                CSSDisplayTableRow => CSSDisplayBlock,
-                CSSDisplayTableColumnGroup => return NoGenerator,
-                CSSDisplayTableColumn => return NoGenerator,
-                CSSDisplayTableCell => CSSDisplayBlock,
-                CSSDisplayTableCaption => CSSDisplayBlock,
+                display::list_item => display::block,
+                // TODO: Inline tables are laid out as block-level tables for now.
+                display::inline_table => display::table,
+                display::table_header_group | display::table_footer_group => {
+                    display::table_row_group
+                }
                 v => v
             }
         } else {
             match node.type_id() {
-                ElementNodeTypeId(_) => CSSDisplayInline,
-                TextNodeTypeId => CSSDisplayInline,
+                ElementNodeTypeId(_) => display::inline,
+                TextNodeTypeId => display::inline,
                 DoctypeNodeTypeId | CommentNodeTypeId => return NoGenerator,
             }
         };
 
+        // Absolutely positioned elements are taken out of the flow of their parent, and lay out
+        // their contents as blocks do (CSS 2.1 § 9.7). As floats do, they go into the inline flow
+        // of their previous sibling, if there is one, so that the inline content around them is
+        // not split.
+        //
+        // TODO: Absolutely positioned children of table parts.
+        let parent_class = parent_generator.flow.class();
+        if LayoutTreeBuilder::is_absolutely_positioned(node) &&
+                (parent_class == InlineFlowClass || parent_generator.flow.is_block_like()) {
+            let sibling_is_inline = match sibling_generator {
+                Some(ref mut generator) => generator.flow.class() == InlineFlowClass,
+                None => false,
+            };
+            if parent_class == InlineFlowClass || !sibling_is_inline {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                AbsoluteFlowType))
+            }
+            let absolute_generator = self.create_child_generator(node,
+                                                                 sibling_generator.unwrap(),
+                                                                 AbsoluteFlowType);
+            return Mixed(absolute_generator, ~SiblingGenerator)
+        }
+
+        // Table cells, inline-blocks and absolutely positioned elements lay out their contents as
+        // blocks do.
+        let parent_class = match parent_class {
+            AbsoluteFlowClass | InlineBlockFlowClass | TableCellFlowClass => BlockFlowClass,
+            class => class,
+        };
+
+        // Table parts go into the flows of their parent table parts. Anonymous flows stand in for
+        // the parts that are missing (CSS 2.1 § 17.2.1).
+        match parent_class {
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass |
+            TableColGroupFlowClass if LayoutTreeBuilder::is_whitespace_text(node) => {
+                return NoGenerator
+            }
+            _ => (),
+        }
+        match (display, parent_class) {
+            (display::table_row_group, TableFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                TableRowGroupFlowType))
+            }
+            (display::table_column_group, TableFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                TableColGroupFlowType))
+            }
+            (display::table_caption, TableFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                BlockFlowType))
+            }
+            (display::table_column, TableFlowClass) => {
+                return self.wrap_in_anonymous_table_flow(node,
+                                                         parent_generator,
+                                                         sibling_generator,
+                                                         TableColGroupFlowType)
+            }
+            (_, TableFlowClass) => {
+                return self.wrap_in_anonymous_table_flow(node,
+                                                         parent_generator,
+                                                         sibling_generator,
+                                                         TableRowGroupFlowType)
+            }
+
+            (display::table_row, TableRowGroupFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                TableRowFlowType))
+            }
+            (display::table_cell, TableRowFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                TableCellFlowType))
+            }
+            (display::table_column, TableRowGroupFlowClass) |
+            (display::table_column, TableRowFlowClass) => return NoGenerator,
+            (_, TableRowGroupFlowClass) => {
+                return self.wrap_in_anonymous_table_flow(node,
+                                                         parent_generator,
+                                                         sibling_generator,
+                                                         TableRowFlowType)
+            }
+            (_, TableRowFlowClass) => {
+                return self.wrap_in_anonymous_table_flow(node,
+                                                         parent_generator,
+                                                         sibling_generator,
+                                                         TableCellFlowType)
+            }
+
+            (display::table_column, TableColGroupFlowClass) => return ParentGenerator,
+            (_, TableColGroupFlowClass) => return NoGenerator,
+
+            (display::table, BlockFlowClass) |
+            (display::table, FloatFlowClass) => {
+                return NewGenerator(self.create_child_generator(node,
+                                                                parent_generator,
+                                                                TableFlowType))
+            }
+            (display::table_column, BlockFlowClass) |
+            (display::table_column, FloatFlowClass) => return NoGenerator,
+            (display::table_row_group, BlockFlowClass) |
+            (display::table_row_group, FloatFlowClass) |
+            (display::table_row, BlockFlowClass) |
+            (display::table_row, FloatFlowClass) |
+            (display::table_cell, BlockFlowClass) |
+            (display::table_cell, FloatFlowClass) |
+            (display::table_column_group, BlockFlowClass) |
+            (display::table_column_group, FloatFlowClass) |
+            (display::table_caption, BlockFlowClass) |
+            (display::table_caption, FloatFlowClass) => {
+                return self.wrap_in_anonymous_table_flow(node,
+                                                         parent_generator,
+                                                         sibling_generator,
+                                                         TableFlowType)
+            }
+
+            // Tables that are children of inlines split their parent flows, as blocks do.
+            (display::table, InlineFlowClass) => {
+                let mut container_gen = parent_generator.container_generator();
+                let table_gen = self.create_child_generator(node,
+                                                            &mut container_gen,
+                                                            TableFlowType);
+                return ReparentingGenerator(table_gen);
+            }
+            _ => (),
+        }
+
         // FIXME(pcwalton): Unsafe, blah blah. Should fix this.
         let mut sibling_flow: Option<&mut FlowContext> = sibling_generator.map_mut(|gen| {
             unsafe {
@@ -429,10 +758,10 @@ impl LayoutTreeBuilder {
         // TODO(eatkinson): use the value of the float property to
         // determine whether to float left or right.
         let is_float = if (node.is_element()) {
-            match node.style().float() {
-                CSSFloatNone => None,
-                CSSFloatLeft => Some(FloatLeft),
-                CSSFloatRight => Some(FloatRight)
+            match node.style().Box.float {
+                float::none => None,
+                float::left => Some(FloatLeft),
+                float::right => Some(FloatRight)
             }
         } else {
             None
@@ -443,10 +772,10 @@ impl LayoutTreeBuilder {
             Some(ref mut flow) => Some(flow.class()),
         };
         
-        let new_generator = match (display, parent_generator.flow.class(), sibling_flow_class) {
+        let new_generator = match (display, parent_class, sibling_flow_class) {
             // Floats
-            (CSSDisplayBlock, BlockFlowClass, _) |
-            (CSSDisplayBlock, FloatFlowClass, _) if is_float.is_some() => {
+            (display::block, BlockFlowClass, _) |
+            (display::block, FloatFlowClass, _) if is_float.is_some() => {
                 self.create_child_generator(node,
                                             parent_generator,
                                             FloatFlowType(is_float.unwrap()))
@@ -454,7 +783,7 @@ impl LayoutTreeBuilder {
             // If we're placing a float after an inline, append the float to the inline flow,
             // then continue building from the inline flow in case there are more inlines
             // afterward.
-            (CSSDisplayBlock, _, Some(InlineFlowClass)) if is_float.is_some() => {
+            (display::block, _, Some(InlineFlowClass)) if is_float.is_some() => {
                 let float_type = FloatFlowType(is_float.unwrap());
                 let float_generator = self.create_child_generator(node, 
                                                                   sibling_generator.unwrap(), 
@@ -464,13 +793,13 @@ impl LayoutTreeBuilder {
             // This is a catch-all case for when:
             // a) sibling_flow is None
             // b) sibling_flow is a BlockFlow
-            (CSSDisplayBlock, InlineFlowClass, _) if is_float.is_some() => {
+            (display::block, InlineFlowClass, _) if is_float.is_some() => {
                 self.create_child_generator(node,
                                             parent_generator,
                                             FloatFlowType(is_float.unwrap()))
             }
 
-            (CSSDisplayBlock, BlockFlowClass, _) => {
+            (display::block, BlockFlowClass, _) => {
                 match (parent_generator.flow.as_block().is_root, node.parent_node().is_some()) {
                     // If this is the root node, then use the root flow's
                     // context. Otherwise, make a child block context.
@@ -484,56 +813,56 @@ impl LayoutTreeBuilder {
                 }
             }
 
-            (CSSDisplayBlock, FloatFlowClass, _) => {
+            (display::block, FloatFlowClass, _) => {
                 self.create_child_generator(node, parent_generator, BlockFlowType)
             }
 
             // Inlines that are children of inlines are part of the same flow
-            (CSSDisplayInline, InlineFlowClass, _) => return ParentGenerator,
-            (CSSDisplayInlineBlock, InlineFlowClass, _) => return ParentGenerator,
+            (display::inline, InlineFlowClass, _) => return ParentGenerator,
 
-            // Inlines that are children of blocks create new flows if their
-            // previous sibling was a block.
-            (CSSDisplayInline, BlockFlowClass, Some(BlockFlowClass)) |
-            (CSSDisplayInlineBlock, BlockFlowClass, Some(BlockFlowClass)) => {
-                self.create_child_generator(node, parent_generator, InlineFlowType)
+            // Inline-blocks lay out their contents in a flow of their own, which sits in an inline
+            // flow as a single box. As inlines do, they use the inline flow of their parent or of
+            // their previous sibling if there is one, and start one otherwise.
+            (display::inline_block, InlineFlowClass, _) => {
+                self.create_inline_block_generator(node, parent_generator)
+            }
+            (display::inline_block, _, Some(InlineFlowClass)) => {
+                let inline_block_generator =
+                    self.create_inline_block_generator(node, sibling_generator.unwrap());
+                return Mixed(inline_block_generator, ~SiblingGenerator)
+            }
+            (display::inline_block, _, _) => {
+                let mut inline_generator = self.create_child_generator(node,
+                                                                       parent_generator,
+                                                                       InlineFlowType);
+                let inline_block_generator =
+                    self.create_inline_block_generator(node, &mut inline_generator);
+                return Mixed(inline_block_generator, ~NewGenerator(inline_generator))
             }
 
             // The first two cases should only be hit when a FloatFlow
             // is the first child of a BlockFlow. Other times, we will
-            (CSSDisplayInline, _, Some(FloatFlowClass)) |
-            (CSSDisplayInlineBlock, _, Some(FloatFlowClass)) |
-            (CSSDisplayInline, FloatFlowClass, _) |
-            (CSSDisplayInlineBlock, FloatFlowClass, _) => {
+            (display::inline, _, Some(FloatFlowClass)) |
+            (display::inline, FloatFlowClass, _) => {
                 self.create_child_generator(node, parent_generator, InlineFlowType)
             }
 
-            // Inlines whose previous sibling was not a block try to use their
-            // sibling's flow context.
-            (CSSDisplayInline, BlockFlowClass, _) |
-            (CSSDisplayInlineBlock, BlockFlowClass, _) => {
-                return match sibling_generator {
-                    None => NewGenerator(self.create_child_generator(node, 
-                                                                     parent_generator, 
-                                                                     InlineFlowType)),
-                    Some(*) => SiblingGenerator
-                }
+            // Inlines whose previous sibling was an inline use their sibling's flow context.
+            // Otherwise they create a new flow.
+            (display::inline, BlockFlowClass, Some(InlineFlowClass)) => return SiblingGenerator,
+            (display::inline, BlockFlowClass, _) => {
+                self.create_child_generator(node, parent_generator, InlineFlowType)
             }
 
             // blocks that are children of inlines need to split their parent
-            // flows.
-            (CSSDisplayBlock, InlineFlowClass, _) => {
-                match grandparent_generator {
-                    None => fail!("expected to have a grandparent block flow"),
-                    Some(grandparent_gen) => {
-                        assert!(grandparent_gen.flow.is_block_like());
-
-                        let block_gen = self.create_child_generator(node,
-                                                                    grandparent_gen,
-                                                                    BlockFlowType);
-                        return ReparentingGenerator(block_gen);
-                    }
-                }
+            // flows. The inline flow has no parent pointer, so its generator keeps the flow that
+            // holds it. (CSS 2.1 § 9.2.1.1)
+            (display::block, InlineFlowClass, _) => {
+                let mut container_gen = parent_generator.container_generator();
+                let block_gen = self.create_child_generator(node,
+                                                            &mut container_gen,
+                                                            BlockFlowType);
+                return ReparentingGenerator(block_gen);
             }
 
             _ => return ParentGenerator
@@ -542,6 +871,114 @@ impl LayoutTreeBuilder {
         NewGenerator(new_generator)
     }
 
+    /// Creates the flow of the inline-block `node` as a child of the inline flow of
+    /// `inline_generator`, along with the box that stands for it in the lines of that flow.
+    fn create_inline_block_generator<'a>(&mut self,
+                                         node: AbstractNode<LayoutView>,
+                                         inline_generator: &mut BoxGenerator<'a>)
+                                         -> BoxGenerator<'a> {
+        let base = RenderBoxBase::new(node, self.next_box_id());
+        let placeholder = @mut InlineBlockRenderBox::new(base) as @mut RenderBox;
+        inline_generator.flow.as_inline().boxes.push(placeholder);
+
+        let mut generator = self.create_child_generator(node,
+                                                        inline_generator,
+                                                        InlineBlockFlowType);
+        generator.flow.as_inline_block().placeholder = Some(placeholder);
+        generator
+    }
+
+    /// Returns true if `node` is an absolutely or fixed positioned element.
+    fn is_absolutely_positioned(node: AbstractNode<LayoutView>) -> bool {
+        node.is_element() && match node.style().Box.position {
+            position::absolute | position::fixed => true,
+            position::static_ | position::relative => false,
+        }
+    }
+
+    /// Returns true if `node` is a text node with nothing but whitespace in it. Such nodes are
+    /// dropped between table parts (CSS 2.1 § 17.2.1).
+    fn is_whitespace_text(node: AbstractNode<LayoutView>) -> bool {
+        node.is_text() && do node.with_imm_text |text_node| {
+            text_node.element.data.is_whitespace()
+        }
+    }
+
+    /// Returns true if `flow` is an anonymous table part, with no render box of its own.
+    fn is_anonymous_table_flow(flow: &mut FlowContext) -> bool {
+        match flow.class() {
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass | TableCellFlowClass => {
+                flow.as_block().box.is_none()
+            }
+            TableColGroupFlowClass => flow.as_table_colgroup().box.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Creates the generator for `node` inside an anonymous table part of type `wrapper_type`,
+    /// because the flow of `parent_generator` cannot hold it directly. Consecutive such nodes
+    /// share the anonymous flow: if the previous sibling generator is one, it is reused.
+    fn wrap_in_anonymous_table_flow<'a>(&mut self,
+                                        node: AbstractNode<LayoutView>,
+                                        parent_generator: &mut BoxGenerator<'a>,
+                                        sibling_generator: Option<&mut BoxGenerator<'a>>,
+                                        wrapper_type: FlowType)
+                                        -> BoxGenResult<'a> {
+        let wrapper_class = match wrapper_type {
+            TableFlowType => TableFlowClass,
+            TableRowGroupFlowType => TableRowGroupFlowClass,
+            TableRowFlowType => TableRowFlowClass,
+            TableCellFlowType => TableCellFlowClass,
+            TableColGroupFlowType => TableColGroupFlowClass,
+            _ => fail!("not a table flow type"),
+        };
+
+        let reuse_sibling = match sibling_generator {
+            Some(ref mut sibling_gen) => {
+                sibling_gen.flow.class() == wrapper_class &&
+                    LayoutTreeBuilder::is_anonymous_table_flow(sibling_gen.flow)
+            }
+            None => false,
+        };
+        let mut wrapper = if reuse_sibling {
+            do sibling_generator.unwrap().with_clone |clone| {
+                clone
+            }
+        } else {
+            self.create_child_generator(node, parent_generator, wrapper_type)
+        };
+
+        // FIXME(pcwalton): Unsafe, as in `box_generator_for_node`.
+        let mut last_child_generator = match flow::last_child(wrapper.flow) {
+            None => None,
+            Some(last_child) => {
+                let last_child: &mut FlowContext = *last_child;
+                Some(BoxGenerator::new_child(unsafe {
+                    cast::transmute_copy(&last_child)
+                }, &mut wrapper))
+            }
+        };
+
+        let result = {
+            let last_child_gen_ref = match last_child_generator {
+                Some(ref mut generator) => Some(generator),
+                None => None,
+            };
+            self.box_generator_for_node(node, &mut wrapper, last_child_gen_ref)
+        };
+        let this_generator = match result {
+            NoGenerator => return NoGenerator,
+            ParentGenerator => {
+                do wrapper.with_clone |clone| {
+                    clone
+                }
+            }
+            SiblingGenerator => last_child_generator.unwrap(),
+            NewGenerator(gen) | ReparentingGenerator(gen) | Mixed(gen, _) => gen,
+        };
+        Mixed(this_generator, ~NewGenerator(wrapper))
+    }
+
     pub fn create_child_generator<'a>(
                                   &mut self,
                                   node: AbstractNode<LayoutView>,
@@ -550,37 +987,28 @@ impl LayoutTreeBuilder {
                                   -> BoxGenerator<'a> {
         let new_flow = self.make_flow(ty, node);
         parent_generator.flow.add_new_child(new_flow);
-        let flow_ref = flow::last_child(parent_generator.flow).unwrap();
-        BoxGenerator::new(*flow_ref)
+        // FIXME: Unsafe, as in `box_generator_for_node`. The generator of the parent is needed too.
+        let new_flow: &'a mut FlowContext = unsafe {
+            let last_child: &mut FlowContext = *flow::last_child(parent_generator.flow).unwrap();
+            cast::transmute_copy(&last_child)
+        };
+        BoxGenerator::new_child(new_flow, parent_generator)
     }
 
     /// Fix up any irregularities such as:
     ///
-    /// * split inlines (CSS 2.1 Section 9.2.1.1)
     /// * elide non-preformatted whitespace-only text boxes and their flows (CSS 2.1 Section
     ///   9.2.2.1).
     ///
-    /// The latter can only be done immediately adjacent to, or at the beginning or end of a block
+    /// This can only be done immediately adjacent to, or at the beginning or end of a block
     /// flow. Otherwise, the whitespace might affect whitespace collapsing with adjacent text.
+    ///
+    /// Inlines that are split by blocks (CSS 2.1 Section 9.2.1.1) need no fixup: the blocks never
+    /// go into inline flows, since `box_generator_for_node` puts them after the inline flow.
     pub fn simplify_children_of_flow(&self, ctx: &LayoutContext, parent_flow: &mut FlowContext) {
         match parent_flow.class() {
-            InlineFlowClass => {
-                let mut found_child_inline = false;
-                let mut found_child_block = false;
-
-                for child_ctx in flow::child_iter(parent_flow) {
-                    match child_ctx.class() {
-                        InlineFlowClass | InlineBlockFlowClass => found_child_inline = true,
-                        BlockFlowClass => found_child_block = true,
-                        _ => {}
-                    }
-                }
-
-                if found_child_block && found_child_inline {
-                    self.fixup_split_inline(parent_flow)
-                }
-            }
-            BlockFlowClass | FloatFlowClass => {
+            AbsoluteFlowClass | BlockFlowClass | FloatFlowClass | InlineBlockFlowClass |
+            TableCellFlowClass => {
                 // check first/last child for whitespace-ness
                 let mut do_remove = false;
                 let p_id = flow::base(parent_flow).id;
@@ -641,21 +1069,29 @@ impl LayoutTreeBuilder {
                 // flows, but there's not a quick way to check at the moment.
                 for child_flow in flow::child_iter(parent_flow) {
                     match child_flow.class() {
-                        InlineFlowClass | InlineBlockFlowClass => {
+                        InlineFlowClass => {
                             let mut scanner = TextRunScanner::new();
                             scanner.scan_for_runs(ctx, *child_flow);
                         }
-                        _ => {}
+                        _ => self.simplify_anonymous_table_flow(ctx, *child_flow),
                     }
                 }
             }
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass => {
+                for child_flow in flow::child_iter(parent_flow) {
+                    self.simplify_anonymous_table_flow(ctx, *child_flow);
+                }
+            }
             _ => {}
         }
     }
 
-    pub fn fixup_split_inline(&self, _: &mut FlowContext) {
-        // TODO: finish me. 
-        fail!(~"TODO: handle case where an inline is split by a block")
+    /// Anonymous table parts are never popped as nodes are, so their parent simplifies them
+    /// instead.
+    fn simplify_anonymous_table_flow(&self, ctx: &LayoutContext, flow: &mut FlowContext) {
+        if LayoutTreeBuilder::is_anonymous_table_flow(flow) {
+            self.simplify_children_of_flow(ctx, flow)
+        }
     }
 
     /// Entry point for box creation. Should only be called on the root DOM element.
@@ -667,11 +1103,45 @@ impl LayoutTreeBuilder {
         let mut new_flow = self.make_flow(RootFlowType, root);
         {
             let new_generator = BoxGenerator::new(new_flow);
-            self.construct_recursively(layout_ctx, root, None, new_generator, None);
+            self.construct_recursively(layout_ctx, root, new_generator, None);
         }
         return Ok(new_flow)
     }
 
+    /// Builds the block flow of `element`, which is a block in the flow of its parent, and the
+    /// flows inside it. Part of a flow tree is built again this way when the element changes.
+    /// Returns `None` if the element generated no flow after all.
+    pub fn construct_block_subtree(&mut self,
+                                   layout_ctx: &LayoutContext,
+                                   element: AbstractNode<LayoutView>)
+                                   -> Option<~FlowContext:> {
+        debug!("Constructing flow subtree for %s", element.debug_str());
+
+        // The flow of the element is built as the child of a stand-in for the flow of its parent.
+        let parent = element.parent_node().expect("the root has no block flow of its own");
+        let mut container = self.make_flow(BlockFlowType, parent);
+        {
+            let container_generator = BoxGenerator::new(container);
+            self.construct_recursively(layout_ctx, element, container_generator, None);
+        }
+        let children = &mut flow::mut_base(container).children;
+        if children.len() != 1 {
+            return None
+        }
+        let new_flow = children.pop_front().unwrap();
+        if new_flow.class() == BlockFlowClass {
+            Some(new_flow)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the text of the boxes built so far depends on the elements before them in
+    /// the document, because of counters or quotes.
+    pub fn depends_on_document_order(&self) -> bool {
+        self.generated_content.depends_on_document_order()
+    }
+
     /// Creates a flow of the given type for the supplied node.
     pub fn make_flow(&mut self, flow_type: FlowType, node: AbstractNode<LayoutView>)
                      -> ~FlowContext: {
@@ -684,6 +1154,10 @@ impl LayoutTreeBuilder {
             InlineFlowType          => ~InlineFlow::new(info) as ~FlowContext:,
             RootFlowType            => ~BlockFlow::new_root(info) as ~FlowContext:,
             TableFlowType           => ~TableFlow::new(info) as ~FlowContext:,
+            TableRowGroupFlowType   => ~TableRowGroupFlow::new(info) as ~FlowContext:,
+            TableRowFlowType        => ~TableRowFlow::new(info) as ~FlowContext:,
+            TableCellFlowType       => ~TableCellFlow::new(info) as ~FlowContext:,
+            TableColGroupFlowType   => ~TableColGroupFlow::new(info) as ~FlowContext:,
         };
         debug!("LayoutTreeBuilder: created flow: %s", result.debug_str());
         result
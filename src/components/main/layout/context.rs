@@ -13,5 +13,6 @@ use servo_net::local_image_cache::LocalImageCache;
 pub struct LayoutContext {
     font_ctx: @mut FontContext,
     image_cache: @mut LocalImageCache,
+    /// The part of the page that the window shows. Fixed position content is laid out against it.
     screen_size: Rect<Au>
 }
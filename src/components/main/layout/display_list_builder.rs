@@ -10,7 +10,7 @@ use std::cast::transmute;
 use script::dom::node::AbstractNode;
 
 use gfx;
-use newcss;
+use style;
 
 /// Display list data is usually an AbstractNode with view () to indicate
 /// that nodes in this view shoud not really be touched. The idea is to
@@ -61,7 +61,7 @@ pub trait ToGfxColor {
     fn to_gfx_color(&self) -> gfx::color::Color;
 }
 
-impl ToGfxColor for newcss::color::Color {
+impl ToGfxColor for style::computed_values::RGBA {
     fn to_gfx_color(&self) -> gfx::color::Color {
         gfx::color::rgba(self.red, self.green, self.blue, self.alpha)
     }
@@ -2,12 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use css::node_style::StyledNode;
 use layout::box::{RenderBox, RenderBoxUtils};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
-use layout::flow::{FloatFlowClass, FlowClass, FlowContext, FlowData};
+use layout::flow::{AbsoluteFlowClass, FloatFlowClass, FlowClass, FlowContext, FlowData};
 use layout::flow;
-use layout::model::{MaybeAuto};
+use layout::model::{CollapsibleMargins, MaybeAuto, SizeConstraint, box_sizing_offset};
 use layout::float_context::{FloatContext, PlacementInfo, FloatType};
 
 use std::cell::Cell;
@@ -70,21 +71,15 @@ impl FloatFlow {
         if self.base.node.is_iframe_element() {
             error!("float iframe size messaging not implemented yet");
         }
+        let offset = self.base.abs_position + self.rel_pos;
         let abs_rect = Rect(self.base.abs_position, self.base.position.size);
-        if !abs_rect.intersects(dirty) {
-            return true;
+        if abs_rect.intersects(dirty) {
+            // add box that starts block context
+            self.box.map(|&box| {
+                box.build_display_list(builder, dirty, &offset, &self.base.clip_rect, list)
+            });
         }
 
-
-        let offset = self.base.abs_position + self.rel_pos;
-        // add box that starts block context
-        self.box.map(|&box| {
-            box.build_display_list(builder, dirty, &offset, list)
-        });
-
-
-        // TODO: handle any out-of-flow elements
-
         // go deeper into the flow tree
         for child in self.base.child_iter() {
             let child_base = flow::mut_base(*child);
@@ -112,6 +107,10 @@ impl FlowContext for FloatFlow {
         for child_ctx in self.base.child_iter() {
             //assert!(child_ctx.starts_block_flow() || child_ctx.starts_inline_flow());
 
+            if child_ctx.class() == AbsoluteFlowClass {
+                loop
+            }
+
             let child_base = flow::mut_base(*child_ctx);
             min_width = geometry::max(min_width, child_base.min_width);
             pref_width = geometry::max(pref_width, child_base.pref_width);
@@ -125,8 +124,8 @@ impl FlowContext for FloatFlow {
         self.box.map(|&box| {
             {
                 let base = box.mut_base();
-                let style = base.style();
-                base.model.compute_borders(style);
+                let node = base.node;
+                base.model.compute_borders(node.style());
             }
 
             let (this_minimum_width, this_preferred_width) = box.minimum_and_preferred_widths();
@@ -152,25 +151,22 @@ impl FlowContext for FloatFlow {
         for &box in self.box.iter() {
             let base = box.mut_base();
             let base = &mut *base;
-            let style = base.style();
+            let node = base.node;
+            let style = node.style();
             let model = &mut base.model;
 
             // Can compute padding here since we know containing block width.
             model.compute_padding(style, remaining_width);
 
             // Margins for floats are 0 if auto.
-            let margin_top = MaybeAuto::from_margin(style.margin_top(),
-                                                    remaining_width,
-                                                    style.font_size()).specified_or_zero();
-            let margin_bottom = MaybeAuto::from_margin(style.margin_bottom(),
-                                                       remaining_width,
-                                                       style.font_size()).specified_or_zero();
-            let margin_left = MaybeAuto::from_margin(style.margin_left(),
-                                                     remaining_width,
-                                                     style.font_size()).specified_or_zero();
-            let margin_right = MaybeAuto::from_margin(style.margin_right(),
-                                                      remaining_width,
-                                                      style.font_size()).specified_or_zero();
+            let margin_top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                   remaining_width).specified_or_zero();
+            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                      remaining_width).specified_or_zero();
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    remaining_width).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     remaining_width).specified_or_zero();
 
 
 
@@ -179,9 +175,11 @@ impl FlowContext for FloatFlow {
                                                             remaining_width));
 
 
-            let width = MaybeAuto::from_width(style.width(), 
-                                              remaining_width,
-                                              style.font_size()).specified_or_default(shrink_to_fit);
+            // Margins are 0 right now so model.noncontent_width() is just borders + padding.
+            let offset = box_sizing_offset(style, model.noncontent_width());
+            let width = MaybeAuto::from_style(style.Box.width, remaining_width).minus(offset);
+            let width = width.specified_or_default(shrink_to_fit);
+            let width = SizeConstraint::widths(style, remaining_width, offset).clamp(width);
             debug!("assign_widths_float -- width: %?", width);
 
             model.margin.top = margin_top;
@@ -305,29 +303,24 @@ impl FlowContext for FloatFlow {
         });
 
         
-        //TODO(eatkinson): compute heights properly using the 'height' property.
         for &box in self.box.iter() {
             let base = box.mut_base();
-            let height_prop = MaybeAuto::from_height(base.style().height(),
-                                                     Au(0),
-                                                     base.style().font_size()).specified_or_zero();
-
-            height = geometry::max(height, height_prop) + noncontent_height;
+            height = {
+                let style = base.style();
+                let offset = box_sizing_offset(style, noncontent_height);
+                let height_prop = MaybeAuto::from_style(style.Box.height, Au(0)).minus(offset);
+                let height = height_prop.specified_or_default(height);
+                SizeConstraint::heights(style, offset).clamp(height) + noncontent_height
+            };
             debug!("assign_height_float -- height: %?", height);
 
             base.position.size.height = height;
         }
     }
 
-    fn collapse_margins(&mut self,
-                        _: bool,
-                        _: &mut bool,
-                        _: &mut Au,
-                        _: &mut Au,
-                        collapsing: &mut Au,
-                        _: &mut Au) {
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
         // Margins between a floated box and any other box do not collapse.
-        *collapsing = Au::new(0);
+        CollapsibleMargins::out_of_flow()
     }
 }
 
@@ -24,15 +24,34 @@
 ///   boxes/flows that are subject to inline layout and line breaking and structs to represent
 ///   line breaks and mapping to CSS boxes, for the purpose of handling `getClientRects()` and
 ///   similar methods.
+///
+/// * `InlineBlockFlow`: A flow for an inline-block element. It lays out its contents as a block
+///   does, and is placed in the lines of its parent inline flow as a single box.
+///
+/// * `AbsoluteFlow`: A flow for an absolutely or fixed positioned element. It is laid out like a
+///   block, but it takes no space in its parent and is placed against its containing block once
+///   the positions of all flows are known.
+///
+/// * `TableFlow`, `TableRowGroupFlow`, `TableRowFlow` and `TableCellFlow`: The flows of a table.
+///   The table computes the widths of the columns, which its rows use to place their cells. Each
+///   of them wraps a `BlockFlow` that holds its render box.
 
+use layout::absolute::AbsoluteFlow;
 use layout::block::BlockFlow;
 use layout::float::FloatFlow;
 use layout::box::{RenderBox, RenderBoxUtils};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::inline::InlineFlow;
+use layout::inline_block::InlineBlockFlow;
+use layout::model::CollapsibleMargins;
+use layout::table::TableFlow;
+use layout::table_cell::TableCellFlow;
+use layout::table_colgroup::TableColGroupFlow;
+use layout::table_row::TableRowFlow;
+use layout::table_rowgroup::TableRowGroupFlow;
 use layout::float_context::{FloatContext, Invalid, FloatType};
-use layout::incremental::RestyleDamage;
+use layout::incremental::{Reflow, RestyleDamage};
 use css::node_style::StyledNode;
 use extra::dlist::{DList,MutDListIterator};
 use extra::container::Deque;
@@ -42,9 +61,11 @@ use std::cell::Cell;
 use std::io::stderr;
 use geom::point::Point2D;
 use geom::rect::Rect;
+use geom::size::Size2D;
 use gfx::display_list::DisplayList;
 use gfx::geometry::Au;
 use script::dom::node::{AbstractNode, LayoutView};
+use style::computed_values::{overflow, position, z_index};
 
 /// Virtual methods that make up a float context.
 ///
@@ -58,7 +79,9 @@ pub trait FlowContext {
     /// Returns the class of flow that this is.
     fn class(&self) -> FlowClass;
 
-    /// If this is a block flow, returns the underlying object. Fails otherwise.
+    /// If this is a block flow, returns the underlying object. If this is an absolute,
+    /// inline-block, table, row group, row or cell flow, returns the block flow that holds its
+    /// render box. Fails otherwise.
     fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
         fail!("called as_block() on a non-block flow")
     }
@@ -79,6 +102,41 @@ pub trait FlowContext {
         fail!("called as_float() on a non-float flow")
     }
 
+    /// If this is an inline-block flow, returns the underlying object. Fails otherwise.
+    fn as_inline_block<'a>(&'a mut self) -> &'a mut InlineBlockFlow {
+        fail!("called as_inline_block() on a non-inline-block flow")
+    }
+
+    /// If this is an absolute flow, returns the underlying object. Fails otherwise.
+    fn as_absolute<'a>(&'a mut self) -> &'a mut AbsoluteFlow {
+        fail!("called as_absolute() on a non-absolute flow")
+    }
+
+    /// If this is a table flow, returns the underlying object. Fails otherwise.
+    fn as_table<'a>(&'a mut self) -> &'a mut TableFlow {
+        fail!("called as_table() on a non-table flow")
+    }
+
+    /// If this is a table row group flow, returns the underlying object. Fails otherwise.
+    fn as_table_row_group<'a>(&'a mut self) -> &'a mut TableRowGroupFlow {
+        fail!("called as_table_row_group() on a non-row-group flow")
+    }
+
+    /// If this is a table row flow, returns the underlying object. Fails otherwise.
+    fn as_table_row<'a>(&'a mut self) -> &'a mut TableRowFlow {
+        fail!("called as_table_row() on a non-row flow")
+    }
+
+    /// If this is a table cell flow, returns the underlying object. Fails otherwise.
+    fn as_table_cell<'a>(&'a mut self) -> &'a mut TableCellFlow {
+        fail!("called as_table_cell() on a non-cell flow")
+    }
+
+    /// If this is a table column group flow, returns the underlying object. Fails otherwise.
+    fn as_table_colgroup<'a>(&'a mut self) -> &'a mut TableColGroupFlow {
+        fail!("called as_table_colgroup() on a non-column-group flow")
+    }
+
     // Main methods
 
     /// Pass 1 of reflow: computes minimum and preferred widths.
@@ -101,15 +159,11 @@ pub trait FlowContext {
         fail!("assign_height_inorder not yet implemented")
     }
 
-    /// Collapses margins with the parent flow. This runs as part of assign-heights.
-    fn collapse_margins(&mut self,
-                        top_margin_collapsible: bool,
-                        first_in_flow: &mut bool,
-                        margin_top: &mut Au,
-                        top_offset: &mut Au,
-                        collapsing: &mut Au,
-                        collapsible: &mut Au) {
-        fail!("collapse_margins not yet implemented")
+    /// Returns how the margins of this flow collapse with those around it in the block
+    /// formatting context of its parent. This runs as part of assign-heights, once the height of
+    /// this flow is known.
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
+        fail!("collapsible_margins not yet implemented")
     }
 
     /// Returns a debugging string describing this flow.
@@ -149,13 +203,15 @@ pub fn child_iter<'a>(flow: &'a mut FlowContext) -> MutDListIterator<'a,~FlowCon
 pub trait ImmutableFlowUtils {
     // Convenience functions
 
-    /// Returns true if this flow is a block or a float flow.
+    /// Returns true if this flow is a block, a float, an absolute, an inline-block or a table cell
+    /// flow.
     fn is_block_like(self) -> bool;
 
     /// Returns true if this flow has no children.
     fn is_leaf(self) -> bool;
 
-    /// Returns true if this flow is a block flow, an inline flow, or a float flow.
+    /// Returns true if this flow is a block flow, an inline flow, a float flow, an absolute flow,
+    /// or a table flow.
     fn starts_block_flow(self) -> bool;
 
     /// Returns true if this flow is an inline flow.
@@ -191,6 +247,43 @@ pub trait MutableFlowUtils {
     /// Removes the last child of this flow and destroys it.
     fn remove_last(self);
 
+    /// Replaces the block flow of `element` somewhere below this flow with the flow that `new_flow`
+    /// holds, which it takes. Returns false if there is no such flow.
+    fn replace_block_flow_of(self,
+                             element: AbstractNode<LayoutView>,
+                             new_flow: &Cell<~FlowContext:>)
+                             -> bool;
+
+    /// Adds `Reflow` damage to this flow if its parent gave it a different width than in the
+    /// last layout, since its boxes have to be laid out again then.
+    fn damage_if_width_changed(self);
+
+    /// Gives the children of this flow the containing block of their absolutely positioned
+    /// descendants.
+    fn propagate_absolute_containing_block(self);
+
+    /// Gives the children of this flow the size of the block that contains them.
+    fn propagate_containing_block_size(self);
+
+    /// Gives the children of this flow the rectangle to which their display items are clipped.
+    fn propagate_clip_rect(self);
+
+    /// Returns the value of the `overflow` property of this flow, which is `visible` for flows
+    /// without a box of their own.
+    fn overflow(self) -> overflow::ComputedValue;
+
+    /// Returns the `z-index` of the stacking context that this flow establishes, or `None` if it
+    /// does not establish one. Only positioned elements do, and `z-index: auto` counts as 0.
+    fn stacking_context_z_index(self) -> Option<i32>;
+
+    /// Returns true if this flow is the flow of a fixed-position element, which stays in place
+    /// when the page scrolls.
+    fn is_fixed_position(self) -> bool;
+
+    /// Returns the padding box of the box of this flow in absolute coordinates, if it has a box.
+    /// Like `abs_position`, it is only known once the display list is built.
+    fn absolute_padding_box(self) -> Option<Rect<Au>>;
+
     /// Builds a display list for this flow and its children.
     fn build_display_list<E:ExtraDisplayListData>(
                           self,
@@ -200,6 +293,7 @@ pub trait MutableFlowUtils {
                           -> bool;
 }
 
+#[deriving(Eq)]
 pub enum FlowClass {
     AbsoluteFlowClass,
     BlockFlowClass,
@@ -207,62 +301,10 @@ pub enum FlowClass {
     InlineBlockFlowClass,
     InlineFlowClass,
     TableFlowClass,
-}
-
-// Miscellaneous flows that are not yet implemented.
-
-pub struct AbsoluteFlow {
-    base: FlowData,
-}
-
-impl AbsoluteFlow {
-    pub fn new(base: FlowData) -> AbsoluteFlow {
-        AbsoluteFlow {
-            base: base,
-        }
-    }
-}
-
-impl FlowContext for AbsoluteFlow {
-    fn class(&self) -> FlowClass {
-        AbsoluteFlowClass
-    }
-}
-
-pub struct InlineBlockFlow {
-    base: FlowData,
-}
-
-impl InlineBlockFlow {
-    pub fn new(base: FlowData) -> InlineBlockFlow {
-        InlineBlockFlow {
-            base: base,
-        }
-    }
-}
-
-impl FlowContext for InlineBlockFlow {
-    fn class(&self) -> FlowClass {
-        InlineBlockFlowClass
-    }
-}
-
-pub struct TableFlow {
-    base: FlowData,
-}
-
-impl TableFlow {
-    pub fn new(base: FlowData) -> TableFlow {
-        TableFlow {
-            base: base,
-        }
-    }
-}
-
-impl FlowContext for TableFlow {
-    fn class(&self) -> FlowClass {
-        TableFlowClass
-    }
+    TableRowGroupFlowClass,
+    TableRowFlowClass,
+    TableCellFlowClass,
+    TableColGroupFlowClass,
 }
 
 /// A top-down traversal.
@@ -322,6 +364,23 @@ pub struct FlowData {
     num_floats: uint,
     abs_position: Point2D<Au>,
     is_inorder: bool,
+
+    /// The width that the parent gave this flow in the last assign-widths traversal.
+    assigned_width: Au,
+
+    /// The rectangle outside of which the display items of this flow are not drawn, in absolute
+    /// coordinates, or `None` if no ancestor clips its overflow. Like `absolute_cb`, it is only
+    /// known once the display list is built.
+    clip_rect: Option<Rect<Au>>,
+
+    /// The containing block of the absolutely positioned descendants of this flow, in absolute
+    /// coordinates. (CSS 2.1 § 10.1) Its position is only known once the display list is built.
+    absolute_cb: Rect<Au>,
+
+    /// The size of the content box of the block that contains this flow, against which
+    /// percentages in the offsets of a relatively positioned box are resolved. (CSS 2.1 § 9.3.2)
+    /// Like `absolute_cb`, it is only known once the display list is built.
+    containing_block_size: Size2D<Au>,
 }
 
 pub struct BoxIterator {
@@ -358,7 +417,11 @@ impl FlowData {
             floats_out: Invalid,
             num_floats: 0,
             abs_position: Point2D(Au::new(0), Au::new(0)),
-            is_inorder: false
+            is_inorder: false,
+            assigned_width: Au::new(0),
+            clip_rect: None,
+            absolute_cb: Au::zero_rect(),
+            containing_block_size: Size2D(Au::new(0), Au::new(0)),
         }
     }
 
@@ -368,11 +431,14 @@ impl FlowData {
 }
 
 impl<'self> ImmutableFlowUtils for &'self FlowContext {
-    /// Returns true if this flow is a block or a float flow.
+    /// Returns true if this flow is a block, a float, an absolute, an inline-block or a table cell
+    /// flow.
     fn is_block_like(self) -> bool {
         match self.class() {
-            BlockFlowClass | FloatFlowClass => true,
-            AbsoluteFlowClass | InlineBlockFlowClass | InlineFlowClass | TableFlowClass => false,
+            AbsoluteFlowClass | BlockFlowClass | FloatFlowClass | InlineBlockFlowClass |
+            TableCellFlowClass => true,
+            InlineFlowClass | TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass |
+            TableColGroupFlowClass => false,
         }
     }
 
@@ -381,11 +447,14 @@ impl<'self> ImmutableFlowUtils for &'self FlowContext {
         base(self).children.len() == 0
     }
 
-    /// Returns true if this flow is a block flow, an inline flow, or a float flow.
+    /// Returns true if this flow is a block flow, an inline flow, a float flow, an absolute flow,
+    /// or a table flow.
     fn starts_block_flow(self) -> bool {
         match self.class() {
-            BlockFlowClass | InlineFlowClass | FloatFlowClass => true,
-            AbsoluteFlowClass | InlineBlockFlowClass | TableFlowClass => false,
+            AbsoluteFlowClass | BlockFlowClass | InlineFlowClass | FloatFlowClass |
+            TableFlowClass => true,
+            InlineBlockFlowClass | TableRowGroupFlowClass | TableRowFlowClass |
+            TableCellFlowClass | TableColGroupFlowClass => false,
         }
     }
 
@@ -394,7 +463,8 @@ impl<'self> ImmutableFlowUtils for &'self FlowContext {
         match self.class() {
             InlineFlowClass => true,
             AbsoluteFlowClass | BlockFlowClass | FloatFlowClass | InlineBlockFlowClass |
-            TableFlowClass => false,
+            TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass | TableCellFlowClass |
+            TableColGroupFlowClass => false,
         }
     }
 
@@ -468,6 +538,136 @@ impl<'self> MutableFlowUtils for &'self mut FlowContext {
         let _ = mut_base(self).children.pop_back();
     }
 
+    fn replace_block_flow_of(self,
+                             element: AbstractNode<LayoutView>,
+                             new_flow: &Cell<~FlowContext:>)
+                             -> bool {
+        for kid in child_iter(self) {
+            if kid.class() == BlockFlowClass && base(*kid).node == element {
+                *kid = new_flow.take();
+                return true
+            }
+            if kid.replace_block_flow_of(element, new_flow) {
+                return true
+            }
+        }
+        false
+    }
+
+    fn damage_if_width_changed(self) {
+        let base = mut_base(self);
+        if base.position.size.width != base.assigned_width {
+            base.assigned_width = base.position.size.width;
+            base.restyle_damage.union_in_place(RestyleDamage::none().add(Reflow));
+        }
+    }
+
+    /// Gives the children of this flow the containing block of their absolutely positioned
+    /// descendants. A positioned element establishes it with its padding box; any other flow
+    /// passes on its own. (CSS 2.1 § 10.1)
+    ///
+    /// TODO: Positioned inline elements should establish it too.
+    fn propagate_absolute_containing_block(self) {
+        let containing_block = match box_of(self) {
+            Some(box) if box.base().style().Box.position != position::static_ => {
+                self.absolute_padding_box().unwrap()
+            }
+            _ => base(self).absolute_cb,
+        };
+
+        for kid in child_iter(self) {
+            mut_base(*kid).absolute_cb = containing_block;
+        }
+    }
+
+    /// Gives the children of this flow the size of the content box of its box. A flow without a
+    /// box of its own, like the flow of a run of inline content, passes on its own containing
+    /// block.
+    fn propagate_containing_block_size(self) {
+        let containing_block_size = match box_of(self) {
+            Some(box) => {
+                let box_base = box.base();
+                let model = &box_base.model;
+                Size2D(box_base.position.size.width - model.border.left - model.border.right -
+                       model.padding.left - model.padding.right,
+                       box_base.position.size.height - model.border.top - model.border.bottom -
+                       model.padding.top - model.padding.bottom)
+            }
+            None => base(self).containing_block_size,
+        };
+
+        for kid in child_iter(self) {
+            mut_base(*kid).containing_block_size = containing_block_size;
+        }
+    }
+
+    /// Gives the children of this flow the rectangle to which their display items are clipped:
+    /// the clip rect of this flow, intersected with its padding box if it does not let its
+    /// content overflow. (CSS 2.1 § 11.1.1)
+    ///
+    /// TODO: The clip rect of an absolutely positioned element should come from its containing
+    /// block rather than from its parent.
+    fn propagate_clip_rect(self) {
+        let clip_rect = match self.absolute_padding_box() {
+            Some(padding_box) if self.overflow() != overflow::visible => {
+                match base(self).clip_rect {
+                    None => Some(padding_box),
+                    Some(clip_rect) => {
+                        match clip_rect.intersection(&padding_box) {
+                            None => Some(Au::zero_rect()),
+                            intersection => intersection,
+                        }
+                    }
+                }
+            }
+            _ => base(self).clip_rect,
+        };
+
+        for kid in child_iter(self) {
+            mut_base(*kid).clip_rect = clip_rect;
+        }
+    }
+
+    fn overflow(self) -> overflow::ComputedValue {
+        match box_of(self) {
+            Some(box) => box.base().style().Box.overflow,
+            None => overflow::visible,
+        }
+    }
+
+    fn stacking_context_z_index(self) -> Option<i32> {
+        match box_of(self) {
+            Some(box) => {
+                let style = box.base().style();
+                match (style.Box.position, style.Box.z_index) {
+                    (position::static_, _) => None,
+                    (_, z_index::Integer(value)) => Some(value),
+                    (_, z_index::Auto) => Some(0),
+                }
+            }
+            None => None,
+        }
+    }
+
+    fn is_fixed_position(self) -> bool {
+        match box_of(self) {
+            Some(box) => box.base().style().Box.position == position::fixed,
+            None => false,
+        }
+    }
+
+    fn absolute_padding_box(self) -> Option<Rect<Au>> {
+        do box_of(self).map_move |box| {
+            let box_base = box.base();
+            let border = &box_base.model.border;
+            let abs_position = base(self).abs_position;
+            Rect(Point2D(abs_position.x + box_base.position.origin.x + border.left,
+                         abs_position.y + box_base.position.origin.y + border.top),
+                 Size2D(box_base.position.size.width - border.left - border.right,
+                        box_base.position.size.height - border.top - border.bottom))
+        }
+    }
+
     fn build_display_list<E:ExtraDisplayListData>(
                           self,
                           builder: &DisplayListBuilder,
@@ -479,8 +679,23 @@ impl<'self> MutableFlowUtils for &'self mut FlowContext {
             BlockFlowClass => self.as_block().build_display_list_block(builder, dirty, list),
             InlineFlowClass => self.as_inline().build_display_list_inline(builder, dirty, list),
             FloatFlowClass => self.as_float().build_display_list_float(builder, dirty, list),
-            _ => fail!("Tried to build_display_list_recurse of flow: %?", self),
+            AbsoluteFlowClass => {
+                self.as_absolute().build_display_list_absolute(builder, dirty, list)
+            }
+            InlineBlockFlowClass | TableFlowClass | TableRowGroupFlowClass | TableRowFlowClass |
+            TableCellFlowClass => self.as_block().build_display_list_block(builder, dirty, list),
+            // Columns only affect the widths of the cells.
+            TableColGroupFlowClass => true,
         }
     }
 }
 
+/// Returns the box that a flow draws for its own element, if it has one.
+fn box_of(flow: &mut FlowContext) -> Option<@mut RenderBox> {
+    match flow.class() {
+        AbsoluteFlowClass | BlockFlowClass | InlineBlockFlowClass | TableFlowClass |
+        TableRowGroupFlowClass | TableRowFlowClass | TableCellFlowClass => flow.as_block().box,
+        FloatFlowClass => flow.as_float().box,
+        InlineFlowClass | TableColGroupFlowClass => None,
+    }
+}
@@ -0,0 +1,342 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Generated content: the text of the `::before` and `::after` pseudo-elements, with counters
+//! and quotes, and the markers of list items (CSS 2.1 § 12).
+
+use script::dom::element::{HTMLLIElementTypeId, HTMLOListElementTypeId, HTMLUListElementTypeId};
+use script::dom::node::{AbstractNode, ElementNodeTypeId, LayoutView};
+use std::ascii::StrAsciiExt;
+use std::hashmap::HashMap;
+use std::from_str::FromStr;
+use std::str;
+use style::ComputedValues;
+use style::computed_values::{content, list_style_type};
+
+/// The counter that numbers list items. (CSS Lists 3 § 4.4)
+static LIST_ITEM_COUNTER: &'static str = "list-item";
+
+/// The state that generated content depends on: the counters in scope and the nesting level of
+/// quotes. Both depend on everything that precedes an element in document order, so this is
+/// threaded through box construction.
+pub struct GeneratedContentState {
+    /// The instances of each counter in scope, innermost last.
+    priv counters: HashMap<~str, ~[i32]>,
+    /// For each element whose children are being visited, the counters reset by those children.
+    /// Their scope ends with the element. (CSS 2.1 § 12.4.1)
+    priv scopes: ~[~[~str]],
+    /// The number of open quotes that were not closed yet.
+    priv quote_depth: uint,
+    /// Whether any counter or quote was used, so that the text of some boxes depends on the
+    /// elements before them.
+    priv used: bool,
+}
+
+impl GeneratedContentState {
+    pub fn new() -> GeneratedContentState {
+        GeneratedContentState {
+            counters: HashMap::new(),
+            scopes: ~[~[]],
+            quote_depth: 0,
+            used: false,
+        }
+    }
+
+    /// Returns true if the text of some boxes depends on the elements before them in the
+    /// document, so that boxes cannot be built for part of the document alone.
+    pub fn depends_on_document_order(&self) -> bool {
+        self.used
+    }
+
+    /// Starts visiting the children of an element.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(~[])
+    }
+
+    /// Finishes visiting the children of an element. Counters they reset go out of scope.
+    pub fn pop_scope(&mut self) {
+        for name in self.scopes.pop().move_iter() {
+            let is_empty = {
+                let instances = self.counters.get_mut(&name);
+                instances.pop();
+                instances.is_empty()
+            };
+            if is_empty {
+                self.counters.remove(&name);
+            }
+        }
+    }
+
+    /// Applies the 'counter-reset' then 'counter-increment' properties of an element or
+    /// pseudo-element.
+    pub fn update_counters(&mut self, style: &ComputedValues) {
+        for &(ref name, value) in style.Counters.counter_reset.iter() {
+            self.reset_counter(name.clone(), value)
+        }
+        for &(ref name, value) in style.Counters.counter_increment.iter() {
+            // Incrementing a counter that is not in scope instantiates it. (CSS 2.1 § 12.4)
+            if !self.counters.contains_key(name) {
+                self.reset_counter(name.clone(), 0)
+            }
+            let instances = self.counters.get_mut(name);
+            let innermost = instances.len() - 1;
+            instances[innermost] += value;
+        }
+    }
+
+    fn reset_counter(&mut self, name: ~str, value: i32) {
+        self.used = true;
+        self.push_counter(name, value)
+    }
+
+    fn push_counter(&mut self, name: ~str, value: i32) {
+        self.counters.find_or_insert(name.clone(), ~[]).push(value);
+        let innermost = self.scopes.len() - 1;
+        self.scopes[innermost].push(name);
+    }
+
+    /// Applies the implicit 'list-item' counter of HTML lists: `ol` and `ul` elements reset it,
+    /// honouring the `start` and `reversed` attributes, and list items increment it or set it to
+    /// their `value` attribute. (HTML § 4.4.5–4.4.8)
+    ///
+    /// This does not make boxes depend on document order by itself; the markers that show the
+    /// value do.
+    pub fn update_list_item_counter(&mut self, element: AbstractNode<LayoutView>) {
+        match element.type_id() {
+            ElementNodeTypeId(HTMLOListElementTypeId) => {
+                let start = match get_int_attr(element, "start") {
+                    Some(start) => start,
+                    None if is_reversed(element) => count_list_items(element) as i32,
+                    None => 1,
+                };
+                self.push_counter(LIST_ITEM_COUNTER.to_owned(), start - list_step(element))
+            }
+            ElementNodeTypeId(HTMLUListElementTypeId) => {
+                self.push_counter(LIST_ITEM_COUNTER.to_owned(), 0)
+            }
+            ElementNodeTypeId(HTMLLIElementTypeId) => {
+                let key = LIST_ITEM_COUNTER.to_owned();
+                if !self.counters.contains_key(&key) {
+                    self.push_counter(key.clone(), 0)
+                }
+                let step = match element.parent_node() {
+                    Some(parent) => list_step(parent),
+                    None => 1,
+                };
+                let instances = self.counters.get_mut(&key);
+                let innermost = instances.len() - 1;
+                instances[innermost] = match get_int_attr(element, "value") {
+                    Some(value) => value,
+                    None => instances[innermost] + step,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the text of the marker of a list item whose `::marker` pseudo-element has the
+    /// given style: a bullet, or the value of the 'list-item' counter followed by a period.
+    pub fn generate_marker_text(&mut self, style: &ComputedValues) -> ~str {
+        let list_style = match style.List.list_style_type {
+            list_style_type::none => return ~"",
+            list_style_type::disc => "disc",
+            list_style_type::circle => "circle",
+            list_style_type::square => "square",
+            list_style_type::decimal => "decimal",
+            list_style_type::lower_alpha => "lower-alpha",
+            list_style_type::upper_alpha => "upper-alpha",
+            list_style_type::lower_roman => "lower-roman",
+            list_style_type::upper_roman => "upper-roman",
+        };
+        match style.List.list_style_type {
+            list_style_type::disc | list_style_type::circle | list_style_type::square => {
+                format_counter(0, list_style) + " "
+            }
+            _ => {
+                self.used = true;
+                let value = match self.counters.find(&LIST_ITEM_COUNTER.to_owned()) {
+                    Some(instances) => *instances.last(),
+                    None => 0,
+                };
+                format_counter(value, list_style) + ". "
+            }
+        }
+    }
+
+    /// Returns the text generated by the 'content' property of a pseudo-element of `element`.
+    pub fn generate_text(&mut self, element: AbstractNode<LayoutView>, style: &ComputedValues)
+                         -> ~str {
+        let items = match style.Counters.content {
+            content::Content(ref items) => items,
+            content::normal | content::none => return ~"",
+        };
+        let quotes = &style.List.quotes;
+        let mut text = ~"";
+        for item in items.iter() {
+            match *item {
+                content::StringContent(*) | content::AttrContent(*) => {}
+                _ => self.used = true,
+            }
+            match *item {
+                content::StringContent(ref value) => text.push_str(value.as_slice()),
+                content::AttrContent(ref name) => {
+                    do element.with_imm_element |element| {
+                        match element.get_attr(name.as_slice()) {
+                            Some(value) => text.push_str(value),
+                            None => (),
+                        }
+                    }
+                }
+                content::CounterContent(ref name, ref list_style) => {
+                    let value = match self.counters.find(name) {
+                        Some(instances) => *instances.last(),
+                        None => 0,
+                    };
+                    text.push_str(format_counter(value, list_style.as_slice()))
+                }
+                content::CountersContent(ref name, ref separator, ref list_style) => {
+                    match self.counters.find(name) {
+                        Some(instances) => {
+                            let values = do instances.map |&value| {
+                                format_counter(value, list_style.as_slice())
+                            };
+                            text.push_str(values.connect(separator.as_slice()))
+                        }
+                        None => text.push_str(format_counter(0, list_style.as_slice())),
+                    }
+                }
+                content::OpenQuote => {
+                    if !quotes.is_empty() {
+                        let level = self.quote_depth.min(&(quotes.len() - 1));
+                        let (ref open_quote, _) = quotes[level];
+                        text.push_str(open_quote.as_slice())
+                    }
+                    self.quote_depth += 1;
+                }
+                content::CloseQuote => {
+                    // A close-quote that would make the depth negative is ignored.
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1;
+                        if !quotes.is_empty() {
+                            let level = self.quote_depth.min(&(quotes.len() - 1));
+                            let (_, ref close_quote) = quotes[level];
+                            text.push_str(close_quote.as_slice())
+                        }
+                    }
+                }
+                content::NoOpenQuote => self.quote_depth += 1,
+                content::NoCloseQuote => {
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1;
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// Returns the value of an integer attribute of `element`, if it has a valid one.
+fn get_int_attr(element: AbstractNode<LayoutView>, name: &str) -> Option<i32> {
+    do element.with_imm_element |element| {
+        element.get_attr(name).and_then(|value| FromStr::from_str(value.trim()))
+    }
+}
+
+/// Whether `element` is an `ol` element that counts down.
+fn is_reversed(element: AbstractNode<LayoutView>) -> bool {
+    element.type_id() == ElementNodeTypeId(HTMLOListElementTypeId) &&
+        element.with_imm_element(|element| element.get_attr("reversed").is_some())
+}
+
+/// The amount by which each list item of `list` changes the 'list-item' counter.
+fn list_step(list: AbstractNode<LayoutView>) -> i32 {
+    if is_reversed(list) { -1 } else { 1 }
+}
+
+fn count_list_items(list: AbstractNode<LayoutView>) -> uint {
+    list.children().count(|child| child.type_id() == ElementNodeTypeId(HTMLLIElementTypeId))
+}
+
+/// Formats the value of a counter in the given 'list-style-type'. Styles that cannot represent
+/// the value, and unknown styles, fall back to decimal.
+pub fn format_counter(value: i32, list_style: &str) -> ~str {
+    static LATIN: &'static str = "abcdefghijklmnopqrstuvwxyz";
+    static GREEK: &'static str = "αβγδεζηθικλμνξοπρστυφχψω";
+
+    match list_style {
+        "none" => ~"",
+        "disc" => ~"•",
+        "circle" => ~"◦",
+        "square" => ~"▪",
+        "decimal-leading-zero" if value >= 0 && value < 10 => fmt!("0%d", value as int),
+        "lower-roman" if value >= 1 && value < 4000 => format_roman(value),
+        "upper-roman" if value >= 1 && value < 4000 => format_roman(value).to_ascii_upper(),
+        "lower-alpha" | "lower-latin" if value >= 1 => format_alphabetic(value, LATIN),
+        "upper-alpha" | "upper-latin" if value >= 1 => {
+            format_alphabetic(value, LATIN).to_ascii_upper()
+        }
+        "lower-greek" if value >= 1 => format_alphabetic(value, GREEK),
+        _ => value.to_str(),
+    }
+}
+
+/// a, b, ..., z, aa, ab, ...
+fn format_alphabetic(value: i32, alphabet: &str) -> ~str {
+    let alphabet: ~[char] = alphabet.iter().collect();
+    let base = alphabet.len() as i32;
+    let mut value = value;
+    let mut result = ~[];
+    while value > 0 {
+        value -= 1;
+        result.push(alphabet[(value % base) as uint]);
+        value /= base;
+    }
+    result.reverse();
+    str::from_chars(result)
+}
+
+fn format_roman(value: i32) -> ~str {
+    static NUMERALS: [(i32, &'static str), ..13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"), (50, "l"),
+        (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut value = value;
+    let mut result = ~"";
+    for &(numeral_value, numeral) in NUMERALS.iter() {
+        while value >= numeral_value {
+            result.push_str(numeral);
+            value -= numeral_value;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod format_counter_tests {
+    use super::*;
+
+    #[test]
+    fn decimal() {
+        assert_eq!(format_counter(42, "decimal"), ~"42");
+        assert_eq!(format_counter(-3, "decimal"), ~"-3");
+        assert_eq!(format_counter(7, "decimal-leading-zero"), ~"07");
+        assert_eq!(format_counter(7, "unknown-style"), ~"7");
+    }
+
+    #[test]
+    fn roman() {
+        assert_eq!(format_counter(1994, "lower-roman"), ~"mcmxciv");
+        assert_eq!(format_counter(14, "upper-roman"), ~"XIV");
+        assert_eq!(format_counter(0, "upper-roman"), ~"0");
+    }
+
+    #[test]
+    fn alphabetic() {
+        assert_eq!(format_counter(1, "lower-alpha"), ~"a");
+        assert_eq!(format_counter(26, "lower-latin"), ~"z");
+        assert_eq!(format_counter(28, "upper-alpha"), ~"AB");
+        assert_eq!(format_counter(2, "lower-greek"), ~"β");
+    }
+}
@@ -2,9 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use newcss::complete::CompleteSelectResults;
-
+use css::node_style::StyledNode;
 use script::dom::node::{AbstractNode, LayoutView};
+use servo_util::tree::TreeNodeRef;
+use std::util;
+use style::ComputedValues;
+use style::computed_values::{display, float, position};
 
 /// Individual layout actions that may be necessary after restyling.
 ///
@@ -112,42 +115,49 @@ impl RestyleDamage {
 // version of this macro might be safe anyway, but we want to avoid silent
 // breakage on modifications.
 macro_rules! add_if_not_equal(
-    ($old:expr, $new:expr, $damage:expr, [ $($effect:ident),* ], [ $($getter:ident),* ]) => ({
-        if $( ($old.$getter() != $new.$getter()) )||* {
+    ($old:ident, $new:ident, $damage:ident,
+     [ $($effect:ident),* ], [ $($style_struct:ident.$name:ident),* ]) => ({
+        if $( ($old.$style_struct.$name != $new.$style_struct.$name) )||* {
             $damage.union_in_place( restyle_damage!( $($effect),* ) );
         }
     })
 )
 
-pub fn compute_damage(node: &AbstractNode<LayoutView>,
-                      old_results: &CompleteSelectResults, new_results: &CompleteSelectResults)
-                      -> RestyleDamage {
-    let old = old_results.computed_style();
-    let new = new_results.computed_style();
+pub fn compute_damage(old: &ComputedValues, new: &ComputedValues) -> RestyleDamage {
     let mut damage = RestyleDamage::none();
 
     // This checks every CSS property, as enumerated in
-    // impl<'self> CssComputedStyle<'self>
-    // in src/support/netsurfcss/rust-netsurfcss/netsurfcss.rc.
+    // src/components/style/properties/mod.rs.mako
 
     // FIXME: We can short-circuit more of this.
 
     add_if_not_equal!(old, new, damage, [ Repaint ],
-        [ color, background_color, border_top_color, border_right_color,
-          border_bottom_color, border_left_color ]);
+        [ Color.color, Background.background_color, Background.background_image,
+          Background.background_repeat, Background.background_attachment,
+          Background.background_position,
+          Border.border_top_color, Border.border_right_color,
+          Border.border_bottom_color, Border.border_left_color,
+          Border.border_top_style, Border.border_right_style,
+          Border.border_bottom_style, Border.border_left_style,
+          Border.border_top_left_radius, Border.border_top_right_radius,
+          Border.border_bottom_right_radius, Border.border_bottom_left_radius,
+          Border.box_shadow, Box.z_index ]);
 
     add_if_not_equal!(old, new, damage, [ Repaint, BubbleWidths, Reflow ],
-        [ border_top_width, border_right_width, border_bottom_width,
-          border_left_width, margin_top, margin_right, margin_bottom, margin_left,
-          padding_top, padding_right, padding_bottom, padding_left, position,
-          width, height, float, font_family, font_size, font_style, font_weight,
-          text_align, text_decoration, line_height ]);
-
-    // Handle 'display' specially because it has this 'is_root' parameter.
-    let is_root = node.is_root();
-    if old.display(is_root) != new.display(is_root) {
-        damage.union_in_place(restyle_damage!(Repaint, BubbleWidths, Reflow));
-    }
+        [ Border.border_top_width, Border.border_right_width,
+          Border.border_bottom_width, Border.border_left_width,
+          Margin.margin_top, Margin.margin_right, Margin.margin_bottom, Margin.margin_left,
+          Padding.padding_top, Padding.padding_right, Padding.padding_bottom, Padding.padding_left,
+          Box.display, Box.position, Box.float, Box.clear, Box.width, Box.height,
+          Box.top, Box.right, Box.bottom, Box.left,
+          Box.min_width, Box.max_width, Box.min_height, Box.max_height, Box.box_sizing,
+          Box.overflow, Box.vertical_align, Box.unicode_bidi, InheritedBox.line_height,
+          InheritedBox.direction,
+          Counters.content, Counters.counter_increment, Counters.counter_reset, List.quotes,
+          List.list_style_type, List.list_style_position, List.list_style_image,
+          Font.font_family, Font.font_size, Font.font_style, Font.font_variant, Font.font_weight,
+          InheritedText.text_align, InheritedText.white_space, Text.text_decoration,
+          Table.table_layout, InheritedTable.border_collapse, InheritedTable.border_spacing ]);
 
     // FIXME: test somehow that we checked every CSS property
 
@@ -155,6 +165,92 @@ pub fn compute_damage(node: &AbstractNode<LayoutView>,
 }
 
 
+/// Returns the elements whose flows have to be built again because they or their descendants
+/// changed since the last reflow, none of which is inside another, or `None` if the whole flow
+/// tree has to be built again. Clears the dirty flags of the nodes.
+///
+/// A node whose children, text or attributes changed is dirty, and so is an element that was
+/// restyled in a way that needs reflow, which may generate a different kind of flow. The flows
+/// built again are those of the nearest block-level elements around them whose parents are
+/// block containers too, so that the new flow can take the place of the old one in the flow of
+/// the parent.
+pub fn find_dirty_subtrees(root: AbstractNode<LayoutView>) -> Option<~[AbstractNode<LayoutView>]> {
+    let mut subtrees = ~[];
+    let mut rebuild_all = false;
+    for node in root.traverse_preorder() {
+        let dirty = do node.write_layout_data |data| {
+            util::replace(&mut data.dirty, false)
+        };
+        let restyled = node.restyle_damage().has(Reflow);
+        if rebuild_all || !(dirty || restyled) {
+            loop
+        }
+
+        let start = if restyled {
+            node.parent_node()
+        } else {
+            Some(node)
+        };
+        match start.and_then(enclosing_rebuildable_element) {
+            Some(element) => subtrees.push(element),
+            None => rebuild_all = true,
+        }
+    }
+    if rebuild_all {
+        return None
+    }
+
+    // Subtrees inside other subtrees are built along with them.
+    let mut outermost_subtrees: ~[AbstractNode<LayoutView>] = ~[];
+    for &subtree in subtrees.iter() {
+        let is_inside_other = do subtrees.iter().any |&other| {
+            subtree.ancestors().any(|ancestor| ancestor == other)
+        };
+        if !is_inside_other && !outermost_subtrees.contains(&subtree) {
+            outermost_subtrees.push(subtree)
+        }
+    }
+    Some(outermost_subtrees)
+}
+
+/// Returns the nearest element from `node` up whose flow can be built again on its own, or `None`
+/// if there is none below the root.
+fn enclosing_rebuildable_element(node: AbstractNode<LayoutView>)
+                                 -> Option<AbstractNode<LayoutView>> {
+    let mut node = node;
+    loop {
+        let parent = match node.parent_node() {
+            Some(parent) if parent.is_element() => parent,
+            _ => return None,
+        };
+        if node.is_element() && is_in_flow_block(node) && is_block(parent) {
+            return Some(node)
+        }
+        node = parent
+    }
+}
+
+/// Returns true if the element is a block that is neither floated nor absolutely positioned, and
+/// so has a block flow in the flow of its parent.
+fn is_in_flow_block(element: AbstractNode<LayoutView>) -> bool {
+    let style = element.style();
+    match (style.Box.display, style.Box.float, style.Box.position) {
+        (display::block, float::none, position::static_) |
+        (display::block, float::none, position::relative) |
+        (display::list_item, float::none, position::static_) |
+        (display::list_item, float::none, position::relative) => true,
+        _ => false,
+    }
+}
+
+/// Returns true if the element is a block, which lays out its children in a flow of its own.
+fn is_block(element: AbstractNode<LayoutView>) -> bool {
+    match element.style().Box.display {
+        display::block | display::list_item => true,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod restyle_damage_tests {
     use super::*;
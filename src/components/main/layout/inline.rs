@@ -4,29 +4,33 @@
 
 use css::node_style::StyledNode;
 use std::cell::Cell;
-use layout::box::{CannotSplit, GenericRenderBoxClass, ImageRenderBoxClass, RenderBox};
-use layout::box::{RenderBoxUtils, SplitDidFit, SplitDidNotFit, TextRenderBoxClass};
+use layout::bidi;
+use layout::box::{CannotSplit, GenericRenderBoxClass, ImageRenderBoxClass};
+use layout::box::{InlineBlockRenderBoxClass, RenderBox, RenderBoxBase, RenderBoxUtils};
+use layout::box::{SplitDidFit, SplitDidNotFit, TextRenderBoxClass, font_style_of_style};
+use layout::box::{line_height_of_style};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
-use layout::flow::{FlowClass, FlowContext, FlowData, InlineFlowClass};
+use layout::flow::{AbsoluteFlowClass, FlowClass, FlowContext, FlowData, InlineBlockFlowClass};
+use layout::flow::InlineFlowClass;
 use layout::flow;
 use layout::float_context::FloatContext;
 use layout::util::{ElementMapping};
 use layout::float_context::{PlacementInfo, FloatLeft};
+use layout::model::CollapsibleMargins;
+use layout::model;
 
 use std::local_data;
 use std::u16;
 use std::util;
 use geom::{Point2D, Rect, Size2D};
 use gfx::display_list::DisplayList;
+use gfx::font::FontMetrics;
 use gfx::geometry::Au;
-use newcss::units::{Em, Px};
-use newcss::values::{CSSFontSizeLength};
-use newcss::values::{CSSTextAlignLeft, CSSTextAlignCenter, CSSTextAlignRight, CSSTextAlignJustify};
-use newcss::values::{CSSLineHeightNormal, CSSLineHeightNumber, CSSLineHeightLength, CSSLineHeightPercentage};
-use newcss::values::{CSSVerticalAlignBaseline, CSSVerticalAlignMiddle, CSSVerticalAlignSub, CSSVerticalAlignSuper, 
-                     CSSVerticalAlignTextTop, CSSVerticalAlignTextBottom, CSSVerticalAlignTop, CSSVerticalAlignBottom, 
-                     CSSVerticalAlignLength, CSSVerticalAlignPercentage};
+use gfx::geometry;
+use script::dom::node::{AbstractNode, LayoutView};
+use style::ComputedValues;
+use style::computed_values::{direction, display, text_align, vertical_align};
 use servo_util::range::Range;
 use servo_util::tree::TreeNodeRef;
 use extra::container::Deque;
@@ -444,6 +448,14 @@ impl LineboxScanner {
         self.pending_line.bounds.size.height = Au::max(self.pending_line.bounds.size.height, 
                                                        box.base().position.size.height);
         self.new_boxes.push(box);
+
+        // A preserved newline ends the line.
+        if box.class() == TextRenderBoxClass && box.as_text_render_box().ends_with_newline() {
+            debug!("LineboxScanner: Box b%d ends with a newline, breaking line %u",
+                   box.base().id(),
+                   self.lines.len());
+            self.flush_current_line();
+        }
     }
 }
 
@@ -461,7 +473,16 @@ pub struct InlineFlow {
     // vec of ranges into boxes that represent elements. These ranges
     // must be well-nested, and are only related to the content of
     // boxes (not lines). Ranges are only kept for non-leaf elements.
-    elems: ElementMapping
+    elems: ElementMapping,
+
+    /// The boxes as they were built and the element mapping over them, before lines split the
+    /// boxes. Lines are formed from these each time the flow is laid out again. `None` until lines
+    /// are first formed.
+    built_boxes: Option<(~[@mut RenderBox], ElementMapping)>,
+
+    /// The baseline of the last line box, relative to the top of this flow. `None` if there are
+    /// no lines. An inline-block that contains this flow takes its baseline from it.
+    last_baseline: Option<Au>,
 }
 
 impl InlineFlow {
@@ -471,6 +492,8 @@ impl InlineFlow {
             boxes: ~[],
             lines: ~[],
             elems: ElementMapping::new(),
+            built_boxes: None,
+            last_baseline: None,
         }
     }
 
@@ -479,9 +502,38 @@ impl InlineFlow {
             box.teardown();
         }
         self.boxes = ~[];
+        self.built_boxes = None;
     }
 
-    pub fn build_display_list_inline<E:ExtraDisplayListData>(&self,
+    /// Returns the boxes of this flow as they were built, before lines split them.
+    fn unsplit_boxes<'a>(&'a self) -> &'a [@mut RenderBox] {
+        match self.built_boxes {
+            Some((ref boxes, _)) => boxes.as_slice(),
+            None => self.boxes.as_slice(),
+        }
+    }
+
+    /// Returns the block container of the lines of this flow: the nearest ancestor element of its
+    /// node that is not an inline box.
+    pub fn block_container(&self) -> AbstractNode<LayoutView> {
+        let mut container = self.base.node;
+        loop {
+            let parent = match container.parent_node() {
+                Some(parent) => parent,
+                None => break,
+            };
+            if !parent.is_element() {
+                break
+            }
+            container = parent;
+            if container.style().Box.display != display::inline {
+                break
+            }
+        }
+        container
+    }
+
+    pub fn build_display_list_inline<E:ExtraDisplayListData>(&mut self,
                                                              builder: &DisplayListBuilder,
                                                              dirty: &Rect<Au>,
                                                              list: &Cell<DisplayList<E>>)
@@ -493,28 +545,312 @@ impl InlineFlow {
         }
 
         let abs_rect = Rect(self.base.abs_position, self.base.position.size);
-        if !abs_rect.intersects(dirty) {
-            return true;
+        if abs_rect.intersects(dirty) {
+            // TODO(#228): Once we form line boxes and have their cached bounds, we can be smarter
+            // and not recurse on a line if nothing in it can intersect the dirty region.
+            debug!("FlowContext[%d]: building display list for %u inline boxes",
+                   self.base.id,
+                   self.boxes.len());
+
+            // TODO: The text inside a relatively positioned inline element does not move with it.
+            let containing_block_size = self.base.containing_block_size;
+            for box in self.boxes.iter() {
+                let offset = self.base.abs_position +
+                    box.base().relative_position(containing_block_size);
+                box.build_display_list(builder, dirty, &offset, &self.base.clip_rect, list)
+            }
         }
 
-        // TODO(#228): Once we form line boxes and have their cached bounds, we can be smarter and
-        // not recurse on a line if nothing in it can intersect the dirty region.
-        debug!("FlowContext[%d]: building display list for %u inline boxes",
-               self.base.id,
-               self.boxes.len());
+        // The child flows are the floats, the inline-blocks and the absolutely positioned elements
+        // in this flow.
+        let this_position = self.base.abs_position;
+        for child in self.base.child_iter() {
+            let child_base = flow::mut_base(*child);
+            child_base.abs_position = this_position + child_base.position.origin;
+        }
 
-        for box in self.boxes.iter() {
-            box.build_display_list(builder, dirty, &self.base.abs_position, list)
+        false
+    }
+}
+
+/// The distances by which an inline box reaches above and below its baseline.
+struct InlineExtents {
+    above_baseline: Au,
+    below_baseline: Au,
+}
+
+impl InlineExtents {
+    /// Returns the extents of the inline box of non-replaced content with the given font and
+    /// line height: the leading is split equally above and below the content area. (CSS 2.1 §
+    /// 10.8.1)
+    fn from_font(metrics: &FontMetrics, line_height: Au) -> InlineExtents {
+        let half_leading = (line_height - (metrics.ascent + metrics.descent)).scale_by(0.5);
+        let above_baseline = metrics.ascent + half_leading;
+        InlineExtents {
+            above_baseline: above_baseline,
+            below_baseline: line_height - above_baseline,
         }
+    }
+}
 
-        // TODO(#225): Should `inline-block` elements have flows as children of the inline flow or
-        // should the flow be nested inside the box somehow?
-        
-        // For now, don't traverse the subtree rooted here
-        true
+/// Where a box goes vertically in its line box.
+enum LinePosition {
+    /// The baseline of the box is raised by the given distance above the baseline of the line box.
+    RaisedFromBaseline(Au),
+    /// The top of the inline box is at the top of the line box ('vertical-align: top').
+    AtLineTop,
+    /// The bottom of the inline box is at the bottom of the line box ('vertical-align: bottom').
+    AtLineBottom,
+}
+
+/// Returns the metrics of the primary font of a computed style.
+fn font_metrics_of_style(ctx: &mut LayoutContext, style: &ComputedValues) -> FontMetrics {
+    let font_style = font_style_of_style(style);
+    let font_group = ctx.font_ctx.get_resolved_font_for_style(&font_style);
+    font_group.fonts[0].metrics
+}
+
+/// Returns the x-height of a font, or half its ascent if the font does not give one.
+fn x_height(metrics: &FontMetrics) -> Au {
+    if metrics.x_height > Au::new(0) {
+        metrics.x_height
+    } else {
+        metrics.ascent.scale_by(0.5)
     }
 }
 
+/// Returns true if 'vertical-align' applies to an element of the given style, that is, if the
+/// element is inline-level.
+fn is_inline_level(style: &ComputedValues) -> bool {
+    match style.Box.display {
+        display::inline | display::inline_block => true,
+        _ => false,
+    }
+}
+
+/// Returns the distance by which 'vertical-align' raises the baseline of a box above that of its
+/// parent, given the inline box and the line height of the box and the font of its parent. 'top'
+/// and 'bottom' align the box with the line box instead. (CSS 2.1 § 10.8.1)
+fn baseline_shift(vertical_align: vertical_align::ComputedValue,
+                  extents: &InlineExtents,
+                  line_height: Au,
+                  parent_metrics: &FontMetrics)
+                  -> Au {
+    match vertical_align {
+        vertical_align::baseline | vertical_align::top | vertical_align::bottom => Au::new(0),
+        vertical_align::middle => {
+            // The midpoint of the box goes half the x-height of the parent above its baseline.
+            (x_height(parent_metrics) - extents.above_baseline +
+             extents.below_baseline).scale_by(0.5)
+        }
+        // Fonts do not give us the positions of subscripts and superscripts, so they are derived
+        // from the x-height.
+        vertical_align::sub => -x_height(parent_metrics).scale_by(0.5),
+        vertical_align::super_ => x_height(parent_metrics).scale_by(0.66),
+        vertical_align::text_top => parent_metrics.ascent - extents.above_baseline,
+        vertical_align::text_bottom => extents.below_baseline - parent_metrics.descent,
+        vertical_align::Length(length) => model::from_length(length),
+        vertical_align::Percentage(p) => line_height.scale_by(p),
+    }
+}
+
+/// Returns the distance from the top of a box to its baseline, and the inline box of the box
+/// around that baseline. The baseline of a replaced element is the bottom of its margin box.
+fn vertical_metrics_of_box(box: @mut RenderBox) -> (Au, InlineExtents) {
+    match box.class() {
+        ImageRenderBoxClass => {
+            let image_box = box.as_image_render_box();
+            let (margin_top, margin_bottom, noncontent_height) = {
+                let model = &image_box.base.model;
+                (model.margin.top,
+                 model.margin.bottom,
+                 model.border.top + model.padding.top + model.border.bottom +
+                 model.padding.bottom)
+            };
+            let height = image_box.image_height() + noncontent_height;
+            image_box.base.position.size.height = height;
+
+            let extents = InlineExtents {
+                above_baseline: margin_top + height + margin_bottom,
+                below_baseline: Au::new(0),
+            };
+            (height + margin_bottom, extents)
+        }
+        TextRenderBoxClass => {
+            // The box is the content area, from the ascent to the descent of the font.
+            let text_box = box.as_text_render_box();
+            let metrics = text_box.run.font.metrics;
+            let line_height = text_box.base.calculate_line_height();
+            (metrics.ascent, InlineExtents::from_font(&metrics, line_height))
+        }
+        GenericRenderBoxClass => {
+            let height = box.base().position.size.height;
+            let extents = InlineExtents {
+                above_baseline: height,
+                below_baseline: Au::new(0),
+            };
+            (height, extents)
+        }
+        InlineBlockRenderBoxClass => {
+            // The inline-block has given its box its height and its baseline.
+            let inline_block_box = box.as_inline_block_render_box();
+            let height = inline_block_box.base.position.size.height;
+            let baseline = inline_block_box.baseline;
+            let extents = InlineExtents {
+                above_baseline: baseline,
+                below_baseline: height - baseline,
+            };
+            (baseline, extents)
+        }
+        // FIXME(pcwalton): This isn't very type safe!
+        _ => {
+            fail!(fmt!("Tried to assign height to unknown Box variant: %s", box.debug_str()))
+        }
+    }
+}
+
+/// Returns where a box goes in its line box. The 'vertical-align' of the box, and then those of
+/// the inline elements around it, each raise it relative to the next element out, up to the block
+/// container, whose baseline is that of the line box. 'top' and 'bottom' on the box itself align
+/// it with the line box instead. (CSS 2.1 § 10.8.1)
+///
+/// TODO: 'top' and 'bottom' on the inline elements around the box are treated as 'baseline'.
+fn line_position_of_box(ctx: &mut LayoutContext, base: &RenderBoxBase, extents: InlineExtents)
+                        -> LinePosition {
+    let style = base.nearest_style();
+    let mut vertical_align = style.Box.vertical_align;
+    let mut inline_level = is_inline_level(style);
+    let mut line_height = base.calculate_line_height();
+    let mut extents = extents;
+
+    // Generated content is a child of the element that generates it.
+    let mut parent = match base.pseudo_element {
+        Some(_) => Some(base.node),
+        None => base.nearest_ancestor_element().parent_node(),
+    };
+
+    let mut raise = Au::new(0);
+    let mut is_box = true;
+    while inline_level {
+        let parent_node = match parent {
+            Some(parent_node) => parent_node,
+            None => break,
+        };
+        if !parent_node.is_element() {
+            break
+        }
+
+        let parent_style = parent_node.style();
+        let parent_metrics = font_metrics_of_style(ctx, parent_style);
+        match vertical_align {
+            vertical_align::top if is_box => return AtLineTop,
+            vertical_align::bottom if is_box => return AtLineBottom,
+            _ => {
+                raise = raise + baseline_shift(vertical_align,
+                                               &extents,
+                                               line_height,
+                                               &parent_metrics)
+            }
+        }
+
+        // Now align the parent within its own parent.
+        vertical_align = parent_style.Box.vertical_align;
+        inline_level = is_inline_level(parent_style);
+        line_height = line_height_of_style(parent_style);
+        extents = InlineExtents::from_font(&parent_metrics, line_height);
+        parent = parent_node.parent_node();
+        is_box = false;
+    }
+
+    RaisedFromBaseline(raise)
+}
+
+/// Places the boxes of a line horizontally according to 'text-align'. Justified lines, except
+/// the last one, spread their slack over the word separators of their text. (CSS 2.1 § 16.2)
+///
+/// The boxes are placed in visual order, which the bidi levels of the boxes give. (UAX #9 L2)
+/// 'start' and 'end' align to the left and right in left-to-right containers and the other way
+/// around in right-to-left ones.
+///
+/// The marker of a list item with 'list-style-position: outside' hangs to the start side of the
+/// line and takes up no room in it. (CSS 2.1 § 12.5.1)
+fn align_line_horizontally(boxes: &[@mut RenderBox],
+                           line: &LineBox,
+                           text_align: text_align::ComputedValue,
+                           direction: direction::ComputedValue,
+                           is_last_line: bool) {
+    let mut marker_width = Au::new(0);
+    for i in line.range.eachi() {
+        if boxes[i].base().is_outside_marker() {
+            marker_width = marker_width + boxes[i].base().position.size.width;
+        }
+    }
+    let slack_width = geometry::max(line.green_zone.width - line.bounds.size.width + marker_width,
+                                    Au::new(0));
+
+    let is_rtl = direction == direction::rtl;
+    let mut offset_x = line.bounds.origin.x;
+    match (text_align, is_rtl) {
+        (text_align::left, _) | (text_align::start, false) | (text_align::end, true) => {}
+        (text_align::center, _) => offset_x = offset_x + slack_width.scale_by(0.5),
+        (text_align::right, _) | (text_align::start, true) | (text_align::end, false) => {
+            offset_x = offset_x + slack_width
+        }
+        (text_align::justify, _) => {
+            let is_justified = !is_last_line && justify_line(boxes, line, slack_width);
+            if is_rtl && !is_justified {
+                // Lines that are not justified go to the start side.
+                offset_x = offset_x + slack_width
+            }
+        }
+    }
+
+    let levels: ~[u8] = line.range.eachi().map(|i| boxes[i].base().bidi_level).collect();
+    for &index in bidi::visual_order(levels).iter() {
+        let box = boxes[line.range.begin() + index].mut_base();
+        if box.is_outside_marker() {
+            box.position.origin.x = if is_rtl {
+                line.bounds.origin.x + line.green_zone.width
+            } else {
+                line.bounds.origin.x - box.position.size.width
+            };
+            loop
+        }
+        box.position.origin.x = offset_x;
+        offset_x = offset_x + box.position.size.width;
+    }
+}
+
+/// Widens the word separators of the text boxes of a line so that they take up `slack_width`.
+/// The separators at the end of the line are left alone, as is a line without separators.
+/// Returns false if the line has no separators to widen.
+fn justify_line(boxes: &[@mut RenderBox], line: &LineBox, slack_width: Au) -> bool {
+    let last_box_index = line.range.end() - 1;
+    let mut counts = ~[];
+    let mut total_count = 0;
+    for i in line.range.eachi() {
+        let count = if boxes[i].class() == TextRenderBoxClass {
+            boxes[i].as_text_render_box().word_separator_count(i != last_box_index)
+        } else {
+            0
+        };
+        counts.push(count);
+        total_count += count;
+    }
+
+    if total_count == 0 {
+        return false
+    }
+
+    let word_spacing = slack_width / Au::new(total_count as i32);
+    for (i, &count) in line.range.eachi().zip(counts.iter()) {
+        if count > 0 {
+            boxes[i].as_text_render_box().set_word_spacing(word_spacing, count);
+        }
+    }
+    true
+}
+
 impl FlowContext for InlineFlow {
     fn class(&self) -> FlowClass {
         InlineFlowClass
@@ -532,6 +868,12 @@ impl FlowContext for InlineFlow {
         let mut num_floats = 0;
 
         for kid in self.base.child_iter() {
+            // Floats inside absolutely positioned children and inline-blocks stay there.
+            match kid.class() {
+                AbsoluteFlowClass | InlineBlockFlowClass => loop,
+                _ => {}
+            }
+
             let child_base = flow::mut_base(*kid);
             num_floats += child_base.num_floats;
             child_base.floats_in = FloatContext::new(child_base.num_floats);
@@ -543,7 +885,7 @@ impl FlowContext for InlineFlow {
             let mut min_width = Au::new(0);
             let mut pref_width = Au::new(0);
 
-            for box in this.boxes.iter() {
+            for box in this.unsplit_boxes().iter() {
                 debug!("FlowContext[%d]: measuring %s", self.base.id, box.debug_str());
                 let (this_minimum_width, this_preferred_width) =
                     box.minimum_and_preferred_widths();
@@ -568,23 +910,19 @@ impl FlowContext for InlineFlow {
         debug!("assign_widths_inline: floats_in: %?", self.base.floats_in);
         {
             let this = &mut *self;
+            let containing_block_width = this.base.position.size.width;
             for &box in this.boxes.iter() {
-                box.assign_width();
+                box.assign_width(containing_block_width);
             }
         }
 
+        // Inline-blocks give their boxes their widths once they know them, before lines are
+        // formed.
         for kid in self.base.child_iter() {
             let child_base = flow::mut_base(*kid);
             child_base.position.size.width = self.base.position.size.width;
             child_base.is_inorder = self.base.is_inorder;
         }
-        // There are no child contexts, so stop here.
-
-        // TODO(Issue #225): once there are 'inline-block' elements, this won't be
-        // true.  In that case, set the InlineBlockBox's width to the
-        // shrink-to-fit width, perform inline flow, and set the block
-        // flow context's width as the assigned width of the
-        // 'inline-block' box that created this flow before recursing.
     }
 
     fn assign_height_inorder(&mut self, ctx: &mut LayoutContext) {
@@ -594,15 +932,29 @@ impl FlowContext for InlineFlow {
         self.assign_height(ctx);
     }
 
-    fn assign_height(&mut self, _: &mut LayoutContext) {
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
         debug!("assign_height_inline: assigning height for flow %?", self.base.id);
 
+        // Lines are formed anew from the boxes as they were built.
+        if self.built_boxes.is_none() {
+            self.built_boxes = Some((self.boxes.clone(), self.elems.clone()));
+        } else {
+            let (boxes, elems) = self.built_boxes.get_ref().clone();
+            self.boxes = boxes;
+            self.elems = elems;
+        }
+
+        // Justification widened the text boxes for the lines of the previous layout.
+        for &box in self.boxes.iter() {
+            if box.class() == TextRenderBoxClass {
+                let text_box = box.as_text_render_box();
+                if text_box.word_spacing != Au::new(0) {
+                    text_box.set_word_spacing(Au::new(0), 0);
+                }
+            }
+        }
+
         // Divide the boxes into lines
-        // TODO(#226): Get the CSS `line-height` property from the containing block's style to
-        // determine minimum linebox height.
-        //
-        // TODO(#226): Get the CSS `line-height` property from each non-replaced inline element to
-        // determine its height for computing linebox height.
         debug!("assign_height_inline: floats_in: %?", self.base.floats_in);
 
         // Create the linebox scanner if necessary.
@@ -625,280 +977,95 @@ impl FlowContext for InlineFlow {
             //let mut scanner = maybe_scanner.unwrap();
             scanner.scan_for_lines(self);
 
+            // The block container gives the lines their alignment, and the strut that each line
+            // box starts with. (CSS 2.1 § 10.8.1)
+            let container = self.block_container();
+            let text_align = container.style().InheritedText.text_align;
+            let direction = container.style().InheritedBox.direction;
+            let strut = InlineExtents::from_font(&font_metrics_of_style(ctx, container.style()),
+                                                 line_height_of_style(container.style()));
+
             let mut line_height_offset = Au::new(0);
+            self.last_baseline = None;
 
             // Now, go through each line and lay out the boxes inside
-            for line in self.lines.mut_iter() {
-                // We need to distribute extra width based on text-align.
-                let mut slack_width = line.green_zone.width - line.bounds.size.width;
-                if slack_width < Au::new(0) {
-                    slack_width = Au::new(0);
-                }
-                //assert!(slack_width >= Au::new(0), "Too many boxes on line");
-
-                // Get the text alignment.
-                // TODO(Issue #222): use 'text-align' property from InlineFlow's
-                // block container, not from the style of the first box child.
-                let linebox_align = if line.range.begin() < self.boxes.len() {
-                    let first_box = self.boxes[line.range.begin()];
-                    first_box.base().nearest_ancestor_element().style().text_align()
-                } else {
-                    // Nothing to lay out, so assume left alignment.
-                    CSSTextAlignLeft
-                };
-
-                // Set the box x positions
-                let mut offset_x = line.bounds.origin.x;
-                match linebox_align {
-                    // So sorry, but justified text is more complicated than shuffling linebox coordinates.
-                    // TODO(Issue #213): implement `text-align: justify`
-                    CSSTextAlignLeft | CSSTextAlignJustify => {
-                        for i in line.range.eachi() {
-                            let box = self.boxes[i].mut_base();
-                            box.position.origin.x = offset_x;
-                            offset_x = offset_x + box.position.size.width;
-                        }
-                    }
-                    CSSTextAlignCenter => {
-                        offset_x = offset_x + slack_width.scale_by(0.5);
-                        for i in line.range.eachi() {
-                            let box = self.boxes[i].mut_base();
-                            box.position.origin.x = offset_x;
-                            offset_x = offset_x + box.position.size.width;
-                        }
-                    }
-                    CSSTextAlignRight => {
-                        offset_x = offset_x + slack_width;
-                        for i in line.range.eachi() {
-                            let box = self.boxes[i].mut_base();
-                            box.position.origin.x = offset_x;
-                            offset_x = offset_x + box.position.size.width;
-                        }
-                    }
-                };
+            let line_count = self.lines.len();
+            for (line_index, line) in self.lines.mut_iter().enumerate() {
+                align_line_horizontally(self.boxes,
+                                        line,
+                                        text_align,
+                                        direction,
+                                        line_index + 1 == line_count);
 
                 // Set the top y position of the current linebox.
                 // `line_height_offset` is updated at the end of the previous loop.
                 line.bounds.origin.y = line.bounds.origin.y + line_height_offset;
 
-                // Calculate the distance from baseline to the top of the linebox.
-                let mut topmost = Au::new(0);
-                // Calculate the distance from baseline to the bottom of the linebox.
-                let mut bottommost = Au::new(0);
+                // The distances from the baseline of the line box to its top and to its bottom.
+                let mut above_baseline = strut.above_baseline;
+                let mut below_baseline = strut.below_baseline;
 
-                // Calculate the biggest height among boxes with 'top' value.
-                let mut biggest_top = Au::new(0);
-                // Calculate the biggest height among boxes with 'bottom' value.
-                let mut biggest_bottom = Au::new(0);
+                // The heights of the tallest boxes aligned with the top and with the bottom of the
+                // line box.
+                let mut tallest_at_top = Au::new(0);
+                let mut tallest_at_bottom = Au::new(0);
 
+                let mut placements = ~[];
                 for box_i in line.range.eachi() {
                     let cur_box = self.boxes[box_i];
-
-                    let (top_from_base, bottom_from_base, ascent) = match cur_box.class() {
-                        ImageRenderBoxClass => {
-                            let image_box = cur_box.as_image_render_box();
-                            let mut height = image_box.image_height();
-
-                            // TODO: margin, border, padding's top and bottom should be calculated in advance,
-                            // since baseline of image is bottom margin edge.
-                            let mut top = Au::new(0);
-                            let mut bottom = Au::new(0);
-                            {
-                                let model = &image_box.base.model;
-                                top = model.border.top + model.padding.top + model.margin.top;
-                                bottom = model.border.bottom + model.padding.bottom +
-                                    model.margin.bottom;
-                            }
-
-                            let noncontent_height = top + bottom;
-                            height = height + noncontent_height;
-                            image_box.base.position.size.height = height;
-                            image_box.base.position.translate(&Point2D(Au::new(0), -height));
-
-                            let ascent = height + bottom;
-                            (height, Au::new(0), ascent)
-                        },
-                        TextRenderBoxClass => {
-                            let text_box = cur_box.as_text_render_box();
-                            let range = &text_box.range;
-                            let run = &text_box.run;
-                            
-                            // Compute the height based on the line-height and font size
-                            let text_bounds = run.metrics_for_range(range).bounding_box;
-                            let em_size = text_bounds.size.height;
-                            let line_height = text_box.base.calculate_line_height(em_size);
-
-                            // Find the top and bottom of the content area.
-                            // Those are used in text-top and text-bottom value of 'vertical-align'
-                            let text_ascent = text_box.run.font.metrics.ascent;
-                           
-                            // Offset from the top of the box is 1/2 of the leading + ascent
-                            let text_offset = text_ascent + (line_height - em_size).scale_by(0.5);
-                            text_bounds.translate(&Point2D(text_box.base.position.origin.x, Au::new(0)));
-
-                            (text_offset, line_height - text_offset, text_ascent)
-                        },
-                        GenericRenderBoxClass => {
-                            let base = cur_box.base();
-                            (base.position.size.height, Au::new(0), base.position.size.height)
-                        },
-                        // FIXME(pcwalton): This isn't very type safe!
-                        _ => {
-                            fail!(fmt!("Tried to assign height to unknown Box variant: %s",
-                                       cur_box.debug_str()))
-                        }
-                    };
-                    let mut top_from_base = top_from_base;
-                    let mut bottom_from_base = bottom_from_base;
-
-                    // To calculate text-top and text-bottom value of 'vertical-align',
-                    //  we should find the top and bottom of the content area of parent box.
-                    // The content area is defined in "http://www.w3.org/TR/CSS2/visudet.html#inline-non-replaced". 
-                    // TODO: We should extract em-box info from font size of parent
-                    //  and calcuate the distances from baseline to the top and the bottom of parent's content area.
-
-                    // It should calculate the distance from baseline to the top of parent's content area.
-                    // But, it is assumed now as font size of parent.
-                    let mut parent_text_top = Au::new(0);
-                    // It should calculate the distance from baseline to the bottom of parent's content area.
-                    // But, it is assumed now as 0.
-                    let parent_text_bottom  = Au::new(0);
-                    let cur_box_base = cur_box.mut_base();
-                    // Get parent node
-                    let parent = cur_box_base.node
-                                             .parent_node()
-                                             .map_default(cur_box_base.node, |parent| *parent);
-                    // TODO: When the calculation of font-size style is supported, it should be
-                    // updated.
-                    let font_size = match parent.style().font_size() {
-                        CSSFontSizeLength(Px(length)) => length,
-                        // todo: this is based on a hard coded font size, should be the parent
-                        // element's font size
-                        CSSFontSizeLength(Em(length)) => length * 16.0,
-                        _ => 16.0 // px units
-                    };
-                    parent_text_top = Au::from_frac_px(font_size);
-
-                    // This flag decides whether topmost and bottommost are updated or not.
-                    // That is, if the box has top or bottom value, no_update_flag becomes true.
-                    let mut no_update_flag = false;
-                    // Calculate a relative offset from baseline.
-                    let offset = match cur_box_base.vertical_align() {
-                        CSSVerticalAlignBaseline => {
-                            -ascent
-                        },
-                        CSSVerticalAlignMiddle => {
-                            // TODO: x-height value should be used from font info.
-                            let xheight = Au::new(0);
-                            -(xheight + cur_box.box_height()).scale_by(0.5)
-                        },
-                        CSSVerticalAlignSub => {
-                            // TODO: The proper position for subscripts should be used.
-                            // Lower the baseline to the proper position for subscripts
-                            let sub_offset = Au::new(0);
-                            (sub_offset - ascent)
-                        },
-                        CSSVerticalAlignSuper => {
-                            // TODO: The proper position for superscripts should be used.
-                            // Raise the baseline to the proper position for superscripts
-                            let super_offset = Au::new(0);
-                            (-super_offset - ascent)
-                        },
-                        CSSVerticalAlignTextTop => {
-                            let box_height = top_from_base + bottom_from_base;
-                            let prev_bottom_from_base = bottom_from_base;
-                            top_from_base = parent_text_top;
-                            bottom_from_base = box_height - top_from_base;
-                            (bottom_from_base - prev_bottom_from_base - ascent)
-                        },
-                        CSSVerticalAlignTextBottom => {
-                            let box_height = top_from_base + bottom_from_base;
-                            let prev_bottom_from_base = bottom_from_base;
-                            bottom_from_base = parent_text_bottom;
-                            top_from_base = box_height - bottom_from_base;
-                            (bottom_from_base - prev_bottom_from_base - ascent)
-                        },
-                        CSSVerticalAlignTop => {
-                            if biggest_top < (top_from_base + bottom_from_base) {
-                                biggest_top = top_from_base + bottom_from_base;
-                            }
-                            let offset_top = top_from_base - ascent;
-                            no_update_flag = true;
-                            offset_top
-                        },
-                        CSSVerticalAlignBottom => {
-                            if biggest_bottom < (top_from_base + bottom_from_base) {
-                                biggest_bottom = top_from_base + bottom_from_base;
-                            }
-                            let offset_bottom = -(bottom_from_base + ascent);
-                            no_update_flag = true;
-                            offset_bottom
-                        },
-                        CSSVerticalAlignLength(length) => {
-                            let length_offset = match length {
-                                Em(l) => Au::from_frac_px(cur_box.base().font_style().pt_size * l),
-                                Px(l) => Au::from_frac_px(l),
-                            };
-                            -(length_offset + ascent)
-                        },
-                        CSSVerticalAlignPercentage(p) => {
-                            let pt_size = cur_box.base().font_style().pt_size; 
-                            let line_height = cur_box.base()
-                                                     .calculate_line_height(Au::from_pt(pt_size));
-                            let percent_offset = line_height.scale_by(p / 100.0);
-                            -(percent_offset + ascent)
+                    let (ascent, extents) = vertical_metrics_of_box(cur_box);
+                    let position = line_position_of_box(ctx, cur_box.base(), extents);
+                    let height = extents.above_baseline + extents.below_baseline;
+                    match position {
+                        RaisedFromBaseline(raise) => {
+                            above_baseline = Au::max(above_baseline,
+                                                     extents.above_baseline + raise);
+                            below_baseline = Au::max(below_baseline,
+                                                     extents.below_baseline - raise);
                         }
-                    };
-
-                    // If the current box has 'top' or 'bottom' value, no_update_flag is true.
-                    // Otherwise, topmost and bottomost are updated.
-                    if !no_update_flag && top_from_base > topmost {
-                        topmost = top_from_base;
+                        AtLineTop => tallest_at_top = Au::max(tallest_at_top, height),
+                        AtLineBottom => tallest_at_bottom = Au::max(tallest_at_bottom, height),
                     }
-                    if !no_update_flag && bottom_from_base > bottommost {
-                        bottommost = bottom_from_base;
-                    }
-
-                    let cur_box_base = cur_box.mut_base();
-                    cur_box_base.position.origin.y = line.bounds.origin.y + offset;
+                    placements.push((ascent, extents, position));
                 }
 
-                // Calculate the distance from baseline to the top of the biggest box with 'bottom' value.
-                // Then, if necessary, update the topmost.
-                let topmost_of_bottom = biggest_bottom - bottommost;
-                if topmost_of_bottom > topmost {
-                    topmost = topmost_of_bottom;
+                // Boxes aligned with the top or the bottom of the line box make it taller if they
+                // do not fit.
+                if tallest_at_top > above_baseline + below_baseline {
+                    below_baseline = tallest_at_top - above_baseline;
                 }
-
-                // Calculate the distance from baseline to the bottom of the biggest box with 'top' value.
-                // Then, if necessary, update the bottommost.
-                let bottommost_of_top = biggest_top - topmost;
-                if bottommost_of_top > bottommost {
-                    bottommost = bottommost_of_top;
+                if tallest_at_bottom > above_baseline + below_baseline {
+                    above_baseline = tallest_at_bottom - below_baseline;
                 }
 
-                // Now, the baseline offset from the top of linebox is set as topmost.
-                let baseline_offset = topmost;
-
-                // All boxes' y position is updated following the new baseline offset.
-                for box_i in line.range.eachi() {
-                    let cur_box = self.boxes[box_i];
-                    let cur_box_base = cur_box.mut_base();
-                    let adjust_offset = match cur_box_base.vertical_align() {
-                        CSSVerticalAlignTop => Au::new(0),
-                        CSSVerticalAlignBottom => baseline_offset + bottommost,
-                        _ => baseline_offset,
+                let line_top = line.bounds.origin.y;
+                let baseline = line_top + above_baseline;
+                let line_bottom = baseline + below_baseline;
+                for (box_i, &(ascent, extents, position)) in line.range.eachi()
+                                                                   .zip(placements.iter()) {
+                    let box_baseline = match position {
+                        RaisedFromBaseline(raise) => baseline - raise,
+                        AtLineTop => line_top + extents.above_baseline,
+                        AtLineBottom => line_bottom - extents.below_baseline,
                     };
-
-                    cur_box_base.position.origin.y = cur_box_base.position.origin.y +
-                        adjust_offset;
+                    self.boxes[box_i].mut_base().position.origin.y = box_baseline - ascent;
                 }
 
+                self.last_baseline = Some(baseline);
+
                 // This is used to set the top y position of the next linebox in the next loop.
-                line_height_offset = line_height_offset + topmost + bottommost - line.bounds.size.height;
-                line.bounds.size.height = topmost + bottommost;
+                let line_height = above_baseline + below_baseline;
+                line_height_offset = line_height_offset + line_height - line.bounds.size.height;
+                line.bounds.size.height = line_height;
             } // End of `lines.each` loop.
 
+            // Inline-blocks go where their boxes were placed.
+            for kid in self.base.child_iter() {
+                if kid.class() == InlineBlockFlowClass {
+                    kid.as_inline_block().place_from_placeholder();
+                }
+            }
+
             self.base.position.size.height = 
                 if self.lines.len() > 0 {
                     self.lines.last().bounds.origin.y + self.lines.last().bounds.size.height
@@ -912,18 +1079,10 @@ impl FlowContext for InlineFlow {
         //}
     }
 
-    fn collapse_margins(&mut self,
-                        _: bool,
-                        _: &mut bool,
-                        _: &mut Au,
-                        _: &mut Au,
-                        collapsing: &mut Au,
-                        collapsible: &mut Au) {
-        *collapsing = Au::new(0);
-        // Non-empty inline flows prevent collapsing between the previous margion and the next.
-        if self.base.position.size.height > Au::new(0) {
-            *collapsible = Au::new(0);
-        }
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
+        // Non-empty inline flows contain line boxes, which prevent collapsing between the margins
+        // before them and the margins after them.
+        CollapsibleMargins::without_margins(self.base.position.size.height == Au::new(0))
     }
 }
 
@@ -0,0 +1,258 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS 'inline-block' elements. An inline-block lays out its contents as a block does, and sits
+//! in the line boxes of its parent inline flow as a single unbreakable box.
+//!
+//! The flow of an inline-block is a child of the inline flow, which also holds an
+//! `InlineBlockRenderBox` that stands for the element in its line boxes. The inline-block flow
+//! gives that box its size, and takes its position from it once the inline flow has broken its
+//! lines.
+
+use css::node_style::StyledNode;
+use layout::block::BlockFlow;
+use layout::box::{RenderBox, RenderBoxUtils};
+use layout::context::LayoutContext;
+use layout::flow::{AbsoluteFlowClass, BlockFlowClass, FloatFlowClass, FlowClass, FlowContext};
+use layout::flow::{FlowData, InlineBlockFlowClass, InlineFlowClass};
+use layout::flow;
+use layout::float_context::FloatContext;
+use layout::model::{Auto, MaybeAuto, Specified};
+use layout::model;
+
+use gfx::geometry::Au;
+use gfx::geometry;
+use style::computed_values::LPA_Length;
+
+pub struct InlineBlockFlow {
+    /// The block flow that lays out the contents of the element, and holds its render box.
+    block_flow: BlockFlow,
+
+    /// The box that stands for this element in the line boxes of the parent inline flow.
+    placeholder: Option<@mut RenderBox>,
+
+    /// The minimum width of the contents of the element, for shrink-to-fit.
+    min_content_width: Au,
+
+    /// The preferred width of the contents of the element, for shrink-to-fit.
+    pref_content_width: Au,
+}
+
+impl InlineBlockFlow {
+    pub fn new(base: FlowData) -> InlineBlockFlow {
+        let mut block_flow = BlockFlow::new(base);
+        block_flow.establishes_bfc = true;
+        InlineBlockFlow {
+            block_flow: block_flow,
+            placeholder: None,
+            min_content_width: Au::new(0),
+            pref_content_width: Au::new(0),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown();
+        self.placeholder = None;
+    }
+
+    /// Moves this flow to where the parent inline flow placed its placeholder box.
+    pub fn place_from_placeholder(&mut self) {
+        for &placeholder in self.placeholder.iter() {
+            self.block_flow.base.position.origin = placeholder.base().position.origin;
+        }
+    }
+}
+
+/// Returns the baseline of the last line box among the in-flow children of a flow, relative to
+/// the top of that flow, if there is any.
+fn last_baseline_of_children(base: &mut FlowData) -> Option<Au> {
+    let mut baseline = None;
+    for kid in base.child_iter() {
+        match kid.class() {
+            AbsoluteFlowClass | FloatFlowClass => loop,
+            _ => {}
+        }
+
+        let kid_y = flow::base(*kid).position.origin.y;
+        let kid_baseline = match kid.class() {
+            InlineFlowClass => kid.as_inline().last_baseline,
+            BlockFlowClass => last_baseline_of_children(&mut kid.as_block().base),
+            // TODO: Tables have the baseline of their first row.
+            _ => None,
+        };
+        for kid_baseline in kid_baseline.iter() {
+            baseline = Some(kid_y + *kid_baseline);
+        }
+    }
+    baseline
+}
+
+impl FlowContext for InlineBlockFlow {
+    fn class(&self) -> FlowClass {
+        InlineBlockFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_inline_block<'a>(&'a mut self) -> &'a mut InlineBlockFlow {
+        self
+    }
+
+    /// Computes the minimum and preferred widths of the margin box of the element, and hands them
+    /// to the placeholder box, through which the parent inline flow sees them. Percentages are
+    /// ignored here.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au::new(0);
+        let mut pref_width = Au::new(0);
+        let mut num_floats = 0;
+
+        for child_ctx in self.block_flow.base.child_iter() {
+            if child_ctx.class() == AbsoluteFlowClass {
+                loop
+            }
+
+            let child_base = flow::mut_base(*child_ctx);
+            min_width = geometry::max(min_width, child_base.min_width);
+            pref_width = geometry::max(pref_width, child_base.pref_width);
+            num_floats = num_floats + child_base.num_floats;
+        }
+
+        self.min_content_width = min_width;
+        self.pref_content_width = geometry::max(pref_width, min_width);
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let node = base.node;
+            let style = node.style();
+            base.model.compute_borders(style);
+            base.model.compute_padding(style, Au::new(0));
+
+            match style.Box.width {
+                LPA_Length(width) => {
+                    min_width = model::from_length(width);
+                    pref_width = min_width;
+                }
+                _ => pref_width = self.pref_content_width,
+            }
+
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    Au::new(0)).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     Au::new(0)).specified_or_zero();
+            let noncontent_width = margin_left + margin_right + base.model.border.left +
+                base.model.border.right + base.model.padding.left + base.model.padding.right;
+            min_width = min_width + noncontent_width;
+            pref_width = pref_width + noncontent_width;
+        }
+
+        self.block_flow.base.min_width = min_width;
+        self.block_flow.base.pref_width = pref_width;
+        self.block_flow.base.num_floats = num_floats;
+
+        for &placeholder in self.placeholder.iter() {
+            let placeholder = placeholder.as_inline_block_render_box();
+            placeholder.min_width = min_width;
+            placeholder.pref_width = pref_width;
+        }
+    }
+
+    /// Computes the width and the horizontal margins of the element. 'auto' margins are 0, and
+    /// an 'auto' width shrinks to fit. (CSS 2.1 § 10.3.9) The parent inline flow has set the
+    /// width of this flow to the width of the containing block.
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+        debug!("assign_widths_inline_block: assigning width for flow %?",
+               self.block_flow.base.id);
+        let cb_width = self.block_flow.base.position.size.width;
+        let mut x_offset = Au::new(0);
+        let mut content_width = cb_width;
+        let mut margin_box_width = cb_width;
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let base = &mut *base;
+            let node = base.node;
+            let style = node.style();
+            let model = &mut base.model;
+
+            model.compute_padding(style, cb_width);
+            let border_padding = model.border.left + model.border.right + model.padding.left +
+                model.padding.right;
+
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    cb_width).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     cb_width).specified_or_zero();
+            let width = match MaybeAuto::from_style(style.Box.width, cb_width) {
+                Specified(width) => width,
+                Auto => {
+                    let available_width = cb_width - margin_left - margin_right - border_padding;
+                    geometry::min(geometry::max(self.min_content_width, available_width),
+                                  self.pref_content_width)
+                }
+            };
+
+            model.margin.top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                     cb_width).specified_or_zero();
+            model.margin.right = margin_right;
+            model.margin.bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                        cb_width).specified_or_zero();
+            model.margin.left = margin_left;
+
+            x_offset = model.offset();
+            content_width = width;
+            margin_box_width = margin_left + border_padding + width + margin_right;
+
+            // The associated box is the border box of this flow.
+            base.position.origin.x = margin_left;
+            base.position.size.width = width + border_padding;
+        }
+
+        self.block_flow.base.position.size.width = margin_box_width;
+        for &placeholder in self.placeholder.iter() {
+            placeholder.mut_base().position.size.width = margin_box_width;
+        }
+
+        let num_floats = self.block_flow.base.num_floats;
+        self.block_flow.base.floats_in = FloatContext::new(num_floats);
+        self.block_flow.base.is_inorder = false;
+        self.block_flow.propagate_assigned_width_to_children(x_offset, content_width);
+    }
+
+    /// Lays out the contents of the element, and gives the placeholder box its height and its
+    /// baseline: that of the last line box inside, or the bottom margin edge if there is none.
+    /// (CSS 2.1 § 10.8.1)
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height_inline_block: assigning height for flow %?",
+               self.block_flow.base.id);
+        // Like a table cell, an element with floats inside starts an in-order traversal.
+        if self.block_flow.base.num_floats > 0 {
+            self.block_flow.assign_height_inorder(ctx);
+        } else {
+            self.block_flow.assign_height(ctx);
+        }
+
+        let height = self.block_flow.base.position.size.height;
+        let baseline = match last_baseline_of_children(&mut self.block_flow.base) {
+            Some(baseline) => baseline,
+            None => height,
+        };
+        for &placeholder in self.placeholder.iter() {
+            let placeholder = placeholder.as_inline_block_render_box();
+            placeholder.base.position.size.height = height;
+            placeholder.baseline = baseline;
+        }
+    }
+
+    /// The height was assigned in `assign_height`. Floats do not flow around an inline-block
+    /// differently than around any other box of the line.
+    fn assign_height_inorder(&mut self, _: &mut LayoutContext) {
+        self.block_flow.base.floats_out = self.block_flow.base.floats_in.clone();
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("InlineBlockFlow(f%d)", self.block_flow.base.id)
+    }
+}
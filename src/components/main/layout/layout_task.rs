@@ -6,32 +6,39 @@
 /// rendered.
 
 use css::matching::MatchMethods;
-use css::select::new_css_select_ctx;
+use css::node_style::StyledNode;
+use css::node_util::NodeUtil;
+use css::select::new_stylist;
 use layout::aux::LayoutAuxMethods;
 use layout::box_builder::LayoutTreeBuilder;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder};
-use layout::flow::{FlowContext, ImmutableFlowUtils, MutableFlowUtils, PreorderFlowTraversal};
-use layout::flow::{PostorderFlowTraversal};
+use layout::flow::{FloatFlowClass, FlowContext, ImmutableFlowUtils, InlineBlockFlowClass};
+use layout::flow::{InlineFlowClass, MutableFlowUtils, PostorderFlowTraversal};
+use layout::flow::{PreorderFlowTraversal};
 use layout::flow;
-use layout::incremental::{RestyleDamage, BubbleWidths};
+use layout::incremental::{RestyleDamage, BubbleWidths, Repaint, find_dirty_subtrees};
+use layout::incremental;
+use layout::web_fonts;
 
 use std::cast::transmute;
 use std::cell::Cell;
-use std::comm::{Port};
+use std::comm::{Port, SharedChan};
+use std::i32;
+use std::uint;
+use std::util;
 use extra::arc::Arc;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use gfx::display_list::DisplayList;
-use gfx::font_context::FontContext;
+use gfx::display_list::{BackgroundAndBordersStackingLevel, ContentStackingLevel, DisplayList};
+use gfx::display_list::{BlockBackgroundsAndBordersStackingLevel, FloatStackingLevel};
+use gfx::display_list::{StackingContext, StackingLevel};
+use gfx::font_context::{FontContext, WebFontFace};
 use gfx::geometry::Au;
 use gfx::opts::Opts;
-use gfx::render_task::{RenderMsg, RenderChan, RenderLayer};
+use gfx::render_task::{RenderMsg, RenderChan, RenderLayer, ScrollLayer};
 use gfx::render_task;
-use newcss::select::SelectCtx;
-use newcss::stylesheet::Stylesheet;
-use newcss::types::OriginAuthor;
 use script::dom::event::ReflowEvent;
 use script::dom::node::{AbstractNode, LayoutView};
 use script::layout_interface::{AddStylesheetMsg, ContentBoxQuery};
@@ -44,10 +51,13 @@ use script::script_task::{ReflowCompleteMsg, ScriptChan, SendEventMsg};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::{ImageResponder, LocalImageCache};
+use servo_net::resource_task::ResourceTask;
 use servo_util::tree::TreeNodeRef;
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
 use servo_util::range::Range;
+use style::{AuthorOrigin, Device, Screen, Stylesheet, Stylist};
+use style::computed_values::{Length, overflow};
 use extra::url::Url;
 
 struct LayoutTask {
@@ -59,12 +69,29 @@ struct LayoutTask {
     image_cache_task: ImageCacheTask,
     local_image_cache: @mut LocalImageCache,
     font_ctx: @mut FontContext,
+    /// Fetches the fonts of `@font-face` rules.
+    resource_task: ResourceTask,
+    /// The web fonts that finished loading since the last reflow, and where loads send them.
+    web_font_port: Port<WebFontFace>,
+    web_font_chan: SharedChan<WebFontFace>,
     doc_url: Option<Url>,
     screen_size: Option<Size2D<Au>>,
+    /// The ratio of device pixels to CSS pixels that media queries are evaluated against.
+    device_pixel_ratio: f32,
 
     display_list: Option<Arc<DisplayList<AbstractNode<()>>>>,
-
-    css_select_ctx: @mut SelectCtx,
+    /// The size of the page in the last display list, in pixels.
+    display_list_size: Size2D<uint>,
+    /// Whether the last display list had boxes whose contents scroll independently.
+    display_list_had_scroll_layers: bool,
+
+    /// The flow tree of the last reflow, which the next reflow builds again only in part.
+    flow_tree: Option<~FlowContext:>,
+    /// Whether building any part of the flow tree again may change the rest of it, because the
+    /// generated content of some element counts the elements before it.
+    flow_tree_depends_on_document_order: bool,
+
+    stylist: Stylist,
     profiler_chan: ProfilerChan,
 }
 
@@ -84,6 +111,17 @@ impl PostorderFlowTraversal for ComputeDamageTraversal {
     }
 }
 
+/// Gives every flow the same restyle damage.
+struct SetDamageTraversal(RestyleDamage);
+
+impl PreorderFlowTraversal for SetDamageTraversal {
+    #[inline]
+    fn process(&mut self, flow: &mut FlowContext) -> bool {
+        flow::mut_base(flow).restyle_damage = **self;
+        true
+    }
+}
+
 /// Propagates restyle damage up and down the tree as appropriate.
 ///
 /// FIXME(pcwalton): Merge this with flow tree building and/or other traversals.
@@ -132,7 +170,9 @@ struct AssignWidthsTraversal<'self>(&'self mut LayoutContext);
 impl<'self> PreorderFlowTraversal for AssignWidthsTraversal<'self> {
     #[inline]
     fn process(&mut self, flow: &mut FlowContext) -> bool {
+        flow.damage_if_width_changed();
         flow.assign_widths(**self);
+        flow.propagate_absolute_containing_block();
         true
     }
 }
@@ -153,6 +193,13 @@ impl<'self> PostorderFlowTraversal for AssignHeightsTraversal<'self> {
     fn should_process(&mut self, flow: &mut FlowContext) -> bool {
         !flow::base(flow).is_inorder
     }
+
+    /// Flows without reflow damage keep their heights. Their floats and inorder children cannot
+    /// have changed either, since those are laid out by a parent that would have the damage too.
+    #[inline]
+    fn should_prune(&mut self, flow: &mut FlowContext) -> bool {
+        flow::base(flow).restyle_damage.lacks(incremental::Reflow)
+    }
 }
 
 /// The display list building traversal. In WebKit this corresponds to `paint`. In Gecko this
@@ -160,21 +207,333 @@ impl<'self> PostorderFlowTraversal for AssignHeightsTraversal<'self> {
 struct DisplayListBuildingTraversal<'self> {
     builder: DisplayListBuilder<'self>,
     root_pos: Rect<Au>,
-    display_list: ~Cell<DisplayList<AbstractNode<()>>>,
+
+    /// The stacking contexts that the traversal is inside, innermost last. The display items of
+    /// each flow go into a layer of the innermost one.
+    stacking_contexts: ~[StackingContext<AbstractNode<()>>],
+
+    /// The boxes whose contents scroll independently and the fixed-position elements, in the order
+    /// of their items in the display list.
+    scroll_layers: ~[ScrollLayer],
+
+    /// The display lists of the contents of the scroll layers, which are added to the end of the
+    /// display list of the page once it is complete.
+    scroll_layer_lists: ~[DisplayList<AbstractNode<()>>],
+
+    /// The ID of the layer that the items of the traversal go into: that of the innermost box
+    /// whose contents scroll independently or fixed-position element that the traversal is
+    /// inside, or `None` for the page.
+    current_layer: Option<uint>,
+
+    /// Whether the traversal is inside a fixed-position element. The fixed-position elements
+    /// inside it are drawn in its layer.
+    in_fixed_layer: bool,
+}
+
+impl<'self> DisplayListBuildingTraversal<'self> {
+    /// Returns the innermost stacking context.
+    fn current_stacking_context<'a>(&'a mut self) -> &'a mut StackingContext<AbstractNode<()>> {
+        let last = self.stacking_contexts.len() - 1;
+        &mut self.stacking_contexts[last]
+    }
+
+    /// Builds the display list of the given flow and its descendants in a stacking context of
+    /// their own, and returns it. The flow either establishes a stacking context or is painted as
+    /// if it did.
+    fn build_stacking_context(&mut self, flow: &mut FlowContext)
+                              -> StackingContext<AbstractNode<()>> {
+        self.stacking_contexts.push(StackingContext::new());
+        if !self.build_flow(flow, BackgroundAndBordersStackingLevel) {
+            self.process(flow);
+            for kid in flow::child_iter(flow) {
+                let _ = kid.traverse_preorder(self);
+            }
+        }
+        self.stacking_contexts.pop()
+    }
+
+    /// Builds the display items of the given flow into the given layer of the innermost stacking
+    /// context. Returns true if the descendants of the flow have been built too.
+    fn build_flow(&mut self, flow: &mut FlowContext, level: StackingLevel) -> bool {
+        let list = Cell::new(util::replace(self.current_stacking_context().list_for_level(level),
+                                           DisplayList::new()));
+        let built_descendants = flow.build_display_list(&self.builder, &self.root_pos, &list);
+        *self.current_stacking_context().list_for_level(level) = list.take();
+        if built_descendants {
+            return true
+        }
+
+        match flow.overflow() {
+            overflow::scroll | overflow::auto => {
+                match flow.absolute_padding_box() {
+                    Some(padding_box) => {
+                        self.build_scroll_layer(flow, padding_box);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            overflow::visible | overflow::hidden => false,
+        }
+    }
+
+    /// Builds the display list of the contents of the given flow, which scroll independently of
+    /// the page, and records the items in a scroll layer. Its children have not been visited yet.
+    /// The layer is a child of the current layer, which the contents of the box scroll with.
+    fn build_scroll_layer(&mut self, flow: &mut FlowContext, padding_box: Rect<Au>) {
+        self.process(flow);
+        for kid in flow::child_iter(flow) {
+            // The layer clips the contents, so they are not clipped again.
+            flow::mut_base(*kid).clip_rect = None;
+        }
+
+        // All of the contents are drawn, since the compositor can scroll to any of them.
+        let old_root_pos = self.root_pos;
+        self.root_pos = unbounded_rect();
+        let id = layer_id_of(flow);
+        let parent = util::replace(&mut self.current_layer, Some(id));
+
+        // The contents are painted in the order of a stacking context of their own, since they are
+        // drawn apart from the rest of the page.
+        self.stacking_contexts.push(StackingContext::new());
+        for kid in flow::child_iter(flow) {
+            let _ = kid.traverse_preorder(self);
+        }
+        let list = self.stacking_contexts.pop().flatten();
+
+        self.current_layer = parent;
+        self.root_pos = old_root_pos;
+
+        // The contents can be scrolled to the right and down only. (CSS 2.1 § 11.1.1)
+        let mut size = padding_box.size;
+        for item in list.list.iter() {
+            let bounds = item.bounds();
+            size.width = Au::max(size.width,
+                                 bounds.origin.x + bounds.size.width - padding_box.origin.x);
+            size.height = Au::max(size.height,
+                                  bounds.origin.y + bounds.size.height - padding_box.origin.y);
+        }
+
+        // The range of the items is known once the display list of the page is complete.
+        self.scroll_layers.push(ScrollLayer {
+            id: id,
+            parent: parent,
+            range: Range::new(0, 0),
+            rect: padding_box,
+            size: size,
+            fixed_position: false,
+        });
+        self.scroll_layer_lists.push(list)
+    }
+
+    /// Builds the display list of the given fixed-position flow and its descendants, and records
+    /// the items in a layer that the compositor keeps in place when the page scrolls. The flow is
+    /// laid out against the viewport as if the page were not scrolled. The layer is a child of
+    /// the page, since the boxes around the element do not move it when they scroll.
+    ///
+    /// TODO: The layer is composited above the page, whatever the `z-index` of the element.
+    fn build_fixed_layer(&mut self, flow: &mut FlowContext) {
+        // The initial containing block does not clip its contents, and the compositor can show
+        // the layer over any part of the page.
+        flow::mut_base(flow).clip_rect = None;
+        let old_root_pos = self.root_pos;
+        self.root_pos = unbounded_rect();
+        let id = layer_id_of(flow);
+        let old_layer = util::replace(&mut self.current_layer, Some(id));
+        self.in_fixed_layer = true;
+
+        let list = self.build_stacking_context(flow).flatten();
+
+        self.in_fixed_layer = false;
+        self.current_layer = old_layer;
+        self.root_pos = old_root_pos;
+
+        let mut bounds: Option<Rect<Au>> = None;
+        for item in list.list.iter() {
+            bounds = match bounds {
+                None => Some(item.bounds()),
+                Some(bounds) => Some(bounds.union(&item.bounds())),
+            };
+        }
+        let bounds = match bounds {
+            None => return,
+            Some(bounds) => bounds,
+        };
+
+        self.scroll_layers.push(ScrollLayer {
+            id: id,
+            parent: None,
+            range: Range::new(0, 0),
+            rect: bounds,
+            size: bounds.size,
+            fixed_position: true,
+        });
+        self.scroll_layer_lists.push(list)
+    }
+
+    /// Flattens the stacking context of the root flow into the display list of the page, and adds
+    /// the contents of the scroll layers at the end of it.
+    fn finish(&mut self, root_context: StackingContext<AbstractNode<()>>)
+              -> DisplayList<AbstractNode<()>> {
+        let mut display_list = root_context.flatten();
+        let scroll_layer_lists = util::replace(&mut self.scroll_layer_lists, ~[]);
+        for (scroll_layer, list) in self.scroll_layers.mut_iter()
+                                                      .zip(scroll_layer_lists.move_iter()) {
+            scroll_layer.range = Range::new(display_list.len(), list.len());
+            display_list.append_list(list)
+        }
+        display_list
+    }
 }
 
 impl<'self> PreorderFlowTraversal for DisplayListBuildingTraversal<'self> {
     #[inline]
     fn process(&mut self, flow: &mut FlowContext) -> bool {
+        // The flow has its final position now, so its absolutely positioned descendants can be
+        // placed against it, and its size and clip rect can be given to its children.
+        flow.propagate_absolute_containing_block();
+        flow.propagate_containing_block_size();
+        flow.propagate_clip_rect();
         true
     }
 
+    /// Sorts the display items of the flow into the layers of the innermost stacking context. The
+    /// flows that establish stacking contexts, and the floats and inline-blocks, which are painted
+    /// as if they did, are built with their descendants here. (CSS 2.1 Appendix E)
     #[inline]
     fn should_prune(&mut self, flow: &mut FlowContext) -> bool {
-        flow.build_display_list(&self.builder, &self.root_pos, self.display_list)
+        match flow.stacking_context_z_index() {
+            Some(_) if flow.is_fixed_position() && !self.in_fixed_layer => {
+                self.build_fixed_layer(flow);
+                true
+            }
+            Some(z_index) => {
+                let context = self.build_stacking_context(flow);
+                self.current_stacking_context().add_positioned_descendant(z_index, context);
+                true
+            }
+            None => {
+                match flow.class() {
+                    FloatFlowClass | InlineBlockFlowClass => {
+                        let level = if flow.class() == FloatFlowClass {
+                            FloatStackingLevel
+                        } else {
+                            ContentStackingLevel
+                        };
+                        let list = self.build_stacking_context(flow).flatten();
+                        self.current_stacking_context().list_for_level(level).append_list(list);
+                        true
+                    }
+                    InlineFlowClass => self.build_flow(flow, ContentStackingLevel),
+                    _ => self.build_flow(flow, BlockBackgroundsAndBordersStackingLevel),
+                }
+            }
+        }
     }
 }
 
+/// Returns the ID of the layer of the given flow: the address of its node, which stays the same
+/// when the page is laid out again, so the compositor can keep the scroll offset of the layer.
+fn layer_id_of(flow: &FlowContext) -> uint {
+    unsafe {
+        flow::base(flow).node.raw_object() as uint
+    }
+}
+
+/// Returns a rectangle that contains every display item, for the display lists of layers that the
+/// compositor can show any part of.
+fn unbounded_rect() -> Rect<Au> {
+    Rect(Point2D(Au(i32::min_value / 4), Au(i32::min_value / 4)),
+         Size2D(Au(i32::max_value / 2), Au(i32::max_value / 2)))
+}
+
+/// Builds the subtrees of the given elements again and puts them in place of their old flows.
+/// Returns false if that cannot be done, in which case the whole flow tree has to be built again.
+fn rebuild_subtrees(layout_ctx: &LayoutContext,
+                    flow_tree: &mut FlowContext,
+                    elements: &[AbstractNode<LayoutView>])
+                    -> bool {
+    flow_tree.traverse_preorder(&mut SetDamageTraversal(RestyleDamage::none()));
+
+    for &element in elements.iter() {
+        let mut builder = LayoutTreeBuilder::new();
+        let mut new_flow = match builder.construct_block_subtree(layout_ctx, element) {
+            Some(new_flow) => new_flow,
+            None => return false,
+        };
+        if builder.depends_on_document_order() {
+            return false
+        }
+
+        new_flow.traverse_preorder(&mut SetDamageTraversal(RestyleDamage::all()));
+        if !flow_tree.replace_block_flow_of(element, &Cell::new(new_flow)) {
+            return false
+        }
+
+        // The display items of the subtree are all new, so they are all rendered again.
+        for node in element.traverse_preorder() {
+            node.set_restyle_damage(node.restyle_damage().add(Repaint))
+        }
+    }
+    true
+}
+
+/// Returns the parts of the page that look different in the new display list: the bounds of the
+/// items that were added, removed or moved, and of the items of the nodes that were repainted.
+fn damaged_rects(old_list: &DisplayList<AbstractNode<()>>,
+                 new_list: &DisplayList<AbstractNode<()>>)
+                 -> ~[Rect<Au>] {
+    let (old_items, new_items) = (&old_list.list, &new_list.list);
+    let mut rects = ~[];
+    for i in range(0, uint::max(old_items.len(), new_items.len())) {
+        if i < old_items.len() && i < new_items.len() {
+            let (old_item, new_item) = (&old_items[i], &new_items[i]);
+            if old_item.bounds() == new_item.bounds() &&
+                    old_item.base().extra == new_item.base().extra &&
+                    old_item.base().clip_rect == new_item.base().clip_rect &&
+                    !needs_repaint(new_item.base().extra) {
+                loop
+            }
+        }
+        if i < old_items.len() {
+            add_damaged_rect(&mut rects, old_items[i].bounds())
+        }
+        if i < new_items.len() {
+            add_damaged_rect(&mut rects, new_items[i].bounds())
+        }
+    }
+    rects
+}
+
+/// Returns true if the node of a display item was restyled in a way that changes how it looks.
+/// Text takes its look from its parent element.
+fn needs_repaint(node: AbstractNode<()>) -> bool {
+    let node: AbstractNode<LayoutView> = unsafe {
+        transmute(node)
+    };
+    if node.restyle_damage().has(Repaint) {
+        return true
+    }
+    match node.parent_node() {
+        Some(parent) if !node.is_element() => parent.restyle_damage().has(Repaint),
+        _ => false,
+    }
+}
+
+/// Adds a rect to the damaged rects, merging it into the first one that it intersects.
+fn add_damaged_rect(rects: &mut ~[Rect<Au>], rect: Rect<Au>) {
+    if rect.size.width == Au(0) || rect.size.height == Au(0) {
+        return
+    }
+    for damaged_rect in rects.mut_iter() {
+        if damaged_rect.intersects(&rect) {
+            *damaged_rect = damaged_rect.union(&rect);
+            return
+        }
+    }
+    rects.push(rect)
+}
+
 struct LayoutImageResponder {
     id: PipelineId,
     script_chan: ScriptChan,
@@ -198,6 +557,7 @@ impl LayoutTask {
                   script_chan: ScriptChan,
                   render_chan: RenderChan<AbstractNode<()>>,
                   img_cache_task: ImageCacheTask,
+                  resource_task: ResourceTask,
                   opts: Opts,
                   profiler_chan: ProfilerChan) {
 
@@ -206,6 +566,7 @@ impl LayoutTask {
         let script_chan = Cell::new(script_chan);
         let render_chan = Cell::new(render_chan);
         let img_cache_task = Cell::new(img_cache_task);
+        let resource_task = Cell::new(resource_task);
         let profiler_chan = Cell::new(profiler_chan);
 
         do spawn {
@@ -215,6 +576,7 @@ impl LayoutTask {
                                              script_chan.take(),
                                              render_chan.take(),
                                              img_cache_task.take(),
+                                             resource_task.take(),
                                              &opts,
                                              profiler_chan.take());
             layout.start();
@@ -227,10 +589,12 @@ impl LayoutTask {
            script_chan: ScriptChan,
            render_chan: RenderChan<AbstractNode<()>>, 
            image_cache_task: ImageCacheTask,
+           resource_task: ResourceTask,
            opts: &Opts,
            profiler_chan: ProfilerChan)
            -> LayoutTask {
         let fctx = @mut FontContext::new(opts.render_backend, true, profiler_chan.clone());
+        let (web_font_port, web_font_chan) = stream();
 
         LayoutTask {
             id: id,
@@ -241,12 +605,21 @@ impl LayoutTask {
             image_cache_task: image_cache_task.clone(),
             local_image_cache: @mut LocalImageCache(image_cache_task),
             font_ctx: fctx,
+            resource_task: resource_task,
+            web_font_port: web_font_port,
+            web_font_chan: SharedChan::new(web_font_chan),
             doc_url: None,
             screen_size: None,
+            device_pixel_ratio: 1.0,
 
             display_list: None,
-            
-            css_select_ctx: @mut new_css_select_ctx(),
+            display_list_size: Size2D(0, 0),
+            display_list_had_scroll_layers: false,
+
+            flow_tree: None,
+            flow_tree_depends_on_document_order: false,
+
+            stylist: new_stylist(),
             profiler_chan: profiler_chan,
         }
     }
@@ -298,9 +671,52 @@ impl LayoutTask {
         true
     }
 
-    fn handle_add_stylesheet(&self, sheet: Stylesheet) {
-        let sheet = Cell::new(sheet);
-        self.css_select_ctx.append_sheet(sheet.take(), OriginAuthor);
+    fn handle_add_stylesheet(&mut self, sheet: Stylesheet) {
+        do sheet.each_font_face_rule(self.stylist.device()) |rule| {
+            let id = self.id.clone();
+            let script_chan = self.script_chan.clone();
+            web_fonts::load_font_face(rule,
+                                      self.resource_task.clone(),
+                                      self.web_font_chan.clone(),
+                                      || script_chan.send(SendEventMsg(id.clone(), ReflowEvent)))
+        }
+        self.stylist.add_stylesheet(sheet, AuthorOrigin);
+    }
+
+    /// Adds the web fonts that finished loading to the font context. Returns whether there were
+    /// any, in which case all text has to be laid out again.
+    fn add_loaded_web_fonts(&mut self) -> bool {
+        let mut added = false;
+        while self.web_font_port.peek() {
+            self.font_ctx.add_web_font(self.web_font_port.recv());
+            added = true
+        }
+        added
+    }
+
+    /// Returns the flow tree of the document, and whether it was built entirely. The tree of the
+    /// last reflow is kept if it can be, and only the subtrees of the elements that changed are
+    /// built again. The flows that were built have all of the restyle damage and the others none.
+    fn build_flow_tree(&mut self, layout_ctx: &LayoutContext, root: AbstractNode<LayoutView>)
+                       -> (~FlowContext:, bool) {
+        let dirty_subtrees = find_dirty_subtrees(root);
+        let flow_tree = util::replace(&mut self.flow_tree, None);
+        if flow_tree.is_some() && dirty_subtrees.is_some() &&
+                !self.flow_tree_depends_on_document_order {
+            let mut flow_tree = flow_tree.unwrap();
+            if rebuild_subtrees(layout_ctx, flow_tree, dirty_subtrees.unwrap()) {
+                return (flow_tree, false)
+            }
+        }
+
+        let mut builder = LayoutTreeBuilder::new();
+        let mut flow_tree: ~FlowContext: = match builder.construct_trees(layout_ctx, root) {
+            Ok(root) => root,
+            Err(*) => fail!(~"Root flow should always exist")
+        };
+        self.flow_tree_depends_on_document_order = builder.depends_on_document_order();
+        flow_tree.traverse_preorder(&mut SetDamageTraversal(RestyleDamage::all()));
+        (flow_tree, true)
     }
 
     /// The high-level routine that performs layout tasks.
@@ -324,9 +740,26 @@ impl LayoutTask {
         self.doc_url = Some(doc_url);
         let screen_size = Size2D(Au::from_px(data.window_size.width as int),
                                  Au::from_px(data.window_size.height as int));
-        let resized = self.screen_size != Some(screen_size);
+        let resized = self.screen_size != Some(screen_size) ||
+            self.device_pixel_ratio != data.device_pixel_ratio;
         debug!("resized: %?", resized);
         self.screen_size = Some(screen_size);
+        self.device_pixel_ratio = data.device_pixel_ratio;
+
+        // Media queries may evaluate differently with the new viewport size or device pixel
+        // ratio, in which case all elements are restyled.
+        let media_queries_changed = resized && {
+            let device = Device::new(Screen,
+                                     Length(*screen_size.width as i64),
+                                     Length(*screen_size.height as i64),
+                                     self.device_pixel_ratio as f64);
+            self.stylist.set_device(device)
+        };
+
+        // Text runs are made again with the fonts that have loaded since the last reflow.
+        if self.add_loaded_web_fonts() {
+            self.flow_tree = None
+        }
 
         // Create a layout context for use throughout the following passes.
         let mut layout_ctx = self.build_layout_context();
@@ -339,25 +772,20 @@ impl LayoutTask {
         }
 
         // Perform CSS selector matching if necessary.
-        match data.damage.level {
-            ReflowDocumentDamage => {}
-            MatchSelectorsDocumentDamage => {
-                do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
-                    node.restyle_subtree(self.css_select_ctx);
-                }
+        let needs_restyle = match data.damage.level {
+            ReflowDocumentDamage => media_queries_changed,
+            MatchSelectorsDocumentDamage => true,
+        };
+        if needs_restyle {
+            do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
+                node.restyle_subtree(&self.stylist);
             }
         }
 
-        // Construct the flow tree.
-        let mut layout_root: ~FlowContext: = do profile(time::LayoutTreeBuilderCategory,
-                                                   self.profiler_chan.clone()) {
-            let mut builder = LayoutTreeBuilder::new();
-            let layout_root: ~FlowContext: = match builder.construct_trees(&layout_ctx, *node) {
-                Ok(root) => root,
-                Err(*) => fail!(~"Root flow should always exist")
-            };
-
-            layout_root
+        // Construct the flow tree, or the parts of it that changed.
+        let (mut layout_root, rebuilt_entirely) = do profile(time::LayoutTreeBuilderCategory,
+                                                             self.profiler_chan.clone()) {
+            self.build_flow_tree(&layout_ctx, *node)
         };
 
         // Propagate damage.
@@ -372,16 +800,12 @@ impl LayoutTask {
         // Perform the primary layout passes over the flow tree to compute the locations of all
         // the boxes.
         do profile(time::LayoutMainCategory, self.profiler_chan.clone()) {
+            // Widths are assigned to every flow, since that is how flows whose width changed
+            // find out that they have to be laid out again.
             let _ = layout_root.traverse_postorder(&mut BubbleWidthsTraversal(&mut layout_ctx));
-
-            // FIXME(kmc): We want to do
-            //     for flow in layout_root.traverse_preorder_prune(|f| f.restyle_damage().lacks(Reflow)) 
-            // but FloatContext values can't be reused, so we need to recompute them every time.
-            // NOTE: this currently computes borders, so any pruning should separate that operation out.
             let _ = layout_root.traverse_preorder(&mut AssignWidthsTraversal(&mut layout_ctx));
 
             // For now, this is an inorder traversal
-            // FIXME: prune this traversal as well
             let _ = layout_root.traverse_postorder(&mut AssignHeightsTraversal(&mut layout_ctx));
         }
 
@@ -389,21 +813,24 @@ impl LayoutTask {
         if data.goal == ReflowForDisplay {
             do profile(time::LayoutDispListBuildCategory, self.profiler_chan.clone()) {
                 // TODO: Set options on the builder before building.
-                // TODO: Be smarter about what needs painting.
-                let root_pos = &flow::base(layout_root).position.clone();
                 let mut traversal = DisplayListBuildingTraversal {
                     builder: DisplayListBuilder {
                         ctx: &layout_ctx,
                     },
                     root_pos: flow::base(layout_root).position.clone(),
-                    display_list: ~Cell::new(DisplayList::<AbstractNode<()>>::new()),
+                    stacking_contexts: ~[],
+                    scroll_layers: ~[],
+                    scroll_layer_lists: ~[],
+                    current_layer: None,
+                    in_fixed_layer: false,
                 };
 
-                let _ = layout_root.traverse_preorder(&mut traversal);
+                // The root element establishes the root stacking context.
+                let root_context = traversal.build_stacking_context(layout_root);
 
                 let root_size = flow::base(layout_root).position.size;
 
-                let display_list = Arc::new(traversal.display_list.take());
+                let display_list = Arc::new(traversal.finish(root_context));
 
                 for i in range(0,display_list.get().list.len()) {
                     let node: AbstractNode<LayoutView> = unsafe {
@@ -417,22 +844,39 @@ impl LayoutTask {
                             debug!("Creating initial range for node");
                             layout_data.boxes.range = Some(Range::new(i,1));
                         } else {
-                            debug!("Appending item to range");
-                            unsafe {
-                                let old_node: AbstractNode<()> = transmute(node);
-                                assert!(old_node == display_list.get().list[i-1].base().extra,
-                                "Non-contiguous arrangement of display items");
-                            }
-
-                            layout_data.boxes.range.unwrap().extend_by(1);
+                            debug!("Extending range to item");
+                            // The items of an element are not contiguous when it generates
+                            // content before or after the items of its children.
+                            let mut range = layout_data.boxes.range.unwrap();
+                            range.extend_to(i + 1);
+                            layout_data.boxes.range = Some(range);
                         }
                     }
                 }
 
+                let size = Size2D(root_size.width.to_nearest_px() as uint,
+                                  root_size.height.to_nearest_px() as uint);
+                let scroll_layers = util::replace(&mut traversal.scroll_layers, ~[]);
+
+                // Only the parts of the page that changed are rendered again, unless the page as a
+                // whole may have.
+                let damaged_rects = match self.display_list {
+                    Some(ref old_display_list) if !rebuilt_entirely && !resized &&
+                            size == self.display_list_size && scroll_layers.is_empty() &&
+                            !self.display_list_had_scroll_layers => {
+                        Some(damaged_rects(old_display_list.get(), display_list.get()))
+                    }
+                    _ => None,
+                };
+
+                self.display_list_size = size;
+                self.display_list_had_scroll_layers = !scroll_layers.is_empty();
+
                 let render_layer = RenderLayer {
                     display_list: display_list.clone(),
-                    size: Size2D(root_size.width.to_nearest_px() as uint,
-                                 root_size.height.to_nearest_px() as uint)
+                    size: size,
+                    scroll_layers: scroll_layers,
+                    damaged_rects: damaged_rects,
                 };
 
                 self.display_list = Some(display_list.clone());
@@ -441,6 +885,8 @@ impl LayoutTask {
             } // time(layout: display list building)
         }
 
+        self.flow_tree = Some(layout_root);
+
         // Tell script that we're done.
         //
         // FIXME(pcwalton): This should probably be *one* channel, but we can't fix this without
@@ -538,8 +984,18 @@ impl LayoutTask {
                             // iterate in reverse to ensure we have the most recently painted render box
                             for display_item in display_list.list.rev_iter() {
                                 let bounds = display_item.bounds();
+                                // Items are not hit where they are clipped away.
+                                let clipped_out = match display_item.base().clip_rect {
+                                    Some(clip) => {
+                                        x < clip.origin.x || x >= clip.origin.x + clip.size.width ||
+                                            y < clip.origin.y ||
+                                            y >= clip.origin.y + clip.size.height
+                                    }
+                                    None => false,
+                                };
                                 // TODO this check should really be performed by a method of DisplayItem
-                                if x <= bounds.origin.x + bounds.size.width &&
+                                if !clipped_out &&
+                                    x <= bounds.origin.x + bounds.size.width &&
                                     bounds.origin.x <= x &&
                                         y < bounds.origin.y + bounds.size.height &&
                                         bounds.origin.y <  y {
@@ -7,14 +7,12 @@
 use std::num::Zero;
 use geom::side_offsets::SideOffsets2D;
 use gfx::geometry::Au;
-use newcss::complete::CompleteStyle;
-use newcss::units::{Length, Em, Px};
-use newcss::values::{CSSBorderWidth, CSSBorderWidthLength, CSSBorderWidthMedium};
-use newcss::values::{CSSBorderWidthThick, CSSBorderWidthThin, CSSFontSize, CSSFontSizeLength};
-use newcss::values::{CSSWidth, CSSWidthLength, CSSWidthPercentage, CSSWidthAuto};
-use newcss::values::{CSSHeight, CSSHeightLength, CSSHeightPercentage, CSSHeightAuto};
-use newcss::values::{CSSMargin, CSSMarginLength, CSSMarginPercentage, CSSMarginAuto};
-use newcss::values::{CSSPadding, CSSPaddingLength, CSSPaddingPercentage};
+use style::ComputedValues;
+use style::computed_values::{LengthOrPercentage, LP_Length, LP_Percentage};
+use style::computed_values::{LengthOrPercentageOrAuto, LPA_Length, LPA_Percentage, LPA_Auto};
+use style::computed_values::{LengthOrPercentageOrNone, LPN_Length, LPN_Percentage, LPN_None};
+use style::computed_values::{Length, box_sizing};
+
 /// Encapsulates the borders, padding, and margins, which we collectively call the "box model".
 pub struct BoxModel {
     border: SideOffsets2D<Au>,
@@ -24,16 +22,10 @@ pub struct BoxModel {
     content_box_width: Au,
 }
 
-fn from_length(length: Length, font_size: CSSFontSize) -> Au {
-    match length {
-        Px(v) => Au::from_frac_px(v),
-        Em(em) => {
-            match font_size {
-                CSSFontSizeLength(Px(v)) => Au::from_frac_px(em * v),
-                _ => fail!("expected non-relative font size")
-            }
-        }
-    }
+/// Converts a computed length, which is already in application units, to an `Au`.
+#[inline]
+pub fn from_length(length: Length) -> Au {
+    Au::new(*length as i32)
 }
 
 /// Useful helper data type when computing values for blocks and positioned elements.
@@ -44,43 +36,108 @@ pub enum MaybeAuto {
 
 impl MaybeAuto {
     #[inline]
-    pub fn from_margin(margin: CSSMargin, containing_width: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match margin {
-            CSSMarginAuto => Auto,
-            CSSMarginPercentage(percent) => Specified(containing_width.scale_by(percent/100.0)),
-            CSSMarginLength(length) => Specified(from_length(length, font_size))
+    pub fn from_style(length: LengthOrPercentageOrAuto, containing_length: Au) -> MaybeAuto {
+        match length {
+            LPA_Auto => Auto,
+            LPA_Percentage(percent) => Specified(containing_length.scale_by(percent)),
+            LPA_Length(length) => Specified(from_length(length))
         }
     }
 
     #[inline]
-    pub fn from_width(width: CSSWidth, containing_width: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match width {
-            CSSWidthAuto => Auto,
-            CSSWidthPercentage(percent) => Specified(containing_width.scale_by(percent/100.0)),
-            CSSWidthLength(length) => Specified(from_length(length, font_size))
+    pub fn specified_or_default(&self, default: Au) -> Au {
+        match *self {
+            Auto => default,
+            Specified(value) => value,
         }
     }
 
     #[inline]
-    pub fn from_height(height: CSSHeight, cb_height: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match height {
-            CSSHeightAuto => Auto,
-            CSSHeightPercentage(percent) => Specified(cb_height.scale_by(percent/100.0)),
-            CSSHeightLength(length) => Specified(from_length(length, font_size))
-        }
+    pub fn specified_or_zero(&self) -> Au {
+        self.specified_or_default(Au::new(0))
     }
 
+    /// Subtracts `offset` from a specified length, which does not become negative. This turns a
+    /// 'width' or 'height' that includes borders and padding into the size of the content box.
     #[inline]
-    pub fn specified_or_default(&self, default: Au) -> Au {
+    pub fn minus(&self, offset: Au) -> MaybeAuto {
         match *self {
-            Auto => default,
-            Specified(value) => value,
+            Auto => Auto,
+            Specified(value) => Specified(Au::max(value - offset, Au::new(0))),
         }
     }
+}
 
-    #[inline]
-    pub fn specified_or_zero(&self) -> Au {
-        self.specified_or_default(Au::new(0))
+/// Returns how much larger than the content box the 'width', 'height', 'min-*' and 'max-*'
+/// properties of a box are along one dimension, whose borders and padding add up to
+/// `border_padding`. This is zero unless the box has 'box-sizing: border-box'.
+pub fn box_sizing_offset(style: &ComputedValues, border_padding: Au) -> Au {
+    match style.Box.box_sizing {
+        box_sizing::content_box => Au::new(0),
+        box_sizing::border_box => border_padding,
+    }
+}
+
+/// The used values of 'min-width' and 'max-width', or of 'min-height' and 'max-height', as sizes
+/// of the content box. (CSS 2.1 § 10.4 and § 10.7)
+pub struct SizeConstraint {
+    min: Au,
+    /// None if the size has no maximum.
+    max: Option<Au>,
+}
+
+impl SizeConstraint {
+    /// Resolves the given minimum and maximum against the size of the containing block, which is
+    /// None if it is not known yet. Percentages of an unknown size place no constraint.
+    pub fn new(min: LengthOrPercentage,
+               max: LengthOrPercentageOrNone,
+               containing_length: Option<Au>,
+               offset: Au)
+               -> SizeConstraint {
+        let min = match (min, containing_length) {
+            (LP_Length(length), _) => from_length(length),
+            (LP_Percentage(percent), Some(containing_length)) => {
+                containing_length.scale_by(percent)
+            }
+            (LP_Percentage(_), None) => Au::new(0),
+        };
+        let max = match (max, containing_length) {
+            (LPN_Length(length), _) => Some(from_length(length)),
+            (LPN_Percentage(percent), Some(containing_length)) => {
+                Some(containing_length.scale_by(percent))
+            }
+            (LPN_Percentage(_), None) | (LPN_None, _) => None,
+        };
+        SizeConstraint {
+            min: Au::max(min - offset, Au::new(0)),
+            max: max.map_move(|max| Au::max(max - offset, Au::new(0))),
+        }
+    }
+
+    /// Returns the constraint that 'min-width' and 'max-width' place on the given style.
+    pub fn widths(style: &ComputedValues, containing_width: Au, offset: Au) -> SizeConstraint {
+        SizeConstraint::new(style.Box.min_width,
+                            style.Box.max_width,
+                            Some(containing_width),
+                            offset)
+    }
+
+    /// Returns the constraint that 'min-height' and 'max-height' place on the given style.
+    ///
+    /// TODO: Resolve percentages against the height of the containing block, when it is
+    /// specified.
+    pub fn heights(style: &ComputedValues, offset: Au) -> SizeConstraint {
+        SizeConstraint::new(style.Box.min_height, style.Box.max_height, None, offset)
+    }
+
+    /// Applies the maximum and then the minimum to the given size, so the minimum wins if they
+    /// conflict.
+    pub fn clamp(&self, size: Au) -> Au {
+        let size = match self.max {
+            Some(max) => Au::min(size, max),
+            None => size,
+        };
+        Au::max(size, self.min)
     }
 }
 
@@ -101,21 +158,23 @@ impl Zero for BoxModel {
 
 impl BoxModel {
     /// Populates the box model parameters from the given computed style.
-    pub fn compute_borders(&mut self, style: CompleteStyle) {
+    pub fn compute_borders(&mut self, style: &ComputedValues) {
         // Compute the borders.
-        let font_size = style.font_size();
-        self.border.top = self.compute_border_width(style.border_top_width(), font_size);
-        self.border.right = self.compute_border_width(style.border_right_width(), font_size);
-        self.border.bottom = self.compute_border_width(style.border_bottom_width(), font_size);
-        self.border.left = self.compute_border_width(style.border_left_width(), font_size);
+        self.border.top = from_length(style.Border.border_top_width);
+        self.border.right = from_length(style.Border.border_right_width);
+        self.border.bottom = from_length(style.Border.border_bottom_width);
+        self.border.left = from_length(style.Border.border_left_width);
     }
 
-    pub fn compute_padding(&mut self, style: CompleteStyle, containing_width: Au) {
-        let font_size = style.font_size();
-        self.padding.top = self.compute_padding_length(style.padding_top(), containing_width, font_size);
-        self.padding.right = self.compute_padding_length(style.padding_right(), containing_width, font_size);
-        self.padding.bottom = self.compute_padding_length(style.padding_bottom(), containing_width, font_size);
-        self.padding.left = self.compute_padding_length(style.padding_left(), containing_width, font_size);
+    pub fn compute_padding(&mut self, style: &ComputedValues, containing_width: Au) {
+        self.padding.top = self.compute_padding_length(style.Padding.padding_top,
+                                                       containing_width);
+        self.padding.right = self.compute_padding_length(style.Padding.padding_right,
+                                                         containing_width);
+        self.padding.bottom = self.compute_padding_length(style.Padding.padding_bottom,
+                                                          containing_width);
+        self.padding.left = self.compute_padding_length(style.Padding.padding_left,
+                                                        containing_width);
     }
 
     pub fn noncontent_width(&self) -> Au {
@@ -134,20 +193,132 @@ impl BoxModel {
         self.margin.left + self.border.left + self.padding.left
     }
 
-    /// Helper function to compute the border width in app units from the CSS border width.
-    pub fn compute_border_width(&self, width: CSSBorderWidth, font_size: CSSFontSize) -> Au {
-        match width {
-            CSSBorderWidthLength(length) => from_length(length, font_size),
-            CSSBorderWidthThin => Au::from_px(1),
-            CSSBorderWidthMedium => Au::from_px(5),
-            CSSBorderWidthThick => Au::from_px(10),
+    pub fn compute_padding_length(&self, padding: LengthOrPercentage, content_box_width: Au)
+                                  -> Au {
+        match padding {
+            LP_Length(length) => from_length(length),
+            LP_Percentage(p) => content_box_width.scale_by(p)
         }
     }
+}
 
-    pub fn compute_padding_length(&self, padding: CSSPadding, content_box_width: Au, font_size: CSSFontSize) -> Au {
-        match padding {
-            CSSPaddingLength(length) => from_length(length, font_size),
-            CSSPaddingPercentage(p) => content_box_width.scale_by(p/100.0)
+/// The margins that adjoin at one place in a block formatting context, which collapse into a
+/// single margin. (CSS 2.1 § 8.3.1)
+pub struct AdjoiningMargins {
+    /// The largest of the positive margins, or zero if there are none.
+    most_positive: Au,
+    /// The most negative of the negative margins, or zero if there are none.
+    most_negative: Au,
+}
+
+impl AdjoiningMargins {
+    /// Returns an empty set of margins, which collapse to zero.
+    pub fn new() -> AdjoiningMargins {
+        AdjoiningMargins {
+            most_positive: Au::new(0),
+            most_negative: Au::new(0),
+        }
+    }
+
+    /// Returns the set containing only the given margin.
+    pub fn from_margin(margin: Au) -> AdjoiningMargins {
+        AdjoiningMargins::new().union_margin(margin)
+    }
+
+    /// Returns this set with the given margin added.
+    pub fn union_margin(&self, margin: Au) -> AdjoiningMargins {
+        AdjoiningMargins {
+            most_positive: Au::max(self.most_positive, margin),
+            most_negative: Au::min(self.most_negative, margin),
+        }
+    }
+
+    /// Returns the union of this set and the given one.
+    pub fn union(&self, other: &AdjoiningMargins) -> AdjoiningMargins {
+        AdjoiningMargins {
+            most_positive: Au::max(self.most_positive, other.most_positive),
+            most_negative: Au::min(self.most_negative, other.most_negative),
+        }
+    }
+
+    /// Returns the width of the collapsed margin: the largest positive margin plus the most
+    /// negative margin.
+    pub fn collapse(&self) -> Au {
+        self.most_positive + self.most_negative
+    }
+}
+
+/// How a flow takes part in margin collapsing in the block formatting context of its parent,
+/// once its height is known.
+pub struct CollapsibleMargins {
+    /// Whether the flow is in the normal flow. The margins around a float or an absolutely
+    /// positioned element collapse as if it were not there.
+    in_flow: bool,
+
+    /// The margins that adjoin the top border edge of the flow: its own top margin and those of
+    /// its descendants that collapse with it. If the margins collapse through the flow, all of
+    /// them.
+    top: AdjoiningMargins,
+
+    /// The margins that adjoin the bottom border edge of the flow, unless the margins collapse
+    /// through the flow.
+    bottom: AdjoiningMargins,
+
+    /// Whether the top and bottom margins of the flow adjoin, because it is empty, so that the
+    /// margins above it collapse with those below it.
+    collapse_through: bool,
+
+    /// Whether the flow has clearance, which keeps its top margin from collapsing with the
+    /// margins above it.
+    has_clearance: bool,
+
+    /// The distance from the top of the flow to its top border edge.
+    top_inset: Au,
+
+    /// The distance from the bottom border edge of the flow to its bottom.
+    bottom_inset: Au,
+}
+
+impl CollapsibleMargins {
+    /// Returns the collapsible margins of a flow that is out of the normal flow.
+    pub fn out_of_flow() -> CollapsibleMargins {
+        CollapsibleMargins {
+            in_flow: false,
+            top: AdjoiningMargins::new(),
+            bottom: AdjoiningMargins::new(),
+            collapse_through: false,
+            has_clearance: false,
+            top_inset: Au::new(0),
+            bottom_inset: Au::new(0),
+        }
+    }
+
+    /// Returns the collapsible margins of an in-flow flow whose margins do not collapse with
+    /// those of its children, given its top and bottom margins.
+    pub fn from_margins(top: Au, bottom: Au) -> CollapsibleMargins {
+        CollapsibleMargins {
+            in_flow: true,
+            top: AdjoiningMargins::from_margin(top),
+            bottom: AdjoiningMargins::from_margin(bottom),
+            collapse_through: false,
+            has_clearance: false,
+            top_inset: top,
+            bottom_inset: bottom,
+        }
+    }
+
+    /// Returns the collapsible margins of an in-flow flow without margins of its own, such as a
+    /// line box: if it is empty, the margins around it collapse through it; otherwise it separates
+    /// them.
+    pub fn without_margins(empty: bool) -> CollapsibleMargins {
+        CollapsibleMargins {
+            in_flow: true,
+            top: AdjoiningMargins::new(),
+            bottom: AdjoiningMargins::new(),
+            collapse_through: empty,
+            has_clearance: false,
+            top_inset: Au::new(0),
+            bottom_inset: Au::new(0),
         }
     }
 }
@@ -0,0 +1,711 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS tables. The table flow places the cells in a grid, computes the widths of its columns, and
+//! stacks its captions and row groups.
+
+use css::node_style::StyledNode;
+use layout::block::BlockFlow;
+use layout::box::{RenderBox, RenderBoxUtils};
+use layout::context::LayoutContext;
+use layout::flow::{FlowClass, FlowContext, FlowData, TableColGroupFlowClass, TableFlowClass};
+use layout::flow::TableRowGroupFlowClass;
+use layout::flow;
+use layout::float_context::FloatContext;
+use layout::model::{Auto, CollapsibleMargins, MaybeAuto, Specified};
+use layout::model;
+
+use std::uint;
+use std::vec;
+use geom::side_offsets::SideOffsets2D;
+use geom::size::Size2D;
+use gfx::geometry::{Au, to_frac_px};
+use gfx::geometry;
+use style::computed_values::{LPA_Length, border_collapse, table_layout};
+
+pub struct TableFlow {
+    /// The block flow that holds the render box of the table, if it is not anonymous.
+    block_flow: BlockFlow,
+
+    /// The minimum width of each column, from the cells in it.
+    column_min_widths: ~[Au],
+
+    /// The preferred width of each column, from the cells in it.
+    column_pref_widths: ~[Au],
+
+    /// The used width of each column.
+    column_widths: ~[Au],
+
+    /// The horizontal and vertical space between cells. It is always zero in the collapsing
+    /// border model.
+    border_spacing: Size2D<Au>,
+
+    /// The minimum width of the contents of the table: its columns and captions.
+    min_content_width: Au,
+
+    /// The preferred width of the contents of the table: its columns and captions.
+    pref_content_width: Au,
+}
+
+/// A cell of the table, as the table sees it when it computes the widths of the columns.
+struct CellInfo {
+    /// The index of the first row of the cell, counting the rows of all row groups.
+    row: uint,
+    /// The index of the first column of the cell.
+    column: uint,
+    /// The number of columns that the cell spans.
+    colspan: uint,
+    /// The number of rows that the cell spans, never past the end of its row group.
+    rowspan: uint,
+    /// The render box of the cell, if it is not anonymous.
+    box: Option<@mut RenderBox>,
+    /// The minimum width of the cell, including its borders and padding.
+    min_width: Au,
+    /// The preferred width of the cell, including its borders and padding.
+    pref_width: Au,
+}
+
+impl TableFlow {
+    pub fn new(base: FlowData) -> TableFlow {
+        TableFlow {
+            block_flow: BlockFlow::new(base),
+            column_min_widths: ~[],
+            column_pref_widths: ~[],
+            column_widths: ~[],
+            border_spacing: Size2D(Au::new(0), Au::new(0)),
+            min_content_width: Au::new(0),
+            pref_content_width: Au::new(0),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown();
+        self.column_min_widths = ~[];
+        self.column_pref_widths = ~[];
+        self.column_widths = ~[];
+    }
+
+    /// Gives each cell its first column, skipping the columns that cells from the rows above
+    /// still occupy, and returns the cells of the table in document order along with the number
+    /// of rows. (CSS 2.1 § 17.5)
+    fn place_cells(&mut self) -> (~[CellInfo], uint) {
+        let mut cells = ~[];
+        let mut row_count = 0;
+        for kid in self.block_flow.base.child_iter() {
+            match kid.class() {
+                TableRowGroupFlowClass => (),
+                _ => loop,
+            }
+
+            // For each column, the number of rows that a cell from the rows above still occupies.
+            // Row spans end with the row group.
+            let first_row = row_count;
+            let mut occupied_rows: ~[uint] = ~[];
+            for row in flow::child_iter(*kid) {
+                let mut column = 0;
+                for cell_flow in flow::child_iter(*row) {
+                    let cell = cell_flow.as_table_cell();
+                    while column < occupied_rows.len() && occupied_rows[column] > 0 {
+                        column += 1;
+                    }
+                    cell.column = column;
+
+                    let rows = if cell.rowspan == 0 { uint::max_value } else { cell.rowspan };
+                    for occupied_column in range(column, column + cell.colspan) {
+                        if occupied_column < occupied_rows.len() {
+                            occupied_rows[occupied_column] = rows;
+                        } else {
+                            occupied_rows.push(rows);
+                        }
+                    }
+
+                    cells.push(CellInfo {
+                        row: row_count,
+                        column: column,
+                        colspan: cell.colspan,
+                        rowspan: cell.rowspan,
+                        box: cell.block_flow.box,
+                        min_width: cell.block_flow.base.min_width,
+                        pref_width: cell.block_flow.base.pref_width,
+                    });
+                    column += cell.colspan;
+                }
+
+                for rows in occupied_rows.mut_iter() {
+                    if *rows > 0 {
+                        *rows -= 1;
+                    }
+                }
+                row_count += 1;
+            }
+
+            for cell in cells.mut_iter() {
+                if cell.row >= first_row &&
+                        (cell.rowspan == 0 || cell.row + cell.rowspan > row_count) {
+                    cell.rowspan = row_count - cell.row;
+                }
+            }
+        }
+        (cells, row_count)
+    }
+
+    /// Resolves the borders that meet in the collapsing border model (CSS 2.1 § 17.6.2). Of two
+    /// borders that meet, only the wider one is kept. When they are equally wide, the border of
+    /// the cell to the left or above wins, and cells win over the table.
+    ///
+    /// TODO: Border styles ('hidden' in particular), and the borders of rows, row groups, columns
+    /// and column groups.
+    fn collapse_borders(&mut self, cells: &mut [CellInfo], row_count: uint, column_count: uint) {
+        let zero = SideOffsets2D::new_all_same(Au::new(0));
+
+        // The cell that occupies each slot of the grid.
+        let mut grid = vec::from_elem(row_count * column_count, None::<uint>);
+        for (index, cell) in cells.iter().enumerate() {
+            for row in range(cell.row, cell.row + cell.rowspan) {
+                for column in range(cell.column, cell.column + cell.colspan) {
+                    grid[row * column_count + column] = Some(index);
+                }
+            }
+        }
+
+        let original_borders = do cells.map |cell| {
+            match cell.box {
+                Some(box) => box.base().model.border,
+                None => zero,
+            }
+        };
+        let mut borders = do original_borders.map |border| { *border };
+
+        for (index, cell) in cells.iter().enumerate() {
+            if cell.column > 0 {
+                match grid[cell.row * column_count + cell.column - 1] {
+                    Some(left) if original_borders[left].right >= original_borders[index].left => {
+                        borders[index].left = Au::new(0);
+                    }
+                    Some(left) => borders[left].right = Au::new(0),
+                    None => (),
+                }
+            }
+            if cell.row > 0 {
+                match grid[(cell.row - 1) * column_count + cell.column] {
+                    Some(above) if original_borders[above].bottom >= original_borders[index].top => {
+                        borders[index].top = Au::new(0);
+                    }
+                    Some(above) => borders[above].bottom = Au::new(0),
+                    None => (),
+                }
+            }
+        }
+
+        // The border of the table only survives where it is wider than the borders of all the
+        // cells along that edge.
+        let mut table_border = match self.block_flow.box {
+            Some(box) => box.base().model.border,
+            None => zero,
+        };
+        let mut widest_cell_border = zero;
+        for (index, cell) in cells.iter().enumerate() {
+            let border = &original_borders[index];
+            if cell.column == 0 {
+                widest_cell_border.left = geometry::max(widest_cell_border.left, border.left);
+            }
+            if cell.column + cell.colspan == column_count {
+                widest_cell_border.right = geometry::max(widest_cell_border.right, border.right);
+            }
+            if cell.row == 0 {
+                widest_cell_border.top = geometry::max(widest_cell_border.top, border.top);
+            }
+            if cell.row + cell.rowspan == row_count {
+                widest_cell_border.bottom = geometry::max(widest_cell_border.bottom,
+                                                          border.bottom);
+            }
+        }
+
+        let table_wins_left = table_border.left > widest_cell_border.left;
+        let table_wins_right = table_border.right > widest_cell_border.right;
+        let table_wins_top = table_border.top > widest_cell_border.top;
+        let table_wins_bottom = table_border.bottom > widest_cell_border.bottom;
+        for (index, cell) in cells.iter().enumerate() {
+            if table_wins_left && cell.column == 0 {
+                borders[index].left = Au::new(0);
+            }
+            if table_wins_right && cell.column + cell.colspan == column_count {
+                borders[index].right = Au::new(0);
+            }
+            if table_wins_top && cell.row == 0 {
+                borders[index].top = Au::new(0);
+            }
+            if table_wins_bottom && cell.row + cell.rowspan == row_count {
+                borders[index].bottom = Au::new(0);
+            }
+        }
+        if !table_wins_left { table_border.left = Au::new(0) }
+        if !table_wins_right { table_border.right = Au::new(0) }
+        if !table_wins_top { table_border.top = Au::new(0) }
+        if !table_wins_bottom { table_border.bottom = Au::new(0) }
+
+        for (index, cell) in cells.mut_iter().enumerate() {
+            for &box in cell.box.iter() {
+                let delta = (borders[index].left + borders[index].right) -
+                    (original_borders[index].left + original_borders[index].right);
+                cell.min_width = cell.min_width + delta;
+                cell.pref_width = cell.pref_width + delta;
+                box.mut_base().model.border = borders[index];
+            }
+        }
+        for &box in self.block_flow.box.iter() {
+            box.mut_base().model.border = table_border;
+        }
+    }
+
+    /// Computes the widths of the columns with the fixed table layout algorithm: only the
+    /// columns and the cells of the first row matter, and the columns without a width share what
+    /// remains. (CSS 2.1 § 17.5.2.1)
+    ///
+    /// TODO: The table should grow when the specified columns are wider than it.
+    fn fixed_column_widths(&mut self, columns_width: Au) -> ~[Au] {
+        let column_count = self.column_min_widths.len();
+        let mut widths = vec::from_elem(column_count, None::<Au>);
+        let mut column = 0;
+        let mut seen_first_row = false;
+        for kid in self.block_flow.base.child_iter() {
+            match kid.class() {
+                TableColGroupFlowClass => {
+                    for width in kid.as_table_colgroup().column_widths().iter() {
+                        match MaybeAuto::from_style(*width, columns_width) {
+                            Specified(width) if column < column_count => {
+                                widths[column] = Some(width)
+                            }
+                            _ => (),
+                        }
+                        column += 1;
+                    }
+                }
+                TableRowGroupFlowClass if !seen_first_row => {
+                    let first_row = match flow::child_iter(*kid).next() {
+                        Some(row) => row,
+                        None => loop,
+                    };
+                    seen_first_row = true;
+                    for cell_flow in flow::child_iter(*first_row) {
+                        let cell = cell_flow.as_table_cell();
+                        for &box in cell.block_flow.box.iter() {
+                            let base = box.mut_base();
+                            let style = base.node.style();
+                            let width = match MaybeAuto::from_style(style.Box.width,
+                                                                    columns_width) {
+                                Specified(width) => width,
+                                Auto => loop,
+                            };
+                            base.model.compute_padding(style, columns_width);
+                            let width = width + base.model.noncontent_width();
+
+                            // A cell that spans several columns shares its width among them.
+                            let end = (cell.column + cell.colspan).min(&column_count);
+                            if end <= cell.column {
+                                loop
+                            }
+                            let share = width / Au::new((end - cell.column) as i32);
+                            for column in range(cell.column, end) {
+                                if widths[column].is_none() {
+                                    widths[column] = Some(share);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut specified_width = Au::new(0);
+        let mut auto_count = 0;
+        for width in widths.iter() {
+            match *width {
+                Some(width) => specified_width = specified_width + width,
+                None => auto_count += 1,
+            }
+        }
+        let auto_width = if auto_count > 0 {
+            geometry::max(columns_width - specified_width, Au::new(0)) /
+                Au::new(auto_count as i32)
+        } else {
+            Au::new(0)
+        };
+        do widths.map |width| {
+            match *width {
+                Some(width) => width,
+                None => auto_width,
+            }
+        }
+    }
+}
+
+/// Returns the sum of `widths`.
+fn sum(widths: &[Au]) -> Au {
+    let mut total = Au::new(0);
+    for width in widths.iter() {
+        total = total + *width;
+    }
+    total
+}
+
+/// Returns the width taken by the spacing around `column_count` columns.
+fn spacing_width(spacing: Au, column_count: uint) -> Au {
+    if column_count == 0 {
+        Au::new(0)
+    } else {
+        spacing * Au::new((column_count + 1) as i32)
+    }
+}
+
+/// Raises the sum of `widths` to `total`, if it is less, by widening each of them equally.
+fn distribute_excess(widths: &mut [Au], total: Au) {
+    let current = sum(widths);
+    if widths.is_empty() || total <= current {
+        return
+    }
+    let extra = (total - current) / Au::new(widths.len() as i32);
+    for width in widths.mut_iter() {
+        *width = *width + extra;
+    }
+}
+
+/// Distributes `width` among columns with the given minimum and preferred widths, as the
+/// automatic table layout algorithm does. Columns always get their minimum width; if there is
+/// more room they grow towards their preferred widths, and then beyond them in proportion.
+fn distribute_width(min_widths: &[Au], pref_widths: &[Au], width: Au) -> ~[Au] {
+    if min_widths.is_empty() {
+        return ~[]
+    }
+
+    let total_min = sum(min_widths);
+    let total_pref = sum(pref_widths);
+    if width <= total_min {
+        return min_widths.to_owned()
+    }
+
+    if width <= total_pref {
+        let ratio = to_frac_px(width - total_min) / to_frac_px(total_pref - total_min);
+        let mut widths = ~[];
+        for (min_width, pref_width) in min_widths.iter().zip(pref_widths.iter()) {
+            widths.push(*min_width + (*pref_width - *min_width).scale_by(ratio));
+        }
+        return widths
+    }
+
+    let extra = width - total_pref;
+    if total_pref == Au::new(0) {
+        let share = extra / Au::new(pref_widths.len() as i32);
+        return do pref_widths.map |_| { share }
+    }
+    do pref_widths.map |pref_width| {
+        *pref_width + extra.scale_by(to_frac_px(*pref_width) / to_frac_px(total_pref))
+    }
+}
+
+impl FlowContext for TableFlow {
+    fn class(&self) -> FlowClass {
+        TableFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_table<'a>(&'a mut self) -> &'a mut TableFlow {
+        self
+    }
+
+    /// Places the cells in the grid of the table, and computes the minimum and preferred widths
+    /// of each column from the cells in it. Cells that span several columns come last, and
+    /// widen the columns they span equally if they do not fit. (CSS 2.1 § 17.5.2.2)
+    ///
+    /// TODO: Floats inside captions.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut collapse = false;
+        self.border_spacing = Size2D(Au::new(0), Au::new(0));
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let style = base.node.style();
+            base.model.compute_borders(style);
+            match style.InheritedTable.border_collapse {
+                border_collapse::collapse => collapse = true,
+                border_collapse::separate => {
+                    let spacing = style.InheritedTable.border_spacing;
+                    self.border_spacing = Size2D(model::from_length(spacing.horizontal),
+                                                 model::from_length(spacing.vertical));
+                }
+            }
+        }
+
+        let (mut cells, row_count) = self.place_cells();
+
+        let mut specified_column_widths = ~[];
+        let mut caption_min_width = Au::new(0);
+        let mut caption_pref_width = Au::new(0);
+        for kid in self.block_flow.base.child_iter() {
+            match kid.class() {
+                TableRowGroupFlowClass => (),
+                TableColGroupFlowClass => {
+                    specified_column_widths.push_all_move(kid.as_table_colgroup().column_widths())
+                }
+                _ => {
+                    let child_base = flow::mut_base(*kid);
+                    caption_min_width = geometry::max(caption_min_width, child_base.min_width);
+                    caption_pref_width = geometry::max(caption_pref_width, child_base.pref_width);
+                }
+            }
+        }
+
+        let mut column_count = specified_column_widths.len();
+        for cell in cells.iter() {
+            column_count = column_count.max(&(cell.column + cell.colspan));
+        }
+
+        if collapse {
+            self.collapse_borders(cells, row_count, column_count);
+        }
+
+        let mut min_widths = vec::from_elem(column_count, Au::new(0));
+        let mut pref_widths = vec::from_elem(column_count, Au::new(0));
+        for cell in cells.iter() {
+            if cell.colspan == 1 {
+                min_widths[cell.column] = geometry::max(min_widths[cell.column], cell.min_width);
+                pref_widths[cell.column] = geometry::max(pref_widths[cell.column],
+                                                         cell.pref_width);
+            }
+        }
+
+        for (column, width) in specified_column_widths.iter().enumerate() {
+            match *width {
+                LPA_Length(width) => {
+                    pref_widths[column] = geometry::max(min_widths[column],
+                                                        model::from_length(width))
+                }
+                _ => (),
+            }
+        }
+
+        // Narrower spans first, so that wider ones see the columns they already widened.
+        let spacing = self.border_spacing.width;
+        for colspan in range(2, column_count + 1) {
+            for cell in cells.iter() {
+                if cell.colspan != colspan {
+                    loop
+                }
+                let end = cell.column + colspan;
+                let spanned_spacing = spacing * Au::new((colspan - 1) as i32);
+                distribute_excess(min_widths.mut_slice(cell.column, end),
+                                  cell.min_width - spanned_spacing);
+                distribute_excess(pref_widths.mut_slice(cell.column, end),
+                                  cell.pref_width - spanned_spacing);
+                for column in range(cell.column, end) {
+                    pref_widths[column] = geometry::max(pref_widths[column], min_widths[column]);
+                }
+            }
+        }
+
+        let spacing_width = spacing_width(spacing, column_count);
+        let min_content_width = geometry::max(sum(min_widths) + spacing_width, caption_min_width);
+        let pref_content_width = geometry::max(sum(pref_widths) + spacing_width,
+                                               caption_pref_width);
+        let mut min_width = min_content_width;
+        let mut pref_width = pref_content_width;
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let style = base.node.style();
+            base.model.compute_padding(style, Au::new(0));
+            match style.Box.width {
+                LPA_Length(width) => {
+                    pref_width = geometry::max(min_width, model::from_length(width))
+                }
+                _ => (),
+            }
+
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    Au::new(0)).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     Au::new(0)).specified_or_zero();
+            let noncontent_width = base.model.noncontent_width() + margin_left + margin_right;
+            min_width = min_width + noncontent_width;
+            pref_width = pref_width + noncontent_width;
+        }
+
+        self.column_min_widths = min_widths;
+        self.column_pref_widths = pref_widths;
+        self.min_content_width = min_content_width;
+        self.pref_content_width = pref_content_width;
+        self.block_flow.base.min_width = min_width;
+        self.block_flow.base.pref_width = pref_width;
+        // Floats do not escape table cells.
+        self.block_flow.base.num_floats = 0;
+    }
+
+    /// Sizes the table to fit its contents, like a float, unless it has a 'width'. Then
+    /// distributes that width among the columns, and gives the row groups and captions their
+    /// widths. (CSS 2.1 § 17.5.2)
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+        debug!("assign_widths_table: assigning width for flow %?", self.block_flow.base.id);
+        let containing_width = self.block_flow.base.position.size.width;
+        let mut x_offset = Au::new(0);
+        let mut width = geometry::min(geometry::max(self.min_content_width, containing_width),
+                                      self.pref_content_width);
+        let mut fixed_layout = false;
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let base = &mut *base;
+            let style = base.node.style();
+            let direction = base.containing_block_direction();
+            let model = &mut base.model;
+
+            model.compute_padding(style, containing_width);
+
+            // Margins are 0 right now so model.noncontent_width() is just borders + padding.
+            let available_width = containing_width - model.noncontent_width();
+
+            let margin_top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                   containing_width).specified_or_zero();
+            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                      containing_width).specified_or_zero();
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left, containing_width);
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right, containing_width);
+
+            let used_width = match MaybeAuto::from_style(style.Box.width, containing_width) {
+                Specified(width) => {
+                    fixed_layout = style.Table.table_layout == table_layout::fixed;
+                    geometry::max(width, self.min_content_width)
+                }
+                Auto => {
+                    let available_width = available_width - margin_left.specified_or_zero() -
+                        margin_right.specified_or_zero();
+                    geometry::min(geometry::max(self.min_content_width, available_width),
+                                  self.pref_content_width)
+                }
+            };
+
+            let (used_width, margin_left, margin_right) =
+                self.block_flow.compute_horiz(Specified(used_width),
+                                              margin_left,
+                                              margin_right,
+                                              available_width,
+                                              direction);
+
+            model.margin.top = margin_top;
+            model.margin.right = margin_right;
+            model.margin.bottom = margin_bottom;
+            model.margin.left = margin_left;
+
+            x_offset = model.offset();
+            width = used_width;
+
+            // The associated box is the border box of this flow.
+            base.position.origin.x = model.margin.left;
+            base.position.size.width = width + model.padding.left + model.padding.right +
+                model.border.left + model.border.right;
+        }
+
+        let spacing = self.border_spacing;
+        let columns_width = width - spacing_width(spacing.width, self.column_min_widths.len());
+        self.column_widths = if fixed_layout {
+            self.fixed_column_widths(columns_width)
+        } else {
+            distribute_width(self.column_min_widths, self.column_pref_widths, columns_width)
+        };
+
+        for kid in self.block_flow.base.child_iter() {
+            let kid_width = match kid.class() {
+                TableRowGroupFlowClass => {
+                    let row_group = kid.as_table_row_group();
+                    row_group.column_widths = self.column_widths.clone();
+                    row_group.border_spacing = spacing;
+                    width
+                }
+                TableColGroupFlowClass => Au::new(0),
+                _ => width,
+            };
+
+            let child_base = flow::mut_base(*kid);
+            child_base.position.origin.x = x_offset;
+            child_base.position.size.width = kid_width;
+            child_base.is_inorder = false;
+            child_base.floats_in = FloatContext::new(0);
+        }
+    }
+
+    fn assign_height_inorder(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height_inorder_table: assigning height for table %?",
+               self.block_flow.base.id);
+        self.assign_height(ctx);
+    }
+
+    /// Stacks the captions and row groups, with the vertical spacing around the row groups.
+    ///
+    /// TODO: 'caption-side', and keeping the captions outside the borders of the table.
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+        debug!("assign_height_table: assigning height for table %?", self.block_flow.base.id);
+        let mut top_offset = Au::new(0);
+        for &box in self.block_flow.box.iter() {
+            let base = box.base();
+            let model = &base.model;
+            top_offset = model.margin.top + model.border.top + model.padding.top;
+        }
+
+        let spacing = self.border_spacing.height;
+        let mut y = top_offset;
+        let mut has_row_groups = false;
+        for kid in self.block_flow.base.child_iter() {
+            match kid.class() {
+                TableRowGroupFlowClass => {
+                    y = y + spacing;
+                    has_row_groups = true;
+                }
+                _ => (),
+            }
+            let child_base = flow::mut_base(*kid);
+            child_base.position.origin.y = y;
+            y = y + child_base.position.size.height;
+        }
+        if has_row_groups {
+            y = y + spacing;
+        }
+
+        let mut height = y - top_offset;
+        let mut noncontent_height = Au::new(0);
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let specified_height = MaybeAuto::from_style(base.style().Box.height,
+                                                         Au::new(0)).specified_or_zero();
+            height = geometry::max(height, specified_height);
+
+            // The associated box is the border box of this flow.
+            base.position.origin.y = base.model.margin.top;
+            noncontent_height = base.model.padding.top + base.model.padding.bottom +
+                base.model.border.top + base.model.border.bottom;
+            base.position.size.height = height + noncontent_height;
+            noncontent_height = noncontent_height + base.model.margin.top +
+                base.model.margin.bottom;
+        }
+
+        self.block_flow.base.position.size.height = height + noncontent_height;
+        self.block_flow.base.floats_out = self.block_flow.base.floats_in.clone();
+    }
+
+    fn collapsible_margins(&mut self) -> CollapsibleMargins {
+        // Tables establish block formatting contexts, so only their own margins collapse with
+        // their siblings'.
+        match self.block_flow.box {
+            Some(box) => {
+                let margin = box.base().model.margin;
+                CollapsibleMargins::from_margins(margin.top, margin.bottom)
+            }
+            None => CollapsibleMargins::without_margins(false),
+        }
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("TableFlow(f%d, %u columns)", self.block_flow.base.id, self.column_widths.len())
+    }
+}
@@ -0,0 +1,196 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table cells. A cell lays out its contents as a block does; the table decides its width,
+//! and the rows it spans decide its height.
+
+use css::node_style::StyledNode;
+use layout::block::BlockFlow;
+use layout::box::RenderBoxUtils;
+use layout::context::LayoutContext;
+use layout::flow::{FlowClass, FlowContext, FlowData, TableCellFlowClass};
+use layout::flow;
+use layout::float_context::FloatContext;
+use layout::model;
+
+use gfx::geometry::Au;
+use gfx::geometry;
+use style::computed_values::{LPA_Length, vertical_align};
+
+pub struct TableCellFlow {
+    /// The block flow that lays out the contents of the cell, and holds its render box.
+    block_flow: BlockFlow,
+
+    /// The number of columns that this cell spans.
+    colspan: uint,
+
+    /// The number of rows that this cell spans. Zero means all the remaining rows of its row
+    /// group.
+    rowspan: uint,
+
+    /// The index of the first column of this cell. The table assigns it in `bubble_widths`.
+    column: uint,
+
+    /// The height of the contents of the cell, before it is stretched to the height of its rows.
+    content_height: Au,
+
+    /// How far the contents of the cell were moved down by 'vertical-align'.
+    vertical_offset: Au,
+}
+
+impl TableCellFlow {
+    pub fn new(base: FlowData) -> TableCellFlow {
+        let (colspan, rowspan) = if base.node.is_table_cell_element() {
+            do base.node.with_imm_table_cell_element |cell| {
+                (cell.ColSpan() as uint, cell.RowSpan() as uint)
+            }
+        } else {
+            (1, 1)
+        };
+
+        let mut block_flow = BlockFlow::new(base);
+        block_flow.establishes_bfc = true;
+        TableCellFlow {
+            block_flow: block_flow,
+            colspan: colspan,
+            rowspan: rowspan,
+            column: 0,
+            content_height: Au::new(0),
+            vertical_offset: Au::new(0),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown()
+    }
+
+    /// Stretches this cell to `height`, the height of the rows it spans, and moves its contents
+    /// down according to 'vertical-align'. 'baseline' is treated as 'top' for now.
+    pub fn set_row_height(&mut self, height: Au) {
+        let extra_height = geometry::max(height - self.content_height, Au::new(0));
+        let vertical_offset = match self.block_flow.box {
+            Some(box) => {
+                let base = box.base();
+                match base.style().Box.vertical_align {
+                    vertical_align::middle => extra_height.scale_by(0.5),
+                    vertical_align::bottom => extra_height,
+                    _ => Au::new(0),
+                }
+            }
+            None => Au::new(0),
+        };
+
+        let delta = vertical_offset - self.vertical_offset;
+        if delta != Au::new(0) {
+            for kid in self.block_flow.base.child_iter() {
+                let child_base = flow::mut_base(*kid);
+                child_base.position.origin.y = child_base.position.origin.y + delta;
+            }
+        }
+        self.vertical_offset = vertical_offset;
+
+        // Cells have no margins, so the render box is as tall as the flow.
+        self.block_flow.base.position.size.height = height;
+        for &box in self.block_flow.box.iter() {
+            box.mut_base().position.size.height = height;
+        }
+    }
+}
+
+impl FlowContext for TableCellFlow {
+    fn class(&self) -> FlowClass {
+        TableCellFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_table_cell<'a>(&'a mut self) -> &'a mut TableCellFlow {
+        self
+    }
+
+    /// Computes the minimum and preferred widths of the cell, including its borders and padding.
+    /// A specified 'width' raises the preferred width, but the cell never gets narrower than its
+    /// contents. Percentages are ignored here.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au::new(0);
+        let mut pref_width = Au::new(0);
+        let mut num_floats = 0;
+
+        for child_ctx in self.block_flow.base.child_iter() {
+            let child_base = flow::mut_base(*child_ctx);
+            min_width = geometry::max(min_width, child_base.min_width);
+            pref_width = geometry::max(pref_width, child_base.pref_width);
+            num_floats = num_floats + child_base.num_floats;
+        }
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let style = base.node.style();
+            base.model.compute_borders(style);
+            base.model.compute_padding(style, Au::new(0));
+
+            match style.Box.width {
+                LPA_Length(width) => pref_width = geometry::max(pref_width,
+                                                                model::from_length(width)),
+                _ => (),
+            }
+            pref_width = geometry::max(pref_width, min_width);
+
+            let noncontent_width = base.model.noncontent_width();
+            min_width = min_width + noncontent_width;
+            pref_width = pref_width + noncontent_width;
+        }
+
+        self.block_flow.base.min_width = min_width;
+        self.block_flow.base.pref_width = pref_width;
+        self.block_flow.base.num_floats = num_floats;
+    }
+
+    /// The row has given this cell its border box. Cells establish a new block formatting
+    /// context, so floats inside them start with an empty float context.
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+        debug!("assign_widths_table_cell: assigning width for flow %?", self.block_flow.base.id);
+        let width = self.block_flow.base.position.size.width;
+        let mut x_offset = Au::new(0);
+        let mut content_width = width;
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let style = base.node.style();
+            base.model.compute_padding(style, width);
+            x_offset = base.model.offset();
+            content_width = width - base.model.noncontent_width();
+
+            base.position.origin.x = Au::new(0);
+            base.position.size.width = width;
+        }
+
+        let num_floats = self.block_flow.base.num_floats;
+        self.block_flow.base.floats_in = FloatContext::new(num_floats);
+        self.block_flow.base.is_inorder = false;
+        self.block_flow.propagate_assigned_width_to_children(x_offset, content_width);
+    }
+
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height_table_cell: assigning height for cell %?", self.block_flow.base.id);
+        // Like the root flow, a cell with floats inside starts an in-order traversal.
+        if self.block_flow.base.num_floats > 0 {
+            self.block_flow.assign_height_inorder(ctx);
+        } else {
+            self.block_flow.assign_height(ctx);
+        }
+        self.content_height = self.block_flow.base.position.size.height;
+        self.vertical_offset = Au::new(0);
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("TableCellFlow(f%d, column %u, colspan %u, rowspan %u)",
+             self.block_flow.base.id,
+             self.column,
+             self.colspan,
+             self.rowspan)
+    }
+}
@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table column groups and columns. They take no space of their own; they only give widths
+//! to the columns of the table.
+
+use css::node_style::StyledNode;
+use layout::box::{RenderBox, RenderBoxUtils};
+use layout::context::LayoutContext;
+use layout::flow::{FlowClass, FlowContext, FlowData, TableColGroupFlowClass};
+
+use style::computed_values::LengthOrPercentageOrAuto;
+
+pub struct TableColGroupFlow {
+    /// Data common to all flows.
+    base: FlowData,
+
+    /// The render box of the column group, if it is not anonymous.
+    box: Option<@mut RenderBox>,
+
+    /// The render boxes of the columns in this group.
+    cols: ~[@mut RenderBox],
+}
+
+impl TableColGroupFlow {
+    pub fn new(base: FlowData) -> TableColGroupFlow {
+        TableColGroupFlow {
+            base: base,
+            box: None,
+            cols: ~[],
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        for box in self.box.iter() {
+            box.teardown();
+        }
+        for col in self.cols.iter() {
+            col.teardown();
+        }
+        self.box = None;
+        self.cols = ~[];
+    }
+
+    /// Returns the specified 'width' of each column in this group. A group without columns
+    /// stands for a single column.
+    ///
+    /// TODO: Support the `span` attribute of `col` and `colgroup`.
+    pub fn column_widths(&self) -> ~[LengthOrPercentageOrAuto] {
+        if self.cols.is_empty() {
+            return match self.box {
+                Some(box) => ~[box.base().node.style().Box.width],
+                None => ~[],
+            }
+        }
+        do self.cols.map |col| {
+            col.base().node.style().Box.width
+        }
+    }
+}
+
+impl FlowContext for TableColGroupFlow {
+    fn class(&self) -> FlowClass {
+        TableColGroupFlowClass
+    }
+
+    fn as_table_colgroup<'a>(&'a mut self) -> &'a mut TableColGroupFlow {
+        self
+    }
+
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+    }
+
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+    }
+
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("TableColGroupFlow(f%d, %u columns)", self.base.id, self.cols.len())
+    }
+}
@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table rows. A row places its cells in the columns that the table sized.
+
+use css::node_style::StyledNode;
+use layout::block::BlockFlow;
+use layout::box::RenderBoxUtils;
+use layout::context::LayoutContext;
+use layout::flow::{FlowClass, FlowContext, FlowData, TableRowFlowClass};
+use layout::flow;
+use layout::float_context::FloatContext;
+use layout::model::MaybeAuto;
+
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use gfx::geometry;
+
+pub struct TableRowFlow {
+    /// The block flow that holds the render box of the row, if it is not anonymous.
+    block_flow: BlockFlow,
+
+    /// The used widths of the columns of the table. The table sets them in `assign_widths`.
+    column_widths: ~[Au],
+
+    /// The horizontal and vertical space between cells.
+    border_spacing: Size2D<Au>,
+}
+
+impl TableRowFlow {
+    pub fn new(base: FlowData) -> TableRowFlow {
+        TableRowFlow {
+            block_flow: BlockFlow::new(base),
+            column_widths: ~[],
+            border_spacing: Size2D(Au::new(0), Au::new(0)),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown()
+    }
+
+    /// Sets the height of this row, and stretches the cells that span only this row to it. The
+    /// row group stretches the cells that span several rows.
+    pub fn set_height(&mut self, height: Au) {
+        self.block_flow.base.position.size.height = height;
+        for &box in self.block_flow.box.iter() {
+            box.mut_base().position.size.height = height;
+        }
+
+        for kid in self.block_flow.base.child_iter() {
+            let cell = kid.as_table_cell();
+            if cell.rowspan == 1 {
+                cell.set_row_height(height);
+            }
+        }
+    }
+}
+
+impl FlowContext for TableRowFlow {
+    fn class(&self) -> FlowClass {
+        TableRowFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_table_row<'a>(&'a mut self) -> &'a mut TableRowFlow {
+        self
+    }
+
+    /// The table computes the widths of the columns from the cells directly; a row only sums up
+    /// the widths of its cells.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au::new(0);
+        let mut pref_width = Au::new(0);
+        for kid in self.block_flow.base.child_iter() {
+            let child_base = flow::mut_base(*kid);
+            min_width = min_width + child_base.min_width;
+            pref_width = pref_width + child_base.pref_width;
+        }
+
+        self.block_flow.base.min_width = min_width;
+        self.block_flow.base.pref_width = pref_width;
+        // Floats do not escape table cells.
+        self.block_flow.base.num_floats = 0;
+    }
+
+    /// Places the cells in their columns. A cell that spans several columns also spans the
+    /// spacing between them.
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+        debug!("assign_widths_table_row: assigning width for flow %?", self.block_flow.base.id);
+        let width = self.block_flow.base.position.size.width;
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            base.position.origin.x = Au::new(0);
+            base.position.size.width = width;
+        }
+
+        let spacing = self.border_spacing.width;
+        let column_count = self.column_widths.len();
+        let mut column_positions = ~[];
+        let mut x = spacing;
+        for column_width in self.column_widths.iter() {
+            column_positions.push(x);
+            x = x + *column_width + spacing;
+        }
+
+        for kid in self.block_flow.base.child_iter() {
+            let (first_column, span_end) = {
+                let cell = kid.as_table_cell();
+                let first_column = cell.column.min(&column_count);
+                (first_column, (first_column + cell.colspan).min(&column_count))
+            };
+
+            let child_base = flow::mut_base(*kid);
+            if first_column == span_end {
+                // The table has no column for this cell.
+                child_base.position.origin.x = x;
+                child_base.position.size.width = Au::new(0);
+            } else {
+                let last_column = span_end - 1;
+                child_base.position.origin.x = column_positions[first_column];
+                child_base.position.size.width = column_positions[last_column] +
+                    self.column_widths[last_column] - column_positions[first_column];
+            }
+            child_base.is_inorder = false;
+            child_base.floats_in = FloatContext::new(0);
+        }
+    }
+
+    /// Makes the row as tall as the tallest cell that spans only this row, and as its specified
+    /// 'height'.
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+        debug!("assign_height_table_row: assigning height for row %?", self.block_flow.base.id);
+        let mut height = Au::new(0);
+        for kid in self.block_flow.base.child_iter() {
+            let cell = kid.as_table_cell();
+            cell.block_flow.base.position.origin.y = Au::new(0);
+            if cell.rowspan == 1 {
+                height = geometry::max(height, cell.content_height);
+            }
+        }
+
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            let specified_height = MaybeAuto::from_style(base.node.style().Box.height,
+                                                         Au::new(0)).specified_or_zero();
+            height = geometry::max(height, specified_height);
+        }
+
+        self.set_height(height);
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("TableRowFlow(f%d)", self.block_flow.base.id)
+    }
+}
@@ -0,0 +1,198 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table row groups. A row group stacks its rows, and makes them tall enough for the cells
+//! that span several of them.
+
+use layout::block::BlockFlow;
+use layout::box::RenderBoxUtils;
+use layout::context::LayoutContext;
+use layout::flow::{FlowClass, FlowContext, FlowData, TableRowGroupFlowClass};
+use layout::flow;
+use layout::float_context::FloatContext;
+
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use gfx::geometry;
+
+pub struct TableRowGroupFlow {
+    /// The block flow that holds the render box of the row group, if it is not anonymous.
+    block_flow: BlockFlow,
+
+    /// The used widths of the columns of the table. The table sets them in `assign_widths`.
+    column_widths: ~[Au],
+
+    /// The horizontal and vertical space between cells.
+    border_spacing: Size2D<Au>,
+}
+
+/// A cell that spans several rows of a row group.
+struct SpanningCell {
+    /// The index of the first row of the cell.
+    first_row: uint,
+    /// The index of the row after the last row of the cell.
+    end_row: uint,
+    /// The height of the contents of the cell.
+    content_height: Au,
+}
+
+impl TableRowGroupFlow {
+    pub fn new(base: FlowData) -> TableRowGroupFlow {
+        TableRowGroupFlow {
+            block_flow: BlockFlow::new(base),
+            column_widths: ~[],
+            border_spacing: Size2D(Au::new(0), Au::new(0)),
+        }
+    }
+
+    pub fn teardown(&mut self) {
+        self.block_flow.teardown()
+    }
+}
+
+/// Returns the height of the rows from `first_row` up to, but not including, `end_row`, with the
+/// `spacing` between them.
+fn spanned_height(row_heights: &[Au], first_row: uint, end_row: uint, spacing: Au) -> Au {
+    let mut height = Au::new(0);
+    for row_height in row_heights.slice(first_row, end_row).iter() {
+        height = height + *row_height;
+    }
+    let gaps = end_row - first_row - 1;
+    height + spacing * Au::new(gaps as i32)
+}
+
+impl FlowContext for TableRowGroupFlow {
+    fn class(&self) -> FlowClass {
+        TableRowGroupFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_table_row_group<'a>(&'a mut self) -> &'a mut TableRowGroupFlow {
+        self
+    }
+
+    /// The table computes the widths of the columns from the cells directly; a row group only
+    /// keeps the widths of its widest row.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au::new(0);
+        let mut pref_width = Au::new(0);
+        for kid in self.block_flow.base.child_iter() {
+            let child_base = flow::mut_base(*kid);
+            min_width = geometry::max(min_width, child_base.min_width);
+            pref_width = geometry::max(pref_width, child_base.pref_width);
+        }
+
+        self.block_flow.base.min_width = min_width;
+        self.block_flow.base.pref_width = pref_width;
+        self.block_flow.base.num_floats = 0;
+    }
+
+    /// Gives every row the width of the row group, and the widths of the columns.
+    fn assign_widths(&mut self, _: &mut LayoutContext) {
+        debug!("assign_widths_table_rowgroup: assigning width for flow %?",
+               self.block_flow.base.id);
+        let width = self.block_flow.base.position.size.width;
+        for &box in self.block_flow.box.iter() {
+            let base = box.mut_base();
+            base.position.origin.x = Au::new(0);
+            base.position.size.width = width;
+        }
+
+        for kid in self.block_flow.base.child_iter() {
+            {
+                let row = kid.as_table_row();
+                row.column_widths = self.column_widths.clone();
+                row.border_spacing = self.border_spacing;
+            }
+
+            let child_base = flow::mut_base(*kid);
+            child_base.position.origin.x = Au::new(0);
+            child_base.position.size.width = width;
+            child_base.is_inorder = false;
+            child_base.floats_in = FloatContext::new(0);
+        }
+    }
+
+    /// Stacks the rows. If the rows that a cell spans are not tall enough for it, the last of
+    /// them grows. (CSS 2.1 § 17.5.3)
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+        debug!("assign_height_table_rowgroup: assigning height for row group %?",
+               self.block_flow.base.id);
+        let mut row_heights = ~[];
+        let mut spanning_cells = ~[];
+        for kid in self.block_flow.base.child_iter() {
+            let row_index = row_heights.len();
+            row_heights.push(flow::mut_base(*kid).position.size.height);
+            for cell_flow in flow::child_iter(*kid) {
+                let cell = cell_flow.as_table_cell();
+                if cell.rowspan != 1 {
+                    spanning_cells.push(SpanningCell {
+                        first_row: row_index,
+                        end_row: row_index + cell.rowspan,
+                        content_height: cell.content_height,
+                    });
+                }
+            }
+        }
+
+        // A row span of zero, or one that goes past the last row, ends with the row group.
+        let row_count = row_heights.len();
+        for cell in spanning_cells.mut_iter() {
+            if cell.end_row == cell.first_row || cell.end_row > row_count {
+                cell.end_row = row_count;
+            }
+        }
+
+        let spacing = self.border_spacing.height;
+        for cell in spanning_cells.iter() {
+            let available_height = spanned_height(row_heights.as_slice(),
+                                                  cell.first_row,
+                                                  cell.end_row,
+                                                  spacing);
+            if cell.content_height > available_height {
+                row_heights[cell.end_row - 1] = row_heights[cell.end_row - 1] +
+                    cell.content_height - available_height;
+            }
+        }
+
+        let mut y = Au::new(0);
+        let mut spanning_cell_index = 0;
+        for (row_index, kid) in self.block_flow.base.child_iter().enumerate() {
+            if row_index > 0 {
+                y = y + spacing;
+            }
+
+            let row = kid.as_table_row();
+            if row.block_flow.base.position.size.height != row_heights[row_index] {
+                row.set_height(row_heights[row_index]);
+            }
+            row.block_flow.base.position.origin.y = y;
+            y = y + row_heights[row_index];
+
+            for cell_flow in row.block_flow.base.child_iter() {
+                let cell = cell_flow.as_table_cell();
+                if cell.rowspan != 1 {
+                    let spanning_cell = &spanning_cells[spanning_cell_index];
+                    cell.set_row_height(spanned_height(row_heights.as_slice(),
+                                                       spanning_cell.first_row,
+                                                       spanning_cell.end_row,
+                                                       spacing));
+                    spanning_cell_index += 1;
+                }
+            }
+        }
+
+        self.block_flow.base.position.size.height = y;
+        for &box in self.block_flow.box.iter() {
+            box.mut_base().position.size.height = y;
+        }
+    }
+
+    fn debug_str(&self) -> ~str {
+        fmt!("TableRowGroupFlow(f%d)", self.block_flow.base.id)
+    }
+}
@@ -6,13 +6,73 @@
 
 use std::vec;
 
+use css::node_style::StyledNode;
+use gfx::font::FontGroup;
 use gfx::text::text_run::TextRun;
+use gfx::text::util::{CompressionMode, CompressNone, CompressWhitespace};
 use gfx::text::util::{CompressWhitespaceNewline, transform_text};
+use layout::bidi::{Embedding, bidi_class, is_right_to_left_class, resolve_levels};
 use layout::box::{RenderBox, RenderBoxBase, RenderBoxUtils, TextRenderBox};
 use layout::box::{TextRenderBoxClass, UnscannedTextRenderBoxClass};
 use layout::context::LayoutContext;
 use layout::flow::FlowContext;
+use script::dom::node::{AbstractNode, LayoutView};
 use servo_util::range::Range;
+use servo_util::tree::TreeNodeRef;
+use style::ComputedValues;
+use style::computed_values::{direction, text_decoration, unicode_bidi, white_space};
+
+/// Returns how text with the given 'white-space' is compressed before it is shaped.
+pub fn compression_mode_of_white_space(white_space: white_space::ComputedValue)
+                                       -> CompressionMode {
+    match white_space {
+        white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
+        white_space::pre | white_space::pre_wrap => CompressNone,
+        white_space::pre_line => CompressWhitespace,
+    }
+}
+
+/// Returns true if lines may wrap inside text with the given 'white-space'.
+pub fn white_space_allows_wrapping(white_space: white_space::ComputedValue) -> bool {
+    match white_space {
+        white_space::normal | white_space::pre_wrap | white_space::pre_line => true,
+        white_space::pre | white_space::nowrap => false,
+    }
+}
+
+/// Returns true if spaces and tabs at the start of a line are kept in text with the given
+/// 'white-space'.
+pub fn white_space_preserves_spaces(white_space: white_space::ComputedValue) -> bool {
+    match white_space {
+        white_space::pre | white_space::pre_wrap => true,
+        white_space::normal | white_space::nowrap | white_space::pre_line => false,
+    }
+}
+
+/// Splits a range of the characters of `text` after each newline, so that every piece but the
+/// last one ends with a newline. Newlines are only left in text whose 'white-space' preserves
+/// them.
+fn split_after_newlines(text: &str, range: Range) -> ~[Range] {
+    let mut pieces = ~[];
+    let mut piece = Range::new(range.begin(), 0);
+    for (i, ch) in text.iter().enumerate() {
+        if i < range.begin() {
+            loop
+        }
+        if i >= range.end() {
+            break
+        }
+        piece.extend_by(1);
+        if ch == '\n' {
+            pieces.push(piece);
+            piece = Range::new(i + 1, 0);
+        }
+    }
+    if piece.length() > 0 {
+        pieces.push(piece);
+    }
+    pieces
+}
 
 /// A stack-allocated object for scanning an inline flow into `TextRun`-containing `TextBox`es.
 struct TextRunScanner {
@@ -51,6 +111,9 @@ impl TextRunScanner {
             self.flush_clump_to_list(ctx, flow, last_whitespace, &mut out_boxes);
         }
 
+        let container = flow.as_immutable_inline().block_container();
+        let out_boxes = split_boxes_at_bidi_levels(container, out_boxes);
+
         debug!("TextRunScanner: swapping out boxes.");
 
         // Swap out the old and new box list of the flow.
@@ -113,30 +176,27 @@ impl TextRunScanner {
                 let text = old_box.as_unscanned_text_render_box().raw_text();
                 let font_style = old_box.base().font_style();
                 let decoration = old_box.base().text_decoration();
-
-                // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
-                let compression = CompressWhitespaceNewline;
+                let compression = compression_mode_of_white_space(old_box.base().white_space());
 
                 let (transformed_text, whitespace) = transform_text(text, compression, last_whitespace);
                 new_whitespace = whitespace;
 
                 if transformed_text.len() > 0 {
-                    // TODO(#177): Text run creation must account for the renderability of text by
-                    // font group fonts. This is probably achieved by creating the font group above
-                    // and then letting `FontGroup` decide which `Font` to stick into the text run.
                     let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
-                    let run = @fontgroup.create_textrun(transformed_text, decoration);
+                    let runs = create_textruns(ctx,
+                                               fontgroup,
+                                               transformed_text.as_slice(),
+                                               decoration);
 
                     debug!("TextRunScanner: pushing single text box in range: %? (%?)", self.clump, text);
-                    let range = Range::new(0, run.char_len());
-                    let new_box = @mut TextRenderBox::new(*old_box.mut_base(), run, range);
-
-                    out_boxes.push(new_box as @mut RenderBox);
+                    let range = Range::new(0, transformed_text.char_len());
+                    push_text_boxes(*old_box.mut_base(), runs, range, out_boxes);
                 }
             },
             (false, true) => {
-                // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
-                let compression = CompressWhitespaceNewline;
+                // Boxes only merge if they have the same 'white-space'.
+                let white_space = in_boxes[self.clump.begin()].base().white_space();
+                let compression = compression_mode_of_white_space(white_space);
 
                 // First, transform/compress text of all the nodes.
                 let mut last_whitespace_in_clump = new_whitespace;
@@ -166,11 +226,7 @@ impl TextRunScanner {
                     char_total += added_chars;
                 }
 
-                // Now create the run.
-                //
-                // TODO(#177): Text run creation must account for the renderability of text by
-                // font group fonts. This is probably achieved by creating the font group above
-                // and then letting `FontGroup` decide which `Font` to stick into the text run.
+                // Now create the runs, one for each font that the text needs.
                 let in_box = in_boxes[self.clump.begin()];
                 let font_style = in_box.base().font_style();
                 let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
@@ -179,13 +235,13 @@ impl TextRunScanner {
                 // TextRuns contain a cycle which is usually resolved by the teardown
                 // sequence. If no clump takes ownership, however, it will leak.
                 let clump = self.clump;
-                let run = if clump.length() != 0 && run_str.len() > 0 {
-                    Some(@TextRun::new(fontgroup.fonts[0], run_str, decoration))
+                let runs = if clump.length() != 0 && run_str.len() > 0 {
+                    create_textruns(ctx, fontgroup, run_str.as_slice(), decoration)
                 } else {
-                    None
+                    ~[]
                 };
 
-                // Make new boxes with the run and adjusted text indices.
+                // Make new boxes with the runs and adjusted text indices.
                 debug!("TextRunScanner: pushing box(es) in range: %?", self.clump);
                 for i in clump.eachi() {
                     let range = new_ranges[i - self.clump.begin()];
@@ -196,10 +252,7 @@ impl TextRunScanner {
                         loop
                     }
 
-                    let new_box = @mut TextRenderBox::new(*in_boxes[i].mut_base(),
-                                                          run.unwrap(),
-                                                          range);
-                    out_boxes.push(new_box as @mut RenderBox);
+                    push_text_boxes(*in_boxes[i].mut_base(), runs, range, out_boxes);
                 }
             }
         } // End of match.
@@ -228,3 +281,164 @@ impl TextRunScanner {
         new_whitespace
     } // End of `flush_clump_to_list`.
 }
+
+/// Creates the text runs of `text` in the fonts of `fontgroup` that render its characters, each
+/// with the range of the characters of `text` that it holds.
+fn create_textruns(ctx: &LayoutContext,
+                   fontgroup: @FontGroup,
+                   text: &str,
+                   decoration: text_decoration::ComputedValue)
+                   -> ~[(@TextRun, Range)] {
+    let runs = fontgroup.create_textruns(&mut *ctx.font_ctx, text, decoration);
+    runs.move_iter().map(|(run, range)| (@run, range)).collect()
+}
+
+/// Makes text boxes with the given base for the characters in `range` of the text that `runs`
+/// hold, one for each run that they overlap and for each line that newlines end in it.
+fn push_text_boxes(base: RenderBoxBase,
+                   runs: &[(@TextRun, Range)],
+                   range: Range,
+                   out_boxes: &mut ~[@mut RenderBox]) {
+    for &(run, run_range) in runs.iter() {
+        let mut piece = range.intersect(&run_range);
+        if piece.is_empty() {
+            loop
+        }
+        piece.shift_by(-(run_range.begin().to_int()));
+        for &piece in split_after_newlines(run.text.as_slice(), piece).iter() {
+            let new_box = @mut TextRenderBox::new(base, run, piece);
+            out_boxes.push(new_box as @mut RenderBox);
+        }
+    }
+}
+
+/// Returns the embedding of the contents of an element with the given style inside the embedding
+/// `outer`. Only 'unicode-bidi: embed' and 'unicode-bidi: bidi-override' open a new embedding.
+fn embedding_of_style(outer: Embedding, style: &ComputedValues) -> Embedding {
+    let is_rtl = style.InheritedBox.direction == direction::rtl;
+    match style.Box.unicode_bidi {
+        unicode_bidi::normal => outer,
+        unicode_bidi::embed => outer.push(is_rtl, false),
+        unicode_bidi::bidi_override => outer.push(is_rtl, true),
+    }
+}
+
+/// Returns the embedding of the given box, which lies in the paragraph of the block container
+/// `container` whose own embedding is `paragraph`, by applying the embeddings of the inline
+/// elements between them from the outermost inwards.
+fn embedding_of_box(box: @mut RenderBox,
+                    container: AbstractNode<LayoutView>,
+                    paragraph: Embedding)
+                    -> Embedding {
+    let base = box.base();
+    let mut ancestors = ~[];
+    let mut node = match base.pseudo_element {
+        // Generated content lies inside the element that generated it.
+        Some(_) => Some(base.node),
+        None => base.node.parent_node(),
+    };
+    loop {
+        match node {
+            Some(element) if element != container && element.is_element() => {
+                ancestors.push(element);
+                node = element.parent_node();
+            }
+            _ => break,
+        }
+    }
+
+    let mut embedding = paragraph;
+    for element in ancestors.iter().invert() {
+        embedding = embedding_of_style(embedding, element.style());
+    }
+    if base.pseudo_element.is_some() {
+        embedding = embedding_of_style(embedding, base.style());
+    }
+    embedding
+}
+
+/// Resolves the embedding levels of the inline content of the block container `container` with
+/// the Unicode Bidirectional Algorithm, and splits the text boxes among `boxes` wherever the
+/// level changes. The pieces at right-to-left levels are shaped right to left. Every box records
+/// its level, by which lines reorder their boxes.
+///
+/// Boxes that are not text, such as images and inline blocks, count as object replacement
+/// characters.
+fn split_boxes_at_bidi_levels(container: AbstractNode<LayoutView>, boxes: ~[@mut RenderBox])
+                              -> ~[@mut RenderBox] {
+    let container_style = container.style();
+    let paragraph_level = match container_style.InheritedBox.direction {
+        direction::ltr => 0,
+        direction::rtl => 1,
+    };
+    let mut paragraph = Embedding::new(paragraph_level);
+    paragraph.is_override = container_style.Box.unicode_bidi == unicode_bidi::bidi_override;
+
+    let mut chars = ~[];
+    let mut embeddings = ~[];
+    for &box in boxes.iter() {
+        let embedding = embedding_of_box(box, container, paragraph);
+        if box.class() == TextRenderBoxClass {
+            let text_box = box.as_text_render_box();
+            let text = text_box.run.text.slice_chars(text_box.range.begin(), text_box.range.end());
+            for ch in text.iter() {
+                chars.push(ch);
+                embeddings.push(embedding);
+            }
+        } else {
+            chars.push('\ufffc');
+            embeddings.push(embedding);
+        }
+    }
+
+    // Text that is all left to right at level 0 needs no more work, which is the common case.
+    let is_left_to_right = paragraph_level == 0 &&
+        embeddings.iter().all(|embedding| embedding.level == 0) &&
+        !chars.iter().any(|&ch| is_right_to_left_class(bidi_class(ch)));
+    if is_left_to_right {
+        for &box in boxes.iter() {
+            box.mut_base().bidi_level = 0;
+        }
+        return boxes
+    }
+
+    let levels = resolve_levels(chars, embeddings, paragraph_level);
+    let mut out_boxes = ~[];
+    let mut char_index = 0;
+    for &box in boxes.iter() {
+        if box.class() != TextRenderBoxClass {
+            box.mut_base().bidi_level = levels[char_index];
+            out_boxes.push(box);
+            char_index += 1;
+            loop
+        }
+
+        let text_box = box.as_text_render_box();
+        let (run, range) = (text_box.run, text_box.range);
+        let mut piece_start = 0;
+        while piece_start < range.length() {
+            let level = levels[char_index + piece_start];
+            let mut piece_end = piece_start + 1;
+            while piece_end < range.length() && levels[char_index + piece_end] == level {
+                piece_end += 1;
+            }
+
+            let mut base = *box.mut_base();
+            base.bidi_level = level;
+            let new_box = if level & 1 == 0 {
+                let piece = Range::new(range.begin() + piece_start, piece_end - piece_start);
+                @mut TextRenderBox::new(base, run, piece)
+            } else {
+                // Right-to-left pieces get runs of their own, shaped right to left.
+                let text = run.text.slice_chars(range.begin() + piece_start,
+                                                range.begin() + piece_end);
+                let rtl_run = @TextRun::new(run.font, text.to_owned(), run.decoration, true);
+                @mut TextRenderBox::new(base, rtl_run, Range::new(0, piece_end - piece_start))
+            };
+            out_boxes.push(new_box as @mut RenderBox);
+            piece_start = piece_end;
+        }
+        char_index += range.length();
+    }
+    out_boxes
+}
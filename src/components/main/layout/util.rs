@@ -9,6 +9,7 @@ use servo_util::range::Range;
 use std::iter::Enumerate;
 use std::vec::VecIterator;
 
+#[deriving(Clone)]
 pub struct NodeRange {
     node: AbstractNode<LayoutView>,
     range: Range,
@@ -20,6 +21,7 @@ impl NodeRange {
     }
 }
 
+#[deriving(Clone)]
 struct ElementMapping {
     priv entries: ~[NodeRange],
 }
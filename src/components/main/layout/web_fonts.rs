@@ -0,0 +1,58 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Downloading the fonts of `@font-face` rules.
+
+use layout::box::css_font_weight;
+
+use std::cell::Cell;
+use std::comm::SharedChan;
+use std::task;
+use extra::arc::Arc;
+use gfx::font::WebFontSource;
+use gfx::font_context::WebFontFace;
+use servo_net::resource_task::{ResourceTask, load_whole_resource};
+use style::FontFaceRule;
+use style::computed_values::font_style;
+
+/// Downloads the font of an `@font-face` rule on a task of its own, trying its sources in order.
+/// The first one that loads is sent on `face_chan`, and then `on_load` is called so that the
+/// document is laid out again with it.
+///
+/// The data is not checked here: a font that the font engine cannot read is left out when font
+/// groups are resolved.
+pub fn load_font_face(rule: &FontFaceRule,
+                      resource_task: ResourceTask,
+                      face_chan: SharedChan<WebFontFace>,
+                      on_load: ~fn()) {
+    let family = rule.family.clone();
+    let weight = css_font_weight(rule.weight);
+    let italic = rule.style != font_style::normal;
+    let unicode_range = rule.unicode_range.clone();
+    let sources = Cell::new(rule.sources.clone());
+    let on_load = Cell::new(on_load);
+
+    do task::spawn {
+        for url in sources.take().move_iter() {
+            match load_whole_resource(&resource_task, url.clone()) {
+                Ok(data) => {
+                    debug!("web fonts: loaded `%s` from %s", family, url.to_str());
+                    face_chan.send(WebFontFace {
+                        family: family.clone(),
+                        weight: weight,
+                        italic: italic,
+                        source: WebFontSource {
+                            url: url.to_str(),
+                            data: Arc::new(data),
+                            unicode_range: unicode_range.clone(),
+                        },
+                    });
+                    (on_load.take())();
+                    return
+                }
+                Err(()) => info!("web fonts: failed to load %s", url.to_str()),
+            }
+        }
+    }
+}
@@ -41,6 +41,7 @@ impl Pipeline {
                        constellation_chan: ConstellationChan,
                        compositor_chan: CompositorChan,
                        image_cache_task: ImageCacheTask,
+                       resource_task: ResourceTask,
                        profiler_chan: ProfilerChan,
                        opts: Opts,
                        script_pipeline: &Pipeline,
@@ -61,6 +62,7 @@ impl Pipeline {
                            script_pipeline.script_chan.clone(),
                            render_chan.clone(),
                            image_cache_task.clone(),
+                           resource_task,
                            opts.clone(),
                            profiler_chan);
 
@@ -126,7 +128,7 @@ impl Pipeline {
                                script_port,
                                script_chan.clone(),
                                constellation_chan.clone(),
-                               resource_task,
+                               resource_task.clone(),
                                image_cache_task.clone(),
                                size);
 
@@ -143,6 +145,7 @@ impl Pipeline {
                                script_chan.clone(),
                                render_chan.clone(),
                                image_cache_task,
+                               resource_task,
                                opts.clone(),
                                profiler_chan);
         };
@@ -8,6 +8,7 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::MouseWindowMoveEvent;
 use windowing::{Forward, Back};
 
 use alert::{Alert, AlertMethods};
@@ -104,6 +105,15 @@ impl WindowMethods<Application> for Window {
             }
         }
 
+        do glut::motion_func |x, y| {
+            event_queue.push(MouseWindowEventClass(MouseWindowMoveEvent(Point2D(x as f32,
+                                                                                y as f32))))
+        }
+        do glut::passive_motion_func |x, y| {
+            event_queue.push(MouseWindowEventClass(MouseWindowMoveEvent(Point2D(x as f32,
+                                                                                y as f32))))
+        }
+
         window
     }
 
@@ -151,8 +161,8 @@ impl WindowMethods<Application> for Window {
     }
 
     fn hidpi_factor(@mut self) -> f32 {
-        //FIXME: Do nothing in GLUT now.
-    0f32
+        // GLUT does not tell the hidpi factor, so assume there is none.
+        1f32
     }
 }
 
@@ -0,0 +1,83 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A windowing implementation that has no window at all.
+//!
+//! This is used with the headless compositor (`-z`), which composites in software and writes the
+//! result to a file, so that Servo can render pages on machines without a display or a GPU.
+
+use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
+use windowing::{IdleWindowEvent, FinishedWindowEvent};
+
+use geom::size::Size2D;
+use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
+use servo_msg::compositor_msg::{FinishedLoading, Blank, ReadyState};
+
+/// A structure responsible for setting up and tearing down the entire windowing system.
+pub struct Application;
+
+impl ApplicationMethods for Application {
+    fn new() -> Application {
+        Application
+    }
+}
+
+/// The type of a window.
+pub struct Window {
+    event_queue: ~[WindowEvent],
+
+    ready_state: ReadyState,
+    render_state: RenderState,
+}
+
+impl WindowMethods<Application> for Window {
+    /// Creates a new window.
+    fn new(_: &Application) -> @mut Window {
+        @mut Window {
+            event_queue: ~[],
+
+            ready_state: Blank,
+            render_state: IdleRenderState,
+        }
+    }
+
+    /// Returns the size of the window. This is the default size of the windows of the other
+    /// implementations.
+    fn size(&self) -> Size2D<f32> {
+        Size2D(800.0, 600.0)
+    }
+
+    /// There is no screen to present to.
+    fn present(&mut self) {}
+
+    /// Returns the next event. There are no user events, only those that the compositor causes.
+    fn recv(@mut self) -> WindowEvent {
+        if !self.event_queue.is_empty() {
+            self.event_queue.shift()
+        } else {
+            IdleWindowEvent
+        }
+    }
+
+    /// Sets the ready state.
+    fn set_ready_state(@mut self, ready_state: ReadyState) {
+        self.ready_state = ready_state;
+    }
+
+    /// Sets the render state.
+    fn set_render_state(@mut self, render_state: RenderState) {
+        if self.ready_state == FinishedLoading &&
+            self.render_state == RenderingRenderState &&
+            render_state == IdleRenderState {
+            // page loaded
+            self.event_queue.push(FinishedWindowEvent);
+        }
+
+        self.render_state = render_state;
+    }
+
+    fn hidpi_factor(@mut self) -> f32 {
+        1.0
+    }
+}
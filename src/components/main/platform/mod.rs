@@ -12,6 +12,7 @@ pub use platform::common::glfw_windowing::{Application, Window};
 pub use platform::common::shared_gl_windowing::{Application, Window};
 
 pub mod common {
+    pub mod headless_windowing;
     #[cfg(not(shared_gl_windowing), target_os="android")]
     pub mod glut_windowing;
     #[cfg(not(shared_gl_windowing), not(target_os="android"))]
@@ -12,6 +12,8 @@ pub enum MouseWindowEvent {
     MouseWindowClickEvent(uint, Point2D<f32>),
     MouseWindowMouseDownEvent(uint, Point2D<f32>),
     MouseWindowMouseUpEvent(uint, Point2D<f32>),
+    /// The mouse moved to the given point.
+    MouseWindowMoveEvent(Point2D<f32>),
 }
 
 pub enum WindowNavigateMsg {
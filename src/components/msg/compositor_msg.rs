@@ -11,10 +11,37 @@ use layers::platform::surface::{NativeSurface, NativeSurfaceMethods};
 
 use constellation_msg::PipelineId;
 
-pub struct LayerBuffer {
-    /// The native surface which can be shared between threads or processes. On Mac this is an
+/// The pixels of a layer buffer.
+pub enum LayerBufferSurface {
+    /// A native surface which can be shared between threads or processes. On Mac this is an
     /// `IOSurface`; on Linux this is an X Pixmap; on Android this is an `EGLImageKHR`.
-    native_surface: NativeSurface,
+    NativeLayerBufferSurface(NativeSurface),
+    /// Pixels in main memory, for the headless compositor, which composites on the CPU. The
+    /// pixels are in BGRA order with premultiplied alpha, and there is no padding between rows.
+    CpuLayerBufferSurface(~[u8]),
+}
+
+impl LayerBufferSurface {
+    /// See `NativeSurfaceMethods::mark_will_leak`. Pixels in memory cannot leak.
+    pub fn mark_will_leak(&mut self) {
+        match *self {
+            NativeLayerBufferSurface(ref mut native_surface) => native_surface.mark_will_leak(),
+            CpuLayerBufferSurface(_) => {}
+        }
+    }
+
+    /// See `NativeSurfaceMethods::mark_wont_leak`.
+    pub fn mark_wont_leak(&mut self) {
+        match *self {
+            NativeLayerBufferSurface(ref mut native_surface) => native_surface.mark_wont_leak(),
+            CpuLayerBufferSurface(_) => {}
+        }
+    }
+}
+
+pub struct LayerBuffer {
+    /// The pixels of this buffer.
+    surface: LayerBufferSurface,
 
     /// The rect in the containing RenderLayer that this represents.
     rect: Rect<f32>,
@@ -39,7 +66,7 @@ impl LayerBufferSet {
     /// Notes all buffer surfaces will leak if not destroyed via a call to `destroy`.
     pub fn mark_will_leak(&mut self) {
         for buffer in self.buffers.mut_iter() {
-            buffer.native_surface.mark_will_leak()
+            buffer.surface.mark_will_leak()
         }
     }
 }
@@ -73,15 +100,51 @@ impl Epoch {
     }
 }
 
+/// Identifies one of the layers that the renderer of a pipeline draws.
+#[deriving(Clone, Eq)]
+pub enum LayerId {
+    /// The layer of the page itself.
+    PageLayerId,
+    /// The layer of a box on the page whose contents scroll independently, or of a fixed-position
+    /// element. The ID is the same when the page is laid out again, as long as the element is.
+    ScrollLayerId(uint),
+}
+
+/// Where the layer of a box whose contents scroll independently, or of a fixed-position element,
+/// goes on its page.
+#[deriving(Clone)]
+pub struct ScrollLayerInfo {
+    /// The ID of the layer, as in `ScrollLayerId`.
+    id: uint,
+    /// The ID of the scroll layer that this layer is drawn in and scrolls with, or `None` if it is
+    /// drawn in the page.
+    parent: Option<uint>,
+    /// The padding box of the element in page coordinates, to which the layer is clipped. For a
+    /// fixed-position element and the boxes inside it, the bounds of its contents in viewport
+    /// coordinates instead.
+    rect: Rect<f32>,
+    /// The size of the contents of the element, which is the size of the layer.
+    page_size: Size2D<f32>,
+    /// Whether the layer stays in place in the viewport when the page scrolls, rather than
+    /// scrolling its own contents.
+    fixed_position: bool,
+}
+
 /// The interface used by the renderer to acquire draw targets for each render frame and
 /// submit them to be drawn to the display.
 pub trait RenderListener {
-    fn get_graphics_metadata(&self) -> NativeGraphicsMetadata;
+    /// Returns the metadata that the renderer needs to create native surfaces, or `None` if the
+    /// compositor is headless and takes the pixels of the buffers in memory.
+    fn get_graphics_metadata(&self) -> Option<NativeGraphicsMetadata>;
     fn new_layer(&self, PipelineId, Size2D<uint>);
     fn set_layer_page_size(&self, PipelineId, Size2D<uint>, Epoch);
+    /// Replaces the scroll layers of the page of the given pipeline.
+    fn set_scroll_layers(&self, PipelineId, ~[ScrollLayerInfo], Epoch);
     fn set_layer_clip_rect(&self, PipelineId, Rect<uint>);
+    /// Marks the given part of the page of the given pipeline as needing to be rendered again.
+    fn invalidate_rect(&self, PipelineId, Rect<uint>);
     fn delete_layer(&self, PipelineId);
-    fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, Epoch);
+    fn paint(&self, id: PipelineId, layer_id: LayerId, layer_buffer_set: ~LayerBufferSet, Epoch);
     fn set_render_state(&self, render_state: RenderState);
 }
 
@@ -122,11 +185,16 @@ impl Tile for ~LayerBuffer {
         self.screen_pos.size
     }
     fn mark_wont_leak(&mut self) {
-        self.native_surface.mark_wont_leak()
+        self.surface.mark_wont_leak()
     }
     fn destroy(self, graphics_context: &NativePaintingGraphicsContext) {
         let mut this = self;
-        this.native_surface.destroy(graphics_context)
+        match this.surface {
+            NativeLayerBufferSurface(ref mut native_surface) => {
+                native_surface.destroy(graphics_context)
+            }
+            CpuLayerBufferSurface(_) => {}
+        }
     }
 }
 
@@ -34,7 +34,8 @@ pub enum Msg {
     LoadIframeUrlMsg(Url, PipelineId, SubpageId, Future<Size2D<uint>>, IFrameSandboxState),
     NavigateMsg(NavigationDirection),
     RendererReadyMsg(PipelineId),
-    ResizedWindowMsg(Size2D<uint>),
+    /// The window was resized. Carries the new size and the device pixel ratio.
+    ResizedWindowMsg(Size2D<uint>, f32),
 }
 
 /// Represents the two different ways to which a page can be navigated
@@ -33,6 +33,21 @@ pub enum ProgressMsg {
 /// Handle to a resource task
 pub type ResourceTask = SharedChan<ControlMsg>;
 
+/// Loads the whole content of a resource, blocking until it is done.
+pub fn load_whole_resource(resource_task: &ResourceTask, url: Url) -> Result<~[u8], ()> {
+    let (port, chan) = stream();
+    resource_task.send(Load(url, chan));
+    let mut buf = ~[];
+    loop {
+        match port.recv() {
+            UrlChange(*) => (),
+            Payload(data) => buf.push_all(data),
+            Done(Ok(())) => return Ok(buf),
+            Done(Err(())) => return Err(())
+        }
+    }
+}
+
 /**
 Creates a task to load a specific resource
 
@@ -154,3 +169,17 @@ fn should_delegate_to_scheme_loader() {
     assert!(progress.recv() == Done(Ok(())));
     resource_task.send(Exit);
 }
+
+#[test]
+fn test_load_whole_resource() {
+    let loader_factory = |_url: Url, progress_chan: Chan<ProgressMsg>| {
+        progress_chan.send(Payload(~[1, 2]));
+        progress_chan.send(Payload(~[3]));
+        progress_chan.send(Done(Ok(())));
+    };
+    let loader_factories = ~[(~"snicklefritz", loader_factory)];
+    let resource_task = create_resource_task_with_loaders(loader_factories);
+    let result = load_whole_resource(&resource_task, url::from_str(~"snicklefritz://heya").get());
+    assert!(result == Ok(~[1, 2, 3]));
+    resource_task.send(Exit);
+}
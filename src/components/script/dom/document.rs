@@ -459,6 +459,12 @@ impl Document {
         }
     }
 
+    pub fn style_changed(&self) {
+        for window in self.window.iter() {
+            window.style_changed()
+        }
+    }
+
     pub fn wait_until_safe_to_modify_dom(&self) {
         for window in self.window.iter() {
             window.wait_until_safe_to_modify_dom();
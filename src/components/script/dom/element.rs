@@ -12,9 +12,10 @@ use dom::clientrectlist::ClientRectList;
 use dom::node::{ElementNodeTypeId, Node, ScriptView, AbstractNode};
 use layout_interface::{ContentBoxQuery, ContentBoxResponse, ContentBoxesQuery};
 use layout_interface::{ContentBoxesResponse};
-use newcss::stylesheet::Stylesheet;
+use style::{PropertyDeclarationBlock, TElement, parse_style_attribute};
 
 use js::jsapi::{JSContext, JSObject};
+use servo_util::url::make_url;
 
 use std::cell::Cell;
 use std::comm;
@@ -25,7 +26,16 @@ pub struct Element {
     node: Node<ScriptView>,
     tag_name: ~str,     // TODO: This should be an atom, not a ~str.
     attrs: ~[Attr],
-    style_attribute: Option<Stylesheet>,
+    style_attribute: Option<PropertyDeclarationBlock>,
+    // User action state, for the :hover, :active and :focus pseudo-classes.
+    hover_state: bool,
+    active_state: bool,
+    focus_state: bool,
+    // The checkedness of checkboxes and radio buttons, or the selectedness of options, for the
+    // :checked pseudo-class. The `checked` and `selected` attributes only set it until it is
+    // changed by the user or by script, which makes it dirty.
+    checkedness: bool,
+    dirty_checkedness: bool,
 }
 
 impl CacheableWrapper for Element {
@@ -125,6 +135,11 @@ impl<'self> Element {
             tag_name: tag_name,
             attrs: ~[],
             style_attribute: None,
+            hover_state: false,
+            active_state: false,
+            focus_state: false,
+            checkedness: false,
+            dirty_checkedness: false,
         }
     }
 
@@ -158,10 +173,23 @@ impl<'self> Element {
         }
 
         if "style" == name {
-            self.style_attribute = Some(
-                Stylesheet::from_attribute(
-                    FromStr::from_str("http://www.example.com/").unwrap(),
-                    null_str_as_empty_ref(raw_value)));
+            // URLs in the style attribute are relative to the document.
+            let mut doc_url = None;
+            for doc in self.node.owner_doc.iter() {
+                do doc.with_base |doc| {
+                    for window in doc.window.iter() {
+                        doc_url = window.page.url.map(|&(ref url, _)| url.clone());
+                    }
+                }
+            }
+            let base_url = make_url(~"", doc_url);
+            self.style_attribute = Some(parse_style_attribute(null_str_as_empty_ref(raw_value),
+                                                              &base_url));
+        }
+
+        if !self.dirty_checkedness && (("checked" == name && "input" == self.tag_name) ||
+                                       ("selected" == name && "option" == self.tag_name)) {
+            self.checkedness = true;
         }
 
         //XXXjdm We really need something like a vtable so we can call AfterSetAttr.
@@ -180,12 +208,73 @@ impl<'self> Element {
             _ => ()
         }
 
+        // Attributes such as the size of an image change the boxes of the element.
+        abstract_self.mark_dirty();
+
+        self.content_changed();
+    }
+
+    /// Reflows the document that this element belongs to.
+    pub fn content_changed(&self) {
         match self.node.owner_doc {
             Some(owner) => do owner.with_base |owner| { owner.content_changed() },
             None => {}
         }
     }
 
+    pub fn set_hover_state(&mut self, state: bool) {
+        if self.hover_state != state {
+            self.hover_state = state;
+            self.state_changed();
+        }
+    }
+
+    pub fn set_active_state(&mut self, state: bool) {
+        if self.active_state != state {
+            self.active_state = state;
+            self.state_changed();
+        }
+    }
+
+    pub fn set_focus_state(&mut self, state: bool) {
+        if self.focus_state != state {
+            self.focus_state = state;
+            self.state_changed();
+        }
+    }
+
+    pub fn checkedness(&self) -> bool {
+        self.checkedness
+    }
+
+    /// Checks or unchecks a checkbox, radio button or option. From then on, its checkedness no
+    /// longer follows its `checked` or `selected` attribute.
+    pub fn set_checkedness(&mut self, checked: bool) {
+        self.dirty_checkedness = true;
+        if self.checkedness != checked {
+            self.checkedness = checked;
+            self.state_changed();
+        }
+    }
+
+    /// Returns whether this element is a checkbox or a radio button.
+    pub fn is_checkable(&self) -> bool {
+        "input" == self.tag_name && match self.get_attr("type") {
+            Some(input_type) => input_type.eq_ignore_ascii_case("checkbox") ||
+                                input_type.eq_ignore_ascii_case("radio"),
+            None => false,
+        }
+    }
+
+    /// Dynamic pseudo-classes may now match differently, so the document must be restyled. The
+    /// caller reflows once it has changed the states of all the elements it is interested in.
+    fn state_changed(&self) {
+        match self.node.owner_doc {
+            Some(owner) => do owner.with_base |owner| { owner.style_changed() },
+            None => {}
+        }
+    }
+
     fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
         let doc = self.node.owner_doc.unwrap();
         let win = doc.with_base(|doc| doc.window.unwrap());
@@ -196,6 +285,54 @@ impl<'self> Element {
     }
 }
 
+impl TElement for Element {
+    fn get_local_name<'a>(&'a self) -> &'a str {
+        self.tag_name.as_slice()
+    }
+
+    fn get_namespace_url<'a>(&'a self) -> &'a str {
+        // FIXME: Only HTML elements are supported for now.
+        "http://www.w3.org/1999/xhtml"
+    }
+
+    fn get_attr<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.get_attr(name)
+    }
+
+    fn get_link<'a>(&'a self) -> Option<&'a str> {
+        // FIXME: This is HTML only.
+        match self.tag_name.as_slice() {
+            "a" | "area" | "link" => self.get_attr("href"),
+            _ => None,
+        }
+    }
+
+    fn get_hover_state(&self) -> bool {
+        self.hover_state
+    }
+
+    fn get_active_state(&self) -> bool {
+        self.active_state
+    }
+
+    fn get_focus_state(&self) -> bool {
+        self.focus_state
+    }
+
+    fn get_checked_state(&self) -> bool {
+        (self.is_checkable() || "option" == self.tag_name) && self.checkedness
+    }
+
+    fn get_disabled_state(&self) -> bool {
+        match self.tag_name.as_slice() {
+            "button" | "input" | "select" | "textarea" | "optgroup" | "option" | "fieldset" => {
+                self.get_attr("disabled").is_some()
+            }
+            _ => false,
+        }
+    }
+}
+
 impl Element {
     pub fn TagName(&self) -> DOMString {
         Some(self.tag_name.to_owned().to_ascii_upper())
@@ -23,6 +23,8 @@ pub enum Event_ {
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
+    /// The mouse moved to the given point.
+    MouseMoveEvent(Point2D<f32>),
 }
 
 pub struct Event {
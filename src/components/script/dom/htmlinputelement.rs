@@ -51,10 +51,13 @@ impl HTMLInputElement {
     }
 
     pub fn Checked(&self) -> bool {
-        false
+        self.htmlelement.element.checkedness()
     }
 
-    pub fn SetChecked(&mut self, _checked: bool) {
+    // FIXME: Checking a radio button should uncheck the others in its group, as clicking does.
+    pub fn SetChecked(&mut self, checked: bool) {
+        self.htmlelement.element.set_checkedness(checked);
+        self.htmlelement.element.content_changed();
     }
 
     pub fn Disabled(&self) -> bool {
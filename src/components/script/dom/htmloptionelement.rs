@@ -40,10 +40,12 @@ impl HTMLOptionElement {
     }
 
     pub fn Selected(&self) -> bool {
-        false
+        self.htmlelement.element.checkedness()
     }
 
-    pub fn SetSelected(&mut self, _selected: bool) -> ErrorResult {
+    pub fn SetSelected(&mut self, selected: bool) -> ErrorResult {
+        self.htmlelement.element.set_checkedness(selected);
+        self.htmlelement.element.content_changed();
         Ok(())
     }
 
@@ -4,14 +4,31 @@
 
 use dom::bindings::utils::{DOMString, ErrorResult};
 use dom::htmlelement::HTMLElement;
+use std::from_str::FromStr;
 
 pub struct HTMLTableCellElement {
     htmlelement: HTMLElement,
 }
 
 impl HTMLTableCellElement {
+    /// Parses a `colspan` or `rowspan` attribute as a non-negative integer. Like HTML, this
+    /// ignores leading whitespace and anything after the digits.
+    fn get_span_attr(&self, name: &str) -> Option<u32> {
+        match self.htmlelement.element.get_attr(name) {
+            Some(value) => {
+                let digits: ~str = value.trim_left().iter().take_while(|c| c.is_digit()).collect();
+                FromStr::from_str(digits)
+            }
+            None => None,
+        }
+    }
+
     pub fn ColSpan(&self) -> u32 {
-        0
+        // Missing, invalid and zero values mean 1. (HTML § 4.9.11)
+        match self.get_span_attr("colspan") {
+            Some(0) | None => 1,
+            Some(span) => span.min(&1000),
+        }
     }
 
     pub fn SetColSpan(&self, _col_span: u32) -> ErrorResult {
@@ -19,7 +36,11 @@ impl HTMLTableCellElement {
     }
 
     pub fn RowSpan(&self) -> u32 {
-        0
+        // Zero means that the cell spans the remaining rows of its row group.
+        match self.get_span_attr("rowspan") {
+            None => 1,
+            Some(span) => span.min(&65534),
+        }
     }
 
     pub fn SetRowSpan(&self, _col_span: u32) -> ErrorResult {
@@ -10,19 +10,18 @@ use dom::bindings::utils::{BindingObject, CacheableWrapper, null_str_as_empty};
 use dom::characterdata::CharacterData;
 use dom::document::AbstractDocument;
 use dom::element::{Element, ElementTypeId, HTMLImageElementTypeId, HTMLIframeElementTypeId};
-use dom::element::{HTMLStyleElementTypeId};
+use dom::element::{HTMLStyleElementTypeId, HTMLTableCellElementTypeId};
 use dom::htmlimageelement::HTMLImageElement;
 use dom::htmliframeelement::HTMLIFrameElement;
+use dom::htmltablecellelement::HTMLTableCellElement;
 use dom::text::Text;
 
 use std::cast;
 use std::cast::transmute;
-use std::libc::c_void;
 use std::unstable::raw::Box;
 use extra::arc::Arc;
 use js::jsapi::{JSObject, JSContext};
-use netsurfcss::util::VoidPtrLike;
-use newcss::complete::CompleteSelectResults;
+use style::{ComputedValues, TNode};
 use servo_util::tree::{TreeNode, TreeNodeRef};
 use servo_util::range::Range;
 use gfx::display_list::DisplayList;
@@ -348,6 +347,17 @@ impl<'self, View> AbstractNode<View> {
         self.transmute_mut(f)
     }
 
+    pub fn is_table_cell_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLTableCellElementTypeId)
+    }
+
+    pub fn with_imm_table_cell_element<R>(self, f: &fn(&HTMLTableCellElement) -> R) -> R {
+        if !self.is_table_cell_element() {
+            fail!(~"node is not a table cell element");
+        }
+        self.transmute(f)
+    }
+
     pub fn is_style_element(self) -> bool {
         self.type_id() == ElementNodeTypeId(HTMLStyleElementTypeId)
     }
@@ -625,6 +635,7 @@ impl Node<ScriptView> {
           }
           CommentNodeTypeId | TextNodeTypeId => {
             self.wait_until_safe_to_modify_dom();
+            abstract_self.mark_dirty();
 
             do abstract_self.with_mut_characterdata() |characterdata| {
                 characterdata.data = null_str_as_empty(value);
@@ -687,8 +698,12 @@ impl Node<ScriptView> {
         self.wait_until_safe_to_modify_dom();
 
         // If the node already exists it is removed from current parent node.
-        node.parent_node().map(|parent| parent.remove_child(node));
+        for &parent in node.parent_node().iter() {
+            parent.remove_child(node);
+            parent.mark_dirty();
+        }
         abstract_self.add_child(node);
+        abstract_self.mark_dirty();
         match self.owner_doc {
             Some(doc) => do node.with_mut_base |node| {
                 node.add_to_doc(doc);
@@ -720,6 +735,7 @@ impl Node<ScriptView> {
         self.wait_until_safe_to_modify_dom();
 
         abstract_self.remove_child(node);
+        abstract_self.mark_dirty();
         self.remove_from_doc();
         Ok(node)
     }
@@ -772,23 +788,6 @@ impl Node<ScriptView> {
     }
 }
 
-/// The CSS library requires that DOM nodes be convertible to `*c_void` via the `VoidPtrLike`
-/// trait.
-impl VoidPtrLike for AbstractNode<LayoutView> {
-    fn from_void_ptr(node: *c_void) -> AbstractNode<LayoutView> {
-        assert!(node.is_not_null());
-        unsafe {
-            cast::transmute(node)
-        }
-    }
-
-    fn to_void_ptr(&self) -> *c_void {
-        unsafe {
-            cast::transmute(*self)
-        }
-    }
-}
-
 impl CacheableWrapper for Node<ScriptView> {
     fn get_wrappercache(&mut self) -> &mut WrapperCache {
         unsafe { cast::transmute(&mut self.wrapper) }
@@ -819,11 +818,24 @@ pub struct DisplayBoxes {
 /// Data that layout associates with a node.
 pub struct LayoutData {
     /// The results of CSS styling for this node.
-    style: Option<CompleteSelectResults>,
+    style: Option<ComputedValues>,
+
+    /// The results of CSS styling for the `::before` and `::after` pseudo-elements of this node,
+    /// if they generate content.
+    before_style: Option<ComputedValues>,
+    after_style: Option<ComputedValues>,
+
+    /// The results of CSS styling for the `::marker` pseudo-element of this node, if it is a list
+    /// item.
+    marker_style: Option<ComputedValues>,
 
     /// Description of how to account for recent style changes.
     restyle_damage: Option<int>,
 
+    /// True if the children, the text or the attributes of this node changed since the last
+    /// reflow, so that layout has to build its boxes again.
+    dirty: bool,
+
     /// The boxes assosiated with this flow.
     /// Used for getBoundingClientRect and friends.
     boxes: DisplayBoxes,
@@ -834,7 +846,11 @@ impl LayoutData {
     pub fn new() -> LayoutData {
         LayoutData {
             style: None,
+            before_style: None,
+            after_style: None,
+            marker_style: None,
             restyle_damage: None,
+            dirty: false,
             boxes: DisplayBoxes { display_list: None, range: None },
         }
     }
@@ -857,3 +873,43 @@ impl AbstractNode<LayoutView> {
         }
     }
 }
+
+impl AbstractNode<ScriptView> {
+    /// Tells layout that the children, the text or the attributes of this node changed, so that it
+    /// builds the boxes of the node again on the next reflow.
+    pub fn mark_dirty(self) {
+        do self.with_mut_base |b| {
+            b.layout_data.dirty = true
+        }
+    }
+}
+
+impl TNode<Element> for AbstractNode<LayoutView> {
+    fn parent_node(&self) -> Option<AbstractNode<LayoutView>> {
+        (*self).parent_node()
+    }
+
+    fn prev_sibling(&self) -> Option<AbstractNode<LayoutView>> {
+        (*self).prev_sibling()
+    }
+
+    fn next_sibling(&self) -> Option<AbstractNode<LayoutView>> {
+        (*self).next_sibling()
+    }
+
+    fn first_child(&self) -> Option<AbstractNode<LayoutView>> {
+        (*self).first_child()
+    }
+
+    fn is_element(&self) -> bool {
+        (*self).is_element()
+    }
+
+    fn is_text(&self) -> bool {
+        (*self).is_text()
+    }
+
+    fn with_element<R>(&self, f: &fn(&Element) -> R) -> R {
+        self.with_imm_element(f)
+    }
+}
@@ -9,7 +9,7 @@ use dom::document::AbstractDocument;
 use dom::node::{AbstractNode, ScriptView};
 use dom::navigator::Navigator;
 
-use layout_interface::ReflowForDisplay;
+use layout_interface::{MatchSelectorsDocumentDamage, ReflowForDisplay};
 use script_task::{ExitWindowMsg, FireTimerMsg, Page, ScriptChan};
 use servo_msg::compositor_msg::ScriptListener;
 use servo_net::image_cache_task::ImageCacheTask;
@@ -186,6 +186,11 @@ impl Window {
         self.page.reflow_all(ReflowForDisplay, self.script_chan.clone(), self.compositor);
     }
 
+    /// Tells layout to restyle the document on the next reflow, without reflowing now.
+    pub fn style_changed(&self) {
+        self.page.damage(MatchSelectorsDocumentDamage);
+    }
+
     pub fn wait_until_safe_to_modify_dom(&self) {
         // FIXME: This disables concurrent layout while we are modifying the DOM, since
         //        our current architecture is entirely unsafe in the presence of races.
@@ -13,7 +13,7 @@ use geom::rect::Rect;
 use geom::size::Size2D;
 use geom::point::Point2D;
 use gfx::geometry::Au;
-use newcss::stylesheet::Stylesheet;
+use style::Stylesheet;
 use extra::url::Url;
 
 /// Asynchronous messages that script can send to layout.
@@ -103,6 +103,8 @@ pub struct Reflow {
     script_chan: ScriptChan,
     /// The current window size.
     window_size: Size2D<uint>,
+    /// The ratio of device pixels to CSS pixels.
+    device_pixel_ratio: f32,
     /// The channel that we send a notification to.
     script_join_chan: Chan<()>,
     /// Unique identifier
@@ -12,7 +12,9 @@ use dom::bindings::utils::{CacheableWrapper, GlobalStaticData};
 use dom::document::AbstractDocument;
 use dom::element::Element;
 use dom::event::{Event_, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent, MouseUpEvent};
+use dom::event::MouseMoveEvent;
 use dom::htmldocument::HTMLDocument;
+use dom::node::{AbstractNode, LayoutView};
 use dom::window::Window;
 use layout_interface::{AddStylesheetMsg, DocumentDamage};
 use layout_interface::{DocumentDamageLevel, HitTestQuery, HitTestResponse, LayoutQuery};
@@ -25,6 +27,7 @@ use servo_msg::constellation_msg::{PipelineId, SubpageId};
 use servo_msg::constellation_msg::{LoadIframeUrlMsg, IFrameSandboxed, IFrameUnsandboxed};
 use servo_msg::constellation_msg;
 
+use std::ascii::StrAsciiExt;
 use std::cell::Cell;
 use std::comm;
 use std::comm::{Port, SharedChan};
@@ -33,6 +36,7 @@ use std::ptr;
 use std::task::{spawn_sched, SingleThreaded};
 use std::util::replace;
 use dom::window::TimerData;
+use geom::point::Point2D;
 use geom::size::Size2D;
 use html::hubbub_html_parser::HtmlParserResult;
 use html::hubbub_html_parser::{HtmlDiscoveredStyle, HtmlDiscoveredIFrame, HtmlDiscoveredScript};
@@ -64,13 +68,13 @@ pub enum ScriptMsg {
     /// Sends a DOM event.
     SendEventMsg(PipelineId, Event_),
     /// Window resized.  Sends a DOM event eventually, but first we combine events.
-    ResizeMsg(PipelineId, Size2D<uint>),
+    ResizeMsg(PipelineId, Size2D<uint>, f32),
     /// Fires a JavaScript timeout.
     FireTimerMsg(PipelineId, ~TimerData),
     /// Notifies script that reflow is finished.
     ReflowCompleteMsg(PipelineId, uint),
     /// Notifies script that window has been resized but to not take immediate action.
-    ResizeInactiveMsg(PipelineId, Size2D<uint>),
+    ResizeInactiveMsg(PipelineId, Size2D<uint>, f32),
     /// Notifies the script that a pipeline should be closed.
     ExitPipelineMsg(PipelineId),
     /// Notifies the script that a window associated with a particular pipeline should be closed.
@@ -117,6 +121,9 @@ pub struct Page {
     /// The current size of the window, in pixels.
     window_size: Future<Size2D<uint>>,
 
+    /// The ratio of device pixels to CSS pixels.
+    device_pixel_ratio: f32,
+
     js_info: Option<JSPageInfo>,
 
     /// Cached copy of the most recent url loaded by the script
@@ -127,8 +134,18 @@ pub struct Page {
 
     next_subpage_id: SubpageId,
 
-    /// Pending resize event, if any.
-    resize_event: Option<Size2D<uint>>
+    /// Pending resize event and device pixel ratio, if any.
+    resize_event: Option<(Size2D<uint>, f32)>,
+
+    /// The element under the mouse, which matches `:hover` along with its ancestors.
+    hover_element: Option<AbstractNode<LayoutView>>,
+
+    /// The element that the mouse button was pressed on, which matches `:active` along with its
+    /// ancestors until the button is released.
+    active_element: Option<AbstractNode<LayoutView>>,
+
+    /// The element that matches `:focus`.
+    focus_element: Option<AbstractNode<LayoutView>>,
 }
 
 pub struct PageTree {
@@ -141,7 +158,8 @@ pub struct PageTreeIterator<'self> {
 }
 
 impl PageTree {
-    fn new(id: PipelineId, layout_chan: LayoutChan, size_future: Future<Size2D<uint>>) -> PageTree {
+    fn new(id: PipelineId, layout_chan: LayoutChan, size_future: Future<Size2D<uint>>,
+           device_pixel_ratio: f32) -> PageTree {
         PageTree {
             page: @mut Page {
                 id: id,
@@ -150,11 +168,15 @@ impl PageTree {
                 layout_join_port: None,
                 damage: None,
                 window_size: size_future,
+                device_pixel_ratio: device_pixel_ratio,
                 js_info: None,
                 url: None,
                 next_subpage_id: SubpageId(0),
                 resize_event: None,
-                last_reflow_id: 0
+                last_reflow_id: 0,
+                hover_element: None,
+                active_element: None,
+                focus_element: None,
             },
             inner: ~[],
         }
@@ -216,7 +238,7 @@ impl<'self> Iterator<@mut Page> for PageTreeIterator<'self> {
 
 impl Page {
     /// Adds the given damage.
-    fn damage(&mut self, level: DocumentDamageLevel) {
+    pub fn damage(&mut self, level: DocumentDamageLevel) {
         match self.damage {
             None => {}
             Some(ref mut damage) => {
@@ -293,6 +315,7 @@ impl Page {
                     url: self.url.get_ref().first().clone(),
                     goal: goal,
                     window_size: self.window_size.get(),
+                    device_pixel_ratio: self.device_pixel_ratio,
                     script_chan: script_chan,
                     script_join_chan: join_chan,
                     damage: replace(&mut self.damage, None).unwrap(),
@@ -417,7 +440,7 @@ impl ScriptTask {
         let js_runtime = js::rust::rt();
 
         let script_task = @mut ScriptTask {
-            page_tree: PageTree::new(id, layout_chan, initial_size),
+            page_tree: PageTree::new(id, layout_chan, initial_size, 1.0),
 
             image_cache_task: img_cache_task,
             resource_task: resource_task,
@@ -480,7 +503,10 @@ impl ScriptTask {
             // Only process a resize if layout is idle.
             if page.layout_join_port.is_none() {
                 match page.resize_event.take() {
-                    Some(size) => resizes.push((page.id, size)),
+                    Some((size, device_pixel_ratio)) => {
+                        page.device_pixel_ratio = device_pixel_ratio;
+                        resizes.push((page.id, size))
+                    }
                     None => ()
                 }
             }
@@ -496,9 +522,9 @@ impl ScriptTask {
             // Receive at least one message so we don't spinloop.
             let event = self.port.recv();
             match event {
-                ResizeMsg(id, size) => {
+                ResizeMsg(id, size, device_pixel_ratio) => {
                     let page = self.page_tree.find(id).expect("resize sent to nonexistent pipeline").page;
-                    page.resize_event = Some(size);
+                    page.resize_event = Some((size, device_pixel_ratio));
                 }
                 _ => {
                     sequential.push(event);
@@ -522,7 +548,9 @@ impl ScriptTask {
                 FireTimerMsg(id, timer_data) => self.handle_fire_timer_msg(id, timer_data),
                 NavigateMsg(direction) => self.handle_navigate_msg(direction),
                 ReflowCompleteMsg(id, reflow_id) => self.handle_reflow_complete_msg(id, reflow_id),
-                ResizeInactiveMsg(id, new_size) => self.handle_resize_inactive_msg(id, new_size),
+                ResizeInactiveMsg(id, new_size, device_pixel_ratio) => {
+                    self.handle_resize_inactive_msg(id, new_size, device_pixel_ratio)
+                }
                 ExitPipelineMsg(id) => if self.handle_exit_pipeline_msg(id) { return false },
                 ExitWindowMsg(id) => if self.handle_exit_window_msg(id) { return false },
                 ResizeMsg(*) => fail!("should have handled ResizeMsg already"),
@@ -544,7 +572,10 @@ impl ScriptTask {
         let parent_page_tree = self.page_tree.find(old_id).expect("ScriptTask: received a layout
             whose parent has a PipelineId which does not correspond to a pipeline in the script
             task's page tree. This is a bug.");
-        let new_page_tree = PageTree::new(new_id, layout_chan, size_future);
+        let new_page_tree = PageTree::new(new_id,
+                                          layout_chan,
+                                          size_future,
+                                          parent_page_tree.page.device_pixel_ratio);
         parent_page_tree.inner.push(new_page_tree);
     }
 
@@ -619,10 +650,14 @@ impl ScriptTask {
     }
 
     /// Window was resized, but this script was not active, so don't reflow yet
-    fn handle_resize_inactive_msg(&mut self, id: PipelineId, new_size: Size2D<uint>) {
+    fn handle_resize_inactive_msg(&mut self,
+                                  id: PipelineId,
+                                  new_size: Size2D<uint>,
+                                  device_pixel_ratio: f32) {
         let page = self.page_tree.find(id).expect("Received resize message for PipelineId not associated
             with a page in the page tree. This is a bug.").page;
         page.window_size = Future::from_value(new_size);
+        page.device_pixel_ratio = device_pixel_ratio;
         let last_loaded_url = replace(&mut page.url, None);
         for url in last_loaded_url.iter() {
             page.url = Some((url.first(), true));
@@ -719,6 +754,9 @@ impl ScriptTask {
             window: window,
         });
         page.url = Some((final_url, true));
+        page.hover_element = None;
+        page.active_element = None;
+        page.focus_element = None;
 
         // Send style sheets over to layout.
         //
@@ -801,12 +839,19 @@ impl ScriptTask {
                 }
             }
 
-            // FIXME(pcwalton): This reflows the entire document and is not incremental-y.
+            // An image finished loading. Only the boxes of images are built again.
             ReflowEvent => {
                 debug!("script got reflow event");
 
                 if page.frame.is_some() {
-                    page.damage(MatchSelectorsDocumentDamage);
+                    let root = do page.frame.get_ref().document.with_base |doc| { doc.root };
+                    for node in root.traverse_preorder() {
+                        if node.is_image_element() {
+                            node.mark_dirty()
+                        }
+                    }
+
+                    page.damage(ReflowDocumentDamage);
                     page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
                 }
             }
@@ -814,40 +859,95 @@ impl ScriptTask {
             ClickEvent(_button, point) => {
                 debug!("ClickEvent: clicked at %?", point);
 
-                let root = do page.frame.expect("root frame is None").document.with_base |doc| {
-                    doc.root
-                };
-                let (port, chan) = comm::stream();
-                match page.query_layout(HitTestQuery(root, point, chan), port) {
-                    Ok(node) => match node {
-                        HitTestResponse(node) => {
-                            debug!("clicked on %s", node.debug_str());
-                            let mut node = node;
-                            // traverse node generations until a node that is an element is found
-                            while !node.is_element() {
-                                match node.parent_node() {
-                                    Some(parent) => {
-                                        node = parent;
-                                    }
-                                    None => break
-                                }
-                            }
-                            if node.is_element() {
-                                do node.with_imm_element |element| {
-                                    if "a" == element.tag_name {
-                                        self.load_url_from_element(page, element)
-                                    }
-                                }
-                            }
+                for &node in self.element_at_point(page, point).iter() {
+                    do node.with_imm_element |element| {
+                        if "a" == element.tag_name {
+                            self.load_url_from_element(page, element)
                         }
-                    },
-                    Err(()) => {
-                        debug!(fmt!("layout query error"));
                     }
+                    toggle_checkedness(page, node);
+                }
+                self.reflow_if_restyled(page)
+            }
+
+            MouseDownEvent(_button, point) => {
+                if page.frame.is_some() {
+                    let element = self.element_at_point(page, point);
+                    let old_element = replace(&mut page.active_element, element);
+                    move_user_action_state(old_element, element, true, |element, state| {
+                        element.set_active_state(state)
+                    });
+
+                    let focus_element = element.and_then(focusable_element);
+                    let old_focus_element = replace(&mut page.focus_element, focus_element);
+                    move_user_action_state(old_focus_element, focus_element, false,
+                                           |element, state| element.set_focus_state(state));
+
+                    self.reflow_if_restyled(page)
                 }
             }
-            MouseDownEvent(*) => {}
-            MouseUpEvent(*) => {}
+
+            MouseUpEvent(*) => {
+                if page.frame.is_some() {
+                    page.join_layout();
+                    let old_element = replace(&mut page.active_element, None);
+                    move_user_action_state(old_element, None, true, |element, state| {
+                        element.set_active_state(state)
+                    });
+
+                    self.reflow_if_restyled(page)
+                }
+            }
+
+            MouseMoveEvent(point) => {
+                if page.frame.is_some() {
+                    let element = self.element_at_point(page, point);
+                    let old_element = replace(&mut page.hover_element, element);
+                    move_user_action_state(old_element, element, true, |element, state| {
+                        element.set_hover_state(state)
+                    });
+
+                    self.reflow_if_restyled(page)
+                }
+            }
+        }
+    }
+
+    /// Returns the element at the given point of the page. When a text node is hit, this is the
+    /// element that contains it.
+    fn element_at_point(&self, page: @mut Page, point: Point2D<f32>)
+                        -> Option<AbstractNode<LayoutView>> {
+        let root = do page.frame.expect("root frame is None").document.with_base |doc| {
+            doc.root
+        };
+        let (port, chan) = comm::stream();
+        match page.query_layout(HitTestQuery(root, point, chan), port) {
+            Ok(HitTestResponse(node)) => {
+                debug!("hit %s", node.debug_str());
+                let mut node = node;
+                // traverse node generations until a node that is an element is found
+                while !node.is_element() {
+                    match node.parent_node() {
+                        Some(parent) => {
+                            node = parent;
+                        }
+                        None => return None
+                    }
+                }
+                Some(node)
+            }
+            Err(()) => {
+                debug!(fmt!("layout query error"));
+                None
+            }
+        }
+    }
+
+    /// Reflows the page if the user action state of an element changed, since the pseudo-classes
+    /// of the document then have to be matched again.
+    fn reflow_if_restyled(&self, page: @mut Page) {
+        if page.damage.is_some() {
+            page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
         }
     }
 
@@ -866,3 +966,118 @@ impl ScriptTask {
     }
 }
 
+/// Moves a user action state, such as being hovered, from the element `old` to the element `new`,
+/// and from their ancestors as well if `with_ancestors` is true. Each element whose state changes
+/// is marked for restyling.
+fn move_user_action_state(old: Option<AbstractNode<LayoutView>>,
+                          new: Option<AbstractNode<LayoutView>>,
+                          with_ancestors: bool,
+                          set_state: &fn(&mut Element, bool)) {
+    if old == new {
+        return
+    }
+
+    for node in user_action_elements(old, with_ancestors).iter() {
+        do node.as_mut_element |element| {
+            set_state(element, false)
+        }
+    }
+    for node in user_action_elements(new, with_ancestors).iter() {
+        do node.as_mut_element |element| {
+            set_state(element, true)
+        }
+    }
+}
+
+/// Returns the given element, and its ancestors if `with_ancestors` is true.
+fn user_action_elements(node: Option<AbstractNode<LayoutView>>, with_ancestors: bool)
+                        -> ~[AbstractNode<LayoutView>] {
+    let mut elements = ~[];
+    for &node in node.iter() {
+        elements.push(node);
+        if with_ancestors {
+            for ancestor in node.ancestors() {
+                if ancestor.is_element() {
+                    elements.push(ancestor)
+                }
+            }
+        }
+    }
+    elements
+}
+
+/// Checks or unchecks the given element if it is a checkbox or a radio button that is not disabled,
+/// as clicking it does. Checking a radio button unchecks the others with the same name.
+fn toggle_checkedness(page: @mut Page, node: AbstractNode<LayoutView>) {
+    let (checkable, is_radio, name) = do node.with_imm_element |element| {
+        let is_radio = match element.get_attr("type") {
+            Some(input_type) => input_type.eq_ignore_ascii_case("radio"),
+            None => false,
+        };
+        (element.is_checkable() && element.get_attr("disabled").is_none(),
+         is_radio,
+         element.get_attr("name").map(|&name| name.to_owned()))
+    };
+    if !checkable {
+        return
+    }
+
+    if !is_radio {
+        do node.as_mut_element |element| {
+            let checked = element.checkedness();
+            element.set_checkedness(!checked)
+        }
+        return
+    }
+
+    // Uncheck the whole group, this radio button included, then check it again.
+    //
+    // FIXME: Radio button groups should be scoped to the form owner, not the whole document.
+    for name in name.iter() {
+        let root = do page.frame.expect("root frame is None").document.with_base |doc| {
+            doc.root
+        };
+        for other in root.traverse_preorder() {
+            if other.is_element() {
+                do other.as_mut_element |element| {
+                    let in_group = "input" == element.tag_name &&
+                        element.get_attr("name").map_default(false, |&other_name| {
+                            other_name == name.as_slice()
+                        }) &&
+                        element.get_attr("type").map_default(false, |&input_type| {
+                            input_type.eq_ignore_ascii_case("radio")
+                        });
+                    if in_group {
+                        element.set_checkedness(false)
+                    }
+                }
+            }
+        }
+    }
+    do node.as_mut_element |element| {
+        element.set_checkedness(true)
+    }
+}
+
+/// Returns the element that gets the focus when the given element is clicked: the nearest element
+/// among it and its ancestors that can be focused.
+fn focusable_element(node: AbstractNode<LayoutView>) -> Option<AbstractNode<LayoutView>> {
+    let mut node = Some(node);
+    while node.is_some() {
+        let element = node.unwrap();
+        if element.is_element() {
+            let focusable = do element.with_imm_element |element| {
+                match element.tag_name.as_slice() {
+                    "a" | "area" => element.get_attr("href").is_some(),
+                    "button" | "input" | "select" | "textarea" => true,
+                    _ => element.get_attr("tabindex").is_some(),
+                }
+            };
+            if focusable {
+                return Some(element)
+            }
+        }
+        node = element.parent_node();
+    }
+    None
+}
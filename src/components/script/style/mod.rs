@@ -1,11 +0,0 @@
-/* This Source Code Form is subject to the terms of the Mozilla Public
- * License, v. 2.0. If a copy of the MPL was not distributed with this
- * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
-
-pub mod stylesheets;
-pub mod errors;
-pub mod selectors;
-pub mod properties;
-pub mod namespaces;
-pub mod media_queries;
-pub mod parsing_utils;
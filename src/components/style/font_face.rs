@@ -0,0 +1,335 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::StrAsciiExt;
+use cssparser::*;
+use extra::url::Url;
+use errors::{ErrorLoggerIterator, log_css_error};
+use stylesheets::{CSSRule, CSSFontFaceRule};
+use properties::longhands::{font_family, font_style, font_weight};
+use properties::computed_values;
+use servo_util::url::make_url;
+
+
+/// The font formats that the font engines read. Sources with other `format()` hints are skipped.
+static SUPPORTED_FORMATS: &'static [&'static str] = &["truetype", "opentype"];
+
+static MAX_CODE_POINT: u32 = 0x10FFFF;
+
+
+pub struct FontFaceRule {
+    family: ~str,
+    /// The URLs of the font data, in the order in which they are tried.
+    sources: ~[Url],
+    weight: computed_values::font_weight::ComputedValue,
+    style: computed_values::font_style::ComputedValue,
+    /// The ranges of characters that the face is used for. Empty means all characters.
+    unicode_range: ~[(char, char)],
+}
+
+
+/// Parses `@font-face { ... }`. The `font-family` and `src` descriptors are required. Sources are
+/// resolved against `base_url`, but the font data is not fetched here.
+///
+/// TODO: `local()` sources are parsed, but skipped rather than looked up in the system fonts.
+pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut ~[CSSRule], base_url: &Url) {
+    let location = rule.location;
+    macro_rules! syntax_error(
+        () => {{
+            log_css_error(location, "Invalid @font-face rule");
+            return
+        }};
+    );
+    if rule.prelude.as_slice().skip_whitespace().next().is_some() { syntax_error!() }
+    let block = match rule.block {
+        Some(block) => block,
+        None => syntax_error!(),
+    };
+
+    let mut family = None;
+    let mut sources = None;
+    let mut weight = font_weight::get_initial_value();
+    let mut style = font_style::get_initial_value();
+    let mut unicode_range = ~[];
+    for item in ErrorLoggerIterator(parse_declaration_list(block.move_iter())) {
+        match item {
+            Decl_AtRule(rule) => log_css_error(
+                rule.location, fmt!("Unsupported at-rule in @font-face: @%s", rule.name)),
+            Declaration(Declaration{ location: l, name: n, value: v, important: _ }) => {
+                let value = v.as_slice();
+                let valid = match n.to_ascii_lower().as_slice() {
+                    "font-family" => match parse_family(value) {
+                        Some(value) => { family = Some(value); true }
+                        None => false,
+                    },
+                    "src" => match parse_sources(value, base_url) {
+                        Some(value) => { sources = Some(value); true }
+                        None => false,
+                    },
+                    "font-weight" => match parse_weight(value) {
+                        Some(value) => { weight = value; true }
+                        None => false,
+                    },
+                    "font-style" => match font_style::parse(value, base_url) {
+                        Some(value) => { style = value; true }
+                        None => false,
+                    },
+                    "unicode-range" => match parse_unicode_range(value) {
+                        Some(value) => { unicode_range = value; true }
+                        None => false,
+                    },
+                    _ => false,
+                };
+                if !valid {
+                    log_css_error(l, "Invalid @font-face descriptor")
+                }
+            }
+        }
+    }
+
+    match (family, sources) {
+        (Some(family), Some(sources)) => parent_rules.push(CSSFontFaceRule(FontFaceRule {
+            family: family,
+            sources: sources,
+            weight: weight,
+            style: style,
+            unicode_range: unicode_range,
+        })),
+        _ => syntax_error!(),
+    }
+}
+
+
+/// A single <family-name>.
+fn parse_family(input: &[ComponentValue]) -> Option<~str> {
+    match font_family::from_iter(input.skip_whitespace()) {
+        Some(families) if families.len() == 1 => match families[0] {
+            font_family::FamilyName(ref family) => Some(family.clone()),
+        },
+        _ => None,
+    }
+}
+
+
+/// [ <url> [ format(<string>#) ]? | local(<family-name>) ]#
+///
+/// Sources whose format hints are all unsupported are dropped, and so are local sources, but the
+/// descriptor stays valid.
+fn parse_sources(input: &[ComponentValue], base_url: &Url) -> Option<~[Url]> {
+    let mut sources = ~[];
+    let mut iter = input.skip_whitespace();
+    loop {
+        let url = match iter.next() {
+            Some(&URL(ref value)) => Some(make_url(value.to_owned(), Some(base_url.clone()))),
+            Some(&Function(ref name, ref arguments)) if name.eq_ignore_ascii_case("local") => {
+                if parse_family(arguments.as_slice()).is_none() {
+                    return None
+                }
+                None
+            }
+            _ => return None,
+        };
+        let mut is_supported = url.is_some();
+        let mut next = iter.next();
+        match next {
+            Some(&Function(ref name, ref arguments))
+                    if url.is_some() && name.eq_ignore_ascii_case("format") => {
+                let mut formats = ~[];
+                for argument in arguments.as_slice().skip_whitespace() {
+                    match argument {
+                        &String(ref format) => formats.push(format.to_ascii_lower()),
+                        &Comma => (),
+                        _ => return None,
+                    }
+                }
+                if formats.is_empty() {
+                    return None
+                }
+                is_supported = do formats.iter().any |format| {
+                    SUPPORTED_FORMATS.contains(&format.as_slice())
+                };
+                next = iter.next();
+            }
+            _ => (),
+        }
+        if is_supported {
+            sources.push(url.unwrap());
+        }
+        match next {
+            Some(&Comma) => (),
+            None => return Some(sources),
+            _ => return None,
+        }
+    }
+}
+
+
+/// normal | bold | 100 | 200 | 300 | 400 | 500 | 600 | 700 | 800 | 900
+fn parse_weight(input: &[ComponentValue]) -> Option<computed_values::font_weight::ComputedValue> {
+    let mut iter = input.skip_whitespace();
+    let value = match iter.next() {
+        Some(value) if iter.next().is_none() => value,
+        _ => return None,
+    };
+    // 'bolder' and 'lighter' are relative to a parent, which a font face does not have.
+    match font_weight::from_component_value(value) {
+        Some(font_weight::SpecifiedWeight100) => Some(computed_values::font_weight::Weight100),
+        Some(font_weight::SpecifiedWeight200) => Some(computed_values::font_weight::Weight200),
+        Some(font_weight::SpecifiedWeight300) => Some(computed_values::font_weight::Weight300),
+        Some(font_weight::SpecifiedWeight400) => Some(computed_values::font_weight::Weight400),
+        Some(font_weight::SpecifiedWeight500) => Some(computed_values::font_weight::Weight500),
+        Some(font_weight::SpecifiedWeight600) => Some(computed_values::font_weight::Weight600),
+        Some(font_weight::SpecifiedWeight700) => Some(computed_values::font_weight::Weight700),
+        Some(font_weight::SpecifiedWeight800) => Some(computed_values::font_weight::Weight800),
+        Some(font_weight::SpecifiedWeight900) => Some(computed_values::font_weight::Weight900),
+        _ => None,
+    }
+}
+
+
+/// <urange>#
+fn parse_unicode_range(input: &[ComponentValue]) -> Option<~[(char, char)]> {
+    let mut ranges = ~[];
+    let mut iter = input.skip_whitespace();
+    loop {
+        match iter.next() {
+            Some(&UnicodeRange(start, end)) if start <= end && start <= MAX_CODE_POINT => {
+                // Ranges past the last code point are clamped to it.
+                ranges.push((start as char, end.min(&MAX_CODE_POINT) as char))
+            }
+            _ => return None,
+        }
+        match iter.next() {
+            Some(&Comma) => (),
+            None => return Some(ranges),
+            _ => return None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod font_face_tests {
+    use extra::url;
+    use media_queries::{Device, Screen};
+    use properties::common_types::computed::Length;
+    use properties::computed_values::{font_style, font_weight};
+    use stylesheets::Stylesheet;
+    use super::FontFaceRule;
+
+    /// Parses a style sheet and returns its `@font-face` rules.
+    fn font_faces(css: &str) -> ~[FontFaceRule] {
+        let base_url = url::from_str("http://example.com/css/style.css").unwrap();
+        let stylesheet = Stylesheet::from_str(css, base_url);
+        let device = Device::new(Screen, Length(800 * 60), Length(600 * 60), 1.0);
+        let mut rules = ~[];
+        do stylesheet.each_font_face_rule(&device) |rule| {
+            rules.push(FontFaceRule {
+                family: rule.family.clone(),
+                sources: rule.sources.clone(),
+                weight: rule.weight,
+                style: rule.style,
+                unicode_range: rule.unicode_range.clone(),
+            })
+        }
+        rules
+    }
+
+    /// Parses a style sheet with a single `@font-face` rule, and returns it.
+    fn font_face(css: &str) -> FontFaceRule {
+        let mut rules = font_faces(css);
+        assert_eq!(rules.len(), 1);
+        rules.pop()
+    }
+
+    fn sources(rule: &FontFaceRule) -> ~[~str] {
+        rule.sources.iter().map(|source| source.to_str()).collect()
+    }
+
+    #[test]
+    fn sources_with_formats() {
+        let rule = font_face("@font-face { font-family: Foo; src: url(a.woff) format(\"woff\"), \
+                              url(b.ttf) format(\"truetype\"), \
+                              url(c.otf) format(\"woff\", \"OpenType\"), \
+                              url(/d.ttf) }");
+        assert_eq!(rule.family, ~"Foo");
+        assert_eq!(sources(&rule), ~[~"http://example.com/css/b.ttf",
+                                     ~"http://example.com/css/c.otf",
+                                     ~"http://example.com/d.ttf"]);
+        assert_eq!(rule.weight, font_weight::Weight400);
+        assert_eq!(rule.style, font_style::normal);
+        assert!(rule.unicode_range.is_empty());
+    }
+
+    #[test]
+    fn invalid_sources() {
+        // The rule needs a valid `src` descriptor.
+        assert!(font_faces("@font-face { font-family: Foo; src: url(a.ttf) format() }")
+                .is_empty());
+        assert!(font_faces("@font-face { font-family: Foo; src: url(a.ttf) format(truetype) }")
+                .is_empty());
+        assert!(font_faces("@font-face { font-family: Foo; src: url(a.ttf) url(b.ttf) }")
+                .is_empty());
+        assert!(font_faces("@font-face { font-family: Foo; src: url(a.ttf), }").is_empty());
+        assert!(font_faces("@font-face { font-family: Foo; src: \"a.ttf\" }").is_empty());
+    }
+
+    #[test]
+    fn local_sources() {
+        let rule = font_face("@font-face { font-family: Foo; \
+                              src: local(Foo Bold), local(\"Foo\"), url(a.ttf) }");
+        assert_eq!(sources(&rule), ~[~"http://example.com/css/a.ttf"]);
+
+        // A rule with only local sources has nothing to load, but is still valid.
+        let rule = font_face("@font-face { font-family: Foo; src: local(Foo) }");
+        assert!(rule.sources.is_empty());
+
+        assert!(font_faces("@font-face { font-family: Foo; src: local() }").is_empty());
+        assert!(font_faces("@font-face { font-family: Foo; src: local(Foo) format(\"truetype\") }")
+                .is_empty());
+    }
+
+    #[test]
+    fn invalid_descriptors_are_dropped() {
+        let rule = font_face("@font-face { font-family: Foo; src: url(a.ttf); \
+                              font-weight: bold; font-weight: bolder; \
+                              font-style: italic; font-style: sideways; \
+                              unicode-range: U+0-7F; unicode-range: red; \
+                              font-stretch: condensed; color: red }");
+        assert_eq!(rule.weight, font_weight::Weight700);
+        assert_eq!(rule.style, font_style::italic);
+        assert_eq!(rule.unicode_range, ~[('\x00', '\x7f')]);
+
+        // An invalid `font-family` or `src` does not replace a valid one.
+        let rule = font_face("@font-face { font-family: Foo; font-family: Foo, Bar; \
+                              src: url(a.ttf); src: url(b.ttf) format(); }");
+        assert_eq!(rule.family, ~"Foo");
+        assert_eq!(sources(&rule), ~[~"http://example.com/css/a.ttf"]);
+    }
+
+    #[test]
+    fn unicode_ranges() {
+        let rule = font_face("@font-face { font-family: Foo; src: url(a.ttf); \
+                              unicode-range: U+26, U+0-7F, U+4??, U+10FF00-12FFFF }");
+        assert_eq!(rule.unicode_range, ~[('\x26', '\x26'),
+                                         ('\x00', '\x7f'),
+                                         ('\u0400', '\u04ff'),
+                                         ('\U0010ff00', '\U0010ffff')]);
+
+        assert!(font_face("@font-face { font-family: Foo; src: url(a.ttf); \
+                           unicode-range: U+7F-0 }").unicode_range.is_empty());
+        assert!(font_face("@font-face { font-family: Foo; src: url(a.ttf); \
+                           unicode-range: U+110000 }").unicode_range.is_empty());
+    }
+
+    #[test]
+    fn required_descriptors() {
+        assert!(font_faces("@font-face { src: url(a.ttf) }").is_empty());
+        assert!(font_faces("@font-face { font-family: Foo }").is_empty());
+        assert_eq!(font_faces("@font-face { font-family: Foo; src: url(a.ttf); font-family: 1 }")
+                   .len(), 1);
+        assert!(font_faces("@font-face foo { font-family: Foo; src: url(a.ttf) }").is_empty());
+        assert!(font_faces("@font-face;").is_empty());
+    }
+}
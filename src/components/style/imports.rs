@@ -0,0 +1,81 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use cssparser::*;
+use extra::url::Url;
+use errors::log_css_error;
+use media_queries::{MediaQueryList, parse_media_query_list};
+use stylesheets::{CSSRule, CSSImportRule, Stylesheet};
+use servo_net::resource_task::{ResourceTask, load_whole_resource};
+use servo_util::url::make_url;
+
+
+pub struct ImportRule {
+    url: Url,
+    media_queries: MediaQueryList,
+    // None until loaded, or if loading failed.
+    stylesheet: Option<~Stylesheet>,
+}
+
+
+/// Parses `@import url(...) media-list;`. The URL is resolved against `base_url`, but the
+/// style sheet is not fetched until `load_imports` is called.
+pub fn parse_import_rule(rule: AtRule, parent_rules: &mut ~[CSSRule], base_url: &Url) {
+    let location = rule.location;
+    macro_rules! syntax_error(
+        () => {{
+            log_css_error(location, "Invalid @import rule");
+            return
+        }};
+    );
+    if rule.block.is_some() { syntax_error!() }
+    let mut iter = rule.prelude.move_skip_whitespace();
+    let url = match iter.next() {
+        Some(URL(value)) | Some(String(value)) => make_url(value, Some(base_url.clone())),
+        _ => syntax_error!(),
+    };
+    let media_queries: ~[ComponentValue] = iter.collect();
+    parent_rules.push(CSSImportRule(ImportRule {
+        url: url,
+        media_queries: parse_media_query_list(media_queries.as_slice()),
+        stylesheet: None,
+    }))
+}
+
+
+/// Fetches and parses the style sheets imported by `rules`, recursively.
+///
+/// `ancestors` are the URLs of the style sheets currently being loaded, used to break cycles.
+pub fn load_imports(rules: &mut ~[CSSRule], resource_task: &ResourceTask,
+                    ancestors: &mut ~[Url]) {
+    for rule in rules.mut_iter() {
+        match *rule {
+            CSSImportRule(ref mut import_rule) => import_rule.load(resource_task, ancestors),
+            // @import is only valid at the top-level of a style sheet.
+            _ => (),
+        }
+    }
+}
+
+
+impl ImportRule {
+    fn load(&mut self, resource_task: &ResourceTask, ancestors: &mut ~[Url]) {
+        if ancestors.iter().any(|url| *url == self.url) {
+            info!("@import cycle: %s is already being loaded", self.url.to_str());
+            return
+        }
+        let bytes = match load_whole_resource(resource_task, self.url.clone()) {
+            Ok(bytes) => bytes,
+            Err(()) => {
+                info!("@import: failed to load %s", self.url.to_str());
+                return
+            }
+        };
+        let mut stylesheet = Stylesheet::from_bytes(bytes, self.url.clone());
+        ancestors.push(self.url.clone());
+        load_imports(&mut stylesheet.rules, resource_task, ancestors);
+        ancestors.pop();
+        self.stylesheet = Some(~stylesheet);
+    }
+}
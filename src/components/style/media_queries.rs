@@ -0,0 +1,461 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::StrAsciiExt;
+use cssparser::*;
+use extra::url::Url;
+use errors::{ErrorLoggerIterator, log_css_error};
+use stylesheets::{CSSRule, CSSMediaRule, parse_style_rule, parse_nested_at_rule};
+use namespaces::NamespaceMap;
+use parsing_utils::get_ident_lower;
+use properties::longhands::font_size;
+use properties::common_types::{Float, specified};
+use properties::common_types::computed::Length;
+
+
+pub struct MediaRule {
+    media_queries: MediaQueryList,
+    rules: ~[CSSRule],
+}
+
+
+pub struct MediaQueryList {
+    // "not all" is omitted from the list.
+    // An empty list never matches.
+    media_queries: ~[MediaQuery]
+}
+
+struct MediaQuery {
+    qualifier: Option<Qualifier>,
+    media_type: MediaQueryType,
+    expressions: ~[Expression],
+}
+
+
+enum Qualifier {
+    Only,  // Only hides the query from legacy user agents, it does not change matching.
+    Not,
+}
+
+
+enum MediaQueryType {
+    All,  // Always true
+    MediaType(MediaType),
+    UnknownMediaType,  // Never true
+}
+
+#[deriving(Eq)]
+pub enum MediaType {
+    Screen,
+    Print,
+}
+
+
+/// A media feature expression, such as `(min-width: 600px)`.
+enum Expression {
+    Width(Range<Length>),
+    Height(Range<Length>),
+    Orientation(Orientation),
+    Resolution(Range<Float>),  // In dots per 'px' unit.
+}
+
+enum Range<T> {
+    Min(T),
+    Max(T),
+    Exact(T),
+}
+
+impl<T: Ord> Range<T> {
+    fn evaluate(&self, value: T) -> bool {
+        match *self {
+            Min(ref width) => value >= *width,
+            Max(ref width) => value <= *width,
+            Exact(ref width) => value == *width,
+        }
+    }
+}
+
+#[deriving(Eq)]
+enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+
+pub struct Device {
+    media_type: MediaType,
+    viewport_width: Length,
+    viewport_height: Length,
+    /// The number of device pixels per CSS 'px' unit.
+    device_pixel_ratio: Float,
+}
+
+impl Device {
+    pub fn new(media_type: MediaType, viewport_width: Length, viewport_height: Length,
+               device_pixel_ratio: Float) -> Device {
+        Device {
+            media_type: media_type,
+            viewport_width: viewport_width,
+            viewport_height: viewport_height,
+            device_pixel_ratio: device_pixel_ratio,
+        }
+    }
+}
+
+
+pub fn parse_media_rule(rule: AtRule, parent_rules: &mut ~[CSSRule],
+                        namespaces: &NamespaceMap, base_url: &Url) {
+    let media_queries = parse_media_query_list(rule.prelude);
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(rule.location, "Invalid @media rule");
+            return
+        }
+    };
+    let mut rules = ~[];
+    for rule in ErrorLoggerIterator(parse_rule_list(block.move_iter())) {
+        match rule {
+            QualifiedRule(rule) => parse_style_rule(rule, &mut rules, namespaces, base_url),
+            AtRule(rule) => parse_nested_at_rule(
+                rule.name.to_ascii_lower(), rule, &mut rules, namespaces, base_url),
+        }
+    }
+    parent_rules.push(CSSMediaRule(MediaRule {
+        media_queries: media_queries,
+        rules: rules,
+    }))
+}
+
+
+pub fn parse_media_query_list(input: &[ComponentValue]) -> MediaQueryList {
+    if input.skip_whitespace().next().is_none() {
+        return MediaQueryList{ media_queries: ~[MediaQuery{
+            qualifier: None, media_type: All, expressions: ~[],
+        }] }
+    }
+    let mut queries = ~[];
+    let mut start = 0;
+    loop {
+        let end = match input.slice_from(start).iter().position(is_comma) {
+            Some(position) => start + position,
+            None => input.len(),
+        };
+        // An invalid query is "not all": ignore this comma-separated part.
+        parse_media_query(input.slice(start, end)).map_move(|mq| queries.push(mq));
+        if end == input.len() {
+            return MediaQueryList{ media_queries: queries }
+        }
+        start = end + 1;
+    }
+}
+
+
+#[inline]
+fn is_comma(component_value: &ComponentValue) -> bool {
+    match *component_value {
+        Comma => true,
+        _ => false,
+    }
+}
+
+
+/// [ only | not ]? <media_type> [ and <expression> ]* | <expression> [ and <expression> ]*
+fn parse_media_query(input: &[ComponentValue]) -> Option<MediaQuery> {
+    let iter = &mut input.skip_whitespace();
+    let mut qualifier = None;
+    let mut expressions = ~[];
+    let media_type = match iter.next() {
+        Some(&Ident(ref value)) => {
+            let mut value = value.to_ascii_lower();
+            match value.as_slice() {
+                "only" => qualifier = Some(Only),
+                "not" => qualifier = Some(Not),
+                _ => (),
+            }
+            if qualifier.is_some() {
+                value = match iter.next() {
+                    Some(&Ident(ref value)) => value.to_ascii_lower(),
+                    _ => return None,
+                }
+            }
+            match value.as_slice() {
+                "screen" => MediaType(Screen),
+                "print" => MediaType(Print),
+                "all" => All,
+                "only" | "not" | "and" => return None,
+                _ => UnknownMediaType,
+            }
+        },
+        Some(&ParenthesisBlock(ref block)) => {
+            expressions.push(match parse_expression(block.as_slice()) {
+                Some(expression) => expression,
+                None => return None,
+            });
+            All
+        },
+        _ => return None,
+    };
+    loop {
+        match iter.next() {
+            None => break,
+            Some(&Ident(ref value)) if value.eq_ignore_ascii_case("and") => (),
+            _ => return None,
+        }
+        match iter.next() {
+            Some(&ParenthesisBlock(ref block)) => match parse_expression(block.as_slice()) {
+                Some(expression) => expressions.push(expression),
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some(MediaQuery{ qualifier: qualifier, media_type: media_type, expressions: expressions })
+}
+
+
+/// ( <media_feature> : <value> )
+///
+/// TODO: media features without a value, such as `(color)`.
+fn parse_expression(input: &[ComponentValue]) -> Option<Expression> {
+    let iter = &mut input.skip_whitespace();
+    let name = match iter.next() {
+        Some(&Ident(ref value)) => value.to_ascii_lower(),
+        _ => return None,
+    };
+    match iter.next() {
+        Some(&Colon) => (),
+        _ => return None,
+    }
+    let value = match iter.next() {
+        Some(value) => value,
+        None => return None,
+    };
+    if iter.next().is_some() {
+        return None
+    }
+
+    let (prefix, feature) = if name.starts_with("min-") || name.starts_with("max-") {
+        (name.slice_to(4), name.slice_from(4))
+    } else {
+        ("", name.as_slice())
+    };
+    match feature {
+        "width" => parse_length(value).map_move(|length| Width(make_range(prefix, length))),
+        "height" => parse_length(value).map_move(|length| Height(make_range(prefix, length))),
+        "resolution" => parse_resolution(value).map_move(|ratio| {
+            Resolution(make_range(prefix, ratio))
+        }),
+        "orientation" if prefix.is_empty() => match get_ident_lower(value) {
+            Some(ref keyword) if keyword.as_slice() == "portrait" => Some(Orientation(Portrait)),
+            Some(ref keyword) if keyword.as_slice() == "landscape" => Some(Orientation(Landscape)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+
+#[inline]
+fn make_range<T>(prefix: &str, value: T) -> Range<T> {
+    match prefix {
+        "min-" => Min(value),
+        "max-" => Max(value),
+        _ => Exact(value),
+    }
+}
+
+
+/// Relative lengths in media queries are relative to the initial value of 'font-size'.
+fn parse_length(input: &ComponentValue) -> Option<Length> {
+    let font_size = font_size::get_initial_value();
+    do specified::Length::parse_non_negative(input).map_move |length| {
+        match length {
+            specified::Au(value) => Length(value),
+            specified::Em(value) => font_size.times(value),
+            specified::Ex(value) => font_size.times(value * 0.5),
+        }
+    }
+}
+
+
+/// Returns a number of dots per 'px' unit.
+fn parse_resolution(input: &ComponentValue) -> Option<Float> {
+    match input {
+        &Dimension(ref value, ref unit) if value.value > 0. => {
+            match unit.to_ascii_lower().as_slice() {
+                "dppx" => Some(value.value),
+                "dpi" => Some(value.value / 96.),
+                "dpcm" => Some(value.value * 2.54 / 96.),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+
+impl MediaQueryList {
+    pub fn evaluate(&self, device: &Device) -> bool {
+        do self.media_queries.iter().any |mq| {
+            let media_type_matches = match mq.media_type {
+                MediaType(media_type) => media_type == device.media_type,
+                All => true,
+                UnknownMediaType => false,
+            };
+            let matches = media_type_matches &&
+                mq.expressions.iter().all(|expression| expression.evaluate(device));
+            match mq.qualifier {
+                Some(Not) => !matches,
+                Some(Only) | None => matches,
+            }
+        }
+    }
+}
+
+
+impl Expression {
+    fn evaluate(&self, device: &Device) -> bool {
+        match *self {
+            Width(ref range) => range.evaluate(device.viewport_width),
+            Height(ref range) => range.evaluate(device.viewport_height),
+            Resolution(ref range) => range.evaluate(device.device_pixel_ratio),
+            Orientation(orientation) => {
+                // 'portrait' when the height is greater than or equal to the width.
+                let portrait = device.viewport_height >= device.viewport_width;
+                orientation == if portrait { Portrait } else { Landscape }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod media_queries_tests {
+    use cssparser::{ComponentValue, tokenize};
+    use extra::url;
+    use properties::common_types::computed::Length;
+    use selector_matching::{AuthorOrigin, Stylist};
+    use stylesheets::Stylesheet;
+    use super::*;
+
+    fn px(value: i64) -> Length {
+        Length(value * 60)
+    }
+
+    fn screen(width: i64, height: i64) -> Device {
+        Device::new(Screen, px(width), px(height), 1.0)
+    }
+
+    fn matches(media_query_list: &str, device: &Device) -> bool {
+        let input: ~[ComponentValue] = tokenize(media_query_list).map(|(component_value, _)| {
+            component_value
+        }).collect();
+        parse_media_query_list(input).evaluate(device)
+    }
+
+    #[test]
+    fn media_types() {
+        let device = screen(800, 600);
+        assert!(matches("", &device));
+        assert!(matches("all", &device));
+        assert!(matches("screen", &device));
+        assert!(matches("SCREEN", &device));
+        assert!(!matches("print", &device));
+        assert!(!matches("tv", &device));
+
+        let device = Device::new(Print, px(800), px(600), 1.0);
+        assert!(matches("print", &device));
+        assert!(!matches("screen", &device));
+    }
+
+    #[test]
+    fn not_and_only() {
+        let device = screen(800, 600);
+        assert!(matches("only screen", &device));
+        assert!(!matches("only print", &device));
+        assert!(!matches("not screen", &device));
+        assert!(matches("not print", &device));
+        assert!(!matches("not all", &device));
+        // `not` negates the whole query, not just the media type.
+        assert!(matches("not screen and (max-width: 500px)", &device));
+        assert!(!matches("not screen and (min-width: 500px)", &device));
+        // `only` and `not` must be followed by a media type.
+        assert!(!matches("only", &device));
+        assert!(!matches("not (min-width: 500px)", &device));
+    }
+
+    #[test]
+    fn width_and_height_boundaries() {
+        let device = screen(800, 600);
+        assert!(matches("(min-width: 800px)", &device));
+        assert!(matches("(max-width: 800px)", &device));
+        assert!(matches("(width: 800px)", &device));
+        assert!(!matches("(min-width: 801px)", &device));
+        assert!(!matches("(max-width: 799px)", &device));
+        assert!(matches("(min-width: 800px) and (max-width: 800px)", &device));
+        assert!(matches("(min-height: 600px) and (max-height: 600px)", &device));
+        assert!(!matches("(height: 601px)", &device));
+        // Relative lengths are relative to the initial font size, 16px.
+        assert!(matches("(min-width: 50em)", &device));
+        assert!(!matches("(min-width: 51em)", &device));
+    }
+
+    #[test]
+    fn orientation_and_resolution() {
+        assert!(matches("(orientation: landscape)", &screen(800, 600)));
+        assert!(!matches("(orientation: portrait)", &screen(800, 600)));
+        assert!(matches("(orientation: portrait)", &screen(600, 600)));
+
+        let device = Device::new(Screen, px(800), px(600), 2.0);
+        assert!(matches("(min-resolution: 2dppx)", &device));
+        assert!(matches("(min-resolution: 192dpi)", &device));
+        assert!(!matches("(min-resolution: 3dppx)", &device));
+        assert!(matches("(max-resolution: 1dppx)", &screen(800, 600)));
+    }
+
+    #[test]
+    fn unknown_features_make_the_query_false() {
+        let device = screen(800, 600);
+        assert!(!matches("(unknown-feature: 1px)", &device));
+        assert!(!matches("screen and (unknown-feature: 1px)", &device));
+        assert!(!matches("not screen and (unknown-feature: 1px)", &device));
+        assert!(!matches("(min-orientation: portrait)", &device));
+        assert!(!matches("(min-width: red)", &device));
+        assert!(!matches("(min-width)", &device));
+        assert!(!matches("screen or print", &device));
+    }
+
+    #[test]
+    fn comma_separated_lists() {
+        let device = screen(800, 600);
+        assert!(matches("print, screen", &device));
+        assert!(matches("screen, print", &device));
+        assert!(!matches("print, (max-width: 500px)", &device));
+        // An invalid query in the list is ignored by the others.
+        assert!(matches("(unknown-feature: 1px), screen", &device));
+        assert!(matches("screen and, screen", &device));
+        assert!(!matches("(unknown-feature: 1px), print", &device));
+    }
+
+    #[test]
+    fn viewport_changes_flip_stylist_device() {
+        let base_url = url::from_str("http://example.com/").unwrap();
+        let stylesheet = Stylesheet::from_str("@media (min-width: 600px) { p { color: red } }",
+                                              base_url);
+        let mut stylist = Stylist::new(screen(500, 400));
+        stylist.add_stylesheet(stylesheet, AuthorOrigin);
+
+        // The media query starts to match.
+        assert!(stylist.set_device(screen(800, 600)));
+        // It still matches.
+        assert!(!stylist.set_device(screen(1024, 768)));
+        // The boundary still matches.
+        assert!(!stylist.set_device(screen(600, 600)));
+        // It stops matching.
+        assert!(stylist.set_device(screen(599, 600)));
+        // The height does not matter.
+        assert!(!stylist.set_device(screen(599, 300)));
+    }
+}
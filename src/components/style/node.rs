@@ -0,0 +1,40 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Traits that nodes must implement. Breaks the otherwise-cyclic dependency between layout and
+//! style.
+
+/// A node in the document tree, as seen by selector matching.
+pub trait TNode<E: TElement> : Clone {
+    fn parent_node(&self) -> Option<Self>;
+    fn prev_sibling(&self) -> Option<Self>;
+    fn next_sibling(&self) -> Option<Self>;
+    fn first_child(&self) -> Option<Self>;
+
+    fn is_element(&self) -> bool;
+    fn is_text(&self) -> bool;
+
+    /// Calls the given closure with the element this node wraps. Fails if this node is not an
+    /// element.
+    fn with_element<R>(&self, f: &fn(&E) -> R) -> R;
+}
+
+/// An element, as seen by selector matching.
+pub trait TElement {
+    fn get_local_name<'a>(&'a self) -> &'a str;
+    fn get_namespace_url<'a>(&'a self) -> &'a str;
+    fn get_attr<'a>(&'a self, name: &str) -> Option<&'a str>;
+
+    /// The target of this element if it is a hyperlink (`a`, `area` or `link` with an `href`).
+    fn get_link<'a>(&'a self) -> Option<&'a str>;
+
+    /// Dynamic state, for the user action pseudo-classes.
+    fn get_hover_state(&self) -> bool;
+    fn get_active_state(&self) -> bool;
+    fn get_focus_state(&self) -> bool;
+
+    /// UI element state, for `:checked` and `:disabled`.
+    fn get_checked_state(&self) -> bool;
+    fn get_disabled_state(&self) -> bool;
+}
@@ -12,6 +12,7 @@ pub mod specified {
     use super::{Integer, Float};
     pub use CSSColor = cssparser::Color;
 
+    #[deriving(Eq, Clone)]
     pub enum Length {
         Au(Integer),  // application units
         Em(Float),
@@ -65,9 +66,10 @@ pub mod specified {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentage {
         LP_Length(Length),
-        LP_Percentage(Float),
+        LP_Percentage(Float),  // [0 .. 100%] maps to [0.0 .. 1.0]
     }
     impl LengthOrPercentage {
         fn parse_internal(input: &ComponentValue, negative_ok: bool)
@@ -76,7 +78,7 @@ pub mod specified {
                 &Dimension(ref value, ref unit) if negative_ok || value.value >= 0.
                 => Length::parse_dimension(value.value, unit.as_slice()).map_move(LP_Length),
                 &ast::Percentage(ref value) if negative_ok || value.value >= 0.
-                => Some(LP_Percentage(value.value)),
+                => Some(LP_Percentage(value.value / 100.)),
                 &Number(ref value) if value.value == 0. =>  Some(LP_Length(Au(0))),
                 _ => None
             }
@@ -91,9 +93,10 @@ pub mod specified {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentageOrAuto {
         LPA_Length(Length),
-        LPA_Percentage(Float),
+        LPA_Percentage(Float),  // [0 .. 100%] maps to [0.0 .. 1.0]
         LPA_Auto,
     }
     impl LengthOrPercentageOrAuto {
@@ -103,7 +106,7 @@ pub mod specified {
                 &Dimension(ref value, ref unit) if negative_ok || value.value >= 0.
                 => Length::parse_dimension(value.value, unit.as_slice()).map_move(LPA_Length),
                 &ast::Percentage(ref value) if negative_ok || value.value >= 0.
-                => Some(LPA_Percentage(value.value)),
+                => Some(LPA_Percentage(value.value / 100.)),
                 &Number(ref value) if value.value == 0. => Some(LPA_Length(Au(0))),
                 &Ident(ref value) if value.eq_ignore_ascii_case("auto") => Some(LPA_Auto),
                 _ => None
@@ -118,6 +121,35 @@ pub mod specified {
             LengthOrPercentageOrAuto::parse_internal(input, /* negative_ok = */ false)
         }
     }
+
+    #[deriving(Eq, Clone)]
+    pub enum LengthOrPercentageOrNone {
+        LPN_Length(Length),
+        LPN_Percentage(Float),  // [0 .. 100%] maps to [0.0 .. 1.0]
+        LPN_None,
+    }
+    impl LengthOrPercentageOrNone {
+        fn parse_internal(input: &ComponentValue, negative_ok: bool)
+                     -> Option<LengthOrPercentageOrNone> {
+            match input {
+                &Dimension(ref value, ref unit) if negative_ok || value.value >= 0.
+                => Length::parse_dimension(value.value, unit.as_slice()).map_move(LPN_Length),
+                &ast::Percentage(ref value) if negative_ok || value.value >= 0.
+                => Some(LPN_Percentage(value.value / 100.)),
+                &Number(ref value) if value.value == 0. => Some(LPN_Length(Au(0))),
+                &Ident(ref value) if value.eq_ignore_ascii_case("none") => Some(LPN_None),
+                _ => None
+            }
+        }
+        #[inline]
+        pub fn parse(input: &ComponentValue) -> Option<LengthOrPercentageOrNone> {
+            LengthOrPercentageOrNone::parse_internal(input, /* negative_ok = */ true)
+        }
+        #[inline]
+        pub fn parse_non_negative(input: &ComponentValue) -> Option<LengthOrPercentageOrNone> {
+            LengthOrPercentageOrNone::parse_internal(input, /* negative_ok = */ false)
+        }
+    }
 }
 
 pub mod computed {
@@ -136,6 +168,7 @@ pub mod computed {
         font_weight: font_weight::ComputedValue,
         // TODO, as needed: root font size, viewport size, etc.
     }
+    #[deriving(Eq, Ord, Clone)]
     pub struct Length(Integer);  // in application units
     impl Length {
         pub fn times(self, factor: Float) -> Length {
@@ -154,6 +187,7 @@ pub mod computed {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentage {
         LP_Length(Length),
         LP_Percentage(Float),
@@ -166,6 +200,7 @@ pub mod computed {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentageOrAuto {
         LPA_Length(Length),
         LPA_Percentage(Float),
@@ -179,4 +214,19 @@ pub mod computed {
             specified::LPA_Auto => LPA_Auto,
         }
     }
+
+    #[deriving(Eq, Clone)]
+    pub enum LengthOrPercentageOrNone {
+        LPN_Length(Length),
+        LPN_Percentage(Float),
+        LPN_None,
+    }
+    pub fn compute_LengthOrPercentageOrNone(value: specified::LengthOrPercentageOrNone,
+                                            context: &Context) -> LengthOrPercentageOrNone {
+        match value {
+            specified::LPN_Length(value) => LPN_Length(compute_Length(value, context)),
+            specified::LPN_Percentage(value) => LPN_Percentage(value),
+            specified::LPN_None => LPN_None,
+        }
+    }
 }
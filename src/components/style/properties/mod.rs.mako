@@ -0,0 +1,1900 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// This file is a Mako template: http://www.makotemplates.org/
+
+use std::ascii::StrAsciiExt;
+pub use std::iter;
+pub use extra::url::Url;
+pub use extra::arc::Arc;
+pub use cssparser::*;
+pub use errors::{ErrorLoggerIterator, log_css_error};
+pub use parsing_utils::*;
+pub use self::common_types::*;
+
+pub mod common_types;
+
+
+<%!
+
+def to_rust_ident(name):
+    name = name.replace("-", "_")
+    if name in ["static", "super"]:  # Rust keywords
+        name += "_"
+    return name
+
+class Longhand(object):
+    def __init__(self, name):
+        self.name = name
+        self.ident = to_rust_ident(name)
+
+
+class Shorthand(object):
+    def __init__(self, name, sub_properties):
+        self.name = name
+        self.ident = to_rust_ident(name)
+        self.sub_properties = [Longhand(s) for s in sub_properties]
+
+class StyleStruct(object):
+    def __init__(self, name, inherited):
+        self.name = name
+        self.inherited = inherited
+        self.longhands = []
+
+LONGHANDS = []
+SHORTHANDS = []
+STYLE_STRUCTS = []
+THIS_STYLE_STRUCT = None
+
+def new_style_struct(name, is_inherited):
+    global THIS_STYLE_STRUCT
+    style_struct = StyleStruct(name, is_inherited)
+    STYLE_STRUCTS.append(style_struct)
+    THIS_STYLE_STRUCT = style_struct
+    return ""
+
+%>
+
+pub mod longhands {
+    pub use super::*;
+    pub use std;
+
+    <%def name="longhand(name, no_super=False)">
+    <%
+        property = Longhand(name)
+        THIS_STYLE_STRUCT.longhands.append(property)
+        LONGHANDS.append(property)
+    %>
+        pub mod ${property.ident} {
+            % if not no_super:
+                use super::*;
+            % endif
+            ${caller.body()}
+        }
+    </%def>
+
+    <%def name="single_component_value(name)">
+        <%self:longhand name="${name}">
+            ${caller.body()}
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                one_component_value(input).and_then(from_component_value)
+            }
+        </%self:longhand>
+    </%def>
+
+    <%def name="single_keyword(name, values)">
+        <%self:single_component_value name="${name}">
+            // The computed value is the same as the specified value.
+            pub use to_computed_value = std::util::id;
+            #[deriving(Eq, Clone)]
+            pub enum SpecifiedValue {
+                % for value in values.split():
+                    ${to_rust_ident(value)},
+                % endfor
+            }
+            pub type ComputedValue = SpecifiedValue;
+            #[inline] pub fn get_initial_value() -> ComputedValue {
+                ${to_rust_ident(values.split()[0])}
+            }
+            pub fn from_component_value(v: &ComponentValue) -> Option<SpecifiedValue> {
+                do get_ident_lower(v).and_then |keyword| {
+                    match keyword.as_slice() {
+                        % for value in values.split():
+                            "${value}" => Some(${to_rust_ident(value)}),
+                        % endfor
+                        _ => None,
+                    }
+                }
+            }
+        </%self:single_component_value>
+    </%def>
+
+    <%def name="predefined_type(name, type, initial_value, parse_method='parse')">
+        <%self:longhand name="${name}">
+            pub use to_computed_value = super::super::common_types::computed::compute_${type};
+            pub type SpecifiedValue = specified::${type};
+            pub type ComputedValue = computed::${type};
+            #[inline] pub fn get_initial_value() -> ComputedValue { ${initial_value} }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                one_component_value(input).and_then(specified::${type}::${parse_method})
+            }
+        </%self:longhand>
+    </%def>
+
+
+    // CSS 2.1, Section 8 - Box model
+
+    ${new_style_struct("Margin", is_inherited=False)}
+
+    % for side in ["top", "right", "bottom", "left"]:
+        ${predefined_type("margin-" + side, "LengthOrPercentageOrAuto",
+                          "computed::LPA_Length(computed::Length(0))")}
+    % endfor
+
+    ${new_style_struct("Padding", is_inherited=False)}
+
+    % for side in ["top", "right", "bottom", "left"]:
+        ${predefined_type("padding-" + side, "LengthOrPercentage",
+                          "computed::LP_Length(computed::Length(0))",
+                          "parse_non_negative")}
+    % endfor
+
+    ${new_style_struct("Border", is_inherited=False)}
+
+    % for side in ["top", "right", "bottom", "left"]:
+        ${predefined_type("border-%s-color" % side, "CSSColor", "CurrentColor")}
+    % endfor
+
+    ${single_keyword("border-top-style",
+                     "none solid double dotted dashed hidden groove ridge inset outset")}
+    % for side in ["right", "bottom", "left"]:
+        <%self:longhand name="border-${side}-style", no_super="True">
+            pub use super::border_top_style::*;
+            pub type SpecifiedValue = super::border_top_style::SpecifiedValue;
+            pub type ComputedValue = super::border_top_style::ComputedValue;
+        </%self:longhand>
+    % endfor
+
+    pub fn parse_border_width(component_value: &ComponentValue) -> Option<specified::Length> {
+        match component_value {
+            &Ident(ref value) => match value.to_ascii_lower().as_slice() {
+                "thin" => Some(specified::Length::from_px(1.)),
+                "medium" => Some(specified::Length::from_px(3.)),
+                "thick" => Some(specified::Length::from_px(5.)),
+                _ => None
+            },
+            _ => specified::Length::parse_non_negative(component_value)
+        }
+    }
+    % for side in ["top", "right", "bottom", "left"]:
+        <%self:longhand name="border-${side}-width">
+            pub type SpecifiedValue = specified::Length;
+            pub type ComputedValue = computed::Length;
+            #[inline] pub fn get_initial_value() -> ComputedValue {
+                computed::Length(3 * 60)  // medium
+            }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                one_component_value(input).and_then(parse_border_width)
+            }
+            pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                  -> ComputedValue {
+                if context.has_border_${side} { computed::compute_Length(value, context) }
+                else { computed::Length(0) }
+            }
+        </%self:longhand>
+    % endfor
+
+    // CSS Backgrounds and Borders Module Level 3 - Section 5 - Rounded corners
+
+    <%self:longhand name="border-top-left-radius">
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            horizontal: specified::LengthOrPercentage,
+            vertical: specified::LengthOrPercentage,
+        }
+        #[deriving(Eq, Clone)]
+        pub struct ComputedValue {
+            horizontal: computed::LengthOrPercentage,
+            vertical: computed::LengthOrPercentage,
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            ComputedValue {
+                horizontal: computed::LP_Length(computed::Length(0)),
+                vertical: computed::LP_Length(computed::Length(0)),
+            }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            ComputedValue {
+                horizontal: computed::compute_LengthOrPercentage(value.horizontal, context),
+                vertical: computed::compute_LengthOrPercentage(value.vertical, context),
+            }
+        }
+        /// [ <length> | <percentage> ]{1,2}
+        ///
+        /// The second value is the vertical radius, which is the horizontal one if omitted.
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace()
+                                .map(specified::LengthOrPercentage::parse_non_negative);
+            let horizontal = iter.next().unwrap_or(None);
+            let vertical = iter.next().unwrap_or(horizontal);
+            if iter.next().is_some() {
+                return None
+            }
+            match (horizontal, vertical) {
+                (Some(horizontal), Some(vertical)) => {
+                    Some(SpecifiedValue { horizontal: horizontal, vertical: vertical })
+                }
+                _ => None,
+            }
+        }
+    </%self:longhand>
+    % for corner in ["top-right", "bottom-right", "bottom-left"]:
+        <%self:longhand name="border-${corner}-radius", no_super="True">
+            pub use super::border_top_left_radius::*;
+            pub type SpecifiedValue = super::border_top_left_radius::SpecifiedValue;
+            pub type ComputedValue = super::border_top_left_radius::ComputedValue;
+        </%self:longhand>
+    % endfor
+
+    // CSS Backgrounds and Borders Module Level 3 - Section 7.1 - Drop shadows
+
+    <%self:longhand name="box-shadow">
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedBoxShadow {
+            offset_x: specified::Length,
+            offset_y: specified::Length,
+            blur_radius: specified::Length,
+            spread_radius: specified::Length,
+            color: specified::CSSColor,
+            inset: bool,
+        }
+        pub type SpecifiedValue = ~[SpecifiedBoxShadow];
+        #[deriving(Eq, Clone)]
+        pub struct BoxShadow {
+            offset_x: computed::Length,
+            offset_y: computed::Length,
+            blur_radius: computed::Length,
+            spread_radius: computed::Length,
+            color: computed::CSSColor,
+            inset: bool,
+        }
+        /// The shadows, from the one drawn on top to the one at the bottom.
+        pub type ComputedValue = ~[BoxShadow];
+        #[inline] pub fn get_initial_value() -> ComputedValue { ~[] }  // none
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            value.move_iter().map(|shadow| BoxShadow {
+                offset_x: computed::compute_Length(shadow.offset_x, context),
+                offset_y: computed::compute_Length(shadow.offset_y, context),
+                blur_radius: computed::compute_Length(shadow.blur_radius, context),
+                spread_radius: computed::compute_Length(shadow.spread_radius, context),
+                color: computed::compute_CSSColor(shadow.color),
+                inset: shadow.inset,
+            }).collect()
+        }
+        /// inset? && <length>{2,4} && <color>?
+        ///
+        /// The lengths are the horizontal and vertical offsets, the blur radius, which may not be
+        /// negative, and the spread radius.
+        fn parse_shadow(input: &[&ComponentValue]) -> Option<SpecifiedBoxShadow> {
+            let mut lengths = ~[];
+            let mut lengths_ended = false;
+            let mut color = None;
+            let mut inset = false;
+            for &component_value in input.iter() {
+                if specified::Length::parse(component_value).is_some() {
+                    if lengths_ended || lengths.len() == 4 {
+                        return None
+                    }
+                    lengths.push(component_value);
+                    loop
+                }
+                // The lengths have to be next to each other.
+                lengths_ended = !lengths.is_empty();
+                match get_ident_lower(component_value) {
+                    Some(ref keyword) if !inset && "inset" == keyword.as_slice() => {
+                        inset = true;
+                        loop
+                    }
+                    _ => ()
+                }
+                if color.is_none() {
+                    match specified::CSSColor::parse(component_value) {
+                        Some(value) => { color = Some(value); loop },
+                        None => ()
+                    }
+                }
+                return None
+            }
+            if lengths.len() < 2 {
+                return None
+            }
+            let blur_radius = if lengths.len() > 2 {
+                match specified::Length::parse_non_negative(lengths[2]) {
+                    Some(blur_radius) => blur_radius,
+                    None => return None,
+                }
+            } else {
+                specified::Au(0)
+            };
+            let spread_radius = if lengths.len() > 3 {
+                specified::Length::parse(lengths[3]).unwrap()
+            } else {
+                specified::Au(0)
+            };
+            Some(SpecifiedBoxShadow {
+                offset_x: specified::Length::parse(lengths[0]).unwrap(),
+                offset_y: specified::Length::parse(lengths[1]).unwrap(),
+                blur_radius: blur_radius,
+                spread_radius: spread_radius,
+                color: color.unwrap_or(CurrentColor),
+                inset: inset,
+            })
+        }
+        /// none | <shadow>#
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let components: ~[&ComponentValue] = input.skip_whitespace().collect();
+            if components.len() == 1 {
+                match get_ident_lower(components[0]) {
+                    Some(ref keyword) if "none" == keyword.as_slice() => return Some(~[]),
+                    _ => ()
+                }
+            }
+            let mut shadows = ~[];
+            let mut start = 0;
+            for i in range(0, components.len() + 1) {
+                let is_end = i == components.len() || match *components[i] {
+                    Comma => true,
+                    _ => false,
+                };
+                if is_end {
+                    match parse_shadow(components.slice(start, i)) {
+                        Some(shadow) => shadows.push(shadow),
+                        None => return None,
+                    }
+                    start = i + 1
+                }
+            }
+            Some(shadows)
+        }
+    </%self:longhand>
+
+    // CSS 2.1, Section 9 - Visual formatting model
+
+    ${new_style_struct("Box", is_inherited=False)}
+
+    // TODO: don't parse values we don't support
+    ${single_keyword("display",
+        "inline block list-item inline-block none "
+        "table inline-table table-row-group table-header-group table-footer-group "
+        "table-row table-column-group table-column table-cell table-caption"
+    )}
+
+    ${single_keyword("position", "static absolute relative fixed")}
+    ${single_keyword("float", "none left right")}
+    ${single_keyword("clear", "none left right both")}
+
+    % for side in ["top", "right", "bottom", "left"]:
+        ${predefined_type(side, "LengthOrPercentageOrAuto", "computed::LPA_Auto")}
+    % endfor
+
+    <%self:single_component_value name="z-index">
+        pub use to_computed_value = std::util::id;
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            Auto,
+            Integer(i32),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { Auto }
+        /// auto | <integer>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Ident(ref value) if value.eq_ignore_ascii_case("auto") => Some(Auto),
+                &ast::Number(ref value) => {
+                    value.int_value.map_move(|value| Integer(value as i32))
+                }
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
+    ${single_keyword("unicode-bidi", "normal embed bidi-override")}
+
+    // CSS 2.1, Section 10 - Visual formatting model details
+
+    ${predefined_type("width", "LengthOrPercentageOrAuto",
+                      "computed::LPA_Auto",
+                      "parse_non_negative")}
+    ${predefined_type("height", "LengthOrPercentageOrAuto",
+                      "computed::LPA_Auto",
+                      "parse_non_negative")}
+
+    ${predefined_type("min-width", "LengthOrPercentage",
+                      "computed::LP_Length(computed::Length(0))",
+                      "parse_non_negative")}
+    ${predefined_type("max-width", "LengthOrPercentageOrNone",
+                      "computed::LPN_None",
+                      "parse_non_negative")}
+    ${predefined_type("min-height", "LengthOrPercentage",
+                      "computed::LP_Length(computed::Length(0))",
+                      "parse_non_negative")}
+    ${predefined_type("max-height", "LengthOrPercentageOrNone",
+                      "computed::LPN_None",
+                      "parse_non_negative")}
+
+    <%self:single_component_value name="vertical-align">
+        <% vertical_align_keywords = (
+            "baseline sub super top text-top middle bottom text-bottom".split()) %>
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            % for keyword in vertical_align_keywords:
+                Specified_${to_rust_ident(keyword)},
+            % endfor
+            SpecifiedLengthOrPercentage(specified::LengthOrPercentage),
+        }
+        /// baseline | sub | super | top | text-top | middle | bottom | text-bottom
+        /// | <percentage> | <length>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Ident(ref value) => match value.to_ascii_lower().as_slice() {
+                    % for keyword in vertical_align_keywords:
+                        "${keyword}" => Some(Specified_${to_rust_ident(keyword)}),
+                    % endfor
+                    _ => None,
+                },
+                _ => specified::LengthOrPercentage::parse(input)
+                     .map_move(SpecifiedLengthOrPercentage)
+            }
+        }
+        #[deriving(Eq, Clone)]
+        pub enum ComputedValue {
+            % for keyword in vertical_align_keywords:
+                ${to_rust_ident(keyword)},
+            % endfor
+            Length(computed::Length),
+            Percentage(Float),
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue { baseline }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            match value {
+                % for keyword in vertical_align_keywords:
+                    Specified_${to_rust_ident(keyword)} => ${to_rust_ident(keyword)},
+                % endfor
+                SpecifiedLengthOrPercentage(value)
+                => match computed::compute_LengthOrPercentage(value, context) {
+                    computed::LP_Length(value) => Length(value),
+                    computed::LP_Percentage(value) => Percentage(value),
+                }
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS 2.1, Section 11 - Visual effects
+
+    ${single_keyword("overflow", "visible hidden scroll auto")}
+
+    // CSS Basic User Interface Module Level 3 - Section 7 - Box model addition
+
+    ${single_keyword("box-sizing", "content-box border-box")}
+
+    ${new_style_struct("InheritedBox", is_inherited=True)}
+
+    <%self:single_component_value name="line-height">
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            SpecifiedNormal,
+            SpecifiedLength(specified::Length),
+            SpecifiedNumber(Float),
+            // percentage are the same as em.
+        }
+        /// normal | <number> | <length> | <percentage>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &ast::Number(ref value) if value.value >= 0.
+                => Some(SpecifiedNumber(value.value)),
+                &ast::Percentage(ref value) if value.value >= 0.
+                => Some(SpecifiedLength(specified::Em(value.value / 100.))),
+                &Dimension(ref value, ref unit) if value.value >= 0.
+                => specified::Length::parse_dimension(value.value, unit.as_slice())
+                    .map_move(SpecifiedLength),
+                &Ident(ref value) if value.eq_ignore_ascii_case("normal")
+                => Some(SpecifiedNormal),
+                _ => None,
+            }
+        }
+        #[deriving(Eq, Clone)]
+        pub enum ComputedValue {
+            Normal,
+            Length(computed::Length),
+            Number(Float),
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue { Normal }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            match value {
+                SpecifiedNormal => Normal,
+                SpecifiedLength(value) => Length(computed::compute_Length(value, context)),
+                SpecifiedNumber(value) => Number(value),
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS 2.1, Section 9.10 - Text direction
+
+    ${single_keyword("direction", "ltr rtl")}
+
+    // CSS 2.1, Section 11 - Visual effects
+
+    // CSS 2.1, Section 12 - Generated content, automatic numbering, and lists
+
+    ${new_style_struct("Counters", is_inherited=False)}
+
+    <%self:longhand name="content">
+        pub use to_computed_value = std::util::id;
+        #[deriving(Eq, Clone)]
+        pub enum ContentItem {
+            StringContent(~str),
+            /// attr(<identifier>), resolved against the element when boxes are generated.
+            AttrContent(~str),
+            /// counter(<identifier>, <list-style-type>?)
+            CounterContent(~str, ~str),
+            /// counters(<identifier>, <string>, <list-style-type>?)
+            CountersContent(~str, ~str, ~str),
+            OpenQuote,
+            CloseQuote,
+            NoOpenQuote,
+            NoCloseQuote,
+        }
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            normal,
+            none,
+            Content(~[ContentItem]),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { normal }
+        /// normal | none | [ <string> | <counter> | attr(<identifier>) | open-quote | close-quote
+        /// | no-open-quote | no-close-quote ]+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Some(keyword) => match keyword.as_slice() {
+                    "normal" => return Some(normal),
+                    "none" => return Some(none),
+                    _ => ()
+                },
+                None => ()
+            }
+            let mut content = ~[];
+            for component_value in input.skip_whitespace() {
+                let item = match component_value {
+                    &String(ref value) => StringContent(value.to_owned()),
+                    &Ident(ref value) => match value.to_ascii_lower().as_slice() {
+                        "open-quote" => OpenQuote,
+                        "close-quote" => CloseQuote,
+                        "no-open-quote" => NoOpenQuote,
+                        "no-close-quote" => NoCloseQuote,
+                        _ => return None,
+                    },
+                    &Function(ref name, ref arguments) => {
+                        let arguments = split_arguments(arguments.as_slice());
+                        match parse_function(name.to_ascii_lower(), arguments) {
+                            Some(item) => item,
+                            None => return None,
+                        }
+                    }
+                    _ => return None,
+                };
+                content.push(item)
+            }
+            if content.is_empty() { None } else { Some(Content(content)) }
+        }
+        /// attr(<identifier>) | counter(<identifier>, <list-style-type>?)
+        /// | counters(<identifier>, <string>, <list-style-type>?)
+        fn parse_function(name: &str, arguments: &[~[&ComponentValue]]) -> Option<ContentItem> {
+            let style = match (name, arguments.len()) {
+                ("counter", 2) => get_ident(arguments[1].as_slice()),
+                ("counters", 3) => get_ident(arguments[2].as_slice()),
+                _ => Some(~"decimal"),
+            };
+            let style = match style {
+                Some(style) => style.to_ascii_lower(),
+                None => return None,
+            };
+            let name_argument = get_ident(arguments[0].as_slice());
+            match (name, arguments.len()) {
+                ("attr", 1) => name_argument.map_move(|name| AttrContent(name.to_ascii_lower())),
+                ("counter", 1) | ("counter", 2) => {
+                    name_argument.map_move(|name| CounterContent(name, style.clone()))
+                }
+                ("counters", 2) | ("counters", 3) => {
+                    match (name_argument, get_string(arguments[1].as_slice())) {
+                        (Some(name), Some(separator)) => {
+                            Some(CountersContent(name, separator, style))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        fn get_ident(argument: &[&ComponentValue]) -> Option<~str> {
+            match argument {
+                [&Ident(ref value)] => Some(value.to_owned()),
+                _ => None,
+            }
+        }
+        fn get_string(argument: &[&ComponentValue]) -> Option<~str> {
+            match argument {
+                [&String(ref value)] => Some(value.to_owned()),
+                _ => None,
+            }
+        }
+        /// Splits the arguments of a functional notation on commas, skipping whitespace.
+        fn split_arguments<'a>(arguments: &'a [ComponentValue]) -> ~[~[&'a ComponentValue]] {
+            let mut result = ~[~[]];
+            for component_value in arguments.skip_whitespace() {
+                match component_value {
+                    &Comma => result.push(~[]),
+                    _ => result[result.len() - 1].push(component_value),
+                }
+            }
+            result
+        }
+    </%self:longhand>
+
+    % for name, default_value in [("counter-increment", 1), ("counter-reset", 0)]:
+        <%self:longhand name="${name}">
+            pub use to_computed_value = std::util::id;
+            /// The counters to act on, with the value to add or to reset them to.
+            pub type SpecifiedValue = ~[(~str, i32)];
+            pub type ComputedValue = SpecifiedValue;
+            #[inline] pub fn get_initial_value() -> ComputedValue { ~[] }  // none
+            /// none | [ <identifier> <integer>? ]+
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                match one_component_value(input).and_then(get_ident_lower) {
+                    Some(ref keyword) if "none" == keyword.as_slice() => return Some(~[]),
+                    _ => ()
+                }
+                let mut result = ~[];
+                let mut expecting_value = false;
+                for component_value in input.skip_whitespace() {
+                    match component_value {
+                        &Ident(ref name) => match name.to_ascii_lower().as_slice() {
+                            "none" | "inherit" | "initial" | "unset" => return None,
+                            _ => {
+                                result.push((name.to_owned(), ${default_value}));
+                                expecting_value = true;
+                            }
+                        },
+                        &Number(ref value) if expecting_value => match value.int_value {
+                            Some(value) => {
+                                let (name, _) = result.pop();
+                                result.push((name, value as i32));
+                                expecting_value = false;
+                            }
+                            None => return None,
+                        },
+                        _ => return None,
+                    }
+                }
+                if result.is_empty() { None } else { Some(result) }
+            }
+        </%self:longhand>
+    % endfor
+
+    ${new_style_struct("List", is_inherited=True)}
+
+    <%self:longhand name="quotes">
+        pub use to_computed_value = std::util::id;
+        /// Pairs of open and close quotes, for each level of nesting.
+        pub type SpecifiedValue = ~[(~str, ~str)];
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            ~[(~"\u201c", ~"\u201d"), (~"\u2018", ~"\u2019")]
+        }
+        /// none | [ <string> <string> ]+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Some(ref keyword) if "none" == keyword.as_slice() => return Some(~[]),
+                _ => ()
+            }
+            let mut result = ~[];
+            let mut iter = input.skip_whitespace();
+            loop {
+                match (iter.next(), iter.next()) {
+                    (Some(&String(ref open)), Some(&String(ref close)))
+                    => result.push((open.to_owned(), close.to_owned())),
+                    (None, None) => break,
+                    _ => return None,
+                }
+            }
+            if result.is_empty() { None } else { Some(result) }
+        }
+    </%self:longhand>
+
+    ${single_keyword("list-style-type", "disc circle square decimal lower-alpha upper-alpha "
+                                        "lower-roman upper-roman none")}
+    ${single_keyword("list-style-position", "outside inside")}
+
+    <%self:longhand name="list-style-image">
+        use servo_util::url::make_url;
+        pub use to_computed_value = std::util::id;
+        /// The URL of the image, resolved against the base URL of the style sheet.
+        pub type SpecifiedValue = Option<Url>;
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { None }  // none
+        pub fn from_component_value(input: &ComponentValue, base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &URL(ref url) => Some(Some(make_url(url.to_owned(), Some(base_url.clone())))),
+                &Ident(ref value) if "none" == value.to_ascii_lower().as_slice() => Some(None),
+                _ => None,
+            }
+        }
+        /// none | <uri>
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
+            one_component_value(input).and_then(|input| from_component_value(input, base_url))
+        }
+    </%self:longhand>
+
+    // CSS 2.1, Section 13 - Paged media
+
+    // CSS 2.1, Section 14 - Colors and Backgrounds
+
+    ${new_style_struct("Background", is_inherited=False)}
+
+    ${predefined_type("background-color", "CSSColor",
+                      "RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */")}
+
+    <%self:longhand name="background-image">
+        use servo_util::url::make_url;
+        pub use to_computed_value = std::util::id;
+        /// The URL of the image, resolved against the base URL of the style sheet.
+        pub type SpecifiedValue = Option<Url>;
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { None }  // none
+        pub fn from_component_value(input: &ComponentValue, base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &URL(ref url) => Some(Some(make_url(url.to_owned(), Some(base_url.clone())))),
+                &Ident(ref value) if "none" == value.to_ascii_lower().as_slice() => Some(None),
+                _ => None,
+            }
+        }
+        /// <uri> | none
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
+            one_component_value(input).and_then(|input| from_component_value(input, base_url))
+        }
+    </%self:longhand>
+
+    ${single_keyword("background-repeat", "repeat repeat-x repeat-y no-repeat")}
+
+    // TODO: 'fixed' needs the compositor to keep the background in place while the element
+    // scrolls, so it is not parsed yet.
+    ${single_keyword("background-attachment", "scroll")}
+
+    <%self:longhand name="background-position">
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            horizontal: specified::LengthOrPercentage,
+            vertical: specified::LengthOrPercentage,
+        }
+        #[deriving(Eq, Clone)]
+        pub struct ComputedValue {
+            horizontal: computed::LengthOrPercentage,
+            vertical: computed::LengthOrPercentage,
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            ComputedValue {
+                horizontal: computed::LP_Percentage(0.),
+                vertical: computed::LP_Percentage(0.),
+            }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            ComputedValue {
+                horizontal: computed::compute_LengthOrPercentage(value.horizontal, context),
+                vertical: computed::compute_LengthOrPercentage(value.vertical, context),
+            }
+        }
+
+        /// One of the one or two values of the property.
+        #[deriving(Eq, Clone)]
+        pub enum PositionComponent {
+            PositionLength(specified::LengthOrPercentage),
+            PositionCenter,
+            PositionLeft,
+            PositionRight,
+            PositionTop,
+            PositionBottom,
+        }
+        impl PositionComponent {
+            fn is_horizontal_keyword(&self) -> bool {
+                *self == PositionLeft || *self == PositionRight
+            }
+            fn is_vertical_keyword(&self) -> bool {
+                *self == PositionTop || *self == PositionBottom
+            }
+            fn to_length_or_percentage(self) -> specified::LengthOrPercentage {
+                match self {
+                    PositionLength(value) => value,
+                    PositionLeft | PositionTop => specified::LP_Percentage(0.),
+                    PositionCenter => specified::LP_Percentage(0.5),
+                    PositionRight | PositionBottom => specified::LP_Percentage(1.),
+                }
+            }
+        }
+        /// <percentage> | <length> | left | center | right | top | bottom
+        pub fn parse_component(input: &ComponentValue) -> Option<PositionComponent> {
+            match get_ident_lower(input) {
+                Some(keyword) => match keyword.as_slice() {
+                    "center" => Some(PositionCenter),
+                    "left" => Some(PositionLeft),
+                    "right" => Some(PositionRight),
+                    "top" => Some(PositionTop),
+                    "bottom" => Some(PositionBottom),
+                    _ => None,
+                },
+                None => specified::LengthOrPercentage::parse(input).map_move(PositionLength),
+            }
+        }
+        /// Combines one or two components. A single one gives the horizontal position, unless
+        /// it is 'top' or 'bottom', and the other one is 'center'. Two keywords may come in
+        /// either order, but with a length or a percentage the horizontal position is first.
+        pub fn from_components(first: PositionComponent, second: Option<PositionComponent>)
+                               -> Option<SpecifiedValue> {
+            let second = second.unwrap_or(PositionCenter);
+            let is_keyword = |component: &PositionComponent| match *component {
+                PositionLength(_) => false,
+                _ => true,
+            };
+            let (horizontal, vertical) =
+                if is_keyword(&first) && is_keyword(&second) &&
+                        (first.is_vertical_keyword() || second.is_horizontal_keyword()) {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+            if horizontal.is_vertical_keyword() || vertical.is_horizontal_keyword() {
+                return None
+            }
+            Some(SpecifiedValue {
+                horizontal: horizontal.to_length_or_percentage(),
+                vertical: vertical.to_length_or_percentage(),
+            })
+        }
+        /// [ <percentage> | <length> | left | center | right ]
+        /// [ <percentage> | <length> | top | center | bottom ]?
+        /// | [ left | center | right ] || [ top | center | bottom ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace();
+            let first = match iter.next().and_then(parse_component) {
+                Some(component) => component,
+                None => return None,
+            };
+            let second = match iter.next() {
+                Some(value) => match parse_component(value) {
+                    Some(component) => Some(component),
+                    None => return None,
+                },
+                None => None,
+            };
+            if iter.next().is_some() {
+                return None
+            }
+            from_components(first, second)
+        }
+    </%self:longhand>
+
+    ${new_style_struct("Color", is_inherited=True)}
+
+    <%self:single_component_value name="color">
+        pub type SpecifiedValue = specified::CSSColor;
+        pub type ComputedValue = RGBA;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            RGBA { red: 0., green: 0., blue: 0., alpha: 1. }  /* black */
+        }
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            specified::CSSColor::parse(input)
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            match value {
+                RGBA(color) => color,
+                // 'color: currentColor' is the same as 'color: inherit'
+                CurrentColor => context.current_color,
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS 2.1, Section 15 - Fonts
+
+    ${new_style_struct("Font", is_inherited=True)}
+
+    <%self:longhand name="font-family">
+        pub use to_computed_value = std::util::id;
+        #[deriving(Eq, Clone)]
+        pub enum FontFamily {
+            FamilyName(~str),
+            // Generic
+//            Serif,
+//            SansSerif,
+//            Cursive,
+//            Fantasy,
+//            Monospace,
+        }
+        pub type SpecifiedValue = ~[FontFamily];
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { ~[FamilyName(~"serif")] }
+        /// <familiy-name>#
+        /// <familiy-name> = <string> | [ <ident>+ ]
+        /// TODO: <generic-familiy>
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            from_iter(input.skip_whitespace())
+        }
+        pub fn from_iter<'a>(mut iter: SkipWhitespaceIterator<'a>) -> Option<SpecifiedValue> {
+            let mut result = ~[];
+            macro_rules! add(
+                ($value: expr) => {
+                    {
+                        result.push($value);
+                        match iter.next() {
+                            Some(&Comma) => (),
+                            None => break 'outer,
+                            _ => return None,
+                        }
+                    }
+                }
+            )
+            'outer: loop {
+                match iter.next() {
+                    // TODO: avoid copying strings?
+                    Some(&String(ref value)) => add!(FamilyName(value.to_owned())),
+                    Some(&Ident(ref value)) => {
+                        let value = value.as_slice();
+                        match value.to_ascii_lower().as_slice() {
+//                            "serif" => add!(Serif),
+//                            "sans-serif" => add!(SansSerif),
+//                            "cursive" => add!(Cursive),
+//                            "fantasy" => add!(Fantasy),
+//                            "monospace" => add!(Monospace),
+                            _ => {
+                                let mut idents = ~[value];
+                                loop {
+                                    match iter.next() {
+                                        Some(&Ident(ref value)) => idents.push(value.as_slice()),
+                                        Some(&Comma) => {
+                                            result.push(FamilyName(idents.connect(" ")));
+                                            break
+                                        },
+                                        None => {
+                                            result.push(FamilyName(idents.connect(" ")));
+                                            break 'outer
+                                        },
+                                        _ => return None,
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            Some(result)
+        }
+    </%self:longhand>
+
+
+    ${single_keyword("font-style", "normal italic oblique")}
+    ${single_keyword("font-variant", "normal")}  // Add small-caps when supported
+
+    <%self:single_component_value name="font-weight">
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            Bolder,
+            Lighther,
+            % for weight in range(100, 901, 100):
+                SpecifiedWeight${weight},
+            % endfor
+        }
+        /// normal | bold | bolder | lighter | 100 | 200 | 300 | 400 | 500 | 600 | 700 | 800 | 900
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Ident(ref value) => match value.to_ascii_lower().as_slice() {
+                    "bold" => Some(SpecifiedWeight700),
+                    "normal" => Some(SpecifiedWeight400),
+                    "bolder" => Some(Bolder),
+                    "lighter" => Some(Lighther),
+                    _ => None,
+                },
+                &Number(ref value) => match value.int_value {
+                    Some(100) => Some(SpecifiedWeight100),
+                    Some(200) => Some(SpecifiedWeight200),
+                    Some(300) => Some(SpecifiedWeight300),
+                    Some(400) => Some(SpecifiedWeight400),
+                    Some(500) => Some(SpecifiedWeight500),
+                    Some(600) => Some(SpecifiedWeight600),
+                    Some(700) => Some(SpecifiedWeight700),
+                    Some(800) => Some(SpecifiedWeight800),
+                    Some(900) => Some(SpecifiedWeight900),
+                    _ => None,
+                },
+                _ => None
+            }
+        }
+        #[deriving(Eq, Clone)]
+        pub enum ComputedValue {
+            % for weight in range(100, 901, 100):
+                Weight${weight},
+            % endfor
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue { Weight400 }  // normal
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            match value {
+                % for weight in range(100, 901, 100):
+                    SpecifiedWeight${weight} => Weight${weight},
+                % endfor
+                Bolder => match context.font_weight {
+                    Weight100 => Weight400,
+                    Weight200 => Weight400,
+                    Weight300 => Weight400,
+                    Weight400 => Weight700,
+                    Weight500 => Weight700,
+                    Weight600 => Weight900,
+                    Weight700 => Weight900,
+                    Weight800 => Weight900,
+                    Weight900 => Weight900,
+                },
+                Lighther => match context.font_weight {
+                    Weight100 => Weight100,
+                    Weight200 => Weight100,
+                    Weight300 => Weight100,
+                    Weight400 => Weight100,
+                    Weight500 => Weight100,
+                    Weight600 => Weight400,
+                    Weight700 => Weight400,
+                    Weight800 => Weight700,
+                    Weight900 => Weight700,
+                },
+            }
+        }
+    </%self:single_component_value>
+
+    <%self:single_component_value name="font-size">
+        pub use to_computed_value = super::super::common_types::computed::compute_Length;
+        pub type SpecifiedValue = specified::Length;  // Percentages are the same as em.
+        pub type ComputedValue = computed::Length;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            computed::Length(16 * 60)  // medium
+        }
+        /// <length> | <percentage>
+        /// TODO: support <absolute-size> and <relative-size>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            do specified::LengthOrPercentage::parse_non_negative(input).map_move |value| {
+                match value {
+                    specified::LP_Length(value) => value,
+                    specified::LP_Percentage(value) => specified::Em(value),
+                }
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS 2.1, Section 16 - Text
+
+    ${new_style_struct("InheritedText", is_inherited=True)}
+
+    // 'start' and 'end' are from CSS Text Level 3. 'start' is the initial value, which CSS 2.1
+    // describes as a nameless value that depends on 'direction'.
+    ${single_keyword("text-align", "start end left right center justify")}
+
+    ${single_keyword("white-space", "normal pre nowrap pre-wrap pre-line")}
+
+    ${new_style_struct("Text", is_inherited=False)}
+
+    <%self:longhand name="text-decoration">
+        pub use to_computed_value = std::util::id;
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            underline: bool,
+            overline: bool,
+            line_through: bool,
+            // 'blink' is accepted in the parser but ignored.
+            // Just not blinking the text is a conforming implementation per CSS 2.1.
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            SpecifiedValue { underline: false, overline: false, line_through: false }  // none
+        }
+        /// none | [ underline || overline || line-through || blink ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut result = SpecifiedValue {
+                underline: false, overline: false, line_through: false,
+            };
+            let mut blink = false;
+            let mut empty = true;
+            for component_value in input.skip_whitespace() {
+                match get_ident_lower(component_value) {
+                    None => return None,
+                    Some(keyword) => match keyword.as_slice() {
+                        "underline" => if result.underline { return None }
+                                      else { empty = false; result.underline = true },
+                        "overline" => if result.overline { return None }
+                                      else { empty = false; result.overline = true },
+                        "line-through" => if result.line_through { return None }
+                                          else { empty = false; result.line_through = true },
+                        "blink" => if blink { return None }
+                                   else { empty = false; blink = true },
+                        "none" => return if empty { Some(result) } else { None },
+                        _ => return None,
+                    }
+                }
+            }
+            if !empty { Some(result) } else { None }
+        }
+    </%self:longhand>
+
+    // CSS 2.1, Section 17 - Tables
+
+    ${new_style_struct("Table", is_inherited=False)}
+
+    ${single_keyword("table-layout", "auto fixed")}
+
+    ${new_style_struct("InheritedTable", is_inherited=True)}
+
+    ${single_keyword("border-collapse", "separate collapse")}
+
+    <%self:longhand name="border-spacing">
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            horizontal: specified::Length,
+            vertical: specified::Length,
+        }
+        #[deriving(Eq, Clone)]
+        pub struct ComputedValue {
+            horizontal: computed::Length,
+            vertical: computed::Length,
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            ComputedValue { horizontal: computed::Length(0), vertical: computed::Length(0) }
+        }
+        /// <length> <length>?
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace().map(specified::Length::parse_non_negative);
+            match (iter.next(), iter.next(), iter.next()) {
+                (Some(Some(horizontal)), None, None)
+                => Some(SpecifiedValue { horizontal: horizontal, vertical: horizontal }),
+                (Some(Some(horizontal)), Some(Some(vertical)), None)
+                => Some(SpecifiedValue { horizontal: horizontal, vertical: vertical }),
+                _ => None,
+            }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            ComputedValue {
+                horizontal: computed::compute_Length(value.horizontal, context),
+                vertical: computed::compute_Length(value.vertical, context),
+            }
+        }
+    </%self:longhand>
+
+    // CSS 2.1, Section 18 - User interface
+}
+
+
+pub mod shorthands {
+    pub use super::*;
+    pub use super::longhands::*;
+
+    <%def name="shorthand(name, sub_properties)">
+    <%
+        shorthand = Shorthand(name, sub_properties.split())
+        SHORTHANDS.append(shorthand)
+    %>
+        pub mod ${shorthand.ident} {
+            use super::*;
+            struct Longhands {
+                % for sub_property in shorthand.sub_properties:
+                    ${sub_property.ident}: Option<${sub_property.ident}::SpecifiedValue>,
+                % endfor
+            }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<Longhands> {
+                ${caller.body()}
+            }
+        }
+    </%def>
+
+    <%def name="four_sides_shorthand(name, sub_property_pattern, parser_function)">
+        <%self:shorthand name="${name}" sub_properties="${
+                ' '.join(sub_property_pattern % side
+                         for side in ['top', 'right', 'bottom', 'left'])}">
+            let mut iter = input.skip_whitespace().map(${parser_function});
+            // zero or more than four values is invalid.
+            // one value sets them all
+            // two values set (top, bottom) and (left, right)
+            // three values set top, (left, right) and bottom
+            // four values set them in order
+            let top = iter.next().unwrap_or(None);
+            let right = iter.next().unwrap_or(top);
+            let bottom = iter.next().unwrap_or(top);
+            let left = iter.next().unwrap_or(right);
+            if top.is_some() && right.is_some() && bottom.is_some() && left.is_some()
+            && iter.next().is_none() {
+                Some(Longhands {
+                    % for side in ["top", "right", "bottom", "left"]:
+                        ${to_rust_ident(sub_property_pattern % side)}: ${side},
+                    % endfor
+                })
+            } else {
+                None
+            }
+        </%self:shorthand>
+    </%def>
+
+
+    ${four_sides_shorthand("margin", "margin-%s", "specified::LengthOrPercentageOrAuto::parse")}
+    ${four_sides_shorthand("padding", "padding-%s",
+                           "specified::LengthOrPercentage::parse_non_negative")}
+
+    <%self:shorthand name="background" sub_properties="background-color background-image
+                                                         background-repeat background-attachment
+                                                         background-position">
+        let mut color = None;
+        let mut image = None;
+        let mut repeat = None;
+        let mut attachment = None;
+        let mut position = None;
+        let mut any = false;
+        let components: ~[&ComponentValue] = input.skip_whitespace().collect();
+        let mut i = 0;
+        while i < components.len() {
+            let component_value = components[i];
+            i += 1;
+            any = true;
+            if color.is_none() {
+                match specified::CSSColor::parse(component_value) {
+                    Some(value) => { color = Some(value); loop },
+                    None => ()
+                }
+            }
+            if image.is_none() {
+                match background_image::from_component_value(component_value, _base_url) {
+                    Some(value) => { image = Some(value); loop },
+                    None => ()
+                }
+            }
+            if repeat.is_none() {
+                match background_repeat::from_component_value(component_value) {
+                    Some(value) => { repeat = Some(value); loop },
+                    None => ()
+                }
+            }
+            if attachment.is_none() {
+                match background_attachment::from_component_value(component_value) {
+                    Some(value) => { attachment = Some(value); loop },
+                    None => ()
+                }
+            }
+            if position.is_none() {
+                match background_position::parse_component(component_value) {
+                    Some(first) => {
+                        // The two values of the position have to be next to each other.
+                        let second = if i < components.len() {
+                            background_position::parse_component(components[i])
+                        } else {
+                            None
+                        };
+                        if second.is_some() {
+                            i += 1
+                        }
+                        match background_position::from_components(first, second) {
+                            Some(value) => { position = Some(value); loop },
+                            None => return None,
+                        }
+                    }
+                    None => ()
+                }
+            }
+            return None
+        }
+        if !any {
+            return None
+        }
+        Some(Longhands {
+            background_color: color,
+            background_image: image,
+            background_repeat: repeat,
+            background_attachment: attachment,
+            background_position: position,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="list-style"
+                     sub_properties="list-style-type list-style-position list-style-image">
+        let mut type_ = None;
+        let mut position = None;
+        let mut image = None;
+        // 'none' may stand for either the type or the image, so it is assigned once the other
+        // values are known.
+        let mut nones = 0;
+        for component_value in input.skip_whitespace() {
+            match get_ident_lower(component_value) {
+                Some(ref keyword) if "none" == keyword.as_slice() => { nones += 1; loop },
+                _ => ()
+            }
+            if type_.is_none() {
+                match list_style_type::from_component_value(component_value) {
+                    Some(value) => { type_ = Some(value); loop },
+                    None => ()
+                }
+            }
+            if position.is_none() {
+                match list_style_position::from_component_value(component_value) {
+                    Some(value) => { position = Some(value); loop },
+                    None => ()
+                }
+            }
+            if image.is_none() {
+                match list_style_image::from_component_value(component_value, _base_url) {
+                    Some(value) => { image = Some(value); loop },
+                    None => ()
+                }
+            }
+            return None
+        }
+        // Both the type and the image are initially 'none', so each 'none' keyword just has to
+        // leave one of them unset.
+        let unset = [type_.is_none(), image.is_none()];
+        if nones > unset.iter().count(|&is_unset| is_unset) {
+            return None
+        }
+        Some(Longhands {
+            list_style_type: type_,
+            list_style_position: position,
+            list_style_image: image,
+        })
+    </%self:shorthand>
+
+    ${four_sides_shorthand("border-color", "border-%s-color", "specified::CSSColor::parse")}
+    ${four_sides_shorthand("border-style", "border-%s-style",
+                           "border_top_style::from_component_value")}
+    ${four_sides_shorthand("border-width", "border-%s-width", "parse_border_width")}
+
+    pub fn parse_border(input: &[ComponentValue])
+                     -> Option<(Option<specified::CSSColor>,
+                                Option<border_top_style::SpecifiedValue>,
+                                Option<specified::Length>)> {
+        let mut color = None;
+        let mut style = None;
+        let mut width = None;
+        let mut any = false;
+        for component_value in input.skip_whitespace() {
+            if color.is_none() {
+                match specified::CSSColor::parse(component_value) {
+                    Some(c) => { color = Some(c); any = true; loop },
+                    None => ()
+                }
+            }
+            if style.is_none() {
+                match border_top_style::from_component_value(component_value) {
+                    Some(s) => { style = Some(s); any = true; loop },
+                    None => ()
+                }
+            }
+            if width.is_none() {
+                match parse_border_width(component_value) {
+                    Some(w) => { width = Some(w); any = true; loop },
+                    None => ()
+                }
+            }
+            return None
+        }
+        if any { Some((color, style, width)) } else { None }
+    }
+
+
+    % for side in ["top", "right", "bottom", "left"]:
+        <%self:shorthand name="border-${side}" sub_properties="${' '.join(
+            'border-%s-%s' % (side, prop)
+            for prop in ['color', 'style', 'width']
+        )}">
+            do parse_border(input).map_move |(color, style, width)| {
+                Longhands {
+                    % for prop in ["color", "style", "width"]:
+                        ${"border_%s_%s: %s," % (side, prop, prop)}
+                    % endfor
+                }
+            }
+        </%self:shorthand>
+    % endfor
+
+    <%self:shorthand name="border" sub_properties="${' '.join(
+        'border-%s-%s' % (side, prop)
+        for side in ['top', 'right', 'bottom', 'left']
+        for prop in ['color', 'style', 'width']
+    )}">
+        do parse_border(input).map_move |(color, style, width)| {
+            Longhands {
+                % for side in ["top", "right", "bottom", "left"]:
+                    % for prop in ["color", "style", "width"]:
+                        ${"border_%s_%s: %s," % (side, prop, prop)}
+                    % endfor
+                % endfor
+            }
+        }
+    </%self:shorthand>
+
+    <%self:shorthand name="border-radius" sub_properties="${' '.join(
+        'border-%s-radius' % corner
+        for corner in ['top-left', 'top-right', 'bottom-right', 'bottom-left']
+    )}">
+        // One to four values, for the corners in the same way as for the sides of 'margin'.
+        fn expand(values: &[specified::LengthOrPercentage])
+                  -> Option<~[specified::LengthOrPercentage]> {
+            match values.len() {
+                1 => Some(~[values[0], values[0], values[0], values[0]]),
+                2 => Some(~[values[0], values[1], values[0], values[1]]),
+                3 => Some(~[values[0], values[1], values[2], values[1]]),
+                4 => Some(values.to_owned()),
+                _ => None,
+            }
+        }
+        // The horizontal radii come before a '/' and the vertical ones after it. Without a '/',
+        // they are the same.
+        let mut horizontal = ~[];
+        let mut vertical = ~[];
+        let mut has_slash = false;
+        for component_value in input.skip_whitespace() {
+            match component_value {
+                &Delim('/') if !has_slash => { has_slash = true; loop },
+                _ => ()
+            }
+            let value = match specified::LengthOrPercentage::parse_non_negative(component_value) {
+                Some(value) => value,
+                None => return None,
+            };
+            if has_slash { vertical.push(value) } else { horizontal.push(value) }
+        }
+        if !has_slash {
+            vertical = horizontal.clone()
+        }
+        match (expand(horizontal), expand(vertical)) {
+            (Some(horizontal), Some(vertical)) => Some(Longhands {
+                % for i, corner in enumerate("top-left top-right bottom-right bottom-left".split()):
+                    ${to_rust_ident('border-%s-radius' % corner)}: Some(
+                        border_top_left_radius::SpecifiedValue {
+                            horizontal: horizontal[${i}],
+                            vertical: vertical[${i}],
+                        }),
+                % endfor
+            }),
+            _ => None,
+        }
+    </%self:shorthand>
+
+}
+
+
+pub struct PropertyDeclarationBlock {
+    important: Arc<~[PropertyDeclaration]>,
+    normal: Arc<~[PropertyDeclaration]>,
+}
+
+
+pub fn parse_style_attribute(input: &str, base_url: &Url) -> PropertyDeclarationBlock {
+    parse_property_declaration_list(tokenize(input), base_url)
+}
+
+
+/// Parses the declarations of a style rule or a style attribute. Relative URLs in them are
+/// resolved against `base_url`.
+pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I, base_url: &Url)
+                                                          -> PropertyDeclarationBlock {
+    let mut important = ~[];
+    let mut normal = ~[];
+    for item in ErrorLoggerIterator(parse_declaration_list(input)) {
+        match item {
+            Decl_AtRule(rule) => log_css_error(
+                rule.location, fmt!("Unsupported at-rule in declaration list: @%s", rule.name)),
+            Declaration(Declaration{ location: l, name: n, value: v, important: i}) => {
+                let list = if i { &mut important } else { &mut normal };
+                if !PropertyDeclaration::parse(n, v, list, base_url) {
+                    log_css_error(l, "Invalid property declaration")
+                }
+            }
+        }
+    }
+    PropertyDeclarationBlock {
+        important: Arc::new(important),
+        normal: Arc::new(normal),
+    }
+}
+
+
+#[deriving(Clone)]
+pub enum CSSWideKeyword {
+    Initial,
+    Inherit,
+    Unset,
+}
+
+impl CSSWideKeyword {
+    pub fn parse(input: &[ComponentValue]) -> Option<CSSWideKeyword> {
+        do one_component_value(input).and_then(get_ident_lower).and_then |keyword| {
+            match keyword.as_slice() {
+                "initial" => Some(Initial),
+                "inherit" => Some(Inherit),
+                "unset" => Some(Unset),
+                _ => None
+            }
+        }
+    }
+}
+
+#[deriving(Clone)]
+pub enum DeclaredValue<T> {
+    SpecifiedValue(T),
+    CSSWideKeyword(CSSWideKeyword),
+}
+
+#[deriving(Clone)]
+pub enum PropertyDeclaration {
+    % for property in LONGHANDS:
+        ${property.ident}_declaration(DeclaredValue<longhands::${property.ident}::SpecifiedValue>),
+    % endfor
+}
+
+impl PropertyDeclaration {
+    pub fn parse(name: &str, value: &[ComponentValue],
+                 result_list: &mut ~[PropertyDeclaration], base_url: &Url) -> bool {
+        match name.to_ascii_lower().as_slice() {
+            % for property in LONGHANDS:
+                "${property.name}" => result_list.push(${property.ident}_declaration(
+                    match CSSWideKeyword::parse(value) {
+                        Some(keyword) => CSSWideKeyword(keyword),
+                        None => match longhands::${property.ident}::parse(value, base_url) {
+                            Some(value) => SpecifiedValue(value),
+                            None => return false,
+                        }
+                    }
+                )),
+            % endfor
+            % for shorthand in SHORTHANDS:
+                "${shorthand.name}" => match CSSWideKeyword::parse(value) {
+                    Some(keyword) => {
+                        % for sub_property in shorthand.sub_properties:
+                            result_list.push(${sub_property.ident}_declaration(
+                                CSSWideKeyword(keyword)
+                            ));
+                        % endfor
+                    },
+                    None => match shorthands::${shorthand.ident}::parse(value, base_url) {
+                        Some(result) => {
+                            % for sub_property in shorthand.sub_properties:
+                                result_list.push(${sub_property.ident}_declaration(
+                                    match result.${sub_property.ident} {
+                                        Some(value) => SpecifiedValue(value),
+                                        None => CSSWideKeyword(Initial),
+                                    }
+                                ));
+                            % endfor
+                        },
+                        None => return false,
+                    }
+                },
+            % endfor
+            _ => return false,  // Unknown property
+        }
+        true
+    }
+}
+
+
+pub mod style_structs {
+    use super::longhands;
+    % for style_struct in STYLE_STRUCTS:
+        #[deriving(Eq, Clone)]
+        pub struct ${style_struct.name} {
+            % for longhand in style_struct.longhands:
+                ${longhand.ident}: longhands::${longhand.ident}::ComputedValue,
+            % endfor
+        }
+    % endfor
+}
+
+#[deriving(Eq, Clone)]
+pub struct ComputedValues {
+    % for style_struct in STYLE_STRUCTS:
+        ${style_struct.name}: style_structs::${style_struct.name},
+    % endfor
+}
+
+impl ComputedValues {
+    /// Resolves the currentColor keyword.
+    /// Any color value form computed values (except for the 'color' property itself)
+    /// should go through this method.
+    ///
+    /// Usage example:
+    /// let top_color = style.resolve_color(style.Border.border_top_color);
+    #[inline]
+    pub fn resolve_color(&self, color: computed::CSSColor) -> RGBA {
+        match color {
+            RGBA(rgba) => rgba,
+            CurrentColor => self.Color.color,
+        }
+    }
+}
+
+/// The initial values for all style structs as defined by the specification.
+pub fn get_initial_values() -> ComputedValues {
+    ComputedValues {
+        % for style_struct in STYLE_STRUCTS:
+            ${style_struct.name}: style_structs::${style_struct.name} {
+                % for longhand in style_struct.longhands:
+                    ${longhand.ident}: longhands::${longhand.ident}::get_initial_value(),
+                % endfor
+            },
+        % endfor
+    }
+}
+
+
+/// Computes the style of an element from the declarations that apply to it.
+///
+/// `applicable_declarations` is in increasing precedence order: later declarations win over
+/// earlier ones. `parent_style` is the computed style of the parent element, or `None` for the
+/// root element.
+pub fn cascade(applicable_declarations: &[Arc<~[PropertyDeclaration]>],
+               parent_style: Option<&ComputedValues>)
+            -> ComputedValues {
+    let is_root_element = parent_style.is_none();
+    let initial_keep_alive;
+    let parent_style = match parent_style {
+        Some(parent_style) => parent_style,
+        None => {
+            initial_keep_alive = get_initial_values();
+            &initial_keep_alive
+        }
+    };
+
+    // Some computed values depend on other properties of the same element ('em' lengths depend
+    // on 'font-size', border widths depend on border styles, 'bolder' on the parent's weight...)
+    // Find the winning declarations for those first, and compute them relative to the parent.
+    let mut specified_color = None;
+    let mut specified_font_size = None;
+    let mut specified_font_weight = None;
+    % for side in ["top", "right", "bottom", "left"]:
+        let mut specified_border_${side}_style = None;
+    % endfor
+    for sub_list in applicable_declarations.iter() {
+        for declaration in sub_list.get().iter() {
+            match *declaration {
+                color_declaration(ref value) => specified_color = Some(value),
+                font_size_declaration(ref value) => specified_font_size = Some(value),
+                font_weight_declaration(ref value) => specified_font_weight = Some(value),
+                % for side in ["top", "right", "bottom", "left"]:
+                    border_${side}_style_declaration(ref value)
+                    => specified_border_${side}_style = Some(value),
+                % endfor
+                _ => ()
+            }
+        }
+    }
+
+    let mut context = computed::Context {
+        current_color: parent_style.Color.color,
+        font_size: parent_style.Font.font_size,
+        font_weight: parent_style.Font.font_weight,
+        % for side in ["top", "right", "bottom", "left"]:
+            has_border_${side}: match specified_border_${side}_style {
+                Some(&SpecifiedValue(style)) => has_border(style),
+                Some(&CSSWideKeyword(Inherit))
+                => has_border(parent_style.Border.border_${side}_style),
+                // 'border-*-style' is not inherited, and its initial value is 'none'.
+                Some(&CSSWideKeyword(Initial)) | Some(&CSSWideKeyword(Unset)) | None => false,
+            },
+        % endfor
+    };
+    // 'color', 'font-weight' and 'font-size' are inherited properties: 'inherit', 'unset' or no
+    // declaration at all keep the parent's value that is already in the context.
+    match specified_color {
+        Some(&SpecifiedValue(ref value)) => {
+            context.current_color = longhands::color::to_computed_value(*value, &context)
+        }
+        Some(&CSSWideKeyword(Initial)) => {
+            context.current_color = longhands::color::get_initial_value()
+        }
+        _ => ()
+    }
+    match specified_font_weight {
+        Some(&SpecifiedValue(ref value)) => {
+            context.font_weight = longhands::font_weight::to_computed_value(*value, &context)
+        }
+        Some(&CSSWideKeyword(Initial)) => {
+            context.font_weight = longhands::font_weight::get_initial_value()
+        }
+        _ => ()
+    }
+    match specified_font_size {
+        Some(&SpecifiedValue(ref value)) => {
+            context.font_size = longhands::font_size::to_computed_value(*value, &context)
+        }
+        Some(&CSSWideKeyword(Initial)) => {
+            context.font_size = longhands::font_size::get_initial_value()
+        }
+        _ => ()
+    }
+
+    % for style_struct in STYLE_STRUCTS:
+        % if style_struct.inherited:
+            let mut style_${style_struct.name} = parent_style.${style_struct.name}.clone();
+        % else:
+            let mut style_${style_struct.name} = style_structs::${style_struct.name} {
+                % for longhand in style_struct.longhands:
+                    ${longhand.ident}: longhands::${longhand.ident}::get_initial_value(),
+                % endfor
+            };
+        % endif
+    % endfor
+
+    <% ALREADY_COMPUTED = ["color", "font_size", "font_weight"] %>
+    for sub_list in applicable_declarations.iter() {
+        for declaration in sub_list.get().iter() {
+            match *declaration {
+                % for style_struct in STYLE_STRUCTS:
+                    % for property in style_struct.longhands:
+                        % if property.ident not in ALREADY_COMPUTED:
+                            ${property.ident}_declaration(SpecifiedValue(ref value)) => {
+                                style_${style_struct.name}.${property.ident} =
+                                    longhands::${property.ident}::to_computed_value(
+                                        (*value).clone(), &context);
+                            }
+                            ${property.ident}_declaration(CSSWideKeyword(Initial))
+                            % if not style_struct.inherited:
+                                | ${property.ident}_declaration(CSSWideKeyword(Unset))
+                            % endif
+                            => {
+                                style_${style_struct.name}.${property.ident} =
+                                    longhands::${property.ident}::get_initial_value();
+                            }
+                            ${property.ident}_declaration(CSSWideKeyword(Inherit))
+                            % if style_struct.inherited:
+                                | ${property.ident}_declaration(CSSWideKeyword(Unset))
+                            % endif
+                            => {
+                                style_${style_struct.name}.${property.ident} =
+                                    parent_style.${style_struct.name}.${property.ident}.clone();
+                            }
+                        % endif
+                    % endfor
+                % endfor
+                // Already handled above.
+                _ => ()
+            }
+        }
+    }
+    style_Color.color = context.current_color;
+    style_Font.font_size = context.font_size;
+    style_Font.font_weight = context.font_weight;
+
+    // CSS 2.1 section 9.7: Relationships between 'display', 'position', and 'float'.
+    match style_Box.position {
+        longhands::position::absolute | longhands::position::fixed => {
+            style_Box.float = longhands::float::none;
+            style_Box.display = blockify(style_Box.display);
+        }
+        _ => {
+            if style_Box.float != longhands::float::none || is_root_element {
+                style_Box.display = blockify(style_Box.display);
+            }
+        }
+    }
+
+    ComputedValues {
+        % for style_struct in STYLE_STRUCTS:
+            ${style_struct.name}: style_${style_struct.name},
+        % endfor
+    }
+}
+
+#[inline]
+fn has_border(style: longhands::border_top_style::ComputedValue) -> bool {
+    match style {
+        longhands::border_top_style::none | longhands::border_top_style::hidden => false,
+        _ => true,
+    }
+}
+
+/// The table in CSS 2.1 section 9.7 that maps 'display' values to their computed value for
+/// floated and absolutely positioned boxes, and for the root element.
+fn blockify(display: longhands::display::ComputedValue) -> longhands::display::ComputedValue {
+    match display {
+        longhands::display::inline_table => longhands::display::table,
+        longhands::display::inline | longhands::display::inline_block |
+        longhands::display::table_row_group | longhands::display::table_header_group |
+        longhands::display::table_footer_group | longhands::display::table_row |
+        longhands::display::table_column_group | longhands::display::table_column |
+        longhands::display::table_cell | longhands::display::table_caption
+        => longhands::display::block,
+        _ => display,
+    }
+}
+
+
+pub mod computed_values {
+    % for property in LONGHANDS:
+        pub use ${property.ident} = super::longhands::${property.ident};
+    % endfor
+    // Don't use a side-specific name needlessly:
+    pub use border_style = super::longhands::border_top_style;
+
+    pub use cssparser::RGBA;
+    pub use super::common_types::computed::{
+        CSSColor, Length,
+        LengthOrPercentage, LP_Length, LP_Percentage,
+        LengthOrPercentageOrAuto, LPA_Length, LPA_Percentage, LPA_Auto,
+        LengthOrPercentageOrNone, LPN_Length, LPN_Percentage, LPN_None};
+}
+
+
+#[cfg(test)]
+mod properties_tests {
+    use extra::url;
+    use super::*;
+    use super::computed_values::{LPA_Auto, LPA_Length};
+
+    /// Cascades the normal declarations of `css`, as if it were a style attribute.
+    fn style(css: &str, parent_style: Option<&ComputedValues>) -> ComputedValues {
+        let base_url = url::from_str("http://example.com/").unwrap();
+        cascade([parse_style_attribute(css, &base_url).normal], parent_style)
+    }
+
+    #[test]
+    fn inherit() {
+        let parent = style("width: 3px; margin: 1px 2px; color: red; font-size: 20px", None);
+        let child = style("width: inherit; margin: inherit", Some(&parent));
+        assert_eq!(child.Box.width, LPA_Length(Length(3 * 60)));
+        assert_eq!(child.Margin, parent.Margin);
+        // Inherited properties are inherited without a declaration, but the others are not.
+        assert_eq!(child.Color.color, parent.Color.color);
+        assert_eq!(child.Font.font_size, Length(20 * 60));
+        let grandchild = style("", Some(&child));
+        assert_eq!(grandchild.Box.width, LPA_Auto);
+        assert_eq!(grandchild.Font.font_size, Length(20 * 60));
+    }
+
+    #[test]
+    fn initial() {
+        let initial_values = get_initial_values();
+        let parent = style("width: 3px; margin: 1px 2px; color: red; font-size: 20px", None);
+        let child = style("width: initial; margin: initial; color: initial; font-size: initial",
+                          Some(&parent));
+        assert_eq!(child.Box.width, LPA_Auto);
+        assert_eq!(child.Margin, initial_values.Margin);
+        assert_eq!(child.Color.color, initial_values.Color.color);
+        assert_eq!(child.Font.font_size, Length(16 * 60));
+
+        // The keywords override earlier declarations like any other value.
+        let child = style("width: 1px; width: initial; color: initial; color: inherit",
+                          Some(&parent));
+        assert_eq!(child.Box.width, LPA_Auto);
+        assert_eq!(child.Color.color, parent.Color.color);
+    }
+
+    #[test]
+    fn unset() {
+        // 'unset' is 'inherit' for inherited properties, and 'initial' for the others.
+        let parent = style("width: 3px; margin: 1px 2px; color: red; font-size: 20px", None);
+        let child = style("width: unset; margin: unset; color: unset; font-size: unset",
+                          Some(&parent));
+        assert_eq!(child.Box.width, LPA_Auto);
+        assert_eq!(child.Margin, get_initial_values().Margin);
+        assert!(parent.Color.color != get_initial_values().Color.color);
+        assert_eq!(child.Color.color, parent.Color.color);
+        assert_eq!(child.Font.font_size, Length(20 * 60));
+    }
+}
@@ -0,0 +1,929 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::StrAsciiExt;
+use std::util::replace;
+use extra::arc::Arc;
+use extra::sort::merge_sort;
+
+use media_queries::Device;
+use node::{TElement, TNode};
+use properties::{PropertyDeclaration, PropertyDeclarationBlock};
+use selectors::*;
+use stylesheets::Stylesheet;
+
+
+pub enum StylesheetOrigin {
+    UserAgentOrigin,
+    AuthorOrigin,
+    UserOrigin,
+}
+
+
+pub struct Stylist {
+    priv device: Device,
+    // Kept so that rules can be re-filtered when media queries evaluate differently.
+    priv stylesheets: ~[(Stylesheet, StylesheetOrigin)],
+    priv element_rules: PerPseudoElementRules,
+    priv before_rules: PerPseudoElementRules,
+    priv after_rules: PerPseudoElementRules,
+    priv marker_rules: PerPseudoElementRules,
+}
+
+
+/// The rules whose selectors target one kind of pseudo-element (or the element itself).
+struct PerPseudoElementRules {
+    ua_rules: PerOriginRules,
+    author_rules: PerOriginRules,
+    user_rules: PerOriginRules,
+}
+
+impl PerPseudoElementRules {
+    #[inline]
+    fn new() -> PerPseudoElementRules {
+        PerPseudoElementRules {
+            ua_rules: PerOriginRules::new(),
+            author_rules: PerOriginRules::new(),
+            user_rules: PerOriginRules::new(),
+        }
+    }
+
+    #[inline]
+    fn origin_rules<'a>(&'a mut self, origin: StylesheetOrigin) -> &'a mut PerOriginRules {
+        match origin {
+            UserAgentOrigin => &mut self.ua_rules,
+            AuthorOrigin => &mut self.author_rules,
+            UserOrigin => &mut self.user_rules,
+        }
+    }
+
+    fn sort(&mut self) {
+        self.ua_rules.sort();
+        self.author_rules.sort();
+        self.user_rules.sort();
+    }
+}
+
+
+/// The rules of one origin, each list sorted by increasing specificity. Rules of equal
+/// specificity are kept in source order.
+struct PerOriginRules {
+    normal: ~[Rule],
+    important: ~[Rule],
+}
+
+impl PerOriginRules {
+    #[inline]
+    fn new() -> PerOriginRules {
+        PerOriginRules { normal: ~[], important: ~[] }
+    }
+
+    fn push(&mut self, selector: &Arc<Selector>, declarations: &PropertyDeclarationBlock) {
+        if declarations.normal.get().len() > 0 {
+            self.normal.push(Rule {
+                selector: selector.clone(),
+                declarations: declarations.normal.clone(),
+            })
+        }
+        if declarations.important.get().len() > 0 {
+            self.important.push(Rule {
+                selector: selector.clone(),
+                declarations: declarations.important.clone(),
+            })
+        }
+    }
+
+    fn sort(&mut self) {
+        // Rules are pushed in source order, and merge sort is stable.
+        self.normal = merge_sort(self.normal.as_slice(), le_specificity);
+        self.important = merge_sort(self.important.as_slice(), le_specificity);
+    }
+}
+
+
+#[deriving(Clone)]
+struct Rule {
+    selector: Arc<Selector>,
+    declarations: Arc<~[PropertyDeclaration]>,
+}
+
+
+impl Stylist {
+    #[inline]
+    pub fn new(device: Device) -> Stylist {
+        Stylist {
+            device: device,
+            stylesheets: ~[],
+            element_rules: PerPseudoElementRules::new(),
+            before_rules: PerPseudoElementRules::new(),
+            after_rules: PerPseudoElementRules::new(),
+            marker_rules: PerPseudoElementRules::new(),
+        }
+    }
+
+    pub fn add_stylesheet(&mut self, stylesheet: Stylesheet, origin: StylesheetOrigin) {
+        let device = self.device;
+        for style_rule in stylesheet.iter_style_rules(&device) {
+            for selector in style_rule.selectors.iter() {
+                let rules = match selector.pseudo_element {
+                    None => &mut self.element_rules,
+                    Some(Before) => &mut self.before_rules,
+                    Some(After) => &mut self.after_rules,
+                    Some(Marker) => &mut self.marker_rules,
+                    // TODO: ::first-line and ::first-letter
+                    Some(FirstLine) | Some(FirstLetter) => loop,
+                };
+                rules.origin_rules(origin).push(&Arc::new(selector.clone()),
+                                                &style_rule.declarations);
+            }
+        }
+        self.element_rules.sort();
+        self.before_rules.sort();
+        self.after_rules.sort();
+        self.marker_rules.sort();
+        self.stylesheets.push((stylesheet, origin));
+    }
+
+    /// The device that media queries are evaluated against.
+    pub fn device<'a>(&'a self) -> &'a Device {
+        &self.device
+    }
+
+    /// Updates the device that media queries are evaluated against, for example after the
+    /// viewport was resized. Returns whether any media query evaluates differently, in which case
+    /// the rules were rebuilt and elements need to be restyled.
+    pub fn set_device(&mut self, device: Device) -> bool {
+        let changed = do self.stylesheets.iter().any |&(ref stylesheet, _)| {
+            stylesheet.media_queries_differ(&self.device, &device)
+        };
+        self.device = device;
+        if changed {
+            self.element_rules = PerPseudoElementRules::new();
+            self.before_rules = PerPseudoElementRules::new();
+            self.after_rules = PerPseudoElementRules::new();
+            self.marker_rules = PerPseudoElementRules::new();
+            let stylesheets = replace(&mut self.stylesheets, ~[]);
+            for (stylesheet, origin) in stylesheets.move_iter() {
+                self.add_stylesheet(stylesheet, origin)
+            }
+        }
+        changed
+    }
+
+    /// Returns the declarations that apply to the given element, or to one of its
+    /// pseudo-elements, in cascade order: later declarations take precedence over earlier ones.
+    ///
+    /// The style attribute only applies to the element itself.
+    pub fn get_applicable_declarations<N: TNode<E>, E: TElement>(
+            &self, element: &N, style_attribute: Option<&PropertyDeclarationBlock>,
+            pseudo_element: Option<PseudoElement>)
+            -> ~[Arc<~[PropertyDeclaration]>] {
+        assert!(element.is_element())
+        assert!(style_attribute.is_none() || pseudo_element.is_none())
+        let rules = match pseudo_element {
+            None => &self.element_rules,
+            Some(Before) => &self.before_rules,
+            Some(After) => &self.after_rules,
+            Some(Marker) => &self.marker_rules,
+            Some(FirstLine) | Some(FirstLetter) => return ~[],
+        };
+        let mut applicable_declarations = ~[];
+
+        // In increasing order of precedence, per CSS 2.1 section 6.4.1. Within each origin and
+        // importance, rules are already sorted by specificity then source order. The style
+        // attribute has a higher specificity than any selector, so it comes last in its group.
+        get_matching_rules(element, rules.ua_rules.normal.as_slice(),
+                           &mut applicable_declarations);
+        get_matching_rules(element, rules.user_rules.normal.as_slice(),
+                           &mut applicable_declarations);
+        get_matching_rules(element, rules.author_rules.normal.as_slice(),
+                           &mut applicable_declarations);
+        match style_attribute {
+            Some(block) => applicable_declarations.push(block.normal.clone()),
+            None => (),
+        }
+        get_matching_rules(element, rules.author_rules.important.as_slice(),
+                           &mut applicable_declarations);
+        match style_attribute {
+            Some(block) => applicable_declarations.push(block.important.clone()),
+            None => (),
+        }
+        get_matching_rules(element, rules.user_rules.important.as_slice(),
+                           &mut applicable_declarations);
+        get_matching_rules(element, rules.ua_rules.important.as_slice(),
+                           &mut applicable_declarations);
+
+        applicable_declarations
+    }
+}
+
+
+#[inline]
+fn le_specificity(a: &Rule, b: &Rule) -> bool {
+    a.selector.get().specificity <= b.selector.get().specificity
+}
+
+
+fn get_matching_rules<N: TNode<E>, E: TElement>(element: &N, rules: &[Rule],
+                                                applicable_declarations:
+                                                    &mut ~[Arc<~[PropertyDeclaration]>]) {
+    for rule in rules.iter() {
+        if matches_selector(rule.selector.get(), element) {
+            applicable_declarations.push(rule.declarations.clone())
+        }
+    }
+}
+
+
+#[inline]
+fn matches_selector<N: TNode<E>, E: TElement>(selector: &Selector, element: &N) -> bool {
+    matches_compound_selector(&selector.compound_selectors, element)
+}
+
+
+fn matches_compound_selector<N: TNode<E>, E: TElement>(selector: &CompoundSelector, element: &N)
+                                                       -> bool {
+    if !selector.simple_selectors.iter().all(|simple_selector| {
+            matches_simple_selector(simple_selector, element)
+    }) {
+        return false
+    }
+    match selector.next {
+        None => true,
+        Some((ref next_selector, combinator)) => {
+            let (siblings, just_one) = match combinator {
+                Child => (false, true),
+                Descendant => (false, false),
+                NextSibling => (true, true),
+                LaterSibling => (true, false),
+            };
+            let mut node = element.clone();
+            loop {
+                let next_node = if siblings {
+                    node.prev_sibling()
+                } else {
+                    node.parent_node()
+                };
+                match next_node {
+                    None => return false,
+                    Some(next_node) => node = next_node,
+                }
+                // Text and comment nodes are skipped by combinators.
+                if node.is_element() {
+                    if matches_compound_selector(&**next_selector, &node) {
+                        return true
+                    } else if just_one {
+                        return false
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn matches_simple_selector<N: TNode<E>, E: TElement>(selector: &SimpleSelector, element: &N)
+                                                     -> bool {
+    static WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0C'];
+
+    match *selector {
+        // TODO: case-sensitivity depends on the document type
+        // TODO: intern element names
+        LocalNameSelector{lowercase_name: ref name, _} => {
+            do element.with_element |element: &E| {
+                element.get_local_name().eq_ignore_ascii_case(name.as_slice())
+            }
+        }
+        NamespaceSelector(ref url) => {
+            do element.with_element |element: &E| {
+                element.get_namespace_url() == url.as_slice()
+            }
+        }
+        // TODO: case-sensitivity depends on the document type and quirks mode
+        // TODO: cache and intern IDs on elements.
+        IDSelector(ref id) => {
+            do element.with_element |element: &E| {
+                match element.get_attr("id") {
+                    Some(attr) => attr == id.as_slice(),
+                    None => false
+                }
+            }
+        }
+        // TODO: cache and intern class names on elements.
+        ClassSelector(ref class) => {
+            do element.with_element |element: &E| {
+                match element.get_attr("class") {
+                    None => false,
+                    // TODO: case-sensitivity depends on the document type and quirks mode
+                    Some(ref class_attr)
+                    => class_attr.split_iter(WHITESPACE).any(|c| c == class.as_slice()),
+                }
+            }
+        }
+
+        AttrExists(ref attr) => match_attribute(attr, element, |_| true),
+        AttrEqual(ref attr, ref value) => match_attribute(attr, element, |v| v == value.as_slice()),
+        AttrIncludes(ref attr, ref value) => do match_attribute(attr, element) |attr_value| {
+            attr_value.split_iter(WHITESPACE).any(|v| v == value.as_slice())
+        },
+        AttrDashMatch(ref attr, ref value) => do match_attribute(attr, element) |attr_value| {
+            attr_value == value.as_slice() ||
+            (attr_value.starts_with(value.as_slice()) &&
+             attr_value.char_at(value.len()) == '-')
+        },
+        AttrPrefixMatch(ref attr, ref value) => do match_attribute(attr, element) |attr_value| {
+            attr_value.starts_with(value.as_slice())
+        },
+        AttrSubstringMatch(ref attr, ref value) => do match_attribute(attr, element) |attr_value| {
+            attr_value.contains(value.as_slice())
+        },
+        AttrSuffixMatch(ref attr, ref value) => do match_attribute(attr, element) |attr_value| {
+            attr_value.ends_with(value.as_slice())
+        },
+
+        Link => do element.with_element |element: &E| { element.get_link().is_some() },
+        // Matching :visited would leak the user's history, and we do not keep one anyway.
+        Visited => false,
+        Hover => do element.with_element |element: &E| { element.get_hover_state() },
+        Active => do element.with_element |element: &E| { element.get_active_state() },
+        Focus => do element.with_element |element: &E| { element.get_focus_state() },
+        Checked => do element.with_element |element: &E| { element.get_checked_state() },
+        Disabled => do element.with_element |element: &E| { element.get_disabled_state() },
+
+        Empty => {
+            let mut next_node = element.first_child();
+            loop {
+                match next_node {
+                    None => return true,
+                    Some(node) => {
+                        if node.is_element() || node.is_text() {
+                            return false
+                        }
+                        next_node = node.next_sibling();
+                    }
+                }
+            }
+        }
+        Root => element.parent_node().is_none(),
+        Lang(*) => false,
+
+        FirstChild => matches_first_child(element),
+        LastChild => matches_last_child(element),
+        OnlyChild => matches_first_child(element) && matches_last_child(element),
+        FirstOfType => matches_generic_nth_child(element, 0, 1, true, false),
+        LastOfType => matches_generic_nth_child(element, 0, 1, true, true),
+        OnlyOfType => matches_generic_nth_child(element, 0, 1, true, false) &&
+                      matches_generic_nth_child(element, 0, 1, true, true),
+
+        NthChild(a, b) => matches_generic_nth_child(element, a, b, false, false),
+        NthLastChild(a, b) => matches_generic_nth_child(element, a, b, false, true),
+        NthOfType(a, b) => matches_generic_nth_child(element, a, b, true, false),
+        NthLastOfType(a, b) => matches_generic_nth_child(element, a, b, true, true),
+
+        Negation(ref negated) => {
+            !negated.iter().all(|s| matches_simple_selector(s, element))
+        },
+    }
+}
+
+#[inline]
+fn matches_first_child<N: TNode<E>, E: TElement>(element: &N) -> bool {
+    let mut node = element.clone();
+    loop {
+        match node.prev_sibling() {
+            None => return true,
+            Some(prev_sibling) => {
+                if prev_sibling.is_element() {
+                    return false
+                }
+                node = prev_sibling;
+            }
+        }
+    }
+}
+
+#[inline]
+fn matches_last_child<N: TNode<E>, E: TElement>(element: &N) -> bool {
+    let mut node = element.clone();
+    loop {
+        match node.next_sibling() {
+            None => return true,
+            Some(next_sibling) => {
+                if next_sibling.is_element() {
+                    return false
+                }
+                node = next_sibling;
+            }
+        }
+    }
+}
+
+/// Returns true if the element is the (an + b)-th element child of its parent, for some
+/// non-negative integer n. With `is_of_type`, only siblings with the same element name are
+/// counted; with `is_from_end`, they are counted from the last child backwards.
+#[inline]
+fn matches_generic_nth_child<N: TNode<E>, E: TElement>(element: &N, a: i32, b: i32,
+                                                       is_of_type: bool, is_from_end: bool)
+                                                       -> bool {
+    let (local_name, namespace_url) = do element.with_element |element: &E| {
+        (element.get_local_name().to_owned(), element.get_namespace_url().to_owned())
+    };
+    let mut node = element.clone();
+    // Index is 1-based.
+    let mut index = 1;
+    loop {
+        let sibling = if is_from_end { node.next_sibling() } else { node.prev_sibling() };
+        match sibling {
+            None => break,
+            Some(sibling) => {
+                node = sibling;
+                if node.is_element() {
+                    if !is_of_type {
+                        index += 1;
+                    } else if do node.with_element |element: &E| {
+                        element.get_local_name() == local_name.as_slice() &&
+                        element.get_namespace_url() == namespace_url.as_slice()
+                    } {
+                        index += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if a == 0 {
+        index == b
+    } else {
+        (index - b) / a >= 0 && (index - b) % a == 0
+    }
+}
+
+#[inline]
+fn match_attribute<N: TNode<E>, E: TElement>(attr: &AttrSelector, element: &N,
+                                             f: &fn(&str) -> bool) -> bool {
+    match attr.namespace {
+        // Elements do not have namespaced attributes yet.
+        Some(ref url) if url.as_slice() != "" => false,
+        _ => do element.with_element |element: &E| {
+            match element.get_attr(attr.lowercase_name.as_slice()) {
+                None => false,
+                Some(value) => f(value),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod selector_matching_tests {
+    use extra::url;
+    use media_queries::{Device, Screen};
+    use node::{TElement, TNode};
+    use properties::{cascade, parse_style_attribute};
+    use properties::common_types::computed::{Length, LengthOrPercentageOrAuto, LPA_Auto};
+    use properties::common_types::computed::LPA_Length;
+    use stylesheets::Stylesheet;
+    use super::{AuthorOrigin, StylesheetOrigin, Stylist, UserAgentOrigin, UserOrigin};
+    use super::{matches_first_child, matches_generic_nth_child, matches_last_child};
+
+    struct TestElement {
+        local_name: ~str,
+        attrs: ~[(~str, ~str)],
+    }
+
+    impl TElement for TestElement {
+        fn get_local_name<'a>(&'a self) -> &'a str {
+            self.local_name.as_slice()
+        }
+
+        fn get_namespace_url<'a>(&'a self) -> &'a str {
+            "http://www.w3.org/1999/xhtml"
+        }
+
+        fn get_attr<'a>(&'a self, name: &str) -> Option<&'a str> {
+            for &(ref attr_name, ref value) in self.attrs.iter() {
+                if attr_name.as_slice() == name {
+                    return Some(value.as_slice())
+                }
+            }
+            None
+        }
+
+        fn get_link<'a>(&'a self) -> Option<&'a str> {
+            None
+        }
+
+        fn get_hover_state(&self) -> bool { false }
+        fn get_active_state(&self) -> bool { false }
+        fn get_focus_state(&self) -> bool { false }
+        fn get_checked_state(&self) -> bool { false }
+        fn get_disabled_state(&self) -> bool { false }
+    }
+
+    /// A node of a `TestTree`, which is a text node if it has no element.
+    struct TestNodeData {
+        element: Option<TestElement>,
+        parent: Option<uint>,
+        prev_sibling: Option<uint>,
+        next_sibling: Option<uint>,
+        first_child: Option<uint>,
+        last_child: Option<uint>,
+    }
+
+    /// A document tree whose nodes refer to each other by index.
+    struct TestTree {
+        nodes: ~[TestNodeData],
+    }
+
+    impl TestTree {
+        fn new() -> TestTree {
+            TestTree {
+                nodes: ~[],
+            }
+        }
+
+        fn add_node(&mut self, parent: Option<uint>, element: Option<TestElement>) -> uint {
+            let index = self.nodes.len();
+            let prev_sibling = match parent {
+                Some(parent) => self.nodes[parent].last_child,
+                None => None,
+            };
+            self.nodes.push(TestNodeData {
+                element: element,
+                parent: parent,
+                prev_sibling: prev_sibling,
+                next_sibling: None,
+                first_child: None,
+                last_child: None,
+            });
+            for &prev_sibling in prev_sibling.iter() {
+                self.nodes[prev_sibling].next_sibling = Some(index)
+            }
+            for &parent in parent.iter() {
+                if self.nodes[parent].first_child.is_none() {
+                    self.nodes[parent].first_child = Some(index)
+                }
+                self.nodes[parent].last_child = Some(index)
+            }
+            index
+        }
+
+        /// Adds an element with the given name and attributes as the last child of `parent`.
+        fn add_element(&mut self, parent: Option<uint>, local_name: &str, attrs: &[(&str, &str)])
+                       -> uint {
+            let element = TestElement {
+                local_name: local_name.to_owned(),
+                attrs: attrs.iter().map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                                   .collect(),
+            };
+            self.add_node(parent, Some(element))
+        }
+
+        /// Adds a text node as the last child of `parent`.
+        fn add_text(&mut self, parent: uint) -> uint {
+            self.add_node(Some(parent), None)
+        }
+    }
+
+    #[deriving(Clone)]
+    struct TestNode {
+        tree: @TestTree,
+        index: uint,
+    }
+
+    impl TestNode {
+        fn new(tree: @TestTree, index: uint) -> TestNode {
+            TestNode {
+                tree: tree,
+                index: index,
+            }
+        }
+
+        fn data<'a>(&'a self) -> &'a TestNodeData {
+            &self.tree.nodes[self.index]
+        }
+
+        fn node_at(&self, index: Option<uint>) -> Option<TestNode> {
+            index.map(|&index| TestNode::new(self.tree, index))
+        }
+    }
+
+    impl TNode<TestElement> for TestNode {
+        fn parent_node(&self) -> Option<TestNode> {
+            self.node_at(self.data().parent)
+        }
+
+        fn prev_sibling(&self) -> Option<TestNode> {
+            self.node_at(self.data().prev_sibling)
+        }
+
+        fn next_sibling(&self) -> Option<TestNode> {
+            self.node_at(self.data().next_sibling)
+        }
+
+        fn first_child(&self) -> Option<TestNode> {
+            self.node_at(self.data().first_child)
+        }
+
+        fn is_element(&self) -> bool {
+            self.data().element.is_some()
+        }
+
+        fn is_text(&self) -> bool {
+            self.data().element.is_none()
+        }
+
+        fn with_element<R>(&self, f: &fn(&TestElement) -> R) -> R {
+            f(self.data().element.get_ref())
+        }
+    }
+
+    fn stylist(stylesheets: &[(&str, StylesheetOrigin)]) -> Stylist {
+        let mut stylist = Stylist::new(Device::new(Screen, Length(800 * 60), Length(600 * 60),
+                                                   1.0));
+        for &(css, origin) in stylesheets.iter() {
+            let base_url = url::from_str("http://example.com/").unwrap();
+            stylist.add_stylesheet(Stylesheet::from_str(css, base_url), origin)
+        }
+        stylist
+    }
+
+    /// Returns the computed 'width' of `element`, cascaded as if it were the root element.
+    fn width(stylist: &Stylist, element: &TestNode, style_attribute: Option<&str>)
+             -> LengthOrPercentageOrAuto {
+        let base_url = url::from_str("http://example.com/").unwrap();
+        let style_attribute = do style_attribute.map |&css| {
+            parse_style_attribute(css, &base_url)
+        };
+        let declarations = stylist.get_applicable_declarations::<TestNode, TestElement>(
+            element, style_attribute.as_ref(), None);
+        cascade(declarations, None).Box.width
+    }
+
+    fn px(value: i64) -> LengthOrPercentageOrAuto {
+        LPA_Length(Length(value * 60))
+    }
+
+    /// Returns whether an author rule with the given selectors applies to `element`.
+    fn matches(selectors: &str, element: &TestNode) -> bool {
+        let css = fmt!("%s { width: 1px }", selectors);
+        let stylist = stylist([(css.as_slice(), AuthorOrigin)]);
+        match width(&stylist, element, None) {
+            LPA_Auto => false,
+            value => { assert_eq!(value, px(1)); true }
+        }
+    }
+
+    /// Returns the elements of the tree
+    ///
+    ///     <html><body><div id=a class=x> <p class=first> <span></span><p class=last></div>
+    ///     <p class=after></body></html>
+    ///
+    /// in document order, where the spaces are text nodes.
+    fn document_tree() -> ~[TestNode] {
+        let mut tree = TestTree::new();
+        let html = tree.add_element(None, "html", []);
+        let body = tree.add_element(Some(html), "body", []);
+        let div = tree.add_element(Some(body), "div", [("id", "a"), ("class", "x")]);
+        tree.add_text(div);
+        let first = tree.add_element(Some(div), "p", [("class", "first")]);
+        tree.add_text(div);
+        let span = tree.add_element(Some(div), "span", []);
+        let last = tree.add_element(Some(div), "p", [("class", "last")]);
+        let after = tree.add_element(Some(body), "p", [("class", "after")]);
+        let tree = @tree;
+        [html, body, div, first, span, last, after].iter().map(|&index| {
+            TestNode::new(tree, index)
+        }).collect()
+    }
+
+    #[test]
+    fn higher_specificity_wins() {
+        let elements = document_tree();
+        let div = &elements[2];
+        let stylist = stylist([("#a { width: 4px } div.x { width: 3px } .x { width: 2px } \
+                                 div { width: 1px } * { width: 0px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(4));
+
+        // Specificity takes precedence over the order of the style sheets.
+        let stylist = stylist([("#a { width: 2px } div.x { width: 1px }", AuthorOrigin),
+                               ("body > div.x { width: 3px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+
+        // The style attribute is more specific than any selector.
+        assert_eq!(width(&stylist, div, Some("width: 5px")), px(5));
+
+        // The selectors of a rule are sorted separately.
+        let stylist = stylist([(".x, #a { width: 2px } div.x { width: 1px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+        assert_eq!(width(&stylist, &elements[3], None), LPA_Auto);
+    }
+
+    #[test]
+    fn source_order_breaks_ties() {
+        let elements = document_tree();
+        let div = &elements[2];
+        let stylist = stylist([("body > div { width: 1px } body div { width: 2px } \
+                                 p { width: 4px } html div { width: 3px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(3));
+        assert_eq!(width(&stylist, &elements[1], None), LPA_Auto);
+
+        let stylist = stylist([("div.x { width: 1px } div { width: 4px } body > .x { width: 2px } \
+                                 .x { width: 3px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+
+        // Later style sheets come later in source order.
+        let stylist = stylist([("div { width: 1px }", AuthorOrigin),
+                               ("div { width: 2px }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+
+        // Within a rule, the last valid declaration wins.
+        let stylist = stylist([("div { width: 1px; width: 2px; width: red }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+
+        // The style attribute comes after all rules.
+        assert_eq!(width(&stylist, div, Some("width: 5px")), px(5));
+        assert_eq!(width(&stylist, &elements[1], Some("width: 5px")), px(5));
+
+        // But author rules come after user agent rules, whatever their specificity.
+        let stylist = stylist([("div { width: 1px }", AuthorOrigin),
+                               ("#a { width: 2px }", UserAgentOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(1));
+    }
+
+    #[test]
+    fn important_declarations() {
+        let elements = document_tree();
+        let div = &elements[2];
+
+        // Important declarations win over normal ones, whatever their specificity.
+        let stylist = stylist([("#a { width: 1px } div { width: 2px !important }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+        assert_eq!(width(&stylist, div, Some("width: 3px")), px(2));
+        assert_eq!(width(&stylist, div, Some("width: 3px !important")), px(3));
+
+        // Normal declarations: user agent, then user, then author.
+        let stylist = stylist([("div { width: 3px }", AuthorOrigin),
+                               ("#a { width: 1px }", UserAgentOrigin),
+                               ("#a { width: 2px }", UserOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(3));
+        let stylist = stylist([("#a { width: 1px }", UserAgentOrigin),
+                               ("#a { width: 2px }", UserOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+
+        // Important declarations: author, then user, then user agent.
+        let stylist = stylist([("div { width: 1px !important }", UserAgentOrigin),
+                               ("div { width: 2px !important }", UserOrigin),
+                               ("#a { width: 3px !important }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(1));
+        let stylist = stylist([("div { width: 1px }", UserAgentOrigin),
+                               ("div { width: 2px !important }", UserOrigin),
+                               ("#a { width: 3px !important }", AuthorOrigin)]);
+        assert_eq!(width(&stylist, div, None), px(2));
+        assert_eq!(width(&stylist, div, Some("width: 4px !important")), px(2));
+    }
+
+    #[test]
+    fn descendant_and_child_combinators() {
+        let elements = document_tree();
+        let (html, body, div, first, span, last, after) = (
+            &elements[0], &elements[1], &elements[2], &elements[3], &elements[4], &elements[5],
+            &elements[6]);
+
+        for p in [first, last, after].iter() {
+            assert!(matches("body p", *p));
+            assert!(matches("html p", *p));
+        }
+        assert!(!matches("body p", body));
+        assert!(!matches("html body", html));
+        assert!(matches("html body div span", span));
+        assert!(matches("#a span", span));
+        assert!(!matches("#a div", div));
+        assert!(!matches("p span", span));
+
+        assert!(matches("body > p", after));
+        assert!(!matches("body > p", first));
+        assert!(matches("div > p", first));
+        assert!(matches("div > p", last));
+        assert!(matches("html > body > div > span", span));
+        assert!(!matches("html > div span", span));
+        assert!(matches("html > * span", span));
+        assert!(matches("html div > span", span));
+        assert!(!matches("body > span", span));
+    }
+
+    #[test]
+    fn sibling_combinators() {
+        let elements = document_tree();
+        let (div, first, span, last, after) = (
+            &elements[2], &elements[3], &elements[4], &elements[5], &elements[6]);
+
+        // The text node between the first p and the span is skipped.
+        assert!(matches("p + span", span));
+        assert!(matches(".first + span", span));
+        assert!(matches("span + p", last));
+        assert!(!matches("p + p", last));
+        assert!(matches("div + p", after));
+        assert!(!matches("p + p", after));
+        assert!(!matches("span + p", first));
+
+        assert!(matches("p ~ p", last));
+        assert!(matches(".first ~ .last", last));
+        assert!(!matches("p ~ p", first));
+        assert!(!matches("span ~ .first", first));
+        assert!(!matches("p ~ p", after));
+        assert!(!matches("div ~ div", div));
+
+        // Mixed with the other combinators.
+        assert!(matches("body > div > .first ~ span + p", last));
+        assert!(matches("div ~ p.after", after));
+        assert!(matches("#a p + span", span));
+        assert!(!matches("#a + p ~ span", span));
+        assert!(matches("html > body > div + .after", after));
+    }
+
+    /// Returns a tree of a `div` with the children `p`, `p`, `span`, `p` and `p`, and text nodes
+    /// before, between and after them, along with the elements of the children.
+    fn children_tree() -> (@TestTree, ~[TestNode]) {
+        let mut tree = TestTree::new();
+        let div = tree.add_element(None, "div", []);
+        let mut children = ~[];
+        tree.add_text(div);
+        for &local_name in ["p", "p", "span", "p", "p"].iter() {
+            children.push(tree.add_element(Some(div), local_name, []));
+            tree.add_text(div);
+        }
+        let tree = @tree;
+        (tree, children.iter().map(|&index| TestNode::new(tree, index)).collect())
+    }
+
+    /// Returns which of the children of `children_tree` match `matches`.
+    fn children_matching(matches: &fn(&TestNode) -> bool) -> ~[bool] {
+        let (_, children) = children_tree();
+        children.iter().map(|child| matches(child)).collect()
+    }
+
+    fn nth_child(a: i32, b: i32) -> ~[bool] {
+        do children_matching |child| {
+            matches_generic_nth_child::<TestNode, TestElement>(child, a, b, false, false)
+        }
+    }
+
+    #[test]
+    fn nth_child_matching() {
+        assert_eq!(nth_child(0, 3), ~[false, false, true, false, false]);
+        // :nth-child(-n+3)
+        assert_eq!(nth_child(-1, 3), ~[true, true, true, false, false]);
+        // :nth-child(odd)
+        assert_eq!(nth_child(2, 1), ~[true, false, true, false, true]);
+        // :nth-child(even)
+        assert_eq!(nth_child(2, 0), ~[false, true, false, true, false]);
+        // :nth-child(0n+0)
+        assert_eq!(nth_child(0, 0), ~[false, false, false, false, false]);
+        // :nth-child(n+4)
+        assert_eq!(nth_child(1, 4), ~[false, false, false, true, true]);
+        // :nth-child(3n-1)
+        assert_eq!(nth_child(3, -1), ~[false, true, false, false, true]);
+    }
+
+    #[test]
+    fn nth_last_child_and_of_type_matching() {
+        let nth_last_child = do children_matching |child| {
+            matches_generic_nth_child::<TestNode, TestElement>(child, 0, 2, false, true)
+        };
+        assert_eq!(nth_last_child, ~[false, false, false, true, false]);
+
+        let nth_of_type = do children_matching |child| {
+            matches_generic_nth_child::<TestNode, TestElement>(child, 2, 1, true, false)
+        };
+        assert_eq!(nth_of_type, ~[true, false, true, true, false]);
+
+        let nth_last_of_type = do children_matching |child| {
+            matches_generic_nth_child::<TestNode, TestElement>(child, 0, 1, true, true)
+        };
+        assert_eq!(nth_last_of_type, ~[false, false, true, false, true]);
+    }
+
+    #[test]
+    fn first_and_last_child_matching() {
+        // The text nodes around the children are skipped.
+        let first_child = do children_matching |child| {
+            matches_first_child::<TestNode, TestElement>(child)
+        };
+        assert_eq!(first_child, ~[true, false, false, false, false]);
+
+        let last_child = do children_matching |child| {
+            matches_last_child::<TestNode, TestElement>(child)
+        };
+        assert_eq!(last_child, ~[false, false, false, false, true]);
+
+        let mut tree = TestTree::new();
+        let div = tree.add_element(None, "div", []);
+        let only_child = tree.add_element(Some(div), "p", []);
+        let tree = @tree;
+        let only_child = TestNode::new(tree, only_child);
+        assert!(matches_first_child::<TestNode, TestElement>(&only_child));
+        assert!(matches_last_child::<TestNode, TestElement>(&only_child));
+    }
+}
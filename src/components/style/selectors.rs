@@ -5,9 +5,10 @@
 use std::{vec, iter};
 use std::ascii::StrAsciiExt;
 use cssparser::*;
-use style::namespaces::NamespaceMap;
+use namespaces::NamespaceMap;
 
 
+#[deriving(Clone)]
 pub struct Selector {
     compound_selectors: CompoundSelector,
     pseudo_element: Option<PseudoElement>,
@@ -17,19 +18,23 @@ pub struct Selector {
 pub static STYLE_ATTRIBUTE_SPECIFICITY: u32 = 1 << 31;
 
 
+#[deriving(Eq, Clone)]
 pub enum PseudoElement {
     Before,
     After,
     FirstLine,
     FirstLetter,
+    Marker,
 }
 
 
+#[deriving(Clone)]
 pub struct CompoundSelector {
     simple_selectors: ~[SimpleSelector],
     next: Option<(~CompoundSelector, Combinator)>,  // c.next is left of c
 }
 
+#[deriving(Eq, Clone)]
 pub enum Combinator {
     Child,  //  >
     Descendant,  // space
@@ -37,6 +42,7 @@ pub enum Combinator {
     LaterSibling,  // ~
 }
 
+#[deriving(Clone)]
 pub enum SimpleSelector {
     IDSelector(~str),
     ClassSelector(~str),
@@ -53,14 +59,31 @@ pub enum SimpleSelector {
     AttrSuffixMatch(AttrSelector, ~str),  // [foo$=bar]
 
     // Pseudo-classes
+    Link,
+    Visited,
+    Hover,
+    Active,
+    Focus,
+    Checked,
+    Disabled,
     Empty,
     Root,
     Lang(~str),
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
     NthChild(i32, i32),
+    NthLastChild(i32, i32),
+    NthOfType(i32, i32),
+    NthLastOfType(i32, i32),
     Negation(~[SimpleSelector]),
     // ...
 }
 
+#[deriving(Clone)]
 pub struct AttrSelector {
     lowercase_name: ~str,
     cased_name: ~str,
@@ -173,7 +196,11 @@ fn compute_specificity(mut selector: &CompoundSelector,
                 &ClassSelector(*)
                 | &AttrExists(*) | &AttrEqual(*) | &AttrIncludes(*) | &AttrDashMatch(*)
                 | &AttrPrefixMatch(*) | &AttrSubstringMatch(*) | &AttrSuffixMatch(*)
-                | &Empty | &Root | &Lang(*) | &NthChild(*)
+                | &Link | &Visited | &Hover | &Active | &Focus | &Checked | &Disabled
+                | &Empty | &Root | &Lang(*)
+                | &FirstChild | &LastChild | &OnlyChild
+                | &FirstOfType | &LastOfType | &OnlyOfType
+                | &NthChild(*) | &NthLastChild(*) | &NthOfType(*) | &NthLastOfType(*)
                 => specificity.class_like_selectors += 1,
                 &NamespaceSelector(*) => (),
                 &Negation(ref negated)
@@ -185,7 +212,7 @@ fn compute_specificity(mut selector: &CompoundSelector,
     static MAX_10BIT: u32 = (1u32 << 10) - 1;
     specificity.id_selectors.min(&MAX_10BIT) << 20
     | specificity.class_like_selectors.min(&MAX_10BIT) << 10
-    | specificity.id_selectors.min(&MAX_10BIT)
+    | specificity.element_selectors.min(&MAX_10BIT)
 }
 
 
@@ -395,8 +422,21 @@ fn parse_attribute_selector(content: ~[ComponentValue], namespaces: &NamespaceMa
 
 fn parse_simple_pseudo_class(name: ~str) -> Option<Either<SimpleSelector, PseudoElement>> {
     match name.to_ascii_lower().as_slice() {
+        "link" => Some(Left(Link)),
+        "visited" => Some(Left(Visited)),
+        "hover" => Some(Left(Hover)),
+        "active" => Some(Left(Active)),
+        "focus" => Some(Left(Focus)),
+        "checked" => Some(Left(Checked)),
+        "disabled" => Some(Left(Disabled)),
         "root" => Some(Left(Root)),
         "empty" => Some(Left(Empty)),
+        "first-child" => Some(Left(FirstChild)),
+        "last-child" => Some(Left(LastChild)),
+        "only-child" => Some(Left(OnlyChild)),
+        "first-of-type" => Some(Left(FirstOfType)),
+        "last-of-type" => Some(Left(LastOfType)),
+        "only-of-type" => Some(Left(OnlyOfType)),
 
         // Supported CSS 2.1 pseudo-elements only.
         "before" => Some(Right(Before)),
@@ -414,6 +454,9 @@ fn parse_functional_pseudo_class(name: ~str, arguments: ~[ComponentValue],
     match name.to_ascii_lower().as_slice() {
         "lang" => parse_lang(arguments),
         "nth-child" => parse_nth(arguments).map(|&(a, b)| NthChild(a, b)),
+        "nth-last-child" => parse_nth(arguments).map(|&(a, b)| NthLastChild(a, b)),
+        "nth-of-type" => parse_nth(arguments).map(|&(a, b)| NthOfType(a, b)),
+        "nth-last-of-type" => parse_nth(arguments).map(|&(a, b)| NthLastOfType(a, b)),
         "not" => if inside_negation { None } else { parse_negation(arguments, namespaces) },
         _ => None
     }
@@ -427,6 +470,7 @@ fn parse_pseudo_element(name: ~str) -> Option<PseudoElement> {
         "after" => Some(After),
         "first-line" => Some(FirstLine),
         "first-letter" => Some(FirstLetter),
+        "marker" => Some(Marker),
         _ => None
     }
 }
@@ -480,3 +524,105 @@ fn skip_whitespace(iter: &mut Iter) -> bool {
         iter.next();
     }
 }
+
+
+#[cfg(test)]
+mod selectors_tests {
+    use cssparser::tokenize;
+    use namespaces::NamespaceMap;
+    use super::*;
+
+    fn parse(input: &str) -> Option<~[Selector]> {
+        let input = tokenize(input).map(|(component_value, _)| component_value).collect();
+        parse_selector_list(input, &NamespaceMap::new())
+    }
+
+    fn specificity(input: &str) -> u32 {
+        let selectors = parse(input).unwrap();
+        assert_eq!(selectors.len(), 1);
+        selectors[0].specificity
+    }
+
+    #[test]
+    fn specificities() {
+        assert_eq!(specificity("div#a.b"), 1 << 20 | 1 << 10 | 1);
+        assert_eq!(specificity("#a"), 1 << 20);
+        assert!(specificity("div#a.b") > specificity("#a"));
+        assert_eq!(specificity("*"), 0);
+        assert_eq!(specificity("html > body p"), 3);
+        assert_eq!(specificity("p::before"), 2);
+        assert_eq!(specificity("a[href]:empty"), 2 << 10 | 1);
+        assert_eq!(specificity("a:hover:first-child"), 2 << 10 | 1);
+        assert_eq!(specificity("div > #a ~ .b + p"), 1 << 20 | 1 << 10 | 2);
+        // The negation pseudo-class itself does not count, but its argument does.
+        assert_eq!(specificity(":not(#a)"), 1 << 20);
+        // An ID selector is more specific than any number of classes.
+        assert!(specificity("#a") > specificity(".a.b.c.d.e.f.g.h.i.j.k.l"));
+    }
+
+    /// Parses a selector made of a single simple selector.
+    fn parse_one(input: &str) -> SimpleSelector {
+        let selectors = parse(input).unwrap();
+        assert_eq!(selectors.len(), 1);
+        let compound_selector = &selectors[0].compound_selectors;
+        assert!(compound_selector.next.is_none());
+        assert_eq!(compound_selector.simple_selectors.len(), 1);
+        compound_selector.simple_selectors[0].clone()
+    }
+
+    fn parse_nth_child(input: &str) -> (i32, i32) {
+        match parse_one(input) {
+            NthChild(a, b) => (a, b),
+            _ => fail!(fmt!("%s is not :nth-child()", input)),
+        }
+    }
+
+    #[test]
+    fn nth_child() {
+        assert_eq!(parse_nth_child(":nth-child(2n+1)"), (2, 1));
+        assert_eq!(parse_nth_child(":nth-child(-n+3)"), (-1, 3));
+        assert_eq!(parse_nth_child(":nth-child(odd)"), (2, 1));
+        assert_eq!(parse_nth_child(":nth-child(even)"), (2, 0));
+        assert_eq!(parse_nth_child(":nth-child(0n+0)"), (0, 0));
+        assert_eq!(parse_nth_child(":nth-child(5)"), (0, 5));
+    }
+
+    #[test]
+    fn invalid_nth_child() {
+        assert!(parse(":nth-child()").is_none());
+        assert!(parse(":nth-child(foo)").is_none());
+        assert!(parse(":nth-child(2n+)").is_none());
+    }
+
+    #[test]
+    fn first_and_last_child() {
+        match parse_one(":first-child") {
+            FirstChild => (),
+            _ => fail!(~":first-child"),
+        }
+        match parse_one(":last-child") {
+            LastChild => (),
+            _ => fail!(~":last-child"),
+        }
+        match parse_one(":only-child") {
+            OnlyChild => (),
+            _ => fail!(~":only-child"),
+        }
+    }
+
+    #[test]
+    fn user_action_pseudo_classes() {
+        match parse_one(":hover") {
+            Hover => (),
+            _ => fail!(~":hover"),
+        }
+        match parse_one(":active") {
+            Active => (),
+            _ => fail!(~":active"),
+        }
+        match parse_one(":focus") {
+            Focus => (),
+            _ => fail!(~":focus"),
+        }
+    }
+}
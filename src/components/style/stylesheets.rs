@@ -0,0 +1,261 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::iter::Iterator;
+use std::ascii::StrAsciiExt;
+use std::str;
+use extra::url::Url;
+use cssparser::*;
+use selectors;
+use properties;
+use errors::{ErrorLoggerIterator, log_css_error};
+use namespaces::{NamespaceMap, parse_namespace_rule};
+use media_queries::{MediaRule, parse_media_rule};
+use media_queries;
+use imports::{ImportRule, parse_import_rule, load_imports};
+use font_face::{FontFaceRule, parse_font_face_rule};
+use servo_net::resource_task::ResourceTask;
+
+
+pub struct Stylesheet {
+    rules: ~[CSSRule],
+    namespaces: NamespaceMap,
+    base_url: Url,
+}
+
+
+pub enum CSSRule {
+    CSSStyleRule(StyleRule),
+    CSSMediaRule(MediaRule),
+    CSSImportRule(ImportRule),
+    CSSFontFaceRule(FontFaceRule),
+}
+
+
+pub struct StyleRule {
+    selectors: ~[selectors::Selector],
+    declarations: properties::PropertyDeclarationBlock,
+}
+
+
+impl Stylesheet {
+    pub fn from_bytes_iter<I: Iterator<~[u8]>>(mut input: I, base_url: Url) -> Stylesheet {
+        // TODO: incremental tokinization/parsing
+        let mut bytes = ~[];
+        // TODO: use extend() when we get a new enough rustc
+        for bytes_chunk in input {
+            bytes.push_all(bytes_chunk)
+        }
+        Stylesheet::from_bytes(bytes, base_url)
+    }
+
+    pub fn from_bytes(bytes: &[u8], base_url: Url) -> Stylesheet {
+        // TODO: bytes -> str decoding
+        Stylesheet::from_str(str::from_utf8(bytes).as_slice(), base_url)
+    }
+
+    /// Relative URLs are resolved against `base_url`. `@import` rules are parsed but the
+    /// style sheets they reference are not fetched until `load_imports` is called.
+    pub fn from_str(css: &str, base_url: Url) -> Stylesheet {
+        parse_stylesheet(css, base_url)
+    }
+
+    /// Fetches the style sheets referenced by `@import` rules, and the ones they import.
+    pub fn load_imports(&mut self, resource_task: &ResourceTask) {
+        let mut ancestors = ~[self.base_url.clone()];
+        load_imports(&mut self.rules, resource_task, &mut ancestors)
+    }
+}
+
+
+fn parse_stylesheet(css: &str, base_url: Url) -> Stylesheet {
+    static STATE_CHARSET: uint = 1;
+    static STATE_IMPORTS: uint = 2;
+    static STATE_NAMESPACES: uint = 3;
+    static STATE_BODY: uint = 4;
+    let mut state: uint = STATE_CHARSET;
+
+    let mut rules = ~[];
+    let mut namespaces = NamespaceMap::new();
+
+    for rule in ErrorLoggerIterator(parse_stylesheet_rules(tokenize(css))) {
+        let next_state;  // Unitialized to force each branch to set it.
+        match rule {
+            QualifiedRule(rule) => {
+                next_state = STATE_BODY;
+                parse_style_rule(rule, &mut rules, &namespaces, &base_url)
+            },
+            AtRule(rule) => {
+                let lower_name = rule.name.to_ascii_lower();
+                match lower_name.as_slice() {
+                    "charset" => {
+                        if state > STATE_CHARSET {
+                            log_css_error(rule.location, "@charset must be the first rule")
+                        }
+                        // Valid @charset rules are just ignored
+                        next_state = STATE_IMPORTS;
+                    },
+                    "import" => {
+                        if state > STATE_IMPORTS {
+                            next_state = state;
+                            log_css_error(rule.location,
+                                          "@import must be before any rule but @charset")
+                        } else {
+                            next_state = STATE_IMPORTS;
+                            parse_import_rule(rule, &mut rules, &base_url)
+                        }
+                    },
+                    "namespace" => {
+                        if state > STATE_NAMESPACES {
+                            next_state = state;
+                            log_css_error(
+                                rule.location,
+                                "@namespace must be before any rule but @charset and @import"
+                            )
+                        } else {
+                            next_state = STATE_NAMESPACES;
+                            parse_namespace_rule(rule, &mut namespaces)
+                        }
+                    },
+                    _ => {
+                        next_state = STATE_BODY;
+                        parse_nested_at_rule(lower_name, rule, &mut rules, &namespaces,
+                                             &base_url)
+                    },
+                }
+            },
+        }
+        state = next_state;
+    }
+    Stylesheet{ rules: rules, namespaces: namespaces, base_url: base_url }
+}
+
+
+pub fn parse_style_rule(rule: QualifiedRule, parent_rules: &mut ~[CSSRule],
+                        namespaces: &NamespaceMap, base_url: &Url) {
+    let QualifiedRule{location: location, prelude: prelude, block: block} = rule;
+    match selectors::parse_selector_list(prelude, namespaces) {
+        Some(selectors) => parent_rules.push(CSSStyleRule(StyleRule{
+            selectors: selectors,
+            declarations: properties::parse_property_declaration_list(block.move_iter(),
+                                                                      base_url)
+        })),
+        None => log_css_error(location, "Unsupported CSS selector."),
+    }
+}
+
+
+// lower_name is passed explicitly to avoid computing it twice.
+pub fn parse_nested_at_rule(lower_name: &str, rule: AtRule,
+                            parent_rules: &mut ~[CSSRule], namespaces: &NamespaceMap,
+                            base_url: &Url) {
+    match lower_name {
+        "media" => parse_media_rule(rule, parent_rules, namespaces, base_url),
+        "font-face" => parse_font_face_rule(rule, parent_rules, base_url),
+        _ => log_css_error(rule.location, fmt!("Unsupported at-rule: @%s", lower_name))
+    }
+}
+
+
+impl Stylesheet {
+    /// Returns whether any media query of an `@media` or `@import` rule in this style sheet
+    /// evaluates differently on the two devices.
+    pub fn media_queries_differ(&self, a: &media_queries::Device, b: &media_queries::Device)
+                                -> bool {
+        fn rules_differ(rules: &[CSSRule], a: &media_queries::Device, b: &media_queries::Device)
+                        -> bool {
+            do rules.iter().any |rule| {
+                match *rule {
+                    CSSStyleRule(*) | CSSFontFaceRule(*) => false,
+                    CSSMediaRule(ref rule) => {
+                        rule.media_queries.evaluate(a) != rule.media_queries.evaluate(b) ||
+                        rules_differ(rule.rules.as_slice(), a, b)
+                    }
+                    CSSImportRule(ref rule) => {
+                        rule.media_queries.evaluate(a) != rule.media_queries.evaluate(b) ||
+                        match rule.stylesheet {
+                            Some(ref stylesheet) => stylesheet.media_queries_differ(a, b),
+                            None => false,
+                        }
+                    }
+                }
+            }
+        }
+        rules_differ(self.rules.as_slice(), a, b)
+    }
+
+    pub fn iter_style_rules<'a>(&'a self, device: &'a media_queries::Device) -> StyleRuleIterator<'a> {
+        StyleRuleIterator { device: device, stack: ~[(self.rules.as_slice(), 0)] }
+    }
+
+    /// Calls `callback` with each `@font-face` rule of this style sheet, and of the style sheets
+    /// it imports, whose media queries match the device.
+    pub fn each_font_face_rule(&self, device: &media_queries::Device,
+                               callback: &fn(&FontFaceRule)) {
+        fn each_rule(rules: &[CSSRule], device: &media_queries::Device,
+                     callback: &fn(&FontFaceRule)) {
+            for rule in rules.iter() {
+                match *rule {
+                    CSSStyleRule(*) => (),
+                    CSSFontFaceRule(ref rule) => callback(rule),
+                    CSSMediaRule(ref rule) => {
+                        if rule.media_queries.evaluate(device) {
+                            each_rule(rule.rules.as_slice(), device, |rule| callback(rule))
+                        }
+                    }
+                    CSSImportRule(ref rule) => {
+                        match rule.stylesheet {
+                            Some(ref stylesheet) if rule.media_queries.evaluate(device) => {
+                                each_rule(stylesheet.rules.as_slice(),
+                                          device,
+                                          |rule| callback(rule))
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+        each_rule(self.rules.as_slice(), device, callback)
+    }
+}
+
+struct StyleRuleIterator<'self> {
+    device: &'self media_queries::Device,
+    // FIXME: I couldn’t get this to borrow-check with a stack of VecIterator
+    stack: ~[(&'self [CSSRule], uint)],
+}
+
+impl<'self> Iterator<&'self StyleRule> for StyleRuleIterator<'self> {
+    fn next(&mut self) -> Option<&'self StyleRule> {
+        loop {
+            match self.stack.pop_opt() {
+                None => return None,
+                Some((rule_list, i)) => {
+                    if i + 1 < rule_list.len() {
+                        self.stack.push((rule_list, i + 1))
+                    }
+                    match rule_list[i] {
+                        CSSStyleRule(ref rule) => return Some(rule),
+                        CSSFontFaceRule(*) => (),
+                        CSSMediaRule(ref rule) => {
+                            if rule.media_queries.evaluate(self.device) {
+                                self.stack.push((rule.rules.as_slice(), 0))
+                            }
+                        }
+                        CSSImportRule(ref rule) => {
+                            match rule.stylesheet {
+                                Some(ref stylesheet)
+                                if rule.media_queries.evaluate(self.device) => {
+                                    self.stack.push((stylesheet.rules.as_slice(), 0))
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}